@@ -1,11 +1,21 @@
 use std::collections::HashMap;
 use std::panic;
+use std::time::Duration;
 
 use log::{self, LevelFilter};
 use once_cell::sync::Lazy;
 use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
 use env_logger::{Env, Builder, Target};
-use common::{_info, config::{init_env_from_config, keys::{CONFIG_KEY_GLOBAL_DEBUG, CONFIG_KEY_GLOBAL_LOG_LIMIT}, set_configs}, logger::LOGGER, version::set_root_version};
+use common::{_info, config::{generate_config_key_file, init_env_from_config, keys::{CONFIG_KEY_GLOBAL_DEBUG, CONFIG_KEY_GLOBAL_LOG_LIMIT}, set_config_encryption, set_configs, CONFIG_ENV_KEY_FILE}, logger::LOGGER, version::set_root_version};
+use common::data::dto::{
+    cache_config::{CacheConfig, CacheKey, CacheMode},
+    filter_rule::{FilterAction, FilterMatch, FilterRule, FILTER_RULE_ALL_CLIENTS, FILTER_RULE_DEFAULT_DENY_STATUS, FILTER_RULE_DEFAULT_PRIORITY},
+    ip_rule::{IpRuleAction, IP_RULE_ALL_CLIENTS},
+    public_auth::PublicAuth,
+    request_allowlist::RequestAllowlist
+};
+use client::service::upstream_pool::LoadBalanceStrategy;
+use client::data::repository::underlying_repo::UNIX_SOCKET_PREFIX;
 use trabas::{PROJECT_NAME, PROJECT_VERSION};
 use ctrlc;
 
@@ -33,12 +43,38 @@ const CONFIG_ARG_CL_TLS_TOFU_ENABLE: &str = "tls-tofu-enable";
 const CONFIG_ARG_CL_SERVER_HOST: &str = "server-host";
 const CONFIG_ARG_CL_SERVER_PORT: &str = "server-port";
 const CONFIG_ARG_CL_SERVER_SIGNING_KEY: &str = "server-signing-key";
+const CONFIG_ARG_CL_SERVER_PUBLIC_KEY: &str = "server-public-key";
+const CONFIG_ARG_CL_TOKEN_ID: &str = "token-id";
+const CONFIG_ARG_CL_PUBLIC_BASIC_AUTH: &str = "public-basic-auth";
+const CONFIG_ARG_CL_PUBLIC_BEARER_TOKEN: &str = "public-bearer-token";
+const CONFIG_ARG_CL_PUBLIC_AUTH_CLEAR: &str = "public-auth-clear";
 
 // config arg keys for server
 const CONFIG_ARG_SV_GEN_KEY: &str = "gen-key";
 const CONFIG_ARG_SV_KEY: &str = "key";
 const CONFIG_ARG_SV_PUBLIC_ENDPOINT: &str = "public-endpoint";
 const CONFIG_ARG_SV_PUBLIC_REQUEST_TIMEOUT: &str = "public-request-timeout";
+const CONFIG_ARG_SV_PUBLIC_MAX_HEADER_BYTES: &str = "public-max-header-bytes";
+const CONFIG_ARG_SV_PUBLIC_MAX_HEADER_COUNT: &str = "public-max-header-count";
+const CONFIG_ARG_SV_PUBLIC_MAX_BODY_SIZE: &str = "public-max-body-size";
+const CONFIG_ARG_SV_PUBLIC_HEADER_TIMEOUT: &str = "public-header-timeout";
+const CONFIG_ARG_SV_PUBLIC_BODY_TIMEOUT: &str = "public-body-timeout";
+const CONFIG_ARG_SV_HANDSHAKE_MAX_SKEW: &str = "handshake-max-skew";
+const CONFIG_ARG_SV_RATE_LIMIT_CLIENT_RPS: &str = "rate-limit-client-rps";
+const CONFIG_ARG_SV_RATE_LIMIT_CLIENT_BURST: &str = "rate-limit-client-burst";
+const CONFIG_ARG_SV_RATE_LIMIT_IP_RPS: &str = "rate-limit-ip-rps";
+const CONFIG_ARG_SV_RATE_LIMIT_IP_BURST: &str = "rate-limit-ip-burst";
+const CONFIG_ARG_SV_RATE_LIMIT_MAX_WAIT: &str = "rate-limit-max-wait";
+const CONFIG_ARG_SV_PUBLIC_MAX_CONNECTIONS: &str = "public-max-connections";
+const CONFIG_ARG_SV_PUBLIC_MAX_CLIENT_CONNECTIONS: &str = "public-max-client-connections";
+const CONFIG_ARG_SV_MAX_TUNNELS_PER_CLIENT: &str = "max-tunnels-per-client";
+const CONFIG_ARG_SV_AUDIT_LOG_ENABLE: &str = "audit-log-enable";
+const CONFIG_ARG_SV_AUDIT_LOG_PATH: &str = "audit-log-path";
+const CONFIG_ARG_SV_AUDIT_LOG_MAX_SIZE: &str = "audit-log-max-size";
+const CONFIG_ARG_SV_AUDIT_LOG_MAX_FILES: &str = "audit-log-max-files";
+const CONFIG_ARG_SV_CACHE_MAX_ENTRIES: &str = "cache-max-entries";
+const CONFIG_ARG_SV_CACHE_MAX_SIZE: &str = "cache-max-size";
+const CONFIG_ARG_SV_CACHE_SWEEP_INTERVAL: &str = "cache-sweep-interval";
 const CONFIG_ARG_SV_REDIS_ENABLE: &str = "redis-enable";
 const CONFIG_ARG_SV_REDIS_HOST: &str = "redis-host";
 const CONFIG_ARG_SV_REDIS_PORT: &str = "redis-port";
@@ -49,6 +85,44 @@ const CONFIG_ARG_SV_CACHE_CLIENT_ID: &str = "client-id";
 const CONFIG_ARG_SV_CACHE_METHOD: &str = "method";
 const CONFIG_ARG_SV_CACHE_PATH: &str = "path";
 const CONFIG_ARG_SV_CACHE_EXP_DURATION: &str = "exp-duration";
+const CONFIG_ARG_SV_CACHE_MODE: &str = "mode";
+const CONFIG_ARG_SV_CACHE_KEY_HEADER: &str = "key-header";
+const CONFIG_ARG_SV_CACHE_KEY_COOKIE: &str = "key-cookie";
+const CONFIG_ARG_SV_CACHE_IGNORE_QUERY: &str = "ignore-query";
+const CONFIG_ARG_SV_CACHE_SORT_QUERY: &str = "sort-query";
+const CONFIG_ARG_SV_CACHE_IGNORE_BODY: &str = "ignore-body";
+const CONFIG_ARG_SV_CACHE_AUTHORIZED: &str = "authorized";
+
+// config arg keys for server ip rules
+const CONFIG_ARG_SV_IP_RULE_CLIENT_ID: &str = "client-id";
+const CONFIG_ARG_SV_IP_RULE_ACTION: &str = "action";
+const CONFIG_ARG_SV_IP_RULE_CIDR: &str = "cidr";
+
+// config arg keys for server filter rules
+const CONFIG_ARG_SV_FILTER_RULE_CLIENT_ID: &str = "client-id";
+const CONFIG_ARG_SV_FILTER_RULE_NAME: &str = "name";
+const CONFIG_ARG_SV_FILTER_RULE_PRIORITY: &str = "priority";
+const CONFIG_ARG_SV_FILTER_RULE_METHOD: &str = "method";
+const CONFIG_ARG_SV_FILTER_RULE_PATH: &str = "path";
+const CONFIG_ARG_SV_FILTER_RULE_MATCH_HEADER: &str = "match-header";
+const CONFIG_ARG_SV_FILTER_RULE_MATCH_QUERY: &str = "match-query";
+const CONFIG_ARG_SV_FILTER_RULE_ACTION: &str = "action";
+const CONFIG_ARG_SV_FILTER_RULE_STATUS: &str = "status";
+const CONFIG_ARG_SV_FILTER_RULE_ACTION_HEADER: &str = "action-header";
+
+// config arg keys for server api token
+const CONFIG_ARG_SV_TOKEN_CLIENT_ID: &str = "client-id";
+const CONFIG_ARG_SV_TOKEN_TTL: &str = "ttl";
+const CONFIG_ARG_SV_TOKEN_ID: &str = "id";
+
+// config arg keys for server client public key
+const CONFIG_ARG_SV_CLIENT_KEY_CLIENT_ID: &str = "client-id";
+const CONFIG_ARG_SV_CLIENT_KEY_PUBLIC_KEY: &str = "public-key";
+
+// config arg keys for server public auth
+const CONFIG_ARG_SV_PUBLIC_AUTH_CLIENT_ID: &str = "client-id";
+const CONFIG_ARG_SV_PUBLIC_AUTH_BASIC: &str = "basic-auth";
+const CONFIG_ARG_SV_PUBLIC_AUTH_BEARER: &str = "bearer-token";
 
 // TODO: complete help info
 #[derive(Parser)]
@@ -70,6 +144,17 @@ enum Commands {
         #[arg(long)]
         log_limit: Option<usize>,
     },
+    // migrate the sensitive config values into encrypted form, or back into plaintext,
+    // the key is provided by TRABAS_CONFIG_PASSPHRASE or TRABAS_CONFIG_KEY_FILE env
+    EncryptConfig {
+        #[arg(long)]
+        decrypt: bool,
+        // generate a random key file to be set as TRABAS_CONFIG_KEY_FILE
+        #[arg(long)]
+        gen_key_file: Option<String>,
+        #[arg(long)]
+        force: bool,
+    },
     Client {
         #[command(subcommand, long_help = CLIENT_HELP.as_str())]
         action: ClientActions,
@@ -91,6 +176,68 @@ enum ClientActions {
         port: u16,
         #[arg(long)]
         tls: bool,
+        // additional upstream address (host:port) to balance the requests across,
+        // could be passed multiple times, the --host and --port are ignored if set
+        #[arg(long)]
+        upstream: Vec<String>,
+        // load balancing strategy for the upstreams: round-robin or least-conn
+        #[arg(long, default_value = "round-robin")]
+        lb_strategy: String,
+        // path to the unix domain socket of the underlying service,
+        // the --host and --port are ignored if set
+        #[arg(long)]
+        unix: Option<String>,
+        // connect to the underlying service with TLS (HTTPS upstream)
+        #[arg(long)]
+        upstream_tls: bool,
+        // server name used for SNI and certificate validation of the upstream
+        #[arg(long)]
+        upstream_sni: Option<String>,
+        // path to a custom CA certificate (PEM) of the upstream
+        #[arg(long)]
+        upstream_ca: Option<String>,
+        // skip upstream certificate validation, i.e: self-signed certificates
+        #[arg(long)]
+        upstream_insecure: bool,
+        // serve files from a local directory instead of an underlying service
+        #[arg(long)]
+        dir: Option<String>,
+        // fallback to index.html for unknown paths of the served directory
+        #[arg(long)]
+        spa: bool,
+        // list the content of directories without index.html
+        #[arg(long)]
+        dir_listing: bool,
+        // max idle keep-alive connections per upstream, 0 disables keep-alive
+        #[arg(long, default_value_t = client::config::DEFAULT_POOL_MAX_IDLE_PER_HOST)]
+        max_idle_conns: usize,
+        // max open connections per upstream, 0 means unlimited
+        #[arg(long, default_value_t = client::config::DEFAULT_POOL_MAX_PER_HOST)]
+        max_conns_per_host: usize,
+        // timeout in seconds for connecting to the underlying service, 0 disables it
+        #[arg(long, default_value_t = client::config::DEFAULT_CONNECT_TIMEOUT)]
+        connect_timeout: u64,
+        // timeout in seconds for the first byte of the response, 0 disables it
+        #[arg(long, default_value_t = client::config::DEFAULT_FIRST_BYTE_TIMEOUT)]
+        first_byte_timeout: u64,
+        // timeout in seconds for the whole forwarding, 0 disables it
+        #[arg(long, default_value_t = client::config::DEFAULT_TOTAL_TIMEOUT)]
+        total_timeout: u64,
+        // max size in bytes of the forwarded request and response, 0 means unlimited
+        #[arg(long, default_value_t = 0)]
+        max_body_size: usize,
+        // path glob exposed through the tunnel, could be passed multiple times,
+        // `*` does not cross `/` and `**` matches any number of segments
+        #[arg(long)]
+        allow_path: Vec<String>,
+        // comma separated HTTP methods exposed through the tunnel, i.e: GET,HEAD
+        #[arg(long)]
+        allow_method: Vec<String>,
+    },
+    // generate ed25519 key pair for authenticating with the server without a shared secret
+    Keygen {
+        #[arg(long, help = "Force regenerate the key pair")]
+        force: bool,
     },
     SetConfig {
         #[arg(
@@ -123,6 +270,36 @@ enum ClientActions {
             help="Server Signing Key for signing client signature"
         )]
         server_signing_key: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_CL_SERVER_PUBLIC_KEY,
+            long,
+            help="Server Public Key for verifying the server with key auth, shown by `trabas server identity-key`"
+        )]
+        server_public_key: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_CL_TOKEN_ID, 
+            long,
+            help="API Token ID issued by the server, the server signing key must be the token key"
+        )]
+        token_id: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_CL_PUBLIC_BASIC_AUTH,
+            long,
+            help="Require HTTP Basic credentials (username:password) on the public endpoints"
+        )]
+        public_basic_auth: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_CL_PUBLIC_BEARER_TOKEN,
+            long,
+            help="Require a static bearer token on the public endpoints"
+        )]
+        public_bearer_token: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_CL_PUBLIC_AUTH_CLEAR,
+            long,
+            help="Remove the credentials required on the public endpoints"
+        )]
+        public_auth_clear: bool,
         #[arg(
             long, 
             help = "Force apply the config",
@@ -154,15 +331,59 @@ enum ServerActions {
         // enforce tls
         #[arg(long)]
         tls: bool,
+        // require client certificates signed by the CA, requires --tls
+        #[arg(long)]
+        mtls: bool,
     },
     CacheConfig {
         #[command(subcommand)]
         action: ServerCacheActions,
     },
+    // the cached responses, of the running server or in redis
+    Cache {
+        #[command(subcommand)]
+        action: ServerCacheStoreActions,
+    },
+    IpRule {
+        #[command(subcommand)]
+        action: ServerIpRuleActions,
+    },
+    FilterRule {
+        #[command(subcommand)]
+        action: ServerFilterRuleActions,
+    },
     SSLConfig {
         #[command(subcommand)]
         action: ServerSSLActions,
     },
+    Token {
+        #[command(subcommand)]
+        action: ServerTokenActions,
+    },
+    ClientKey {
+        #[command(subcommand)]
+        action: ServerClientKeyActions,
+    },
+    PublicAuth {
+        #[command(subcommand)]
+        action: ServerPublicAuthActions,
+    },
+    // replace the server secret, the previous one is still accepted during the overlap
+    RotateSecret {
+        #[arg(long, help = "Set the new server secret, generated if not set")]
+        key: Option<String>,
+        #[arg(long, default_value_t = 86400, help = "Seconds the previous secret is still accepted")]
+        overlap: u64,
+        #[arg(long, help = "Show the active server secrets without rotating")]
+        list: bool,
+        #[arg(long, help = "Stop accepting the previous secrets without rotating")]
+        expire_previous: bool,
+    },
+    // public key of the server identity, pinned by the key authenticated clients
+    IdentityKey {
+        #[arg(long, help = "Regenerate the identity key, the clients must pin the new public key")]
+        force: bool,
+    },
     SetConfig {
         #[arg(
             name = CONFIG_ARG_SV_GEN_KEY, 
@@ -188,6 +409,132 @@ enum ServerActions {
             help="Public request timeout in seconds"
         )]
         public_request_timeout: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_MAX_HEADER_BYTES, 
+            long,
+            help="Max size of the public request headers in bytes, 0 means unlimited"
+        )]
+        public_max_header_bytes: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_MAX_HEADER_COUNT, 
+            long,
+            help="Max number of the public request headers, 0 means unlimited"
+        )]
+        public_max_header_count: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_MAX_BODY_SIZE, 
+            long,
+            help="Max size of the public request body in bytes, 0 means unlimited"
+        )]
+        public_max_body_size: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_HEADER_TIMEOUT, 
+            long,
+            help="Timeout of reading the public request headers in seconds, 0 disables it"
+        )]
+        public_header_timeout: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_BODY_TIMEOUT, 
+            long,
+            help="Timeout of reading the public request body in seconds, 0 disables it"
+        )]
+        public_body_timeout: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_HANDSHAKE_MAX_SKEW, 
+            long,
+            help="Max allowed clock skew of the client handshake in seconds"
+        )]
+        handshake_max_skew: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_RATE_LIMIT_CLIENT_RPS, 
+            long,
+            help="Allowed public requests per second of each client, 0 disables it"
+        )]
+        rate_limit_client_rps: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_RATE_LIMIT_CLIENT_BURST, 
+            long,
+            help="Burst capacity of the client rate limit, defaults to the rate"
+        )]
+        rate_limit_client_burst: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_RATE_LIMIT_IP_RPS, 
+            long,
+            help="Allowed public requests per second of each source IP, 0 disables it"
+        )]
+        rate_limit_ip_rps: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_RATE_LIMIT_IP_BURST, 
+            long,
+            help="Burst capacity of the source IP rate limit, defaults to the rate"
+        )]
+        rate_limit_ip_burst: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_RATE_LIMIT_MAX_WAIT, 
+            long,
+            help="Max time in milliseconds a public request waits for capacity before it's rejected"
+        )]
+        rate_limit_max_wait: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_MAX_CONNECTIONS, 
+            long,
+            help="Max concurrent public connections of the server, 0 means unlimited"
+        )]
+        public_max_connections: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_MAX_CLIENT_CONNECTIONS, 
+            long,
+            help="Max concurrent public connections of each client, 0 means unlimited"
+        )]
+        public_max_client_connections: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_MAX_TUNNELS_PER_CLIENT, 
+            long,
+            help="Max tunnels registered with the same client ID, 0 means unlimited"
+        )]
+        max_tunnels_per_client: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_AUDIT_LOG_ENABLE, 
+            long,
+            help="Whether the audit log of the tunnel registrations is enabled"
+        )]
+        audit_log_enable: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_AUDIT_LOG_PATH, 
+            long,
+            help="Path of the audit log file, defaults to audit.log in the config directory"
+        )]
+        audit_log_path: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_AUDIT_LOG_MAX_SIZE, 
+            long,
+            help="Max size in bytes of the audit log file before it's rotated, 0 disables the rotation"
+        )]
+        audit_log_max_size: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_AUDIT_LOG_MAX_FILES, 
+            long,
+            help="Number of rotated audit log files to keep"
+        )]
+        audit_log_max_files: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_MAX_ENTRIES, 
+            long,
+            help="Max entries of the in process cache, the least recently used ones are evicted first, 0 means unlimited"
+        )]
+        cache_max_entries: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_MAX_SIZE, 
+            long,
+            help="Max size in bytes of the in process cache, 0 means unlimited"
+        )]
+        cache_max_size: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_SWEEP_INTERVAL, 
+            long,
+            help="Interval in seconds of removing the expired entries of the in process cache, 0 disables it"
+        )]
+        cache_sweep_interval: Option<String>,
         #[arg(
             name = CONFIG_ARG_SV_REDIS_ENABLE, 
             long,
@@ -232,6 +579,259 @@ enum ServerSSLActions {
         #[arg(long, help = "Force regenerate SSL keys")] 
         force: bool,
     },
+    IssueClientCert {
+        #[arg(long, help = "Client ID, set as the certificate subject")]
+        client_id: String,
+        #[arg(long, default_value_t = 365, help = "Certificate validity in days")]
+        days: u32,
+        #[arg(long, help = "Force reissue the client certificate")]
+        force: bool,
+    },
+}
+
+// Actions for managing per-client API tokens
+#[derive(Subcommand)]
+enum ServerTokenActions {
+    Create {
+        #[arg(
+            name = CONFIG_ARG_SV_TOKEN_CLIENT_ID,
+            long,
+            required = true,
+            help = "Client ID allowed to use the token, could be passed multiple times, `*` for any client"
+        )]
+        client_id: Vec<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_TOKEN_TTL,
+            long,
+            help = "Token lifetime in seconds, never expires if not set"
+        )]
+        ttl: Option<u64>,
+    },
+    List { },
+    Revoke {
+        #[arg(
+            name = CONFIG_ARG_SV_TOKEN_ID,
+            long,
+            help = "Token ID"
+        )]
+        id: String,
+    },
+}
+
+// Actions for managing public keys of the key authenticated clients
+#[derive(Subcommand)]
+enum ServerClientKeyActions {
+    List { },
+    Set {
+        #[arg(
+            name = CONFIG_ARG_SV_CLIENT_KEY_CLIENT_ID,
+            long,
+            help = "Client ID"
+        )]
+        client_id: String,
+        #[arg(
+            name = CONFIG_ARG_SV_CLIENT_KEY_PUBLIC_KEY,
+            long,
+            help = "ed25519 public key in hex, generated by `trabas client keygen`"
+        )]
+        public_key: String,
+    },
+    Remove {
+        #[arg(
+            name = CONFIG_ARG_SV_CLIENT_KEY_CLIENT_ID,
+            long,
+            help = "Client ID"
+        )]
+        client_id: String,
+    },
+}
+
+// Actions for managing the credentials required on the public endpoints
+#[derive(Subcommand)]
+enum ServerPublicAuthActions {
+    List { },
+    Set {
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_AUTH_CLIENT_ID,
+            long,
+            help = "Client ID, or `*` for all clients without their own policy"
+        )]
+        client_id: String,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_AUTH_BASIC,
+            long,
+            help = "HTTP Basic credentials (username:password)"
+        )]
+        basic_auth: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_AUTH_BEARER,
+            long,
+            help = "Static bearer token"
+        )]
+        bearer_token: Option<String>,
+    },
+    Remove {
+        #[arg(
+            name = CONFIG_ARG_SV_PUBLIC_AUTH_CLIENT_ID,
+            long,
+            help = "Client ID"
+        )]
+        client_id: String,
+    },
+}
+
+// Actions for managing the CIDR allow/deny rules of the public requests
+#[derive(Subcommand)]
+enum ServerIpRuleActions {
+    List { },
+    Set {
+        #[arg(
+            name = CONFIG_ARG_SV_IP_RULE_CLIENT_ID,
+            long,
+            default_value = IP_RULE_ALL_CLIENTS,
+            help = "Client ID, the rule applies to all clients if not set"
+        )]
+        client_id: String,
+        #[arg(
+            name = CONFIG_ARG_SV_IP_RULE_ACTION,
+            long,
+            help = "Rule action: allow, deny"
+        )]
+        action: String,
+        #[arg(
+            name = CONFIG_ARG_SV_IP_RULE_CIDR,
+            long,
+            help = "CIDR block or a single address, i.e: 10.0.0.0/8"
+        )]
+        cidr: String,
+    },
+    Remove {
+        #[arg(
+            name = CONFIG_ARG_SV_IP_RULE_CLIENT_ID,
+            long,
+            default_value = IP_RULE_ALL_CLIENTS,
+            help = "Client ID, the rule applies to all clients if not set"
+        )]
+        client_id: String,
+        #[arg(
+            name = CONFIG_ARG_SV_IP_RULE_CIDR,
+            long,
+            help = "CIDR block or a single address"
+        )]
+        cidr: String,
+    },
+}
+
+// Actions for managing the declarative rules of the public requests
+#[derive(Subcommand)]
+enum ServerFilterRuleActions {
+    List { },
+    Set {
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_CLIENT_ID,
+            long,
+            default_value = FILTER_RULE_ALL_CLIENTS,
+            help = "Client ID, the rule applies to all clients if not set"
+        )]
+        client_id: String,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_NAME,
+            long,
+            help = "Rule name, unique by client ID"
+        )]
+        name: String,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_PRIORITY,
+            long,
+            default_value_t = FILTER_RULE_DEFAULT_PRIORITY,
+            help = "Lower priority is evaluated first"
+        )]
+        priority: u32,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_METHOD,
+            long,
+            help = "Comma separated HTTP methods to match, i.e: GET,HEAD"
+        )]
+        method: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_PATH,
+            long,
+            help = "Glob of the path to match, i.e: /admin/**"
+        )]
+        path: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_MATCH_HEADER,
+            long,
+            help = "Header to match, `name` or `name=value glob`"
+        )]
+        match_header: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_MATCH_QUERY,
+            long,
+            help = "Query parameter to match, `name` or `name=value glob`"
+        )]
+        match_query: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_ACTION,
+            long,
+            help = "Rule action: allow, deny, set-header, remove-header"
+        )]
+        action: String,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_STATUS,
+            long,
+            default_value_t = FILTER_RULE_DEFAULT_DENY_STATUS,
+            help = "Response status of the deny action"
+        )]
+        status: u16,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_ACTION_HEADER,
+            long,
+            help = "Header of the set-header (`name: value`) or remove-header (`name`) action"
+        )]
+        action_header: Option<String>,
+    },
+    Remove {
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_CLIENT_ID,
+            long,
+            default_value = FILTER_RULE_ALL_CLIENTS,
+            help = "Client ID, the rule applies to all clients if not set"
+        )]
+        client_id: String,
+        #[arg(
+            name = CONFIG_ARG_SV_FILTER_RULE_NAME,
+            long,
+            help = "Rule name"
+        )]
+        name: String,
+    },
+}
+
+// Actions for the cached responses
+#[derive(Subcommand)]
+enum ServerCacheStoreActions {
+    Purge {
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_CLIENT_ID, 
+            long,
+            help="Client ID, `*` for all clients"
+        )]
+        client_id: String,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_PATH, 
+            long,
+            help="Request Path, a glob (i.e: /products/*) or a regex prefixed by `~` (i.e: ~/products/[0-9]+), all paths if not set"
+        )]
+        path: Option<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_METHOD, 
+            long,
+            help="HTTP Method, all methods if not set"
+        )]
+        method: Option<String>,
+    },
+    Stats { },
 }
 
 // Actions for managing server/request cache
@@ -242,19 +842,19 @@ enum ServerCacheActions {
         #[arg(
             name = CONFIG_ARG_SV_CACHE_CLIENT_ID, 
             long,
-            help="Client ID"
+            help="Client ID, `*` for all clients"
         )]
         client_id: String,
         #[arg(
             name = CONFIG_ARG_SV_CACHE_METHOD, 
             long,
-            help="HTTP Method"
+            help="HTTP Method, `*` for all methods"
         )]
         method: String,
         #[arg(
             name = CONFIG_ARG_SV_CACHE_PATH, 
             long,
-            help="Request Path"
+            help="Request Path, a glob (i.e: /products/*) or a regex prefixed by `~` (i.e: ~/products/[0-9]+)"
         )]
         path: String,
         #[arg(
@@ -263,6 +863,49 @@ enum ServerCacheActions {
             help="Cache expiry duration in seconds"
         )]
         exp_duration: u32,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_MODE, 
+            long,
+            default_value = "fixed",
+            help="Cache mode, `fixed` caches any response for the expiry duration, `http` follows Cache-Control, Expires, Vary and validators of the response and uses the expiry duration as fallback"
+        )]
+        mode: String,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_KEY_HEADER, 
+            long,
+            help="Request header to be part of the cache key, could be passed multiple times"
+        )]
+        key_header: Vec<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_KEY_COOKIE, 
+            long,
+            help="Request cookie to be part of the cache key, could be passed multiple times"
+        )]
+        key_cookie: Vec<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_IGNORE_QUERY, 
+            long,
+            help="Query param left out of the cache key, could be passed multiple times, `*` ignores the whole query string"
+        )]
+        ignore_query: Vec<String>,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_SORT_QUERY, 
+            long,
+            help="Sort the query params of the cache key"
+        )]
+        sort_query: bool,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_IGNORE_BODY, 
+            long,
+            help="Leave the request body out of the cache key"
+        )]
+        ignore_body: bool,
+        #[arg(
+            name = CONFIG_ARG_SV_CACHE_AUTHORIZED, 
+            long,
+            help="Cache requests with `Authorization` too, the credentials are part of the cache key"
+        )]
+        authorized: bool,
     },
     Remove {
         #[arg(
@@ -296,6 +939,45 @@ fn cleanup_logger_state() {
     }
 }
 
+// only the hash of the credentials is kept, exits on invalid values
+fn parse_public_auth(basic_auth: &Option<String>, bearer_token: &Option<String>) -> Vec<PublicAuth> {
+    let mut result = Vec::new();
+    let parsed = [
+        basic_auth.as_ref().map(|v| PublicAuth::basic_from_str(v)),
+        bearer_token.as_ref().map(|v| PublicAuth::bearer(v)),
+    ];
+    for value in parsed.into_iter().flatten() {
+        match value {
+            Ok(auth) => result.push(auth),
+            Err(e) => {
+                let mut cmd = Cli::command();
+                cmd.error(ErrorKind::InvalidValue, e).exit();
+            }
+        }
+    }
+
+    result
+}
+
+fn parse_filter_action(action: &str, status: u16, action_header: &Option<String>) -> Result<FilterAction, String> {
+    let header = action_header.as_ref().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    match action.trim().to_lowercase().as_str() {
+        "allow" => Ok(FilterAction::Allow),
+        "deny" => Ok(FilterAction::Deny { status }),
+        "set-header" => {
+            let (name, value) = header.as_ref()
+                .and_then(|v| v.split_once(':'))
+                .ok_or_else(|| format!("--{} `name: value` is required by set-header", CONFIG_ARG_SV_FILTER_RULE_ACTION_HEADER))?;
+            Ok(FilterAction::SetHeader { name: name.trim().to_string(), value: value.trim().to_string() })
+        },
+        "remove-header" => {
+            let name = header.ok_or_else(|| format!("--{} `name` is required by remove-header", CONFIG_ARG_SV_FILTER_RULE_ACTION_HEADER))?;
+            Ok(FilterAction::RemoveHeader { name })
+        },
+        _ => Err(format!("Unknown filter rule action: {}. Valid values: allow, deny, set-header, remove-header", action)),
+    }
+}
+
 fn setup_exit_handler(debug: bool) {
     if debug { return; }
 
@@ -437,14 +1119,20 @@ async fn main() {
 
             let mut debug_config = true;
             if *set_debug {
-                set_configs(HashMap::from([
+                if let Err(e) = set_configs(HashMap::from([
                     (String::from(CONFIG_KEY_GLOBAL_DEBUG), "true".to_string())
-                ]));
+                ])) {
+                    println!("{}", e);
+                    return;
+                }
                 println!("Global config {} is set to true", CONFIG_KEY_GLOBAL_DEBUG)
             } else if *unset_debug {
-                set_configs(HashMap::from([
+                if let Err(e) = set_configs(HashMap::from([
                     (String::from(CONFIG_KEY_GLOBAL_DEBUG), "false".to_string())
-                ]));
+                ])) {
+                    println!("{}", e);
+                    return;
+                }
                 println!("Global config {} is set to false", CONFIG_KEY_GLOBAL_DEBUG)
             } else {
                 debug_config = false;
@@ -466,14 +1154,125 @@ async fn main() {
             }
 
             if let Some(limit) = log_limit {
-                set_configs(HashMap::from([
+                if let Err(e) = set_configs(HashMap::from([
                     (String::from(CONFIG_KEY_GLOBAL_LOG_LIMIT), limit.to_string())
-                ]));
+                ])) {
+                    println!("{}", e);
+                    return;
+                }
                 println!("Global config {} is set to {}", CONFIG_KEY_GLOBAL_LOG_LIMIT, limit)
             }
         },
+        Commands::EncryptConfig { decrypt, gen_key_file, force } => {
+            cleanup_logger_state();
+
+            if let Some(path) = gen_key_file {
+                if let Err(e) = generate_config_key_file(path, *force) {
+                    println!("Failed to generate config key file: {}", e);
+                    return;
+                }
+                println!("Config key file has been generated: {}", path);
+                println!("Set it before running any command: export {}={}", CONFIG_ENV_KEY_FILE, path);
+                return;
+            }
+
+            match set_config_encryption(!*decrypt) {
+                Ok(count) if *decrypt => println!("Config encryption is disabled, {} sensitive value(s) are stored in plaintext", count),
+                Ok(count) => println!("Config encryption is enabled, {} sensitive value(s) are stored encrypted", count),
+                Err(e) => println!("Failed to migrate the config: {}", e),
+            }
+        },
         Commands::Client { action } => match action {
-            ClientActions::Serve { host, port , tls } => {
+            ClientActions::Serve { 
+                host, 
+                port, 
+                tls, 
+                upstream, 
+                lb_strategy, 
+                unix, 
+                upstream_tls, 
+                upstream_sni, 
+                upstream_ca, 
+                upstream_insecure,
+                dir,
+                spa,
+                dir_listing,
+                max_idle_conns,
+                max_conns_per_host,
+                connect_timeout,
+                first_byte_timeout,
+                total_timeout,
+                max_body_size,
+                allow_path,
+                allow_method
+            } => {
+                if unix.is_some() && !upstream.is_empty() {
+                    let mut cmd = Cli::command();
+                    cmd.error(
+                        ErrorKind::ArgumentConflict,
+                        "Cannot set both --unix and --upstream at once. Use --upstream unix:[path] instead."
+                    ).exit();
+                }
+
+                let lb_strategy = match lb_strategy.parse::<LoadBalanceStrategy>() {
+                    Ok(value) => value,
+                    Err(e) => {
+                        let mut cmd = Cli::command();
+                        cmd.error(ErrorKind::InvalidValue, e).exit();
+                    }
+                };
+
+                if !*upstream_tls && (upstream_sni.is_some() || upstream_ca.is_some() || *upstream_insecure) {
+                    let mut cmd = Cli::command();
+                    cmd.error(
+                        ErrorKind::MissingRequiredArgument,
+                        "--upstream-sni, --upstream-ca and --upstream-insecure require --upstream-tls."
+                    ).exit();
+                }
+
+                let has_unix_upstream = unix.is_some() || upstream.iter().any(|u| u.starts_with(UNIX_SOCKET_PREFIX));
+                if *upstream_tls && has_unix_upstream {
+                    let mut cmd = Cli::command();
+                    cmd.error(
+                        ErrorKind::ArgumentConflict,
+                        "--upstream-tls cannot be used with unix domain socket upstreams."
+                    ).exit();
+                }
+
+                if dir.is_some() && (unix.is_some() || !upstream.is_empty() || *upstream_tls) {
+                    let mut cmd = Cli::command();
+                    cmd.error(
+                        ErrorKind::ArgumentConflict,
+                        "--dir cannot be used together with --unix, --upstream or --upstream-tls."
+                    ).exit();
+                }
+                if dir.is_none() && (*spa || *dir_listing) {
+                    let mut cmd = Cli::command();
+                    cmd.error(
+                        ErrorKind::MissingRequiredArgument,
+                        "--spa and --dir-listing require --dir."
+                    ).exit();
+                }
+
+                let static_files = (*dir).clone()
+                    .map(|d| client::config::StaticFileConfig::new(d, *spa, *dir_listing));
+
+                let allowlist = match RequestAllowlist::new((*allow_path).clone(), (*allow_method).clone()) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        let mut cmd = Cli::command();
+                        cmd.error(ErrorKind::InvalidValue, e).exit();
+                    }
+                };
+
+                let upstream_tls = if *upstream_tls {
+                    Some(client::config::UpstreamTlsConfig::new(
+                        (*upstream_sni).clone(),
+                        (*upstream_ca).clone(),
+                        *upstream_insecure
+                    ))
+                } else { None };
+
                 print_log_header(SERVICE_TAG_CLIENT.to_string());
                 client::entry_point(
                     client::config::ClientRequestConfig::new(
@@ -481,27 +1280,54 @@ async fn main() {
                         *port,
                         *tls
                     )
+                    .with_upstreams((*upstream).clone(), lb_strategy)
+                    .with_unix_socket((*unix).clone())
+                    .with_upstream_tls(upstream_tls)
+                    .with_static_files(static_files)
+                    .with_connection_pool(client::config::ConnectionPoolConfig::new(*max_idle_conns, *max_conns_per_host))
+                    .with_forward_limits(client::config::ForwardLimitConfig::new(
+                        Duration::from_secs(*connect_timeout),
+                        Duration::from_secs(*first_byte_timeout),
+                        Duration::from_secs(*total_timeout),
+                        *max_body_size
+                    ))
+                    .with_allowlist(allowlist)
                 ).await;
             },
+            ClientActions::Keygen { force } => {
+                cleanup_logger_state();
+                client::config::generate_key_pair(*force);
+            },
             ClientActions::SetConfig { 
                 client_id,
                 tls_tofu_enable, 
                 server_host, 
                 server_port, 
                 server_signing_key, 
+                server_public_key,
+                token_id,
+                public_basic_auth,
+                public_bearer_token,
+                public_auth_clear,
                 force 
             } => {
                 cleanup_logger_state();
                 
-                if client_id.is_none() && server_host.is_none() && server_port.is_none() && server_signing_key.is_none() {
+                if client_id.is_none() && server_host.is_none() && server_port.is_none() && server_signing_key.is_none() && server_public_key.is_none()
+                    && token_id.is_none() && public_basic_auth.is_none() && public_bearer_token.is_none() && !*public_auth_clear {
                     let mut cmd = Cli::command();
                     let error_message = format!(
-                        "At least one of the following arguments must be provided: --{}, --{}, --{}, --{}, or --{}",
+                        "At least one of the following arguments must be provided: --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, or --{}",
                         CONFIG_ARG_CL_ID,
                         CONFIG_ARG_CL_TLS_TOFU_ENABLE,
                         CONFIG_ARG_CL_SERVER_HOST,
                         CONFIG_ARG_CL_SERVER_PORT,
-                        CONFIG_ARG_CL_SERVER_SIGNING_KEY
+                        CONFIG_ARG_CL_SERVER_SIGNING_KEY,
+                        CONFIG_ARG_CL_SERVER_PUBLIC_KEY,
+                        CONFIG_ARG_CL_TOKEN_ID,
+                        CONFIG_ARG_CL_PUBLIC_BASIC_AUTH,
+                        CONFIG_ARG_CL_PUBLIC_BEARER_TOKEN,
+                        CONFIG_ARG_CL_PUBLIC_AUTH_CLEAR
                     );
                     
                     cmd.error(
@@ -525,6 +1351,22 @@ async fn main() {
                 if let Some(value) = server_signing_key {
                     client::config::set_server_signing_key((*value).clone(), *force)
                 }
+
+                if let Some(value) = server_public_key {
+                    client::config::set_server_public_key((*value).clone(), *force)
+                }
+
+                if let Some(value) = token_id {
+                    client::config::set_token_id((*value).clone(), *force)
+                }
+
+                if *public_auth_clear {
+                    client::config::clear_public_auth()
+                }
+
+                for auth in parse_public_auth(public_basic_auth, public_bearer_token) {
+                    client::config::set_public_auth(auth, *force)
+                }
             }
         },
         Commands::Server { action } => match action {
@@ -536,7 +1378,13 @@ async fn main() {
                 cache_client_id,
                 return_tunnel_id,
                 tls,
+                mtls,
             } => {
+                if *mtls && !*tls {
+                    let mut cmd = Cli::command();
+                    cmd.error(ErrorKind::MissingRequiredArgument, "--mtls requires --tls.").exit();
+                }
+
                 print_log_header(SERVICE_TAG_SERVER.to_string());
                 let root_host = match host {
                     Some(value) => (*value).clone(),
@@ -557,6 +1405,7 @@ async fn main() {
                         *return_tunnel_id,
                         *tls,
                     )
+                    .with_mtls(*mtls)
                 ).await;
             },
             ServerActions::CacheConfig { action } => match action {
@@ -564,26 +1413,220 @@ async fn main() {
                     cleanup_logger_state();
                     server::config::show_cache_config().await;
                 },
-                ServerCacheActions::Set { client_id, method, path, exp_duration } => {
+                ServerCacheActions::Set { client_id, method, path, exp_duration, mode, key_header, key_cookie, ignore_query, sort_query, ignore_body, authorized } => {
                     cleanup_logger_state();
-                    server::config::set_cache_config((*client_id).clone(), (*method).clone(), (*path).clone(), *exp_duration).await;
+                    let parsed = mode.parse::<CacheMode>().and_then(|mode| {
+                        let key = CacheKey::new((*key_header).clone(), (*key_cookie).clone(), (*ignore_query).clone())?
+                            .with_sort_query(*sort_query)
+                            .with_body(!*ignore_body)
+                            .with_authorized(*authorized);
+                        let config = CacheConfig::new((*client_id).clone(), (*method).clone(), (*path).clone(), *exp_duration)
+                            .with_mode(mode)
+                            .with_key(key);
+                        config.validate()?;
+                        Ok(config)
+                    });
+                    let config = match parsed {
+                        Ok(value) => value,
+                        Err(e) => {
+                            let mut cmd = Cli::command();
+                            cmd.error(ErrorKind::InvalidValue, e).exit();
+                        }
+                    };
+                    server::config::set_cache_config(config).await;
                 },
                 ServerCacheActions::Remove { client_id, method, path } => {
                     cleanup_logger_state();
                     server::config::remove_cache_config((*client_id).clone(), (*method).clone(), (*path).clone()).await;
                 },
             },
+            ServerActions::Cache { action } => match action {
+                ServerCacheStoreActions::Purge { client_id, path, method } => {
+                    cleanup_logger_state();
+                    server::config::purge_cache((*client_id).clone(), (*path).clone(), (*method).clone()).await;
+                },
+                ServerCacheStoreActions::Stats { } => {
+                    cleanup_logger_state();
+                    server::config::show_cache_stats().await;
+                },
+            },
+            ServerActions::Token { action } => match action {
+                ServerTokenActions::Create { client_id, ttl } => {
+                    cleanup_logger_state();
+                    server::config::create_token((*client_id).clone(), *ttl).await;
+                },
+                ServerTokenActions::List { } => {
+                    cleanup_logger_state();
+                    server::config::show_tokens().await;
+                },
+                ServerTokenActions::Revoke { id } => {
+                    cleanup_logger_state();
+                    server::config::revoke_token((*id).clone()).await;
+                },
+            },
+            ServerActions::ClientKey { action } => match action {
+                ServerClientKeyActions::List { } => {
+                    cleanup_logger_state();
+                    server::config::show_client_keys().await;
+                },
+                ServerClientKeyActions::Set { client_id, public_key } => {
+                    cleanup_logger_state();
+                    server::config::set_client_key((*client_id).clone(), (*public_key).clone()).await;
+                },
+                ServerClientKeyActions::Remove { client_id } => {
+                    cleanup_logger_state();
+                    server::config::remove_client_key((*client_id).clone()).await;
+                },
+            },
+            ServerActions::IpRule { action } => match action {
+                ServerIpRuleActions::List { } => {
+                    cleanup_logger_state();
+                    server::config::show_ip_rules().await;
+                },
+                ServerIpRuleActions::Set { client_id, action, cidr } => {
+                    cleanup_logger_state();
+
+                    let action = match action.parse::<IpRuleAction>() {
+                        Ok(value) => value,
+                        Err(e) => {
+                            let mut cmd = Cli::command();
+                            cmd.error(ErrorKind::InvalidValue, e).exit();
+                        }
+                    };
+                    server::config::set_ip_rule((*client_id).clone(), action, (*cidr).clone()).await;
+                },
+                ServerIpRuleActions::Remove { client_id, cidr } => {
+                    cleanup_logger_state();
+                    server::config::remove_ip_rule((*client_id).clone(), (*cidr).clone()).await;
+                },
+            },
+            ServerActions::FilterRule { action } => match action {
+                ServerFilterRuleActions::List { } => {
+                    cleanup_logger_state();
+                    server::config::show_filter_rules().await;
+                },
+                ServerFilterRuleActions::Set {
+                    client_id,
+                    name,
+                    priority,
+                    method,
+                    path,
+                    match_header,
+                    match_query,
+                    action,
+                    status,
+                    action_header
+                } => {
+                    cleanup_logger_state();
+
+                    let parsed = parse_filter_action(action, *status, action_header).and_then(|action| {
+                        let header = match_header.as_ref().map(|v| v.parse::<FilterMatch>()).transpose()?;
+                        let query = match_query.as_ref().map(|v| v.parse::<FilterMatch>()).transpose()?;
+                        Ok(FilterRule::new((*client_id).clone(), (*name).clone(), action)
+                            .with_priority(*priority)
+                            .with_method((*method).clone())
+                            .with_path((*path).clone())
+                            .with_header(header)
+                            .with_query(query))
+                    });
+                    let rule = match parsed {
+                        Ok(value) => value,
+                        Err(e) => {
+                            let mut cmd = Cli::command();
+                            cmd.error(ErrorKind::InvalidValue, e).exit();
+                        }
+                    };
+                    server::config::set_filter_rule(rule).await;
+                },
+                ServerFilterRuleActions::Remove { client_id, name } => {
+                    cleanup_logger_state();
+                    server::config::remove_filter_rule((*client_id).clone(), (*name).clone()).await;
+                },
+            },
+            ServerActions::PublicAuth { action } => match action {
+                ServerPublicAuthActions::List { } => {
+                    cleanup_logger_state();
+                    server::config::show_public_auth().await;
+                },
+                ServerPublicAuthActions::Set { client_id, basic_auth, bearer_token } => {
+                    cleanup_logger_state();
+
+                    if basic_auth.is_none() && bearer_token.is_none() {
+                        let mut cmd = Cli::command();
+                        cmd.error(
+                            ErrorKind::MissingRequiredArgument,
+                            format!("At least one of the following arguments must be provided: --{}, or --{}", CONFIG_ARG_SV_PUBLIC_AUTH_BASIC, CONFIG_ARG_SV_PUBLIC_AUTH_BEARER)
+                        ).exit();
+                    }
+
+                    for auth in parse_public_auth(basic_auth, bearer_token) {
+                        server::config::set_public_auth((*client_id).clone(), auth).await;
+                    }
+                },
+                ServerPublicAuthActions::Remove { client_id } => {
+                    cleanup_logger_state();
+                    server::config::remove_public_auth((*client_id).clone()).await;
+                },
+            },
+            ServerActions::RotateSecret { key, overlap, list, expire_previous } => {
+                cleanup_logger_state();
+
+                if *list && *expire_previous {
+                    let mut cmd = Cli::command();
+                    cmd.error(
+                        ErrorKind::ArgumentConflict,
+                        "Cannot set both --list and --expire-previous at once."
+                    ).exit();
+                }
+
+                if *list {
+                    server::config::show_server_secrets().await;
+                } else if *expire_previous {
+                    server::config::expire_previous_server_secrets().await;
+                } else {
+                    server::config::rotate_server_secret((*key).clone(), *overlap).await;
+                }
+            },
+            ServerActions::IdentityKey { force } => {
+                cleanup_logger_state();
+                server::config::show_identity_key(*force).await;
+            },
             ServerActions::SSLConfig { action } => match action {
                 ServerSSLActions::GenerateKeys { server_conf_path, host, ip, force } => {
                     cleanup_logger_state();
                     server::config::generate_ssl_keys(server_conf_path.clone(), host.clone(), ip.clone(), *force);
                 },
+                ServerSSLActions::IssueClientCert { client_id, days, force } => {
+                    cleanup_logger_state();
+                    server::config::issue_client_cert((*client_id).clone(), *days, *force);
+                },
             },
             ServerActions::SetConfig { 
                 gen_key,
                 key, 
                 public_endpoint, 
                 public_request_timeout, 
+                public_max_header_bytes,
+                public_max_header_count,
+                public_max_body_size,
+                public_header_timeout,
+                public_body_timeout,
+                handshake_max_skew, 
+                rate_limit_client_rps,
+                rate_limit_client_burst,
+                rate_limit_ip_rps,
+                rate_limit_ip_burst,
+                rate_limit_max_wait,
+                public_max_connections,
+                public_max_client_connections,
+                max_tunnels_per_client,
+                audit_log_enable,
+                audit_log_path,
+                audit_log_max_size,
+                audit_log_max_files,
+                cache_max_entries,
+                cache_max_size,
+                cache_sweep_interval,
                 redis_enable, 
                 redis_host, 
                 redis_port, 
@@ -599,14 +1642,56 @@ async fn main() {
                     redis_port.is_none() && 
                     redis_pass.is_none() &&
                     public_endpoint.is_none() &&
-                    public_request_timeout.is_none() {
+                    public_request_timeout.is_none() &&
+                    public_max_header_bytes.is_none() &&
+                    public_max_header_count.is_none() &&
+                    public_max_body_size.is_none() &&
+                    public_header_timeout.is_none() &&
+                    public_body_timeout.is_none() &&
+                    handshake_max_skew.is_none() &&
+                    rate_limit_client_rps.is_none() &&
+                    rate_limit_client_burst.is_none() &&
+                    rate_limit_ip_rps.is_none() &&
+                    rate_limit_ip_burst.is_none() &&
+                    rate_limit_max_wait.is_none() &&
+                    public_max_connections.is_none() &&
+                    public_max_client_connections.is_none() &&
+                    max_tunnels_per_client.is_none() &&
+                    audit_log_enable.is_none() &&
+                    audit_log_path.is_none() &&
+                    audit_log_max_size.is_none() &&
+                    audit_log_max_files.is_none() &&
+                    cache_max_entries.is_none() &&
+                    cache_max_size.is_none() &&
+                    cache_sweep_interval.is_none() {
                     let mut cmd = Cli::command();
                     let error_message = format!(
-                        "At least one of the following arguments must be provided: --{}, --{}, --{}, --{}, --{}, --{}, --{} or --{}",
+                        "At least one of the following arguments must be provided: --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{}, --{} or --{}",
                         CONFIG_ARG_SV_GEN_KEY,
                         CONFIG_ARG_SV_KEY,
                         CONFIG_ARG_SV_PUBLIC_ENDPOINT,
                         CONFIG_ARG_SV_PUBLIC_REQUEST_TIMEOUT,
+                        CONFIG_ARG_SV_PUBLIC_MAX_HEADER_BYTES,
+                        CONFIG_ARG_SV_PUBLIC_MAX_HEADER_COUNT,
+                        CONFIG_ARG_SV_PUBLIC_MAX_BODY_SIZE,
+                        CONFIG_ARG_SV_PUBLIC_HEADER_TIMEOUT,
+                        CONFIG_ARG_SV_PUBLIC_BODY_TIMEOUT,
+                        CONFIG_ARG_SV_HANDSHAKE_MAX_SKEW,
+                        CONFIG_ARG_SV_RATE_LIMIT_CLIENT_RPS,
+                        CONFIG_ARG_SV_RATE_LIMIT_CLIENT_BURST,
+                        CONFIG_ARG_SV_RATE_LIMIT_IP_RPS,
+                        CONFIG_ARG_SV_RATE_LIMIT_IP_BURST,
+                        CONFIG_ARG_SV_RATE_LIMIT_MAX_WAIT,
+                        CONFIG_ARG_SV_PUBLIC_MAX_CONNECTIONS,
+                        CONFIG_ARG_SV_PUBLIC_MAX_CLIENT_CONNECTIONS,
+                        CONFIG_ARG_SV_MAX_TUNNELS_PER_CLIENT,
+                        CONFIG_ARG_SV_AUDIT_LOG_ENABLE,
+                        CONFIG_ARG_SV_AUDIT_LOG_PATH,
+                        CONFIG_ARG_SV_AUDIT_LOG_MAX_SIZE,
+                        CONFIG_ARG_SV_AUDIT_LOG_MAX_FILES,
+                        CONFIG_ARG_SV_CACHE_MAX_ENTRIES,
+                        CONFIG_ARG_SV_CACHE_MAX_SIZE,
+                        CONFIG_ARG_SV_CACHE_SWEEP_INTERVAL,
                         CONFIG_ARG_SV_REDIS_ENABLE,
                         CONFIG_ARG_SV_REDIS_HOST,
                         CONFIG_ARG_SV_REDIS_PORT,
@@ -645,6 +1730,27 @@ async fn main() {
                     (*redis_pass).clone(),
                     (*public_endpoint).clone(),
                     (*public_request_timeout).clone(),
+                    (*public_max_header_bytes).clone(),
+                    (*public_max_header_count).clone(),
+                    (*public_max_body_size).clone(),
+                    (*public_header_timeout).clone(),
+                    (*public_body_timeout).clone(),
+                    (*handshake_max_skew).clone(),
+                    (*rate_limit_client_rps).clone(),
+                    (*rate_limit_client_burst).clone(),
+                    (*rate_limit_ip_rps).clone(),
+                    (*rate_limit_ip_burst).clone(),
+                    (*rate_limit_max_wait).clone(),
+                    (*public_max_connections).clone(),
+                    (*public_max_client_connections).clone(),
+                    (*max_tunnels_per_client).clone(),
+                    (*audit_log_enable).clone(),
+                    (*audit_log_path).clone(),
+                    (*audit_log_max_size).clone(),
+                    (*audit_log_max_files).clone(),
+                    (*cache_max_entries).clone(),
+                    (*cache_max_size).clone(),
+                    (*cache_sweep_interval).clone(),
                     *force);
             }
         },
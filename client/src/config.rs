@@ -1,19 +1,134 @@
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+use std::time::Duration;
 
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
 use sha2::{Sha256, Digest};
 use mac_address::get_mac_address; 
-use tokio_native_tls::native_tls::Certificate;
+use tokio_native_tls::native_tls::{Certificate, Identity};
 
-use common::{_info, config::*};
+use common::{_info, config::*, security::{ed25519_public_key_of, generate_ed25519_keypair, parse_ed25519_public_key}};
+use common::convert::{from_json_string, to_json_string};
+use common::data::dto::public_auth::PublicAuth;
+use common::data::dto::request_allowlist::RequestAllowlist;
+use crate::data::repository::underlying_repo::UNIX_SOCKET_PREFIX;
+use crate::service::upstream_pool::LoadBalanceStrategy;
 
 #[derive(Debug, Clone)]
 pub struct ClientRequestConfig {
     pub host: Option<String>,
     pub port: u16,
-    pub use_tls: bool
+    pub use_tls: bool,
+    // additional upstream addresses (host:port) for load balancing
+    pub upstreams: Vec<String>,
+    pub lb_strategy: LoadBalanceStrategy,
+    // path to the unix domain socket of the underlying service
+    pub unix_socket: Option<String>,
+    // TLS connection to the underlying service (HTTPS upstream)
+    pub upstream_tls: Option<UpstreamTlsConfig>,
+    // serve files from a local directory instead of an underlying service
+    pub static_files: Option<StaticFileConfig>,
+    // keep-alive connections to the underlying service
+    pub connection_pool: ConnectionPoolConfig,
+    // timeouts and size limit for forwarding requests to the underlying service
+    pub forward_limits: ForwardLimitConfig,
+    // paths and methods exposed through the tunnel, everything if empty
+    pub allowlist: RequestAllowlist,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamTlsConfig {
+    // server name for SNI and certificate validation,
+    // defaults to the host of the upstream address
+    pub sni: Option<String>,
+    // path to a custom CA certificate (PEM) to trust
+    pub ca_path: Option<String>,
+    // skip certificate and hostname validation, i.e: self-signed certificates
+    pub insecure: bool,
+}
+
+impl UpstreamTlsConfig {
+    pub fn new(sni: Option<String>, ca_path: Option<String>, insecure: bool) -> Self {
+        UpstreamTlsConfig { sni, ca_path, insecure }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StaticFileConfig {
+    pub dir: String,
+    // fallback to index.html for unknown paths, i.e: single page apps
+    pub spa: bool,
+    // list the directory content when there's no index.html
+    pub listing: bool,
+}
+
+impl StaticFileConfig {
+    pub fn new(dir: String, spa: bool, listing: bool) -> Self {
+        StaticFileConfig { dir, spa, listing }
+    }
+}
+
+pub const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 8;
+pub const DEFAULT_POOL_MAX_PER_HOST: usize = 64;
+pub const DEFAULT_POOL_IDLE_TIMEOUT: u64 = 60; // in seconds
+
+#[derive(Debug, Clone)]
+pub struct ConnectionPoolConfig {
+    // max number of idle connections kept per upstream, 0 disables keep-alive
+    pub max_idle_per_host: usize,
+    // max number of open connections per upstream, 0 means unlimited
+    pub max_per_host: usize,
+    // idle connections older than this are not reused
+    pub idle_timeout: Duration,
+}
+
+impl ConnectionPoolConfig {
+    pub fn new(max_idle_per_host: usize, max_per_host: usize) -> Self {
+        ConnectionPoolConfig {
+            max_idle_per_host,
+            max_per_host,
+            idle_timeout: Duration::from_secs(DEFAULT_POOL_IDLE_TIMEOUT),
+        }
+    }
+}
+
+impl Default for ConnectionPoolConfig {
+    fn default() -> Self {
+        ConnectionPoolConfig::new(DEFAULT_POOL_MAX_IDLE_PER_HOST, DEFAULT_POOL_MAX_PER_HOST)
+    }
+}
+
+pub const DEFAULT_CONNECT_TIMEOUT: u64 = 10; // in seconds
+pub const DEFAULT_FIRST_BYTE_TIMEOUT: u64 = 30; // in seconds
+pub const DEFAULT_TOTAL_TIMEOUT: u64 = 60; // in seconds
+
+#[derive(Debug, Clone)]
+pub struct ForwardLimitConfig {
+    // zero duration disables the timeout
+    pub connect_timeout: Duration,
+    pub first_byte_timeout: Duration,
+    pub total_timeout: Duration,
+    // max size of the request and response in bytes, 0 means unlimited
+    pub max_body_size: usize,
+}
+
+impl ForwardLimitConfig {
+    pub fn new(connect_timeout: Duration, first_byte_timeout: Duration, total_timeout: Duration, max_body_size: usize) -> Self {
+        ForwardLimitConfig { connect_timeout, first_byte_timeout, total_timeout, max_body_size }
+    }
+}
+
+impl Default for ForwardLimitConfig {
+    fn default() -> Self {
+        ForwardLimitConfig::new(
+            Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
+            Duration::from_secs(DEFAULT_FIRST_BYTE_TIMEOUT),
+            Duration::from_secs(DEFAULT_TOTAL_TIMEOUT),
+            0
+        )
+    }
 }
 
 impl ClientRequestConfig {
@@ -25,19 +140,81 @@ impl ClientRequestConfig {
         ClientRequestConfig {
             host,
             port,
-            use_tls
+            use_tls,
+            upstreams: Vec::new(),
+            lb_strategy: LoadBalanceStrategy::RoundRobin,
+            unix_socket: None,
+            upstream_tls: None,
+            static_files: None,
+            connection_pool: ConnectionPoolConfig::default(),
+            forward_limits: ForwardLimitConfig::default(),
+            allowlist: RequestAllowlist::default(),
         }
     }
 
+    pub fn with_upstreams(mut self, upstreams: Vec<String>, lb_strategy: LoadBalanceStrategy) -> Self {
+        self.upstreams = upstreams;
+        self.lb_strategy = lb_strategy;
+        self
+    }
+
+    pub fn with_unix_socket(mut self, unix_socket: Option<String>) -> Self {
+        self.unix_socket = unix_socket;
+        self
+    }
+
+    pub fn with_upstream_tls(mut self, upstream_tls: Option<UpstreamTlsConfig>) -> Self {
+        self.upstream_tls = upstream_tls;
+        self
+    }
+
+    pub fn with_static_files(mut self, static_files: Option<StaticFileConfig>) -> Self {
+        self.static_files = static_files;
+        self
+    }
+
+    pub fn with_connection_pool(mut self, connection_pool: ConnectionPoolConfig) -> Self {
+        self.connection_pool = connection_pool;
+        self
+    }
+
+    pub fn with_forward_limits(mut self, forward_limits: ForwardLimitConfig) -> Self {
+        self.forward_limits = forward_limits;
+        self
+    }
+
+    pub fn with_allowlist(mut self, allowlist: RequestAllowlist) -> Self {
+        self.allowlist = allowlist;
+        self
+    }
+
     pub fn underlying_svc_address(&self) -> String {
+        if let Some(static_files) = &self.static_files {
+            return static_files.dir.clone();
+        }
+        if let Some(path) = &self.unix_socket {
+            return format!("{}{}", UNIX_SOCKET_PREFIX, path);
+        }
+
         match &self.host {
             Some(h) => format!("{}:{}", h, self.port),
             None => format!("127.0.0.1:{}", self.port)
         }
     }
+
+    // when upstreams are explicitly set, the host and port are ignored
+    pub fn underlying_svc_addresses(&self) -> Vec<String> {
+        if self.upstreams.is_empty() {
+            return vec![self.underlying_svc_address()];
+        }
+
+        self.upstreams.clone()
+    }
 }
 
 pub const CONFIG_CA_FILE_NAME: &str = "ca.crt";
+pub const CONFIG_CLIENT_CERT_FILE_NAME: &str = "client.crt";
+pub const CONFIG_CLIENT_KEY_FILE_NAME: &str = "client.key";
 
 // simple validation for config keys
 pub fn validate_configs() {
@@ -45,13 +222,21 @@ pub fn validate_configs() {
     let required_keys = [
         keys::CONFIG_KEY_CLIENT_ID,
         keys::CONFIG_KEY_CLIENT_SERVER_HOST,
-        keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY
     ];
     for key in required_keys {
         if !config.contains_key(key) {
             panic!("{} config has not been set.", key)
         }
     }
+
+    // either the private key (key auth) or the signing key must be set
+    if !config.contains_key(keys::CONFIG_KEY_CLIENT_PRIVATE_KEY) && !config.contains_key(keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY) {
+        panic!("{} or {} config has not been set.", keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY, keys::CONFIG_KEY_CLIENT_PRIVATE_KEY)
+    }
+    // with key auth the ack is verified with the pinned server public key
+    if config.contains_key(keys::CONFIG_KEY_CLIENT_PRIVATE_KEY) && !config.contains_key(keys::CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY) {
+        panic!("{} config has not been set.", keys::CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY)
+    }
 }
 
 pub fn generate_client_id(custom_id: Option<String>, force: bool) -> () {
@@ -67,9 +252,12 @@ pub fn generate_client_id(custom_id: Option<String>, force: bool) -> () {
     } else {
         custom_id.unwrap()
     };
-    set_configs(HashMap::from([
+    if let Err(e) = set_configs(HashMap::from([
         (String::from(keys::CONFIG_KEY_CLIENT_ID), id.clone())
-    ]));
+    ])) {
+        println!("{}", e);
+        return;
+    }
 
     println!("Client ID generated!");
     println!("Value: {}", id);
@@ -109,6 +297,35 @@ fn generate_client_id_from_mac_address(length: usize) -> String {
     format!("{}{}", base, random_string)
 }
 
+pub fn generate_key_pair(force: bool) -> () {
+    // check whether the private key is already generated
+    let config = get_configs_from_proc_env();
+    if let Some(private_key) = config.get(keys::CONFIG_KEY_CLIENT_PRIVATE_KEY) {
+        if !force {
+            println!("Private key is already generated. Consider using --force option to force regenerating");
+            if let Ok(public_key) = ed25519_public_key_of(private_key.clone()) {
+                println!("Public Key: {}", public_key);
+            }
+            return;
+        }
+    }
+
+    let (private_key, public_key) = generate_ed25519_keypair();
+    if let Err(e) = set_configs(HashMap::from([
+        (String::from(keys::CONFIG_KEY_CLIENT_PRIVATE_KEY), private_key)
+    ])) {
+        println!("{}", e);
+        return;
+    }
+
+    println!("Key pair generated!");
+    println!("Public Key: {}", public_key);
+    println!("Register the public key on the server, the private key never leaves this machine:");
+    println!("  trabas server client-key set --client-id [client id] --public-key {}", public_key);
+    println!("Then pin the server public key, shown by `trabas server identity-key` on the server:");
+    println!("  trabas client set-config --server-public-key [server public key]");
+}
+
 pub fn set_server_signing_key(value: String, force: bool) -> () {
     // check whether the signing key is already set
     let config = get_configs_from_proc_env();
@@ -117,15 +334,62 @@ pub fn set_server_signing_key(value: String, force: bool) -> () {
         return;
     }
 
-    set_configs(HashMap::from([
+    if let Err(e) = set_configs(HashMap::from([
         (String::from(keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY), value.clone())
-    ]));
+    ])) {
+        println!("{}", e);
+        return;
+    }
 
     println!("Server Signing Key has been set!");
     println!("Value: {}", value);
     println!("You may find the value later again in the config file")   
 }
 
+pub fn set_server_public_key(value: String, force: bool) -> () {
+    // check whether the server public key is already set
+    let config = get_configs_from_proc_env();
+    if config.contains_key(keys::CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY) && !force {
+        println!("Server Public Key is already set, please check it in the config file. Consider using --force option to force resetting");
+        return;
+    }
+    if let Err(e) = parse_ed25519_public_key(value.clone()) {
+        println!("Invalid Server Public Key: {}", e);
+        return;
+    }
+
+    if let Err(e) = set_configs(HashMap::from([
+        (String::from(keys::CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY), value.clone())
+    ])) {
+        println!("{}", e);
+        return;
+    }
+
+    println!("Server Public Key has been set!");
+    println!("Value: {}", value);
+    println!("You may find the value later again in the config file")   
+}
+
+pub fn set_token_id(value: String, force: bool) -> () {
+    // check whether the token id is already set
+    let config = get_configs_from_proc_env();
+    if config.contains_key(keys::CONFIG_KEY_CLIENT_TOKEN_ID) && !force {
+        println!("Token ID is already set, please check it in the config file. Consider using --force option to force resetting");
+        return;
+    }
+
+    if let Err(e) = set_configs(HashMap::from([
+        (String::from(keys::CONFIG_KEY_CLIENT_TOKEN_ID), value.clone())
+    ])) {
+        println!("{}", e);
+        return;
+    }
+
+    println!("Token ID has been set!");
+    println!("Value: {}", value);
+    println!("You may find the value later again in the config file")   
+}
+
 pub fn set_tls_tofu_enable(value: String, force: bool) -> () {
     // check whether the TLS Trust On First Use is already set
     let config = get_configs_from_proc_env();
@@ -134,9 +398,12 @@ pub fn set_tls_tofu_enable(value: String, force: bool) -> () {
         return;
     }
 
-    set_configs(HashMap::from([
+    if let Err(e) = set_configs(HashMap::from([
         (String::from(keys::CONFIG_KEY_CLIENT_TLS_TOFU_ENABLE), value.clone())
-    ]));
+    ])) {
+        println!("{}", e);
+        return;
+    }
 
     println!("TLS Trust On First Use has been set!");
     println!("Value: {}", value);
@@ -151,9 +418,12 @@ pub fn set_server_host(value: String, force: bool) -> () {
         return;
     }
 
-    set_configs(HashMap::from([
+    if let Err(e) = set_configs(HashMap::from([
         (String::from(keys::CONFIG_KEY_CLIENT_SERVER_HOST), value.clone())
-    ]));
+    ])) {
+        println!("{}", e);
+        return;
+    }
 
     println!("Server Host has been set!");
     println!("Value: {}", value);
@@ -168,15 +438,58 @@ pub fn set_server_port(value: u16, force: bool) -> () {
         return;
     }
 
-    set_configs(HashMap::from([
+    if let Err(e) = set_configs(HashMap::from([
         (String::from(keys::CONFIG_KEY_CLIENT_SERVER_PORT), format!("{}", value))
-    ]));
+    ])) {
+        println!("{}", e);
+        return;
+    }
 
     println!("Server Port has been set!");
     println!("Value: {}", value);
     println!("You may find the value later again in the config file")   
 }
 
+// credentials required to access the public endpoints of this client
+pub fn get_public_auth() -> Vec<PublicAuth> {
+    std::env::var(keys::CONFIG_KEY_CLIENT_PUBLIC_AUTH)
+        .ok()
+        .and_then(|value| from_json_string(&value))
+        .unwrap_or_default()
+}
+
+// only the hash of the credential is stored, it replaces the existing one of the same scheme
+pub fn set_public_auth(value: PublicAuth, force: bool) -> () {
+    let mut public_auth = get_public_auth();
+    if public_auth.iter().any(|auth| auth.scheme == value.scheme) && !force {
+        println!("Public {:?} Auth is already set, please check it in the config file. Consider using --force option to force resetting", value.scheme);
+        return;
+    }
+    public_auth.retain(|auth| auth.scheme != value.scheme);
+    public_auth.push(value.clone());
+
+    if let Err(e) = set_configs(HashMap::from([
+        (String::from(keys::CONFIG_KEY_CLIENT_PUBLIC_AUTH), to_json_string(&public_auth))
+    ])) {
+        println!("{}", e);
+        return;
+    }
+
+    println!("Public {:?} Auth has been set!", value.scheme);
+    println!("The public endpoints of this client require the credential from the next connection")
+}
+
+pub fn clear_public_auth() -> () {
+    if let Err(e) = set_configs(HashMap::from([
+        (String::from(keys::CONFIG_KEY_CLIENT_PUBLIC_AUTH), to_json_string(&Vec::<PublicAuth>::new()))
+    ])) {
+        println!("{}", e);
+        return;
+    }
+
+    println!("Public Auth has been cleared!");
+}
+
 // get CA certificate for TLS connection
 pub fn get_ca_certificate() -> Result<Certificate, String> {
     let config_path = get_config_path();
@@ -188,6 +501,25 @@ pub fn get_ca_certificate() -> Result<Certificate, String> {
     Ok(ca)
 }
 
+// client certificate for mutual TLS, issued by the server CA
+// returns None when the certificate is not provided
+pub fn get_client_identity() -> Result<Option<Identity>, String> {
+    let config_path = get_config_path();
+    let cert_path = format!("{}/ssl/{}", config_path, CONFIG_CLIENT_CERT_FILE_NAME);
+    let key_path = format!("{}/ssl/{}", config_path, CONFIG_CLIENT_KEY_FILE_NAME);
+    if !Path::new(&cert_path).exists() {
+        return Ok(None);
+    }
+
+    let cert_data = fs::read(cert_path)
+        .map_err(|e| format!("Error reading client certificate file: {}",  e))?;
+    let key_data = fs::read(key_path)
+        .map_err(|e| format!("Error reading client key file: {}",  e))?;
+    let identity = Identity::from_pkcs8(&cert_data, &key_data)
+        .map_err(|e| format!("Error loading client certificate: {}",  e))?;
+    Ok(Some(identity))
+}
+
 pub fn validate_tofu(cert: Certificate) -> Result<(), String> {
     // get the fingerprint from the certificate
     let der: Vec<u8> = cert.to_der().map_err(|e| format!("Error converting certificate to DER: {}", e))?;
@@ -204,7 +536,7 @@ pub fn validate_tofu(cert: Certificate) -> Result<(), String> {
         _info!("First connection detected, writing server fingerprint to config...");
         set_configs(HashMap::from([
             (String::from(keys::CONFIG_KEY_CLIENT_SERVER_FINGERPRINT), fingerprint_hex)
-        ]));
+        ]))?;
     }
 
     Ok(())
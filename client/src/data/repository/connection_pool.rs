@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::net::TcpStreamTLS;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+use crate::config::ConnectionPoolConfig;
+
+struct IdleConnection {
+    stream: TcpStreamTLS,
+    idle_since: Instant,
+}
+
+// keep-alive connections to the upstreams, keyed by the upstream address
+pub struct ConnectionPool {
+    config: ConnectionPoolConfig,
+    idle: Mutex<HashMap<String, Vec<IdleConnection>>>,
+    limits: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl ConnectionPool {
+    pub fn new(config: ConnectionPoolConfig) -> Self {
+        ConnectionPool {
+            config,
+            idle: Mutex::new(HashMap::new()),
+            limits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // wait until the number of open connections to the host is below the limit,
+    // the slot is released once the returned permit is dropped
+    pub async fn permit(&self, host: &str) -> Result<Option<OwnedSemaphorePermit>, String> {
+        if self.config.max_per_host == 0 {
+            return Ok(None);
+        }
+
+        let semaphore = {
+            let mut limits = self.limits.lock().await;
+            limits
+                .entry(host.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.config.max_per_host)))
+                .clone()
+        };
+
+        semaphore.acquire_owned().await
+            .map(Some)
+            .map_err(|e| format!("Error acquiring upstream connection slot: {}", e))
+    }
+
+    // take the most recently used idle connection which is still alive,
+    // expired or broken ones are dropped along the way
+    pub async fn take(&self, host: &str) -> Option<TcpStreamTLS> {
+        loop {
+            let connection = {
+                let mut idle = self.idle.lock().await;
+                idle.get_mut(host)?.pop()?
+            };
+
+            if connection.idle_since.elapsed() > self.config.idle_timeout {
+                continue;
+            }
+
+            let mut stream = connection.stream;
+            if is_alive(&mut stream).await {
+                return Some(stream);
+            }
+        }
+    }
+
+    // put the connection back to be reused, unless the idle limit is reached
+    pub async fn release(&self, host: &str, stream: TcpStreamTLS) {
+        let mut idle = self.idle.lock().await;
+        let connections = idle.entry(host.to_string()).or_default();
+        connections.retain(|c| c.idle_since.elapsed() <= self.config.idle_timeout);
+        if connections.len() >= self.config.max_idle_per_host {
+            return;
+        }
+
+        connections.push(IdleConnection { stream, idle_since: Instant::now() });
+    }
+
+    pub async fn idle_count(&self, host: &str) -> usize {
+        self.idle.lock().await.get(host).map(|c| c.len()).unwrap_or(0)
+    }
+}
+
+// an idle connection must not have anything to read,
+// EOF means the upstream has closed it and any data means it's out of sync
+async fn is_alive(stream: &mut TcpStreamTLS) -> bool {
+    let mut buffer = [0; 1];
+    timeout(Duration::ZERO, stream.read(&mut buffer)).await.is_err()
+}
+
+// check whether the connection can be reused after reading the response,
+// the response must be framed and none of the sides asked to close it
+pub fn is_reusable(request: &[u8], response: &[u8]) -> bool {
+    let response_head = head_of(response);
+    let request_head = head_of(request);
+    let connection_of = |head: &str| head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            if name.trim().eq_ignore_ascii_case("connection") {
+                Some(value.trim().to_lowercase())
+            } else {
+                None
+            }
+        })
+        .unwrap_or_default();
+
+    let request_connection = connection_of(&request_head);
+    let response_connection = connection_of(&response_head);
+    if request_connection.contains("close") || response_connection.contains("close") {
+        return false;
+    }
+
+    // HTTP/1.0 closes the connection by default
+    let keep_alive_default = !response_head.starts_with("HTTP/1.0");
+    if !keep_alive_default && !response_connection.contains("keep-alive") {
+        return false;
+    }
+
+    response_head.lines().any(|line| {
+        let line = line.to_lowercase();
+        line.starts_with("content-length:") ||
+        (line.starts_with("transfer-encoding:") && line.contains("chunked"))
+    })
+}
+
+fn head_of(message: &[u8]) -> String {
+    let end = message
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap_or(message.len());
+
+    String::from_utf8_lossy(&message[..end]).to_string()
+}
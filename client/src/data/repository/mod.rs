@@ -1 +1,3 @@
+pub mod connection_pool;
+pub mod static_file_repo;
 pub mod underlying_repo;
\ No newline at end of file
@@ -0,0 +1,347 @@
+use std::io::SeekFrom;
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use http::{header, Method, Request, Response, StatusCode};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use common::convert::{parse_request_bytes, response_to_bytes};
+use crate::config::StaticFileConfig;
+use super::underlying_repo::{ForwardError, UnderlyingRepo};
+
+const INDEX_FILE: &str = "index.html";
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+// serve the tunneled requests from a local directory, without the need of a web server
+pub struct StaticFileRepo {
+    root: PathBuf,
+    spa: bool,
+    listing: bool,
+}
+
+impl StaticFileRepo {
+    pub fn new(config: StaticFileConfig) -> Self {
+        StaticFileRepo {
+            root: PathBuf::from(config.dir),
+            spa: config.spa,
+            listing: config.listing,
+        }
+    }
+
+    async fn handle(&self, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+        if request.method() != Method::GET && request.method() != Method::HEAD {
+            let mut response = text_response(StatusCode::METHOD_NOT_ALLOWED, "Method Not Allowed");
+            response.headers_mut().insert(header::ALLOW, header::HeaderValue::from_static("GET, HEAD"));
+            return response;
+        }
+
+        let request_path = request.uri().path().to_string();
+        let path = match self.resolve_path(&request_path).await {
+            Some(value) => value,
+            None => return text_response(StatusCode::NOT_FOUND, "Not Found"),
+        };
+
+        let metadata = match fs::metadata(&path).await {
+            Ok(value) => value,
+            Err(_) => return self.fallback(request).await,
+        };
+
+        if metadata.is_dir() {
+            // keep relative links of the directory working
+            if !request_path.ends_with('/') {
+                let name = request_path.rsplit('/').next().unwrap_or("");
+                let mut response = text_response(StatusCode::MOVED_PERMANENTLY, "Moved Permanently");
+                if let Ok(location) = header::HeaderValue::from_str(&format!("{}/", name)) {
+                    response.headers_mut().insert(header::LOCATION, location);
+                }
+                return response;
+            }
+
+            let index_path = path.join(INDEX_FILE);
+            if fs::metadata(&index_path).await.map(|m| m.is_file()).unwrap_or(false) {
+                return file_response(request, &index_path).await;
+            }
+            if self.listing {
+                return listing_response(&path, &request_path).await;
+            }
+
+            return text_response(StatusCode::FORBIDDEN, "Forbidden");
+        }
+
+        file_response(request, &path).await
+    }
+
+    // map the request path into a file path under the root directory,
+    // returns None when the path escapes the root, i.e: ../ or symlinks pointing outside
+    async fn resolve_path(&self, request_path: &str) -> Option<PathBuf> {
+        let decoded = percent_decode(request_path)?;
+        let mut path = self.root.clone();
+        for component in Path::new(decoded.trim_start_matches('/')).components() {
+            match component {
+                Component::Normal(segment) => path.push(segment),
+                Component::CurDir => {},
+                _ => return None,
+            }
+        }
+
+        // the file might not exist, let the caller decide how to handle it
+        let canonical = match fs::canonicalize(&path).await {
+            Ok(value) => value,
+            Err(_) => return Some(path),
+        };
+        let canonical_root = fs::canonicalize(&self.root).await.ok()?;
+        if !canonical.starts_with(&canonical_root) {
+            return None;
+        }
+
+        Some(canonical)
+    }
+
+    // single page apps route on the browser side, so unknown paths
+    // without a file extension are answered with the root index.html
+    async fn fallback(&self, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+        let path = request.uri().path();
+        let has_extension = path.rsplit('/').next().map(|name| name.contains('.')).unwrap_or(false);
+        if self.spa && !has_extension {
+            let index_path = self.root.join(INDEX_FILE);
+            if fs::metadata(&index_path).await.map(|m| m.is_file()).unwrap_or(false) {
+                return file_response(request, &index_path).await;
+            }
+        }
+
+        text_response(StatusCode::NOT_FOUND, "Not Found")
+    }
+}
+
+#[async_trait]
+impl UnderlyingRepo for StaticFileRepo {
+    async fn forward(&self, request: Vec<u8>, _: String) -> Result<Vec<u8>, ForwardError> {
+        let request = match parse_request_bytes(&request) {
+            Some(value) => value,
+            None => return Ok(response_to_bytes(&text_response(StatusCode::BAD_REQUEST, "Bad Request"))),
+        };
+
+        let response = self.handle(&request).await;
+
+        Ok(response_to_bytes(&response))
+    }
+
+    async fn test_connection(&self, _: String) -> Result<(), String> {
+        match fs::metadata(&self.root).await {
+            Ok(metadata) if metadata.is_dir() => Ok(()),
+            Ok(_) => Err(format!("{} is not a directory", self.root.display())),
+            Err(e) => Err(format!("Error reading directory {}: {}", self.root.display(), e)),
+        }
+    }
+}
+
+async fn file_response(request: &Request<Vec<u8>>, path: &Path) -> Response<Vec<u8>> {
+    let total = match fs::metadata(path).await {
+        Ok(metadata) => metadata.len() as usize,
+        Err(_) => return text_response(StatusCode::NOT_FOUND, "Not Found"),
+    };
+    let mime_type = mime_type_of(path);
+
+    // an empty file has no range to satisfy, so the range is ignored and the empty body is served
+    let range = request.headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| total > 0);
+    let (status, start, length, content_range) = match range {
+        Some(value) => match parse_range(value, total) {
+            Some((start, end)) => (
+                StatusCode::PARTIAL_CONTENT,
+                start,
+                end - start + 1,
+                Some(format!("bytes {}-{}/{}", start, end, total))
+            ),
+            None => {
+                let mut response = text_response(StatusCode::RANGE_NOT_SATISFIABLE, "Range Not Satisfiable");
+                if let Ok(value) = header::HeaderValue::from_str(&format!("bytes */{}", total)) {
+                    response.headers_mut().insert(header::CONTENT_RANGE, value);
+                }
+                return response;
+            }
+        },
+        None => (StatusCode::OK, 0, total, None),
+    };
+
+    // HEAD has the same headers as GET, but without body
+    let body = if request.method() == Method::HEAD {
+        Vec::new()
+    } else {
+        match read_file_range(path, start, length).await {
+            Ok(value) => value,
+            Err(_) => return text_response(StatusCode::NOT_FOUND, "Not Found"),
+        }
+    };
+    let mut builder = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, mime_type)
+        .header(header::CONTENT_LENGTH, length)
+        .header(header::ACCEPT_RANGES, "bytes");
+    if let Some(value) = content_range {
+        builder = builder.header(header::CONTENT_RANGE, value);
+    }
+
+    builder.body(body).unwrap_or_else(|_| text_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error"))
+}
+
+// read only the requested part, a range of a large file should not load the whole file
+async fn read_file_range(path: &Path, start: usize, length: usize) -> std::io::Result<Vec<u8>> {
+    let mut file = fs::File::open(path).await?;
+    if start > 0 {
+        file.seek(SeekFrom::Start(start as u64)).await?;
+    }
+    let mut data = vec![0; length];
+    file.read_exact(&mut data).await?;
+
+    Ok(data)
+}
+
+async fn listing_response(path: &Path, request_path: &str) -> Response<Vec<u8>> {
+    let mut entries = match fs::read_dir(path).await {
+        Ok(value) => value,
+        Err(_) => return text_response(StatusCode::FORBIDDEN, "Forbidden"),
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_dir = entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false);
+        names.push(if is_dir { format!("{}/", name) } else { name });
+    }
+    names.sort();
+
+    let title = html_escape(request_path);
+    let mut body = format!("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {}</title></head>\n<body>\n<h1>Index of {}</h1>\n<ul>\n", title, title);
+    if request_path != "/" {
+        body.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for name in names {
+        body.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", percent_encode(&name), html_escape(&name)));
+    }
+    body.push_str("</ul>\n</body>\n</html>\n");
+
+    let body = body.into_bytes();
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .unwrap_or_else(|_| text_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error"))
+}
+
+fn text_response(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    let body = message.as_bytes().to_vec();
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static("text/plain; charset=utf-8"));
+    response.headers_mut().insert(header::CONTENT_LENGTH, header::HeaderValue::from(message.len()));
+    response
+}
+
+// only a single range is supported, i.e: bytes=0-499, bytes=500-, bytes=-500
+// returns the inclusive start and end, or None when it's not satisfiable
+pub fn parse_range(value: &str, total: usize) -> Option<(usize, usize)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') || total == 0 {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            (total.saturating_sub(suffix), total - 1)
+        },
+        (start, "") => (start.parse().ok()?, total - 1),
+        (start, end) => (start.parse().ok()?, end.parse::<usize>().ok()?.min(total - 1)),
+    };
+    if start > end || start >= total {
+        return None;
+    }
+
+    Some((start, end))
+}
+
+pub fn mime_type_of(path: &Path) -> &'static str {
+    let extension = path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" | "log" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => DEFAULT_MIME_TYPE,
+    }
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    // reject null bytes, those are never a valid file name
+    if decoded.contains(&0) {
+        return None;
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+fn percent_encode(value: &str) -> String {
+    value.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
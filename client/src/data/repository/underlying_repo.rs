@@ -1,40 +1,235 @@
-use common::net::{HttpReader, TcpStreamTLS};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use common::net::{HttpReadError, HttpReadErrorKind, HttpReadLimits, HttpReader, TcpStreamTLS};
+use http::StatusCode;
 // use log::info;
 use tokio::net::TcpStream;
+use tokio::time::timeout;
 use async_trait::async_trait;
-use tokio::io::AsyncWriteExt;
+use tokio_native_tls::{native_tls, TlsConnector};
+
+use crate::config::{ConnectionPoolConfig, ForwardLimitConfig, UpstreamTlsConfig};
+use crate::service::upstream_pool::is_idempotent_request;
+use super::connection_pool::{is_reusable, ConnectionPool};
+
+// upstream address prefix for unix domain socket, i.e: unix:/var/run/app.sock
+pub const UNIX_SOCKET_PREFIX: &str = "unix:";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForwardErrorKind {
+    // the underlying service cannot be connected, i.e: connection refused
+    ConnectionRefused,
+    // connect, first byte or total timeout exceeded
+    Timeout,
+    // the request exceeds the size limit
+    PayloadTooLarge,
+    // the response of the underlying service exceeds the size limit
+    ResponseTooLarge,
+    // any other failure, i.e: connection closed without response
+    BadGateway,
+    // the request is not in the allowlist of the client
+    Forbidden,
+}
+
+impl ForwardErrorKind {
+    // machine-readable code for the public response
+    pub fn code(&self) -> &'static str {
+        match self {
+            ForwardErrorKind::ConnectionRefused => "upstream_connection_refused",
+            ForwardErrorKind::Timeout => "upstream_timeout",
+            ForwardErrorKind::PayloadTooLarge => "payload_too_large",
+            ForwardErrorKind::ResponseTooLarge => "upstream_response_too_large",
+            ForwardErrorKind::BadGateway => "upstream_error",
+            ForwardErrorKind::Forbidden => "request_not_allowed",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ForwardErrorKind::ConnectionRefused => StatusCode::BAD_GATEWAY,
+            ForwardErrorKind::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            ForwardErrorKind::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ForwardErrorKind::ResponseTooLarge => StatusCode::BAD_GATEWAY,
+            ForwardErrorKind::BadGateway => StatusCode::BAD_GATEWAY,
+            ForwardErrorKind::Forbidden => StatusCode::FORBIDDEN,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ForwardErrorKind::ConnectionRefused => "Underlying service is unreachable",
+            ForwardErrorKind::Timeout => "Underlying service timed out",
+            ForwardErrorKind::PayloadTooLarge => "Payload exceeds the size limit",
+            ForwardErrorKind::ResponseTooLarge => "Response of the underlying service exceeds the size limit",
+            ForwardErrorKind::BadGateway => "Request cannot be processed",
+            ForwardErrorKind::Forbidden => "Request is not allowed by the client",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ForwardError {
+    pub kind: ForwardErrorKind,
+    pub message: String,
+}
+
+impl ForwardError {
+    pub fn new(kind: ForwardErrorKind, message: String) -> Self {
+        ForwardError { kind, message }
+    }
+}
+
+impl fmt::Display for ForwardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for ForwardError {
+    fn from(message: String) -> Self {
+        ForwardError::new(ForwardErrorKind::BadGateway, message)
+    }
+}
+
+impl From<HttpReadError> for ForwardError {
+    fn from(error: HttpReadError) -> Self {
+        let kind = match error.kind {
+            HttpReadErrorKind::PayloadTooLarge => ForwardErrorKind::PayloadTooLarge,
+            HttpReadErrorKind::Timeout => ForwardErrorKind::Timeout,
+            _ => ForwardErrorKind::BadGateway,
+        };
+        ForwardError::new(kind, error.message)
+    }
+}
 
 // TODO: couldn't think of a better name, might change it in the future.
 #[async_trait]
 pub trait UnderlyingRepo: Send + Sync {
-    async fn forward(&self, request: Vec<u8>, host: String) -> Result<Vec<u8>, String>;
+    async fn forward(&self, request: Vec<u8>, host: String) -> Result<Vec<u8>, ForwardError>;
     async fn test_connection(&self, host: String) -> Result<(), String>;
 }
 
-pub struct UnderlyingRepoImpl { }
+pub struct UnderlyingRepoImpl {
+    tls_connector: Option<TlsConnector>,
+    tls_sni: Option<String>,
+    pool: ConnectionPool,
+    limits: ForwardLimitConfig,
+}
 
 impl UnderlyingRepoImpl {
     pub fn new() -> Self {
-        UnderlyingRepoImpl {  }
+        UnderlyingRepoImpl { 
+            tls_connector: None, 
+            tls_sni: None, 
+            pool: ConnectionPool::new(ConnectionPoolConfig::default()),
+            limits: ForwardLimitConfig::default()
+        }
     }
-}
 
-#[async_trait]
-impl UnderlyingRepo for UnderlyingRepoImpl {
-    async fn forward(&self, request: Vec<u8>, host: String) -> Result<Vec<u8>, String> {
-        //_info!("Forwarding request: {} to host: {}", String::from_utf8(request.clone()).unwrap(), host.clone());
-        let stream = TcpStream::connect(host.as_str()).await
-            .map_err(|e| format!("Error connecting to underlying service: {}", e))?;
+    // the TLS handshake is performed to the upstream before forwarding any request
+    pub fn with_tls(config: UpstreamTlsConfig) -> Result<Self, String> {
+        let mut connector_builder = native_tls::TlsConnector::builder();
+        if config.insecure {
+            connector_builder.danger_accept_invalid_certs(true);
+            connector_builder.danger_accept_invalid_hostnames(true);
+        }
+        if let Some(ca_path) = &config.ca_path {
+            let ca_data = fs::read(ca_path)
+                .map_err(|e| format!("Error reading upstream CA file: {}", e))?;
+            let ca = native_tls::Certificate::from_pem(&ca_data)
+                .map_err(|e| format!("Error loading upstream CA certificate: {}", e))?;
+            connector_builder.add_root_certificate(ca);
+        }
+        let connector = connector_builder.build()
+            .map_err(|e| format!("Failed to create upstream TLS connector: {}", e))?;
+
+        Ok(UnderlyingRepoImpl { 
+            tls_connector: Some(TlsConnector::from(connector)), 
+            tls_sni: config.sni, 
+            pool: ConnectionPool::new(ConnectionPoolConfig::default()),
+            limits: ForwardLimitConfig::default()
+        })
+    }
+
+    pub fn with_connection_pool(mut self, config: ConnectionPoolConfig) -> Self {
+        self.pool = ConnectionPool::new(config);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ForwardLimitConfig) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub async fn idle_connections(&self, host: &str) -> usize {
+        self.pool.idle_count(host).await
+    }
+
+    async fn connect(&self, host: &str) -> Result<TcpStreamTLS, ForwardError> {
+        let message = format!("Connecting to underlying service at {} timed out", host);
+        with_timeout(self.limits.connect_timeout, self.connect_without_timeout(host), message).await
+    }
+
+    async fn connect_without_timeout(&self, host: &str) -> Result<TcpStreamTLS, ForwardError> {
+        if let Some(path) = host.strip_prefix(UNIX_SOCKET_PREFIX) {
+            // never fall back to plain text silently
+            if self.tls_connector.is_some() {
+                return Err(format!("TLS is not supported for unix domain socket upstream {}", host).into());
+            }
+            return connect_unix(path).await;
+        }
+
+        let stream = TcpStream::connect(host).await
+            .map_err(|e| connect_error(e, host))?;
+        if let Some(connector) = &self.tls_connector {
+            // use the host part of the address for SNI, if not overridden
+            let domain = match &self.tls_sni {
+                Some(sni) => sni.clone(),
+                None => host_of_address(host),
+            };
+            let tls_stream = connector.connect(domain.as_str(), stream).await
+                .map_err(|e| format!("Error establishing TLS connection to underlying service: {}", e))?;
+            let (read_stream, write_stream) = tokio::io::split(tls_stream);
+
+            return Ok(TcpStreamTLS::from_tcp_tls(read_stream, write_stream));
+        }
+
         let (read_stream, write_stream) = tokio::io::split(stream);
-        let mut stream = TcpStreamTLS::from_tcp(read_stream, write_stream);
-        
-        // forward request
-        stream.write_all(&request).await
-            .map_err(|e| format!("Error connecting to underlying service: {}", e))?;
-        
-        // read response
-        let mut res = Vec::new();
-        HttpReader::from_tcp_stream(&mut stream).read(&mut res, false).await?;
+
+        Ok(TcpStreamTLS::from_tcp(read_stream, write_stream))
+    }
+
+    async fn forward_without_timeout(&self, request: &[u8], host: &str) -> Result<Vec<u8>, ForwardError> {
+        let _permit = self.pool.permit(host).await?;
+
+        // reuse a keep-alive connection, the upstream might have closed it
+        // in the meantime, so retry once with a new connection if it fails.
+        // once the request is written, only idempotent ones are sent again
+        if let Some(mut stream) = self.pool.take(host).await {
+            if write_request(&mut stream, request).await.is_ok() {
+                match self.read_response(&mut stream).await {
+                    Ok(res) => {
+                        if is_reusable(request, &res) {
+                            self.pool.release(host, stream).await;
+                        }
+                        return Ok(res);
+                    },
+                    Err(e) if e.kind != ForwardErrorKind::BadGateway || !is_idempotent_request(request) => return Err(e),
+                    Err(_) => {}
+                }
+            }
+        }
+
+        let mut stream = self.connect(host).await?;
+        write_request(&mut stream, request).await?;
+        let res = self.read_response(&mut stream).await?;
+        if is_reusable(request, &res) {
+            self.pool.release(host, stream).await;
+        }
 
         // this is for debugging
         // let res_str = match String::from_utf8(res.clone()) {
@@ -53,9 +248,117 @@ impl UnderlyingRepo for UnderlyingRepoImpl {
         Ok(res)
     }
 
+    async fn read_response(&self, stream: &mut TcpStreamTLS) -> Result<Vec<u8>, ForwardError> {
+        // wait for the first byte of the response
+        let mut buffer = [0; 1024];
+        let first_read = async {
+            stream.read(&mut buffer).await
+                .map_err(|e| ForwardError::from(format!("Error reading response from underlying service: {}", e)))
+        };
+        let message = String::from("Underlying service did not start responding in time");
+        let n = with_timeout(self.limits.first_byte_timeout, first_read, message).await?;
+        if n == 0 {
+            return Err(String::from("Underlying service closed the connection without response").into());
+        }
+
+        // read the rest of response, stopping as soon as the limit is exceeded
+        let mut res = buffer[..n].to_vec();
+        let max_size = self.limits.max_body_size;
+        let read_limits = HttpReadLimits { max_header_bytes: max_size, max_body_size: max_size, ..Default::default() };
+        HttpReader::from_tcp_stream(stream).with_limits(read_limits).read(&mut res, false).await
+            .map_err(|e| match e.kind {
+                // not the fault of the public client
+                HttpReadErrorKind::HeaderTooLarge | HttpReadErrorKind::PayloadTooLarge => ForwardError::new(
+                    ForwardErrorKind::ResponseTooLarge,
+                    format!("Response of underlying service exceeds the limit of {} bytes: {}", max_size, e.message)
+                ),
+                _ => ForwardError::from(e),
+            })?;
+        if max_size > 0 && res.len() > max_size {
+            return Err(ForwardError::new(
+                ForwardErrorKind::ResponseTooLarge, 
+                format!("Response size {} bytes exceeds the limit of {} bytes", res.len(), max_size)
+            ));
+        }
+
+        Ok(res)
+    }
+}
+
+async fn write_request(stream: &mut TcpStreamTLS, request: &[u8]) -> Result<(), ForwardError> {
+    stream.write_all(request).await
+        .map_err(|e| ForwardError::from(format!("Error writing request to underlying service: {}", e)))
+}
+
+// zero duration means no timeout
+async fn with_timeout<T, F>(duration: Duration, future: F, message: String) -> Result<T, ForwardError>
+where
+    F: Future<Output = Result<T, ForwardError>>
+{
+    if duration.is_zero() {
+        return future.await;
+    }
+
+    match timeout(duration, future).await {
+        Ok(res) => res,
+        Err(_) => Err(ForwardError::new(ForwardErrorKind::Timeout, format!("{} ({} ms)", message, duration.as_millis()))),
+    }
+}
+
+fn connect_error(e: std::io::Error, host: &str) -> ForwardError {
+    let message = format!("Error connecting to underlying service at {}: {}", host, e);
+    match e.kind() {
+        ErrorKind::TimedOut => ForwardError::new(ForwardErrorKind::Timeout, message),
+        _ => ForwardError::new(ForwardErrorKind::ConnectionRefused, message),
+    }
+}
+
+// get host from an address with port, i.e: localhost:8443 -> localhost, [::1]:8443 -> ::1
+pub fn host_of_address(address: &str) -> String {
+    if let Some(rest) = address.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or_default().to_string();
+    }
+    // an ipv6 address without brackets has no port
+    if address.matches(':').count() > 1 {
+        return address.to_string();
+    }
+
+    match address.rsplit_once(':') {
+        Some((host, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => host.to_string(),
+        _ => address.to_string(),
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &str) -> Result<TcpStreamTLS, ForwardError> {
+    let stream = tokio::net::UnixStream::connect(path).await
+        .map_err(|e| connect_error(e, path))?;
+
+    Ok(TcpStreamTLS::from_io(stream))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_: &str) -> Result<TcpStreamTLS, ForwardError> {
+    Err(ForwardError::new(ForwardErrorKind::ConnectionRefused, String::from("Unix domain socket is not supported on this platform")))
+}
+
+#[async_trait]
+impl UnderlyingRepo for UnderlyingRepoImpl {
+    async fn forward(&self, request: Vec<u8>, host: String) -> Result<Vec<u8>, ForwardError> {
+        //_info!("Forwarding request: {} to host: {}", String::from_utf8(request.clone()).unwrap(), host.clone());
+        if self.limits.max_body_size > 0 && request.len() > self.limits.max_body_size {
+            return Err(ForwardError::new(
+                ForwardErrorKind::PayloadTooLarge, 
+                format!("Request size {} bytes exceeds the limit of {} bytes", request.len(), self.limits.max_body_size)
+            ));
+        }
+
+        let message = format!("Underlying service at {} did not complete the response in time", host);
+        with_timeout(self.limits.total_timeout, self.forward_without_timeout(&request, &host), message).await
+    }
+
     async fn test_connection(&self, host: String) -> Result<(), String> {
-        let mut stream = TcpStream::connect(host.as_str()).await
-            .map_err(|e| format!("Error connecting to underlying service: {}", e))?;
+        let mut stream = self.connect(host.as_str()).await.map_err(|e| e.message)?;
 
         stream.shutdown().await.map_err(|e: std::io::Error| format!("Error closing connection: {}", e))?;
 
@@ -1,11 +1,10 @@
 use std::{sync::Arc, time::Duration, vec};
 
-use http::StatusCode;
-
 use common::{
     convert::{from_json_slice, to_json_vec}, 
-    data::dto::{public_request::PublicRequest, public_response::PublicResponse, tunnel_ack::TunnelAck, tunnel_client::TunnelClient}, 
+    data::dto::{public_request::PublicRequest, public_response::PublicResponse, tunnel_ack::TunnelAck, tunnel_challenge::TunnelChallenge, tunnel_client::TunnelClient}, 
     logger::append_header_log,
+    security::validate_signature_ed25519,
     net::{
         http_json_response_as_bytes, 
         prepare_packet, 
@@ -21,15 +20,15 @@ use tokio_native_tls::{native_tls, TlsConnector};
 
 use common::{validate_signature, _error, _info};
 use common::{config::keys as config_keys};
-use crate::{config::{get_ca_certificate, validate_tofu}, service::underlying_service::UnderlyingService};
+use crate::{config::{get_ca_certificate, get_client_identity, get_public_auth, validate_tofu}, service::underlying_service::UnderlyingService};
 use crate::version::{get_client_version, get_min_server_version};
 
 const SOCKET_TIMEOUT_MILLIS: u64 = 5000; // 5 seconds timeout
 
-pub async fn register_handler(underlying_host: String, service: UnderlyingService, use_tls: bool) -> () {
+pub async fn register_handler(service: UnderlyingService, use_tls: bool) -> () {
     // initial connection validation for underlying service
-    if service.test_connection(underlying_host.clone()).await.is_err() {
-        _error!("Failed to connect to the underlying service at {}. Please check the service is running and accessible.", underlying_host);
+    if service.test_connection().await.is_err() {
+        _error!("Failed to connect to the underlying service at {}. Please check the service is running and accessible.", service.upstream_addresses().join(", "));
         return;
     }
     
@@ -71,6 +70,18 @@ pub async fn register_handler(underlying_host: String, service: UnderlyingServic
                 let cert = get_ca_certificate().unwrap();
                 connector_builder.add_root_certificate(cert);
             }
+            // present the client certificate when the server requires mutual TLS
+            match get_client_identity() {
+                Ok(Some(identity)) => {
+                    _info!("Client certificate found, using mutual TLS.");
+                    connector_builder.identity(identity);
+                },
+                Ok(None) => {},
+                Err(e) => {
+                    _error!("Failed to load client certificate: {}", e);
+                    continue;
+                }
+            }
             let connector = match connector_builder.build() {
                 Ok(connector) => TlsConnector::from(connector),
                 Err(e) => {
@@ -112,7 +123,7 @@ pub async fn register_handler(underlying_host: String, service: UnderlyingServic
             (TcpStreamTLS::from_tcp_read(read_stream), TcpStreamTLS::from_tcp_write(write_stream))
          };
         // send connection request to server service
-        let tunnel_client = get_tunnel_client();
+        let tunnel_client = get_tunnel_client().with_allowlist(service.allowlist());
         let packet = prepare_packet(to_json_vec(&tunnel_client));
 
         _info!("Connecting to server service for authentication and registration...");
@@ -122,20 +133,52 @@ pub async fn register_handler(underlying_host: String, service: UnderlyingServic
             continue;
         }
         
-        let mut server_response = Vec::new();
-        if let Err(e) = read_bytes_from_socket_for_internal(&mut read_stream, &mut server_response, SOCKET_TIMEOUT_MILLIS).await {
-            _error!("Failed to read server service response: {}", e);
-            continue;
-        }
-        
-        let (packets, _) = separate_packets(server_response);
-        let server_response = match packets.get(0) {
-            Some(data) => data,
-            None => {
+        let mut server_response = match read_server_packet(&mut read_stream).await {
+            Ok(Some(data)) => data,
+            Ok(None) => {
                 _error!("Handshake failed: Empty response from server service.");
                 return;
+            },
+            Err(e) => {
+                _error!("Failed to read server service response: {}", e);
+                continue;
             }
         };
+
+        // the server nonce must be signed before getting the ack,
+        // the server might reject the connection right away with the ack instead
+        let mut challenge_nonce = None;
+        if let Some(challenge) = from_json_slice::<TunnelChallenge>(&server_response) {
+            let response = if tunnel_client.key_auth {
+                challenge.respond(&tunnel_client, get_private_key())
+            } else {
+                Ok(challenge.respond_with_hmac(&tunnel_client, get_server_secret()))
+            };
+            let response = match response {
+                Ok(value) => value,
+                Err(e) => {
+                    _error!("Handshake failed: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = write_stream.write_all(&prepare_packet(to_json_vec(&response))).await {
+                _error!("Failed to send challenge response: {}", e);
+                continue;
+            }
+
+            server_response = match read_server_packet(&mut read_stream).await {
+                Ok(Some(data)) => data,
+                Ok(None) => {
+                    _error!("Handshake failed: Empty response from server service.");
+                    return;
+                },
+                Err(e) => {
+                    _error!("Failed to read server service response: {}", e);
+                    continue;
+                }
+            };
+            challenge_nonce = Some(challenge.nonce);
+        }
         
         let ack: TunnelAck = match from_json_slice(&server_response) {
             Some(value) => value,
@@ -151,8 +194,10 @@ pub async fn register_handler(underlying_host: String, service: UnderlyingServic
         }
 
         // check server signature
-        let server_mac = format!("{}_{}_{}", ack.id, tunnel_client.id, tunnel_client.alias_id);
-        if !validate_signature(ack.signature.clone(), server_mac) {
+        // the ack must answer the signed challenge, with key auth it is signed by the pinned server key
+        let server_key = if tunnel_client.key_auth { get_server_public_key() } else { get_server_secret() };
+        let ack_valid = validate_ack(&ack, &tunnel_client, challenge_nonce.as_ref(), server_key);
+        if !ack_valid {
             _error!("Server service ack denied: signature validation failed.");
             continue;
         }
@@ -186,7 +231,6 @@ pub async fn register_handler(underlying_host: String, service: UnderlyingServic
         let handler_stopped1 = Arc::new(Mutex::new(false));
         let handler_stopped2 = handler_stopped1.clone();
 
-        let cloned_underlying_host = underlying_host.clone();
         let cloned_service = service.clone();
         let cloned_tunnel_id = ack.id.clone();
 
@@ -194,7 +238,7 @@ pub async fn register_handler(underlying_host: String, service: UnderlyingServic
         // to prevent deadlocks, any lock should be acquired
         // inside a minimal scope
         let receiver_handler = tokio::spawn(async move {
-            tunnel_receiver_handler(handler_stopped1, read_stream_mutex, tx_mutex, cloned_underlying_host, cloned_service, cloned_tunnel_id).await;
+            tunnel_receiver_handler(handler_stopped1, read_stream_mutex, tx_mutex, cloned_service, cloned_tunnel_id).await;
         });
         let sender_handler = tokio::spawn(async move {
             tunnel_sender_handler(handler_stopped2, write_stream_mutex, rx_mutex, ack.id).await;
@@ -213,17 +257,54 @@ pub async fn register_handler(underlying_host: String, service: UnderlyingServic
     _info!("Max server binding retries exceeded.");
 }
 
+async fn read_server_packet(read_stream: &mut TcpStreamTLS) -> Result<Option<Vec<u8>>, String> {
+    let mut server_response = Vec::new();
+    read_bytes_from_socket_for_internal(read_stream, &mut server_response, SOCKET_TIMEOUT_MILLIS).await?;
+
+    let (packets, _) = separate_packets(server_response);
+    Ok(packets.into_iter().next())
+}
+
 fn get_tunnel_client() -> TunnelClient {
     let client_id = std::env::var(config_keys::CONFIG_KEY_CLIENT_ID)
         .expect(format!("{} env has not been set", config_keys::CONFIG_KEY_CLIENT_ID).as_str());
+    // the private key takes precedence over the signing key
+    if !get_private_key().is_empty() {
+        return TunnelClient::new_with_key_auth(client_id, get_client_version(), get_min_server_version())
+            .with_public_auth(get_public_auth());
+    }
+
     let signing_key = std::env::var(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY)
         .expect(format!("{} env has not been set", config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY).as_str());
+    // the signing key belongs to the token when it's set
+    let token_id = std::env::var(config_keys::CONFIG_KEY_CLIENT_TOKEN_ID).ok().filter(|v| !v.is_empty());
     TunnelClient::new(client_id, signing_key, get_client_version(), get_min_server_version())
+        .with_token_id(token_id)
+        .with_public_auth(get_public_auth())
 }
 
-fn validate_signature(signature: String, mac: String) -> bool {
-    let secret = get_server_secret();
-    validate_signature!(signature, mac, secret)
+// the ack is valid when it answers the challenge of this connection and is signed by the server,
+// `server_key` is the pinned server public key with key auth, the server secret otherwise
+pub fn validate_ack(ack: &TunnelAck, tunnel_client: &TunnelClient, challenge_nonce: Option<&String>, server_key: String) -> bool {
+    if challenge_nonce != Some(&ack.id) || server_key.is_empty() {
+        return false;
+    }
+
+    let server_mac = format!("{}_{}", ack.id, tunnel_client.handshake_mac());
+    if tunnel_client.key_auth {
+        validate_signature_ed25519(ack.signature.clone(), server_mac, server_key)
+    } else {
+        validate_signature!(ack.signature.clone(), server_mac, server_key)
+    }
+}
+
+fn get_private_key() -> String {
+    std::env::var(config_keys::CONFIG_KEY_CLIENT_PRIVATE_KEY).unwrap_or_default()
+}
+
+fn get_server_public_key() -> String {
+    std::env::var(config_keys::CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY)
+        .expect(format!("{} env has not been set", config_keys::CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY).as_str())
 }
 
 fn get_server_secret() -> String {
@@ -235,7 +316,6 @@ pub async fn tunnel_receiver_handler(
     handler_stopped: Arc<Mutex<bool>>,
     stream: Arc<Mutex<TcpStreamTLS>>, 
     tx: Arc<Mutex<Sender<PublicResponse>>>, 
-    underlying_host: String, 
     service: UnderlyingService,
     tunnel_id: String,
 ) {
@@ -274,20 +354,26 @@ pub async fn tunnel_receiver_handler(
             _info!("Incoming request: {} received, forwarding to underlying service...", public_request.id);
             
             // dispatch request to underlying service
-            let cloned_underlying_host = underlying_host.clone();
             let cloned_service: UnderlyingService = service.clone();
             let cloned_tx: Arc<Mutex<Sender<PublicResponse>>> = tx.clone();
             tokio::spawn(async move {
                 // TODO: flexible target port based on request (but need to consider security implications)
-                let public_response: PublicResponse = match cloned_service.foward_request(public_request.data, cloned_underlying_host).await {
+                let result = match cloned_service.check_allowlist(&public_request.data) {
+                    Ok(_) => cloned_service.foward_request(public_request.data).await,
+                    Err(e) => Err(e),
+                };
+                let public_response: PublicResponse = match result {
                     Ok(res) => {
                         PublicResponse::new(public_request.id.clone(), "".to_string(), res.clone())
                     },
                     Err(err) => {
                         _error!("Request [{}] cannot be processed: {}", public_request.id.clone(), err);
-                        let msg = String::from("Request cannot be processed");
-                        let res = http_json_response_as_bytes(
-                            HttpResponse::new(false, msg), StatusCode::from_u16(400).unwrap()).unwrap();
+                        // expose the underlying error only for debugging
+                        let debug = std::env::var(config_keys::CONFIG_KEY_GLOBAL_DEBUG).unwrap_or_default() == "true";
+                        let response = HttpResponse::new(false, err.kind.description().to_string())
+                            .with_code(err.kind.code())
+                            .with_detail(if debug { Some(err.message.clone()) } else { None });
+                        let res = http_json_response_as_bytes(response, err.kind.status()).unwrap();
                         PublicResponse::new(public_request.id.clone(), "".to_string(), res.clone())
                     }
                 };
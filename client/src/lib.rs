@@ -1,10 +1,14 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use common::_info;
+use common::{_error, _info};
+use common::data::dto::request_allowlist::RequestAllowlist;
 use config::{ClientRequestConfig, validate_configs};
+use data::repository::static_file_repo::StaticFileRepo;
 use data::repository::underlying_repo::{UnderlyingRepo, UnderlyingRepoImpl};
 use handler::main_handler::register_handler;
 use service::underlying_service::UnderlyingService;
+use service::upstream_pool::{LoadBalanceStrategy, UpstreamPool};
 
 pub mod config;
 pub mod data;
@@ -12,14 +16,49 @@ pub mod handler;
 pub mod service;
 pub mod version;
 
+const UPSTREAM_HEALTH_CHECK_INTERVAL: u64 = 10; // in seconds
+
 pub async fn entry_point(config: ClientRequestConfig) {
     validate_configs();
     
+    // serving local files does not need any underlying service
+    if let Some(static_files) = &config.static_files {
+        _info!("Serving static files from {}.", static_files.dir);
+        let static_file_repo = Arc::new(StaticFileRepo::new(static_files.clone()));
+        serve_with_allowlist(
+            vec![config.underlying_svc_address()],
+            LoadBalanceStrategy::RoundRobin,
+            static_file_repo,
+            config.use_tls,
+            config.allowlist
+        ).await;
+        return;
+    }
+
     // init repo to be injected
-    let underlying_repo = Arc::new(UnderlyingRepoImpl::new());
+    let underlying_repo = match &config.upstream_tls {
+        Some(tls_config) => match UnderlyingRepoImpl::with_tls(tls_config.clone()) {
+            Ok(repo) => Arc::new(repo
+                .with_connection_pool(config.connection_pool.clone())
+                .with_limits(config.forward_limits.clone())),
+            Err(e) => {
+                _error!("{}", e);
+                return;
+            }
+        },
+        None => Arc::new(UnderlyingRepoImpl::new()
+            .with_connection_pool(config.connection_pool.clone())
+            .with_limits(config.forward_limits.clone())),
+    };
     
     // run the service
-    serve(config.underlying_svc_address(), underlying_repo, config.use_tls).await;
+    serve_with_allowlist(
+        config.underlying_svc_addresses(),
+        config.lb_strategy,
+        underlying_repo,
+        config.use_tls,
+        config.allowlist
+    ).await;
 }
 
 pub async fn serve(
@@ -27,10 +66,39 @@ pub async fn serve(
     underlying_repo: Arc<dyn UnderlyingRepo + Send + Sync>,
     use_tls: bool
 ) {
-    let underlying_service = UnderlyingService::new(underlying_repo);
+    serve_upstreams(vec![underlying_svc_address], LoadBalanceStrategy::RoundRobin, underlying_repo, use_tls).await;
+}
+
+pub async fn serve_upstreams(
+    underlying_svc_addresses: Vec<String>,
+    lb_strategy: LoadBalanceStrategy,
+    underlying_repo: Arc<dyn UnderlyingRepo + Send + Sync>,
+    use_tls: bool
+) {
+    serve_with_allowlist(underlying_svc_addresses, lb_strategy, underlying_repo, use_tls, RequestAllowlist::default()).await;
+}
+
+// only the requests matching the allowlist are forwarded to the underlying service
+pub async fn serve_with_allowlist(
+    underlying_svc_addresses: Vec<String>,
+    lb_strategy: LoadBalanceStrategy,
+    underlying_repo: Arc<dyn UnderlyingRepo + Send + Sync>,
+    use_tls: bool,
+    allowlist: RequestAllowlist
+) {
+    let pool = UpstreamPool::new(underlying_svc_addresses, lb_strategy);
+    if pool.len() > 1 {
+        _info!("Balancing requests across {} upstreams with {:?} strategy.", pool.len(), pool.strategy());
+    }
+    if !allowlist.is_empty() {
+        _info!("Exposing only the allowed requests, {}.", allowlist);
+    }
+    let underlying_service = UnderlyingService::new(underlying_repo, pool).with_allowlist(allowlist);
+    let health_check = underlying_service.spawn_health_check(Duration::from_secs(UPSTREAM_HEALTH_CHECK_INTERVAL));
 
     // register handler
-    register_handler(underlying_svc_address, underlying_service, use_tls).await;
+    register_handler(underlying_service, use_tls).await;
 
+    health_check.abort();
     _info!("Client Service Stopped.");
 }
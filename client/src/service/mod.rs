@@ -1 +1,2 @@
-pub mod underlying_service;
\ No newline at end of file
+pub mod underlying_service;
+pub mod upstream_pool;
\ No newline at end of file
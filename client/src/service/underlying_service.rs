@@ -1,28 +1,128 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::data::repository::underlying_repo::UnderlyingRepo;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
 
+use common::{_error, _info};
+use common::data::dto::request_allowlist::RequestAllowlist;
+use crate::data::repository::underlying_repo::{ForwardError, ForwardErrorKind, UnderlyingRepo};
+use crate::service::upstream_pool::{is_idempotent_request, UpstreamPool};
 
 #[derive(Clone)]
 pub struct UnderlyingService {
     repo: Arc<dyn UnderlyingRepo + Send + Sync>,
+    pool: Arc<UpstreamPool>,
+    allowlist: Arc<RequestAllowlist>,
 }
 
 impl UnderlyingService {
-    pub fn new(repo: Arc<dyn UnderlyingRepo + Send + Sync>) -> Self {
-        UnderlyingService { repo }
+    pub fn new(repo: Arc<dyn UnderlyingRepo + Send + Sync>, pool: UpstreamPool) -> Self {
+        UnderlyingService { repo, pool: Arc::new(pool), allowlist: Arc::new(RequestAllowlist::default()) }
     }
 
-    pub async fn foward_request(&self, request: Vec<u8>, host: String) -> Result<Vec<u8>, String> {
-        self.repo.forward(request, host).await
+    pub fn with_allowlist(mut self, allowlist: RequestAllowlist) -> Self {
+        self.allowlist = Arc::new(allowlist);
+        self
     }
 
-    pub async fn test_connection(&self, host: String) -> Result<(), String> {
-      if host.is_empty() {
-          return Err("Default host is not set for connection test.".to_string());
-      }
+    pub fn allowlist(&self) -> RequestAllowlist {
+        (*self.allowlist).clone()
+    }
+
+    // the server checks the advertised allowlist as well,
+    // but it's enforced here in case the server is older or misbehaves
+    pub fn check_allowlist(&self, request: &[u8]) -> Result<(), ForwardError> {
+        if self.allowlist.is_empty() {
+            return Ok(());
+        }
+
+        let line_end = request.iter().position(|b| *b == b'\n').unwrap_or(request.len());
+        let request_line = String::from_utf8_lossy(&request[..line_end]);
+        let mut parts = request_line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method, target),
+            _ => return Err(ForwardError::new(ForwardErrorKind::Forbidden, String::from("Malformed request line"))),
+        };
+        let path = target.split(['?', '#']).next().unwrap_or_default();
 
-      self.repo.test_connection(host).await
+        self.allowlist.check(method, path)
+            .map_err(|e| ForwardError::new(ForwardErrorKind::Forbidden, e))
     }
-}
 
+    // forward the request to one of the upstreams in the pool.
+    // when an upstream fails, it's ejected until the next successful health check
+    // and idempotent requests are retried on another upstream
+    pub async fn foward_request(&self, request: Vec<u8>) -> Result<Vec<u8>, ForwardError> {
+        let retryable = is_idempotent_request(&request);
+        let mut tried: Vec<String> = Vec::new();
+        let mut last_err = ForwardError::new(ForwardErrorKind::ConnectionRefused, String::from("No upstream is available."));
+        while let Some(upstream) = self.pool.acquire(&tried) {
+            let host = upstream.address();
+            match self.repo.forward(request.clone(), host.clone()).await {
+                Ok(res) => return Ok(res),
+                // the upstream is fine, the payload would fail on any other upstream as well
+                Err(e) if matches!(e.kind, ForwardErrorKind::PayloadTooLarge | ForwardErrorKind::ResponseTooLarge) => return Err(e),
+                Err(e) => {
+                    if self.pool.set_healthy(&host, false) {
+                        _error!("Upstream [{}] ejected from the pool: {}", host, e);
+                    }
+                    last_err = e;
+                }
+            }
+
+            tried.push(host);
+            if !retryable {
+                break;
+            }
+        }
+
+        Err(last_err)
+    }
+
+    // test all upstreams, it's considered ok if at least one of them is reachable
+    pub async fn test_connection(&self) -> Result<(), String> {
+        if self.pool.is_empty() {
+            return Err("Default host is not set for connection test.".to_string());
+        }
+
+        self.check_upstreams().await;
+        if self.pool.healthy_count() == 0 {
+            return Err(format!("None of the upstreams is reachable: {}", self.pool.addresses().join(", ")));
+        }
+
+        Ok(())
+    }
+
+    pub fn upstream_addresses(&self) -> Vec<String> {
+        self.pool.addresses()
+    }
+
+    // periodic health checks, keep running until the returned handle is aborted
+    pub fn spawn_health_check(&self, interval: Duration) -> JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            loop {
+                sleep(interval).await;
+                service.check_upstreams().await;
+            }
+        })
+    }
+
+    async fn check_upstreams(&self) {
+        for host in self.pool.addresses() {
+            match self.repo.test_connection(host.clone()).await {
+                Ok(_) => {
+                    if self.pool.set_healthy(&host, true) {
+                        _info!("Upstream [{}] is healthy again, added back to the pool.", host);
+                    }
+                },
+                Err(e) => {
+                    if self.pool.set_healthy(&host, false) {
+                        _error!("Upstream [{}] failed health check, ejected from the pool: {}", host, e);
+                    }
+                }
+            }
+        }
+    }
+}
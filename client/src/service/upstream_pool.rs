@@ -0,0 +1,174 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// HTTP methods that are safe to be retried on another upstream
+const IDEMPOTENT_METHODS: [&str; 6] = ["GET", "HEAD", "OPTIONS", "PUT", "DELETE", "TRACE"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadBalanceStrategy {
+    RoundRobin,
+    LeastConnections,
+}
+
+impl FromStr for LoadBalanceStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "round-robin" | "rr" => Ok(LoadBalanceStrategy::RoundRobin),
+            "least-conn" | "least-connections" => Ok(LoadBalanceStrategy::LeastConnections),
+            _ => Err(format!("Unknown load balancing strategy: {}. Valid values: round-robin, least-conn", value)),
+        }
+    }
+}
+
+pub struct Upstream {
+    pub address: String,
+    healthy: AtomicBool,
+    active: AtomicUsize,
+}
+
+impl Upstream {
+    fn new(address: String) -> Self {
+        Upstream {
+            address,
+            healthy: AtomicBool::new(true),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+// holds an acquired upstream, the active connection count
+// is released once the guard is dropped
+pub struct UpstreamGuard {
+    upstream: Arc<Upstream>,
+}
+
+impl UpstreamGuard {
+    fn new(upstream: Arc<Upstream>) -> Self {
+        upstream.active.fetch_add(1, Ordering::Relaxed);
+        UpstreamGuard { upstream }
+    }
+
+    pub fn address(&self) -> String {
+        self.upstream.address.clone()
+    }
+}
+
+impl Drop for UpstreamGuard {
+    fn drop(&mut self) {
+        self.upstream.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// a pool of underlying service instances behind a single tunnel
+pub struct UpstreamPool {
+    upstreams: Vec<Arc<Upstream>>,
+    strategy: LoadBalanceStrategy,
+    next: AtomicUsize,
+}
+
+impl UpstreamPool {
+    pub fn new(addresses: Vec<String>, strategy: LoadBalanceStrategy) -> Self {
+        let mut upstreams: Vec<Arc<Upstream>> = Vec::new();
+        for address in addresses {
+            // ignore duplicates, a duplicated address would only skew the distribution
+            if !upstreams.iter().any(|u| u.address == address) {
+                upstreams.push(Arc::new(Upstream::new(address)));
+            }
+        }
+
+        UpstreamPool {
+            upstreams,
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.upstreams.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.upstreams.is_empty()
+    }
+
+    pub fn strategy(&self) -> LoadBalanceStrategy {
+        self.strategy
+    }
+
+    pub fn addresses(&self) -> Vec<String> {
+        self.upstreams.iter().map(|u| u.address.clone()).collect()
+    }
+
+    pub fn healthy_count(&self) -> usize {
+        self.upstreams.iter().filter(|u| u.is_healthy()).count()
+    }
+
+    // mark the health state of an upstream,
+    // returns true if the state has changed
+    pub fn set_healthy(&self, address: &str, healthy: bool) -> bool {
+        match self.upstreams.iter().find(|u| u.address == address) {
+            Some(upstream) => upstream.healthy.swap(healthy, Ordering::Relaxed) != healthy,
+            None => false,
+        }
+    }
+
+    // select an upstream based on the strategy, excluding the given addresses.
+    // unhealthy upstreams are ejected from the selection,
+    // but if there's no healthy one left, we still try the rest
+    // rather than failing right away
+    pub fn acquire(&self, exclude: &[String]) -> Option<UpstreamGuard> {
+        let candidates: Vec<&Arc<Upstream>> = self.upstreams
+            .iter()
+            .filter(|u| !exclude.contains(&u.address))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let healthy: Vec<&Arc<Upstream>> = candidates.iter().filter(|u| u.is_healthy()).cloned().collect();
+        let candidates = if healthy.is_empty() { candidates } else { healthy };
+
+        let selected = match self.strategy {
+            LoadBalanceStrategy::RoundRobin => {
+                let idx = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                candidates[idx]
+            },
+            LoadBalanceStrategy::LeastConnections => {
+                // ties are broken by the round-robin counter
+                let offset = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                let mut selected = candidates[offset];
+                for i in 0..candidates.len() {
+                    let candidate = candidates[(offset + i) % candidates.len()];
+                    if candidate.active_connections() < selected.active_connections() {
+                        selected = candidate;
+                    }
+                }
+                selected
+            }
+        };
+
+        Some(UpstreamGuard::new(selected.clone()))
+    }
+}
+
+// check whether a raw HTTP request can be retried safely
+pub fn is_idempotent_request(request: &[u8]) -> bool {
+    let method = request
+        .split(|b| *b == b' ')
+        .next()
+        .map(|m| String::from_utf8_lossy(m).to_uppercase())
+        .unwrap_or_default();
+
+    IDEMPOTENT_METHODS.contains(&method.as_str())
+}
@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use client::handler::main_handler::validate_ack;
+    use common::data::dto::tunnel_ack::TunnelAck;
+    use common::data::dto::tunnel_client::TunnelClient;
+    use common::security::{generate_ed25519_keypair, sign_value};
+
+    fn key_auth_client() -> TunnelClient {
+        TunnelClient::new_with_key_auth(String::from("client-a"), String::from("1.0.0"), String::from("1.0.0"))
+    }
+
+    #[test]
+    fn test_validate_ack_with_identity_key() {
+        let client = key_auth_client();
+        let (identity_key, public_key) = generate_ed25519_keypair();
+        let nonce = String::from("tunnel-1");
+
+        let ack = TunnelAck::success_with_identity_key(nonce.clone(), client.handshake_mac(), identity_key, vec![]).unwrap();
+        assert!(validate_ack(&ack, &client, Some(&nonce), public_key.clone()));
+
+        // the ack must answer the challenge of this connection
+        assert!(!validate_ack(&ack, &client, Some(&String::from("tunnel-2")), public_key.clone()));
+        assert!(!validate_ack(&ack, &client, None, public_key.clone()));
+        // and it must be signed for this client
+        assert!(!validate_ack(&ack, &key_auth_client(), Some(&nonce), public_key));
+    }
+
+    #[test]
+    fn test_validate_ack_rejects_forged_ack() {
+        let client = key_auth_client();
+        let (_, public_key) = generate_ed25519_keypair();
+        let nonce = String::from("tunnel-1");
+
+        // an unsigned ack echoing the nonce, as anyone in the middle could send
+        let unsigned = TunnelAck {
+            id: nonce.clone(),
+            signature: String::new(),
+            success: true,
+            message: "ok".into(),
+            public_endpoints: vec![],
+        };
+        assert!(!validate_ack(&unsigned, &client, Some(&nonce), public_key.clone()));
+
+        // signed by a key other than the pinned one
+        let (forged_key, _) = generate_ed25519_keypair();
+        let forged = TunnelAck::success_with_identity_key(nonce.clone(), client.handshake_mac(), forged_key, vec![]).unwrap();
+        assert!(!validate_ack(&forged, &client, Some(&nonce), public_key));
+
+        // nothing is accepted without a pinned key
+        assert!(!validate_ack(&forged, &client, Some(&nonce), String::new()));
+    }
+
+    #[test]
+    fn test_validate_ack_with_server_secret() {
+        let client = TunnelClient::new(String::from("client-a"), String::from("secret"), String::from("1.0.0"), String::from("1.0.0"));
+        let nonce = String::from("tunnel-1");
+
+        let ack = TunnelAck::success(nonce.clone(), client.handshake_mac(), String::from("secret"), vec![]);
+        assert!(validate_ack(&ack, &client, Some(&nonce), String::from("secret")));
+        assert!(!validate_ack(&ack, &client, Some(&nonce), String::from("other")));
+
+        let forged = TunnelAck {
+            signature: sign_value(format!("{}_{}", nonce, client.handshake_mac()), String::from("other")),
+            ..ack
+        };
+        assert!(!validate_ack(&forged, &client, Some(&nonce), String::from("secret")));
+    }
+}
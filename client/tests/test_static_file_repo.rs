@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+    use client::config::StaticFileConfig;
+    use client::data::repository::static_file_repo::{mime_type_of, parse_range, StaticFileRepo};
+    use client::data::repository::underlying_repo::UnderlyingRepo;
+
+    fn prepare_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("trabas_static_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        fs::write(dir.join("index.html"), "<h1>home</h1>").unwrap();
+        fs::write(dir.join("assets").join("app.js"), "console.log('0123456789');").unwrap();
+        dir
+    }
+
+    async fn get(repo: &StaticFileRepo, path: &str, extra_headers: &str) -> String {
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n", path, extra_headers);
+        let response = repo.forward(request.into_bytes(), String::new()).await.unwrap();
+        String::from_utf8(response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_static_file_repo_serve_files() {
+        let dir = prepare_dir("serve");
+        let repo = StaticFileRepo::new(StaticFileConfig::new(dir.to_string_lossy().to_string(), false, false));
+
+        let response = get(&repo, "/assets/app.js", "").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("content-type: text/javascript"));
+        assert!(response.ends_with("console.log('0123456789');"));
+
+        // directory with index.html
+        let response = get(&repo, "/", "").await;
+        assert!(response.ends_with("<h1>home</h1>"));
+
+        // directory without index.html and listing disabled
+        let response = get(&repo, "/assets/", "").await;
+        assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+
+        let response = get(&repo, "/missing", "").await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_static_file_repo_spa_and_listing() {
+        let dir = prepare_dir("spa");
+        let repo = StaticFileRepo::new(StaticFileConfig::new(dir.to_string_lossy().to_string(), true, true));
+
+        // unknown route falls back to index.html, but missing files are still 404
+        let response = get(&repo, "/dashboard/settings", "").await;
+        assert!(response.ends_with("<h1>home</h1>"), "{}", response);
+        let response = get(&repo, "/assets/missing.js", "").await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+
+        let response = get(&repo, "/assets", "").await;
+        assert!(response.starts_with("HTTP/1.1 301"), "{}", response);
+        assert!(response.contains("location: assets/"));
+
+        let response = get(&repo, "/assets/", "").await;
+        assert!(response.contains("<a href=\"app.js\">app.js</a>"), "{}", response);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_static_file_repo_range_request() {
+        let dir = prepare_dir("range");
+        let repo = StaticFileRepo::new(StaticFileConfig::new(dir.to_string_lossy().to_string(), false, false));
+
+        let response = get(&repo, "/assets/app.js", "Range: bytes=13-22\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 206"), "{}", response);
+        assert!(response.contains("content-range: bytes 13-22/26"));
+        assert!(response.ends_with("0123456789"));
+
+        let response = get(&repo, "/assets/app.js", "Range: bytes=100-\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 416"), "{}", response);
+        assert!(response.contains("content-range: bytes */26"));
+
+        // the last bytes of a file larger than the read buffer
+        let large: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(dir.join("assets").join("large.bin"), &large).unwrap();
+        let request = b"GET /assets/large.bin HTTP/1.1\r\nHost: localhost\r\nRange: bytes=-5\r\n\r\n".to_vec();
+        let response = repo.forward(request, String::new()).await.unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 206"));
+        assert!(response.ends_with(&large[large.len() - 5..]));
+
+        // an empty file has no satisfiable range, the empty body is served instead
+        fs::write(dir.join("empty.txt"), "").unwrap();
+        let response = get(&repo, "/empty.txt", "Range: bytes=0-\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("content-length: 0"));
+        assert!(!response.contains("content-range"));
+
+        assert_eq!(parse_range("bytes=0-", 10), Some((0, 9)));
+        assert_eq!(parse_range("bytes=-3", 10), Some((7, 9)));
+        assert_eq!(parse_range("bytes=5-100", 10), Some((5, 9)));
+        assert_eq!(parse_range("bytes=5-2", 10), None);
+        assert_eq!(parse_range("bytes=0-1,3-4", 10), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_static_file_repo_path_traversal() {
+        let dir = prepare_dir("traversal");
+        fs::write(dir.parent().unwrap().join("trabas_static_secret.txt"), "secret").unwrap();
+        let repo = StaticFileRepo::new(StaticFileConfig::new(dir.to_string_lossy().to_string(), true, true));
+
+        for path in ["/../trabas_static_secret.txt", "/assets/%2e%2e/%2e%2e/trabas_static_secret.txt", "/%2e%2e%2ftrabas_static_secret.txt"] {
+            let response = get(&repo, path, "").await;
+            assert!(response.starts_with("HTTP/1.1 404"), "{}: {}", path, response);
+            assert!(!response.contains("secret"));
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_mime_type_of() {
+        assert_eq!(mime_type_of(&PathBuf::from("index.HTML")), "text/html; charset=utf-8");
+        assert_eq!(mime_type_of(&PathBuf::from("logo.svg")), "image/svg+xml");
+        assert_eq!(mime_type_of(&PathBuf::from("archive")), "application/octet-stream");
+    }
+}
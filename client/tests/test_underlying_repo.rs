@@ -0,0 +1,296 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use client::config::{ConnectionPoolConfig, ForwardLimitConfig, UpstreamTlsConfig};
+    use client::data::repository::connection_pool::is_reusable;
+    use client::data::repository::underlying_repo::{host_of_address, ForwardErrorKind, UnderlyingRepo, UnderlyingRepoImpl};
+
+    const REQUEST: &[u8] = b"GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+    // simple upstream answering "pong", optionally closing the connection
+    // after each response without telling the client
+    async fn start_upstream(close_after_response: bool) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let accepted_clone = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                accepted_clone.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buffer = [0; 1024];
+                    loop {
+                        match socket.read(&mut buffer).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(_) => {}
+                        }
+                        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\npong";
+                        if socket.write_all(response).await.is_err() {
+                            return;
+                        }
+                        if close_after_response {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        (address, accepted)
+    }
+
+    // upstream answering only the first request of each connection,
+    // the next one is received but the connection is dropped without response
+    async fn start_dropping_upstream() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(AtomicUsize::new(0));
+        let received_clone = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let received = received_clone.clone();
+                tokio::spawn(async move {
+                    let mut buffer = [0; 1024];
+                    for answered in 0.. {
+                        match socket.read(&mut buffer).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(_) => {}
+                        }
+                        received.fetch_add(1, Ordering::SeqCst);
+                        if answered > 0 {
+                            return;
+                        }
+                        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\npong";
+                        if socket.write_all(response).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        (address, received)
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_reuse_connection() {
+        let (address, accepted) = start_upstream(false).await;
+        let repo = UnderlyingRepoImpl::new();
+
+        for _ in 0..3 {
+            let res = repo.forward(REQUEST.to_vec(), address.clone()).await.unwrap();
+            assert!(res.ends_with(b"pong"));
+        }
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        assert_eq!(repo.idle_connections(&address).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_stale_connection() {
+        let (address, accepted) = start_upstream(true).await;
+        let repo = UnderlyingRepoImpl::new();
+
+        for _ in 0..3 {
+            let res = repo.forward(REQUEST.to_vec(), address.clone()).await.unwrap();
+            assert!(res.ends_with(b"pong"));
+        }
+
+        // closed connections are never reused
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_keep_alive_disabled() {
+        let (address, accepted) = start_upstream(false).await;
+        let repo = UnderlyingRepoImpl::new().with_connection_pool(ConnectionPoolConfig::new(0, 0));
+
+        for _ in 0..2 {
+            repo.forward(REQUEST.to_vec(), address.clone()).await.unwrap();
+        }
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+        assert_eq!(repo.idle_connections(&address).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_connection_refused() {
+        // bind and drop right away to get a closed port
+        let address = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().to_string();
+        let repo = UnderlyingRepoImpl::new();
+
+        let err = repo.forward(REQUEST.to_vec(), address).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::ConnectionRefused);
+        assert_eq!(err.kind.status().as_u16(), 502);
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_first_byte_timeout() {
+        // accept connections but never respond
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let repo = UnderlyingRepoImpl::new().with_limits(ForwardLimitConfig::new(
+            Duration::from_secs(1),
+            Duration::from_millis(200),
+            Duration::from_secs(5),
+            0
+        ));
+
+        let err = repo.forward(REQUEST.to_vec(), address).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::Timeout);
+        assert_eq!(err.kind.status().as_u16(), 504);
+        assert_eq!(err.kind.code(), "upstream_timeout");
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_max_body_size() {
+        let (address, _) = start_upstream(false).await;
+        let repo = UnderlyingRepoImpl::new().with_limits(ForwardLimitConfig::new(
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            REQUEST.len()
+        ));
+
+        // the response is larger than the request, which is not the fault of the public client
+        let err = repo.forward(REQUEST.to_vec(), address.clone()).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::ResponseTooLarge);
+        assert_eq!(err.kind.status().as_u16(), 502);
+
+        let mut request = REQUEST.to_vec();
+        request.extend_from_slice(b"body");
+        let err = repo.forward(request, address).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::PayloadTooLarge);
+        assert_eq!(err.kind.status().as_u16(), 413);
+    }
+
+    #[test]
+    fn test_is_reusable() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\npong";
+        assert!(is_reusable(REQUEST, response));
+        assert!(!is_reusable(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n", response));
+        assert!(!is_reusable(REQUEST, b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 4\r\n\r\npong"));
+        assert!(!is_reusable(REQUEST, b"HTTP/1.0 200 OK\r\nContent-Length: 4\r\n\r\npong"));
+        assert!(is_reusable(REQUEST, b"HTTP/1.0 200 OK\r\nConnection: keep-alive\r\nContent-Length: 4\r\n\r\npong"));
+        // the body is only delimited by closing the connection
+        assert!(!is_reusable(REQUEST, b"HTTP/1.1 200 OK\r\n\r\npong"));
+    }
+
+    #[test]
+    fn test_host_of_address() {
+        assert_eq!(host_of_address("localhost:8443"), "localhost");
+        assert_eq!(host_of_address("api.example.com:443"), "api.example.com");
+        assert_eq!(host_of_address("10.0.0.1:80"), "10.0.0.1");
+        assert_eq!(host_of_address("[::1]:8443"), "::1");
+        assert_eq!(host_of_address("[fe80::1%eth0]:8443"), "fe80::1%eth0");
+        // without port
+        assert_eq!(host_of_address("api.example.com"), "api.example.com");
+        assert_eq!(host_of_address("[::1]"), "::1");
+        assert_eq!(host_of_address("fe80::1"), "fe80::1");
+        assert_eq!(host_of_address("localhost:"), "localhost:");
+    }
+
+    #[test]
+    fn test_underlying_repo_with_tls_invalid_ca() {
+        let config = UpstreamTlsConfig::new(None, Some(String::from("/nonexistent/ca.crt")), false);
+        assert!(UnderlyingRepoImpl::with_tls(config).is_err());
+
+        let path = std::env::temp_dir().join(format!("trabas_upstream_ca_{}.crt", std::process::id()));
+        std::fs::write(&path, "not a certificate").unwrap();
+        let config = UpstreamTlsConfig::new(None, Some(path.to_string_lossy().to_string()), false);
+        assert!(UnderlyingRepoImpl::with_tls(config).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_with_tls_plain_upstream() {
+        let (address, accepted) = start_upstream(false).await;
+        let repo = UnderlyingRepoImpl::with_tls(UpstreamTlsConfig::new(None, None, true)).unwrap();
+
+        // the handshake fails instead of falling back to plain text
+        let err = repo.forward(REQUEST.to_vec(), address).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::BadGateway);
+        assert!(err.message.contains("TLS"));
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_with_tls_unix_socket() {
+        let repo = UnderlyingRepoImpl::with_tls(UpstreamTlsConfig::default()).unwrap();
+
+        let err = repo.forward(REQUEST.to_vec(), String::from("unix:/tmp/trabas_upstream.sock")).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::BadGateway);
+        assert!(err.message.contains("TLS is not supported"));
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_retry_idempotent_only() {
+        let (address, received) = start_dropping_upstream().await;
+        let repo = UnderlyingRepoImpl::new();
+
+        // the lost response of a reused connection is retried on a new one
+        repo.forward(REQUEST.to_vec(), address.clone()).await.unwrap();
+        let res = repo.forward(REQUEST.to_vec(), address.clone()).await.unwrap();
+        assert!(res.ends_with(b"pong"));
+        assert_eq!(received.load(Ordering::SeqCst), 3);
+
+        // but a POST is never sent twice
+        let (address, received) = start_dropping_upstream().await;
+        let post = b"POST /orders HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\n{}".to_vec();
+        repo.forward(post.clone(), address.clone()).await.unwrap();
+        let err = repo.forward(post, address).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::BadGateway);
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_underlying_repo_max_body_size_while_reading() {
+        // upstreams which would never finish their responses
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buffer = [0; 1024];
+                    let n = socket.read(&mut buffer).await.unwrap_or(0);
+                    if buffer[..n].starts_with(b"GET /declared") {
+                        let _ = socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1073741824\r\n\r\n").await;
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                        return;
+                    }
+                    // the body is delimited by closing the connection
+                    let _ = socket.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await;
+                    while socket.write_all(&[b'x'; 1024]).await.is_ok() {}
+                });
+            }
+        });
+
+        let repo = UnderlyingRepoImpl::new().with_limits(ForwardLimitConfig::new(
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            Duration::from_secs(5),
+            4096
+        ));
+
+        let err = repo.forward(REQUEST.to_vec(), address.clone()).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::ResponseTooLarge);
+        // rejected before reading the body
+        let request = b"GET /declared HTTP/1.1\r\nHost: localhost\r\n\r\n".to_vec();
+        let err = repo.forward(request, address).await.unwrap_err();
+        assert_eq!(err.kind, ForwardErrorKind::ResponseTooLarge);
+        assert_eq!(err.kind.code(), "upstream_response_too_large");
+    }
+}
@@ -5,6 +5,8 @@ use std::fs::{create_dir_all, File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 
+use crate::security::{decrypt_value, encrypt_value, generate_hmac_key, is_encrypted_value};
+
 // TODO: find proper approach to test these
 
 // all config values in this tools will be written locally in this file
@@ -15,6 +17,7 @@ const CONFIG_ENV: &str = ".env";
 pub mod keys {
     pub const CONFIG_KEY_GLOBAL_DEBUG: &str = "GLOBAL_DEBUG";
     pub const CONFIG_KEY_GLOBAL_LOG_LIMIT: &str = "GLOBAL_LOG_LIMIT";
+    pub const CONFIG_KEY_GLOBAL_CONFIG_ENCRYPTION: &str = "GLOBAL_CONFIG_ENCRYPTION";
     // client
     pub const CONFIG_KEY_CLIENT_ID: &str = "CL_ID";
     pub const CONFIG_KEY_CLIENT_TLS_TOFU_ENABLE: &str = "CL_TLS_TOFU_ENABLE";
@@ -22,17 +25,63 @@ pub mod keys {
     pub const CONFIG_KEY_CLIENT_SERVER_PORT: &str = "CL_SERVER_PORT";
     pub const CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY: &str = "CL_SERVER_SIGNING_KEY";
     pub const CONFIG_KEY_CLIENT_SERVER_FINGERPRINT: &str = "CL_SERVER_FINGERPRINT";
+    pub const CONFIG_KEY_CLIENT_TOKEN_ID: &str = "CL_TOKEN_ID";
+    pub const CONFIG_KEY_CLIENT_PRIVATE_KEY: &str = "CL_PRIVATE_KEY";
+    pub const CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY: &str = "CL_SERVER_PUBLIC_KEY";
+    pub const CONFIG_KEY_CLIENT_PUBLIC_AUTH: &str = "CL_PUBLIC_AUTH";
     // server
     pub const CONFIG_KEY_SERVER_SECRET: &str = "SV_SECRET";
+    pub const CONFIG_KEY_SERVER_PREVIOUS_SECRETS: &str = "SV_PREVIOUS_SECRETS";
     pub const CONFIG_KEY_SERVER_PUBLIC_ENDPOINT: &str = "SV_PUBLIC_ENDPOINT";
     pub const CONFIG_KEY_SERVER_PUBLIC_REQUEST_TIMEOUT: &str = "SV_PUBLIC_REQUEST_TIMEOUT";
+    pub const CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_BYTES: &str = "SV_PUBLIC_MAX_HEADER_BYTES";
+    pub const CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_COUNT: &str = "SV_PUBLIC_MAX_HEADER_COUNT";
+    pub const CONFIG_KEY_SERVER_PUBLIC_MAX_BODY_SIZE: &str = "SV_PUBLIC_MAX_BODY_SIZE";
+    pub const CONFIG_KEY_SERVER_PUBLIC_HEADER_TIMEOUT: &str = "SV_PUBLIC_HEADER_TIMEOUT";
+    pub const CONFIG_KEY_SERVER_PUBLIC_BODY_TIMEOUT: &str = "SV_PUBLIC_BODY_TIMEOUT";
+    pub const CONFIG_KEY_SERVER_HANDSHAKE_MAX_SKEW: &str = "SV_HANDSHAKE_MAX_SKEW";
+    pub const CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_RPS: &str = "SV_RATE_LIMIT_CLIENT_RPS";
+    pub const CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_BURST: &str = "SV_RATE_LIMIT_CLIENT_BURST";
+    pub const CONFIG_KEY_SERVER_RATE_LIMIT_IP_RPS: &str = "SV_RATE_LIMIT_IP_RPS";
+    pub const CONFIG_KEY_SERVER_RATE_LIMIT_IP_BURST: &str = "SV_RATE_LIMIT_IP_BURST";
+    pub const CONFIG_KEY_SERVER_RATE_LIMIT_MAX_WAIT: &str = "SV_RATE_LIMIT_MAX_WAIT";
+    pub const CONFIG_KEY_SERVER_PUBLIC_MAX_CONNECTIONS: &str = "SV_PUBLIC_MAX_CONNECTIONS";
+    pub const CONFIG_KEY_SERVER_PUBLIC_MAX_CLIENT_CONNECTIONS: &str = "SV_PUBLIC_MAX_CLIENT_CONNECTIONS";
+    pub const CONFIG_KEY_SERVER_MAX_TUNNELS_PER_CLIENT: &str = "SV_MAX_TUNNELS_PER_CLIENT";
+    pub const CONFIG_KEY_SERVER_AUDIT_LOG_ENABLE: &str = "SV_AUDIT_LOG_ENABLE";
+    pub const CONFIG_KEY_SERVER_AUDIT_LOG_PATH: &str = "SV_AUDIT_LOG_PATH";
+    pub const CONFIG_KEY_SERVER_AUDIT_LOG_MAX_SIZE: &str = "SV_AUDIT_LOG_MAX_SIZE";
+    pub const CONFIG_KEY_SERVER_AUDIT_LOG_MAX_FILES: &str = "SV_AUDIT_LOG_MAX_FILES";
     pub const CONFIG_KEY_SERVER_CACHE_CONFIGS: &str = "SV_CACHE_CONFIGS";
+    pub const CONFIG_KEY_SERVER_CACHE_MAX_ENTRIES: &str = "SV_CACHE_MAX_ENTRIES";
+    pub const CONFIG_KEY_SERVER_CACHE_MAX_SIZE: &str = "SV_CACHE_MAX_SIZE";
+    pub const CONFIG_KEY_SERVER_CACHE_SWEEP_INTERVAL: &str = "SV_CACHE_SWEEP_INTERVAL";
+    pub const CONFIG_KEY_SERVER_IP_RULES: &str = "SV_IP_RULES";
+    pub const CONFIG_KEY_SERVER_FILTER_RULES: &str = "SV_FILTER_RULES";
+    pub const CONFIG_KEY_SERVER_CLIENT_KEYS: &str = "SV_CLIENT_KEYS";
+    pub const CONFIG_KEY_SERVER_IDENTITY_KEY: &str = "SV_IDENTITY_KEY";
+    pub const CONFIG_KEY_SERVER_PUBLIC_AUTH: &str = "SV_PUBLIC_AUTH";
     pub const CONFIG_KEY_SERVER_REDIS_ENABLE: &str = "SV_REDIS_ENABLE";
     pub const CONFIG_KEY_SERVER_REDIS_HOST: &str = "SV_REDIS_HOST";
     pub const CONFIG_KEY_SERVER_REDIS_PORT: &str = "SV_REDIS_PORT";
     pub const CONFIG_KEY_SERVER_REDIS_PASS: &str = "SV_REDIS_PASS";
 }
 
+// the encryption passphrase or key file path of the config values,
+// these are read from the process env only and never written into the config file
+pub const CONFIG_ENV_PASSPHRASE: &str = "TRABAS_CONFIG_PASSPHRASE";
+pub const CONFIG_ENV_KEY_FILE: &str = "TRABAS_CONFIG_KEY_FILE";
+
+// config values encrypted at rest when the config encryption is enabled
+pub const SENSITIVE_CONFIG_KEYS: [&str; 6] = [
+    keys::CONFIG_KEY_SERVER_SECRET,
+    keys::CONFIG_KEY_SERVER_PREVIOUS_SECRETS,
+    keys::CONFIG_KEY_SERVER_IDENTITY_KEY,
+    keys::CONFIG_KEY_SERVER_REDIS_PASS,
+    keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY,
+    keys::CONFIG_KEY_CLIENT_PRIVATE_KEY,
+];
+
 // TODO: should we make a config interface for both client and server (?)
 // and to be injected across the usecases (?)
 // for now, sharing config/env using std::env is a decent solution
@@ -72,6 +121,17 @@ pub fn get_configs_from_dot_env() -> BTreeMap<String, String> {
         }
     }
 
+    // encrypted values are kept as is when there is no way to decrypt them
+    if let Ok(Some(passphrase)) = get_encryption_passphrase() {
+        for value in map.values_mut() {
+            if is_encrypted_value(value) {
+                if let Ok(decrypted) = decrypt_value(value, &passphrase) {
+                    *value = decrypted;
+                }
+            }
+        }
+    }
+
     map
 }
 
@@ -79,12 +139,12 @@ pub fn get_configs_from_proc_env() -> BTreeMap<String, String> {
     env::vars().collect()
 }
 
-pub fn set_configs(values: HashMap<String, String>) {
+pub fn set_configs(values: HashMap<String, String>) -> Result<(), String> {
     // make sure the path is exists
     let config_path_str = get_config_path();
     let config_path = Path::new(config_path_str.as_str());
     if !config_path.exists() {
-        create_dir_all(config_path).expect("Unable to initiate config directory.");
+        create_dir_all(config_path).map_err(|e| format!("Unable to initiate config directory: {}", e))?;
     }
     
     // fecth existing env vars
@@ -93,31 +153,152 @@ pub fn set_configs(values: HashMap<String, String>) {
         config.insert(key, value);
     }
 
+    // sensitive values are written encrypted when enabled
+    let encryption_enabled = config.get(keys::CONFIG_KEY_GLOBAL_CONFIG_ENCRYPTION).map(|v| v == "true").unwrap_or(false);
+    let passphrase = if encryption_enabled {
+        match get_encryption_passphrase()? {
+            Some(value) => Some(value),
+            None => return Err(format!("Config encryption is enabled, set {} or {} env to write the config.", CONFIG_ENV_PASSPHRASE, CONFIG_ENV_KEY_FILE)),
+        }
+    } else {
+        None
+    };
+
+    // prepare the entire file first, so a failure leaves the existing one untouched
+    let mut content = String::new();
+    for (key, value) in config.iter() {
+        let value = match passphrase {
+            Some(ref passphrase) if SENSITIVE_CONFIG_KEYS.contains(&key.as_str()) && !is_encrypted_value(value) => {
+                encrypt_value(value, passphrase).map_err(|e| format!("Unable to encrypt {} config: {}", key, e))?
+            },
+            _ => value.clone(),
+        };
+        content.push_str(&format!("{}={}\n", key, value));
+    }
+
     let env_path = get_env_path();
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(env_path).expect(format!("Unable to open {} file for writing.", CONFIG_ENV).as_str());
+        .open(env_path).map_err(|e| format!("Unable to open {} file for writing: {}", CONFIG_ENV, e))?;
+    file.write_all(content.as_bytes()).map_err(|e| format!("Unable to write to {} file: {}", CONFIG_ENV, e))?;
 
-    // update entire file
+    // update env, unless the value could not be decrypted
     for (key, value) in config {
-        // update env
-        env::set_var(key.clone(), value.clone());
-        writeln!(file, "{}={}", key, value).expect(format!("Unable to write to {} file.", CONFIG_ENV).as_str())
+        if !is_encrypted_value(&value) {
+            env::set_var(key, value);
+        }
     }
+
+    Ok(())
 }
 
 pub fn init_env_from_config() {
     let env_path = get_env_path();
     dotenv::from_filename(env_path).ok();
+
+    // decrypt the encrypted values transparently
+    let encrypted_keys: Vec<String> = env::vars()
+        .filter(|(_, value)| is_encrypted_value(value))
+        .map(|(key, _)| key)
+        .collect();
+    if encrypted_keys.is_empty() {
+        return;
+    }
+
+    let passphrase = match get_encryption_passphrase() {
+        Ok(Some(value)) => value,
+        Ok(None) => {
+            eprintln!("Encrypted configs found ({}), set {} or {} env to decrypt them.", encrypted_keys.join(", "), CONFIG_ENV_PASSPHRASE, CONFIG_ENV_KEY_FILE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    for key in encrypted_keys {
+        let value = env::var(&key).unwrap_or_default();
+        match decrypt_value(&value, &passphrase) {
+            Ok(decrypted) => env::set_var(key, decrypted),
+            Err(e) => eprintln!("Unable to decrypt {} config: {}", key, e),
+        }
+    }
+}
+
+// the passphrase takes precedence over the key file
+pub fn get_encryption_passphrase() -> Result<Option<String>, String> {
+    if let Some(passphrase) = env::var(CONFIG_ENV_PASSPHRASE).ok().filter(|v| !v.is_empty()) {
+        return Ok(Some(passphrase));
+    }
+
+    let key_file = match env::var(CONFIG_ENV_KEY_FILE).ok().filter(|v| !v.is_empty()) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let key = std::fs::read_to_string(&key_file)
+        .map_err(|e| format!("Unable to read config key file {}: {}", key_file, e))?;
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        return Err(format!("Config key file {} is empty", key_file));
+    }
+
+    Ok(Some(key))
+}
+
+// migrate the config file into encrypted form, or back into plaintext,
+// returns the number of the sensitive values
+pub fn set_config_encryption(enable: bool) -> Result<usize, String> {
+    if get_encryption_passphrase()?.is_none() {
+        return Err(format!("Set {} or {} env first.", CONFIG_ENV_PASSPHRASE, CONFIG_ENV_KEY_FILE));
+    }
+
+    // every encrypted value must be readable with the provided passphrase,
+    // otherwise those would be left with a key nobody knows
+    let config = get_configs_from_dot_env();
+    let undecryptable: Vec<&String> = config.iter()
+        .filter(|(_, value)| is_encrypted_value(value))
+        .map(|(key, _)| key)
+        .collect();
+    if !undecryptable.is_empty() {
+        let keys = undecryptable.iter().map(|k| k.as_str()).collect::<Vec<&str>>().join(", ");
+        return Err(format!("Unable to decrypt {} with the provided passphrase or key file", keys));
+    }
+
+    let sensitive_count = config.keys().filter(|key| SENSITIVE_CONFIG_KEYS.contains(&key.as_str())).count();
+    set_configs(HashMap::from([
+        (String::from(keys::CONFIG_KEY_GLOBAL_CONFIG_ENCRYPTION), enable.to_string())
+    ]))?;
+
+    Ok(sensitive_count)
+}
+
+// generate a random key file to be used instead of a passphrase
+pub fn generate_config_key_file(path: &str, force: bool) -> Result<(), String> {
+    if Path::new(path).exists() && !force {
+        return Err(format!("{} already exists. Consider using --force option to overwrite it", path));
+    }
+
+    let mut file = File::create(path).map_err(|e| format!("Unable to create {}: {}", path, e))?;
+    writeln!(file, "{}", generate_hmac_key(32)).map_err(|e| format!("Unable to write {}: {}", path, e))?;
+
+    // keep the key private
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Unable to set {} permissions: {}", path, e))?;
+    }
+
+    Ok(())
 }
 
 // this wraps base config functions
 #[async_trait]
 pub trait ConfigHandler {
     async fn get_configs(&self) -> BTreeMap<String, String>;
-    async fn set_configs(&self, values: HashMap<String, String>);
+    async fn set_configs(&self, values: HashMap<String, String>) -> Result<(), String>;
 }
 
 pub struct ConfigHandlerImpl;
@@ -128,7 +309,7 @@ impl ConfigHandler for ConfigHandlerImpl {
         get_configs_from_dot_env()
     }
 
-    async fn set_configs(&self, values: HashMap<String, String>) {
-        set_configs(values);
+    async fn set_configs(&self, values: HashMap<String, String>) -> Result<(), String> {
+        set_configs(values)
     }
 }
@@ -38,6 +38,36 @@ pub fn parse_request_bytes(request_bytes: &[u8]) -> Option<Request<Vec<u8>>> {
     }
 }
 
+// parse response from bytes, the body is kept as is
+pub fn parse_response_bytes(response_bytes: &[u8]) -> Option<Response<Vec<u8>>> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut res = httparse::Response::new(&mut headers);
+    match res.parse(response_bytes) {
+        Ok(status) if status.is_complete() => {
+            let version = match res.version.unwrap() {
+                1 => Version::HTTP_11,
+                2 => Version::HTTP_2,
+                _ => Version::HTTP_10,
+            };
+
+            let mut response_builder = Response::builder()
+                .status(res.code.unwrap())
+                .version(version);
+
+            for h in res.headers {
+                response_builder = response_builder.header(h.name, h.value);
+            }
+
+            // extract the body part
+            let header_length = status.unwrap();
+            let body = response_bytes[header_length..].to_vec();
+
+            response_builder.body(body).ok()
+        }
+        _ => None,
+    }
+}
+
 // parse request instance to bytes
 pub fn request_to_bytes(request: &Request<Vec<u8>>) -> Vec<u8> {
     let mut bytes = Vec::new();
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+use super::cache_stats::CacheStats;
+
+// a command sent to the running server through its local admin channel, a JSON line each
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AdminCommand {
+    // remove the cached responses of the client, optionally by path pattern and method
+    PurgeCache {
+        client_id: String,
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        method: Option<String>,
+    },
+    CacheStats,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AdminResponse {
+    // number of removed entries
+    Purged { count: usize },
+    CacheStats { stats: CacheStats },
+    Error { message: String },
+}
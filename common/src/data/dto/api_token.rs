@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+use crate::security::generate_hmac_key;
+use crate::string::generate_rand_id;
+
+// client ids wildcard, the token is allowed for any client id
+pub const API_TOKEN_ANY_CLIENT: &str = "*";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    // signing key of the client handshake, used instead of the server secret
+    pub key: String,
+    // client ids allowed to register with this token
+    pub client_ids: Vec<String>,
+    pub created_at: SystemTime,
+    // no expiry if not set
+    #[serde(default)]
+    pub expires_at: Option<SystemTime>,
+}
+
+impl ApiToken {
+    pub fn new(client_ids: Vec<String>, ttl: Option<Duration>) -> Self {
+        let created_at = SystemTime::now();
+        ApiToken {
+            id: format!("tk_{}", generate_rand_id(16)),
+            key: generate_hmac_key(32),
+            client_ids,
+            created_at,
+            expires_at: ttl.map(|ttl| created_at + ttl),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => SystemTime::now() >= expires_at,
+            None => false,
+        }
+    }
+
+    pub fn allows(&self, client_id: &str) -> bool {
+        self.client_ids.iter().any(|id| id == API_TOKEN_ANY_CLIENT || id == client_id)
+    }
+}
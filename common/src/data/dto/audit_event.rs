@@ -0,0 +1,117 @@
+use std::fmt;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const AUDIT_REDACTED: &str = "[REDACTED]";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    // tunnel handshake, succeeded or denied
+    Registration,
+    // the handshake signature does not match any of the signing keys
+    SignatureMismatch,
+    // both directions of an established tunnel have been closed
+    Disconnect,
+}
+
+impl fmt::Display for AuditEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditEventType::Registration => write!(f, "registration"),
+            AuditEventType::SignatureMismatch => write!(f, "signature_mismatch"),
+            AuditEventType::Disconnect => write!(f, "disconnect"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+// a single line of the audit log, it must never contain any secret
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct AuditEvent {
+    // RFC 3339 in UTC
+    pub timestamp: String,
+    pub event: AuditEventType,
+    pub outcome: AuditOutcome,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    pub tunnel_id: String,
+    #[serde(default)]
+    pub peer_addr: Option<String>,
+    // version code of the client
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub token_id: Option<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    // values to be masked in the reason before the event is written
+    #[serde(skip)]
+    secrets: Vec<String>,
+}
+
+impl AuditEvent {
+    pub fn new(event: AuditEventType, outcome: AuditOutcome, tunnel_id: String) -> Self {
+        AuditEvent {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            event,
+            outcome,
+            client_id: None,
+            tunnel_id,
+            peer_addr: None,
+            version: None,
+            token_id: None,
+            reason: None,
+            secrets: Vec::new(),
+        }
+    }
+
+    pub fn with_client_id(mut self, client_id: Option<String>) -> Self {
+        self.client_id = client_id;
+        self
+    }
+
+    pub fn with_peer_addr(mut self, peer_addr: Option<String>) -> Self {
+        self.peer_addr = peer_addr;
+        self
+    }
+
+    pub fn with_version(mut self, version: Option<String>) -> Self {
+        self.version = version.filter(|v| !v.is_empty());
+        self
+    }
+
+    pub fn with_token_id(mut self, token_id: Option<String>) -> Self {
+        self.token_id = token_id;
+        self
+    }
+
+    pub fn with_reason(mut self, reason: String) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn with_secret(mut self, secret: String) -> Self {
+        if !secret.is_empty() {
+            self.secrets.push(secret);
+        }
+        self
+    }
+
+    // mask the secrets, the event is safe to be written afterwards
+    pub fn redacted(mut self) -> Self {
+        if let Some(reason) = self.reason.as_mut() {
+            for secret in &self.secrets {
+                *reason = reason.replace(secret.as_str(), AUDIT_REDACTED);
+            }
+        }
+        self.secrets.clear();
+        self
+    }
+}
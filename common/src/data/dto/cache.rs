@@ -5,11 +5,54 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Cache {
     pub expired_at: SystemTime,
-    pub data: Vec<u8>
+    pub data: Vec<u8>,
+    // request headers the response varies on, lowercased.
+    // when set, the data is empty and the response is kept per the values of these headers
+    #[serde(default)]
+    pub vary: Vec<String>,
+    // validators of the response for conditional requests
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    // the request the response belongs to, the path is without the query string
+    #[serde(default)]
+    pub client_id: String,
+    #[serde(default)]
+    pub method: String,
+    #[serde(default)]
+    pub path: String,
 }
 
 impl Cache {
     pub fn new(expired_at: SystemTime, data: Vec<u8>) -> Self {
-        Cache { expired_at, data }
+        Cache {
+            expired_at,
+            data,
+            vary: Vec::new(),
+            etag: None,
+            last_modified: None,
+            client_id: String::new(),
+            method: String::new(),
+            path: String::new(),
+        }
+    }
+
+    pub fn with_vary(mut self, vary: Vec<String>) -> Self {
+        self.vary = vary;
+        self
+    }
+
+    pub fn with_validators(mut self, etag: Option<String>, last_modified: Option<String>) -> Self {
+        self.etag = etag;
+        self.last_modified = last_modified;
+        self
+    }
+
+    pub fn with_request(mut self, client_id: String, method: String, path: String) -> Self {
+        self.client_id = client_id;
+        self.method = method;
+        self.path = path;
+        self
     }
 }
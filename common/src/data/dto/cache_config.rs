@@ -1,15 +1,277 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 
+use crate::string::{GlobPattern, RegexPattern};
+
+// matches any client id or method
+pub const CACHE_CONFIG_ANY: &str = "*";
+// paths starting with it are regex patterns, i.e: ~/products/[0-9]+
+pub const CACHE_CONFIG_REGEX_PREFIX: &str = "~";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    // any response is cached for the expiry duration
+    #[default]
+    Fixed,
+    // the response headers decide whether and how long it's cached,
+    // the expiry duration is only used when the response does not tell
+    Http,
+}
+
+impl FromStr for CacheMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "fixed" => Ok(CacheMode::Fixed),
+            "http" => Ok(CacheMode::Http),
+            _ => Err(format!("Unknown cache mode: {}. Valid values: fixed, http", value)),
+        }
+    }
+}
+
+impl fmt::Display for CacheMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheMode::Fixed => write!(f, "fixed"),
+            CacheMode::Http => write!(f, "http"),
+        }
+    }
+}
+
+// what makes two requests share a cache entry, besides the client, method and path
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CacheKey {
+    // lowercased request header names
+    #[serde(default)]
+    pub headers: Vec<String>,
+    #[serde(default)]
+    pub cookies: Vec<String>,
+    // query params left out of the key, `*` ignores the whole query string
+    #[serde(default)]
+    pub ignore_query: Vec<String>,
+    // the order of the query params does not matter
+    #[serde(default)]
+    pub sort_query: bool,
+    #[serde(default = "default_key_body")]
+    pub body: bool,
+    // requests with `Authorization` are never cached unless enabled,
+    // the credentials are then part of the key
+    #[serde(default)]
+    pub authorized: bool,
+}
+
+fn default_key_body() -> bool {
+    true
+}
+
+impl Default for CacheKey {
+    fn default() -> Self {
+        CacheKey {
+            headers: Vec::new(),
+            cookies: Vec::new(),
+            ignore_query: Vec::new(),
+            sort_query: false,
+            body: true,
+            authorized: false,
+        }
+    }
+}
+
+impl CacheKey {
+    pub fn new(headers: Vec<String>, cookies: Vec<String>, ignore_query: Vec<String>) -> Result<Self, String> {
+        let mut key = CacheKey::default();
+        for name in headers.iter().flat_map(|h| h.split(',')) {
+            let name = name.trim().to_lowercase();
+            if name.is_empty() {
+                continue;
+            }
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)) {
+                return Err(format!("Invalid header name: {}", name));
+            }
+            if !key.headers.contains(&name) {
+                key.headers.push(name);
+            }
+        }
+        for name in cookies.iter().flat_map(|c| c.split(',')) {
+            let name = name.trim().to_string();
+            if !name.is_empty() && !key.cookies.contains(&name) {
+                key.cookies.push(name);
+            }
+        }
+        for name in ignore_query.iter().flat_map(|q| q.split(',')) {
+            let name = name.trim().to_string();
+            if !name.is_empty() && !key.ignore_query.contains(&name) {
+                key.ignore_query.push(name);
+            }
+        }
+        key.headers.sort();
+        key.cookies.sort();
+
+        Ok(key)
+    }
+
+    pub fn with_sort_query(mut self, sort_query: bool) -> Self {
+        self.sort_query = sort_query;
+        self
+    }
+
+    pub fn with_body(mut self, body: bool) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn with_authorized(mut self, authorized: bool) -> Self {
+        self.authorized = authorized;
+        self
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        if !self.headers.is_empty() {
+            parts.push(format!("headers: {}", self.headers.join(", ")));
+        }
+        if !self.cookies.is_empty() {
+            parts.push(format!("cookies: {}", self.cookies.join(", ")));
+        }
+        if !self.ignore_query.is_empty() {
+            parts.push(format!("ignore query: {}", self.ignore_query.join(", ")));
+        }
+        if self.sort_query {
+            parts.push(String::from("sorted query"));
+        }
+        if !self.body {
+            parts.push(String::from("no body"));
+        }
+        if self.authorized {
+            parts.push(String::from("authorized"));
+        }
+        if parts.is_empty() {
+            return write!(f, "-");
+        }
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+// how the path of a cache config is matched, from the most specific one
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum CachePathKind {
+    Exact,
+    // `*` and `?` do not cross `/`, `**` matches any number of segments
+    Glob,
+    // prefixed by `~`, must match the whole path
+    Regex,
+}
+
+// a rule is unique by client id, method and path.
+// the client id and method could be `*`, the path could be a glob or a regex
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CacheConfig {
     pub client_id: String,
     pub method: String,
     pub path: String,
-    pub exp_duration: u32
+    pub exp_duration: u32,
+    #[serde(default)]
+    pub mode: CacheMode,
+    #[serde(default)]
+    pub key: CacheKey,
 }
 
 impl CacheConfig {
     pub fn new(client_id: String, method: String, path: String, exp_duration: u32) -> Self {
-        CacheConfig { client_id, method, path, exp_duration }
+        CacheConfig { client_id, method, path, exp_duration, mode: CacheMode::default(), key: CacheKey::default() }
+    }
+
+    pub fn with_mode(mut self, mode: CacheMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_key(mut self, key: CacheKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn path_kind(&self) -> CachePathKind {
+        if self.path.starts_with(CACHE_CONFIG_REGEX_PREFIX) {
+            CachePathKind::Regex
+        } else if self.path.contains(['*', '?', '[', '{']) {
+            CachePathKind::Glob
+        } else {
+            CachePathKind::Exact
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.client_id.trim().is_empty() || self.method.trim().is_empty() {
+            return Err(String::from("Client ID and method cannot be empty"));
+        }
+        CompiledCacheConfig::compile(self.clone()).map(|_| ())
+    }
+
+    // the most specific rule wins: a specific client over `*`, then exact over glob over regex paths
+    // (the longer literal part first), then a specific method over `*`
+    pub fn cmp_precedence(&self, other: &CacheConfig) -> Ordering {
+        let literal_len = |config: &CacheConfig| {
+            let meta = match config.path_kind() {
+                CachePathKind::Regex => "~^$\\.*+?()[]{}|",
+                _ => "*?[]{}",
+            };
+            config.path.chars().filter(|c| !meta.contains(*c)).count()
+        };
+
+        (self.client_id == CACHE_CONFIG_ANY).cmp(&(other.client_id == CACHE_CONFIG_ANY))
+            .then(self.path_kind().cmp(&other.path_kind()))
+            .then(literal_len(other).cmp(&literal_len(self)))
+            .then((self.method == CACHE_CONFIG_ANY).cmp(&(other.method == CACHE_CONFIG_ANY)))
+            .then(self.client_id.cmp(&other.client_id))
+            .then(self.path.cmp(&other.path))
+            .then(self.method.cmp(&other.method))
+    }
+}
+
+enum CachePathPattern {
+    Exact,
+    Glob(GlobPattern),
+    Regex(RegexPattern),
+}
+
+// a config with its path pattern compiled once, for matching many requests
+pub struct CompiledCacheConfig {
+    pub config: CacheConfig,
+    path: CachePathPattern,
+}
+
+impl CompiledCacheConfig {
+    pub fn compile(config: CacheConfig) -> Result<Self, String> {
+        let path = match config.path_kind() {
+            CachePathKind::Exact => CachePathPattern::Exact,
+            CachePathKind::Glob => CachePathPattern::Glob(GlobPattern::path(&config.path)?),
+            CachePathKind::Regex => CachePathPattern::Regex(RegexPattern::new(&config.path[CACHE_CONFIG_REGEX_PREFIX.len()..])?),
+        };
+
+        Ok(CompiledCacheConfig { config, path })
     }
-}
\ No newline at end of file
+
+    // the path must not contain the query string
+    pub fn matches(&self, client_id: &str, method: &str, path: &str) -> bool {
+        let config = &self.config;
+        if config.client_id != CACHE_CONFIG_ANY && config.client_id != client_id {
+            return false;
+        }
+        if config.method != CACHE_CONFIG_ANY && !config.method.eq_ignore_ascii_case(method) {
+            return false;
+        }
+
+        match &self.path {
+            CachePathPattern::Exact => config.path == path,
+            CachePathPattern::Glob(pattern) => pattern.is_match(path),
+            CachePathPattern::Regex(pattern) => pattern.is_match(path),
+        }
+    }
+}
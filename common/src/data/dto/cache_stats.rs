@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+// cached responses of a client
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct ClientCacheStats {
+    pub client_id: String,
+    pub entries: usize,
+    // in bytes
+    pub size: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct CacheStats {
+    // stored entries, the expired ones included
+    pub entries: usize,
+    // expired entries which are not removed yet
+    pub expired: usize,
+    // in bytes, of the entries which are not expired
+    pub size: usize,
+    // sorted by client id
+    pub clients: Vec<ClientCacheStats>,
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+// client id of the server-wide rules
+pub const FILTER_RULE_ALL_CLIENTS: &str = "*";
+pub const FILTER_RULE_DEFAULT_PRIORITY: u32 = 100;
+pub const FILTER_RULE_DEFAULT_DENY_STATUS: u16 = 403;
+
+// what to do with a matching request,
+// allow and deny stop the evaluation, the header actions keep it going
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterAction {
+    Allow,
+    Deny { status: u16 },
+    SetHeader { name: String, value: String },
+    RemoveHeader { name: String },
+}
+
+impl FilterAction {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, FilterAction::Allow | FilterAction::Deny { .. })
+    }
+}
+
+impl fmt::Display for FilterAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterAction::Allow => write!(f, "allow"),
+            FilterAction::Deny { status } => write!(f, "deny {}", status),
+            FilterAction::SetHeader { name, value } => write!(f, "set-header {}: {}", name, value),
+            FilterAction::RemoveHeader { name } => write!(f, "remove-header {}", name),
+        }
+    }
+}
+
+// a header or query parameter, present with any value if the value pattern is not set
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FilterMatch {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+// parsed from `name` or `name=value pattern`
+impl FromStr for FilterMatch {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, pattern) = match value.split_once('=') {
+            Some((name, pattern)) => (name.trim(), Some(pattern.trim().to_string())),
+            None => (value.trim(), None),
+        };
+        if name.is_empty() {
+            return Err(format!("Invalid match: {}. Expected `name` or `name=value`", value));
+        }
+
+        Ok(FilterMatch { name: name.to_string(), value: pattern })
+    }
+}
+
+impl fmt::Display for FilterMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.name, value),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+// a rule of the public requests, scoped to a client or server-wide with `*`.
+// the unset conditions match any request, the rules are evaluated by priority (lowest first)
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FilterRule {
+    pub client_id: String,
+    pub name: String,
+    pub priority: u32,
+    // comma separated methods
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    // glob of the path without the client id, i.e: /admin/**
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<FilterMatch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<FilterMatch>,
+    pub action: FilterAction,
+}
+
+impl FilterRule {
+    pub fn new(client_id: String, name: String, action: FilterAction) -> Self {
+        FilterRule {
+            client_id,
+            name,
+            priority: FILTER_RULE_DEFAULT_PRIORITY,
+            method: None,
+            path: None,
+            header: None,
+            query: None,
+            action,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_method(mut self, method: Option<String>) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn with_path(mut self, path: Option<String>) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub fn with_header(mut self, header: Option<FilterMatch>) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn with_query(mut self, query: Option<FilterMatch>) -> Self {
+        self.query = query;
+        self
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.client_id == FILTER_RULE_ALL_CLIENTS
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+// client id of the server-wide rules
+pub const IP_RULE_ALL_CLIENTS: &str = "*";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum IpRuleAction {
+    Allow,
+    Deny,
+}
+
+impl FromStr for IpRuleAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "allow" => Ok(IpRuleAction::Allow),
+            "deny" => Ok(IpRuleAction::Deny),
+            _ => Err(format!("Unknown ip rule action: {}. Valid values: allow, deny", value)),
+        }
+    }
+}
+
+impl fmt::Display for IpRuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpRuleAction::Allow => write!(f, "allow"),
+            IpRuleAction::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+// allow or deny the public requests from a CIDR block,
+// scoped to a client or server-wide with `*`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct IpRule {
+    pub client_id: String,
+    pub action: IpRuleAction,
+    pub cidr: String,
+}
+
+impl IpRule {
+    pub fn new(client_id: String, action: IpRuleAction, cidr: String) -> Self {
+        IpRule { client_id, action, cidr }
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.client_id == IP_RULE_ALL_CLIENTS
+    }
+}
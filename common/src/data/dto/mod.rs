@@ -1,6 +1,16 @@
+pub mod admin_command;
+pub mod api_token;
+pub mod audit_event;
 pub mod cache_config;
 pub mod cache;
+pub mod cache_stats;
+pub mod filter_rule;
+pub mod ip_rule;
+pub mod previous_secret;
+pub mod public_auth;
 pub mod public_request;
 pub mod public_response;
+pub mod request_allowlist;
 pub mod tunnel_ack;
+pub mod tunnel_challenge;
 pub mod tunnel_client;
\ No newline at end of file
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+// server secret replaced by a rotation,
+// still accepted for the client handshakes until it expires
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PreviousSecret {
+    pub secret: String,
+    pub rotated_at: SystemTime,
+    pub expires_at: SystemTime,
+}
+
+impl PreviousSecret {
+    pub fn new(secret: String, overlap: Duration) -> Self {
+        let rotated_at = SystemTime::now();
+        PreviousSecret {
+            secret,
+            rotated_at,
+            expires_at: rotated_at + overlap,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        SystemTime::now() >= self.expires_at
+    }
+}
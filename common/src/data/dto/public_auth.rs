@@ -0,0 +1,92 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::security::constant_time_eq;
+
+// realm of the WWW-Authenticate challenge
+pub const PUBLIC_AUTH_REALM: &str = "trabas";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PublicAuthScheme {
+    Basic,
+    Bearer,
+}
+
+// credential required to access the public endpoints of a client,
+// only the sha256 of the credential is kept, so it's safe to be stored and shared with the server
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PublicAuth {
+    pub scheme: PublicAuthScheme,
+    // sha256 in hex of `username:password` for basic, or the token for bearer
+    pub credential_hash: String,
+}
+
+impl PublicAuth {
+    pub fn basic(username: &str, password: &str) -> Result<Self, String> {
+        if username.is_empty() || username.contains(':') {
+            return Err(String::from("Basic auth username must not be empty or contain `:`"));
+        }
+        if password.is_empty() {
+            return Err(String::from("Basic auth password must not be empty"));
+        }
+
+        Ok(PublicAuth {
+            scheme: PublicAuthScheme::Basic,
+            credential_hash: hash_credential(&format!("{}:{}", username, password)),
+        })
+    }
+
+    // parse `username:password`
+    pub fn basic_from_str(value: &str) -> Result<Self, String> {
+        let (username, password) = value.split_once(':')
+            .ok_or_else(|| String::from("Basic auth must be in `username:password` format"))?;
+
+        PublicAuth::basic(username, password)
+    }
+
+    pub fn bearer(token: &str) -> Result<Self, String> {
+        if token.trim().is_empty() {
+            return Err(String::from("Bearer token must not be empty"));
+        }
+
+        Ok(PublicAuth {
+            scheme: PublicAuthScheme::Bearer,
+            credential_hash: hash_credential(token.trim()),
+        })
+    }
+
+    // check the value of the Authorization header
+    pub fn matches(&self, authorization: &str) -> bool {
+        let (scheme, credential) = match authorization.trim().split_once(' ') {
+            Some(value) => value,
+            None => return false,
+        };
+        let credential = credential.trim();
+        let credential = match self.scheme {
+            PublicAuthScheme::Basic if scheme.eq_ignore_ascii_case("basic") => {
+                match STANDARD.decode(credential).ok().and_then(|v| String::from_utf8(v).ok()) {
+                    Some(value) => value,
+                    None => return false,
+                }
+            },
+            PublicAuthScheme::Bearer if scheme.eq_ignore_ascii_case("bearer") => credential.to_string(),
+            _ => return false,
+        };
+
+        constant_time_eq(hash_credential(&credential).as_bytes(), self.credential_hash.as_bytes())
+    }
+
+    // the WWW-Authenticate challenge of the scheme
+    pub fn challenge(&self) -> String {
+        match self.scheme {
+            PublicAuthScheme::Basic => format!("Basic realm=\"{}\", charset=\"UTF-8\"", PUBLIC_AUTH_REALM),
+            PublicAuthScheme::Bearer => format!("Bearer realm=\"{}\"", PUBLIC_AUTH_REALM),
+        }
+    }
+}
+
+fn hash_credential(value: &str) -> String {
+    hex::encode(Sha256::digest(value.as_bytes()))
+}
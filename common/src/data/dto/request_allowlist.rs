@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::string::glob_match_path;
+
+// requests a client is willing to expose through its tunnels,
+// declared by the client and advertised to the server on the handshake.
+// an empty list does not restrict anything
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct RequestAllowlist {
+    // path globs, `*` does not cross `/` and `**` matches any number of segments
+    #[serde(default)]
+    pub paths: Vec<String>,
+    // uppercased HTTP methods
+    #[serde(default)]
+    pub methods: Vec<String>,
+}
+
+impl RequestAllowlist {
+    pub fn new(paths: Vec<String>, methods: Vec<String>) -> Result<Self, String> {
+        let paths: Vec<String> = paths.into_iter()
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        for path in &paths {
+            if !path.starts_with('/') {
+                return Err(format!("Allowed path must start with `/`: {}", path));
+            }
+            glob_match_path(path, "/")?;
+        }
+
+        let mut upper_methods: Vec<String> = Vec::new();
+        for method in methods.iter().flat_map(|m| m.split(',')) {
+            let method = method.trim().to_uppercase();
+            if method.is_empty() {
+                continue;
+            }
+            if !method.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(format!("Invalid HTTP method: {}", method));
+            }
+            if !upper_methods.contains(&method) {
+                upper_methods.push(method);
+            }
+        }
+
+        Ok(RequestAllowlist { paths, methods: upper_methods })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.methods.is_empty()
+    }
+
+    // the path must not contain the query string
+    pub fn check(&self, method: &str, path: &str) -> Result<(), String> {
+        if !self.methods.is_empty() && !self.methods.iter().any(|m| m.eq_ignore_ascii_case(method)) {
+            return Err(format!("Method {} is not allowed", method));
+        }
+        if self.paths.is_empty() {
+            return Ok(());
+        }
+
+        // dot segments and encoded separators could escape the allowed prefix
+        // once resolved by the underlying service
+        if has_ambiguous_segment(path) {
+            return Err(format!("Path {} is not allowed", path));
+        }
+        for pattern in &self.paths {
+            if glob_match_path(pattern, path).unwrap_or(false) {
+                return Ok(());
+            }
+        }
+
+        Err(format!("Path {} is not allowed", path))
+    }
+}
+
+impl std::fmt::Display for RequestAllowlist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let paths = if self.paths.is_empty() { String::from("*") } else { self.paths.join(", ") };
+        let methods = if self.methods.is_empty() { String::from("*") } else { self.methods.join(", ") };
+        write!(f, "paths: {}, methods: {}", paths, methods)
+    }
+}
+
+fn has_ambiguous_segment(path: &str) -> bool {
+    let decoded = path.to_lowercase().replace("%2e", ".");
+    decoded.split(['/', '\\']).any(|segment| segment == "." || segment == "..")
+        || decoded.contains("%2f")
+        || decoded.contains("%5c")
+}
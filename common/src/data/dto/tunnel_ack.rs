@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::security::{generate_hmac_key, sign_value};
+use crate::security::{sign_value, sign_value_ed25519};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TunnelAck {
@@ -31,6 +31,25 @@ impl TunnelAck {
         }
     }
 
+    // for the key authenticated clients there is no shared key to sign with,
+    // the ack is signed by the server identity key pinned by the client
+    pub fn success_with_identity_key(
+        id: String,
+        client_mac: String,
+        identity_key: String,
+        public_endpoints: Vec<String>,
+    ) -> Result<Self, String> {
+        let mac = format!("{}_{}", id, client_mac);
+        let signature = sign_value_ed25519(mac, identity_key)?;
+        Ok(TunnelAck {
+            id,
+            signature,
+            success: true,
+            message: "ok".into(),
+            public_endpoints,
+        })
+    }
+
     pub fn fails(tunnel_id: String, message: String) -> Self {
         TunnelAck {
             id: tunnel_id,
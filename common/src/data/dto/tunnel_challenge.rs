@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::security::{sign_value, sign_value_ed25519};
+use super::tunnel_client::TunnelClient;
+
+// challenge-response for every handshake,
+// the server sends the nonce (tunnel id) and the client sends it back signed,
+// with the registered ed25519 key for key authenticated clients, otherwise with the signing key
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TunnelChallenge {
+    pub nonce: String,
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl TunnelChallenge {
+    pub fn new(nonce: String) -> Self {
+        TunnelChallenge { nonce, signature: String::new() }
+    }
+
+    pub fn respond(&self, client: &TunnelClient, private_key: String) -> Result<Self, String> {
+        let signature = sign_value_ed25519(challenge_message(self.nonce.clone(), client), private_key)?;
+
+        Ok(TunnelChallenge { nonce: self.nonce.clone(), signature })
+    }
+
+    pub fn respond_with_hmac(&self, client: &TunnelClient, signing_key: String) -> Self {
+        let signature = sign_value(challenge_message(self.nonce.clone(), client), signing_key);
+
+        TunnelChallenge { nonce: self.nonce.clone(), signature }
+    }
+}
+
+// the signed message is bound to the client identity and the handshake time,
+// so the response can not be reused for another client or handshake
+pub fn challenge_message(nonce: String, client: &TunnelClient) -> String {
+    format!("{}_{}", nonce, client.handshake_mac())
+}
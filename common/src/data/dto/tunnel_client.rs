@@ -1,7 +1,9 @@
+use crate::data::dto::public_auth::PublicAuth;
+use crate::data::dto::request_allowlist::RequestAllowlist;
 use crate::security::{generate_hmac_key, sign_value};
 use crate::version::validate_version;
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TunnelClient {
@@ -23,26 +25,101 @@ pub struct TunnelClient {
     // This field is deprecated and might be removed in the future.
     #[serde(default)]
     pub conn_dc_at: Option<SystemTime>,
+    // API token used to sign the handshake,
+    // the server secret is used if not set
+    #[serde(default)]
+    pub token_id: Option<String>,
+    // authenticate with the ed25519 key registered on the server
+    // instead of the signature, see `TunnelChallenge`
+    #[serde(default)]
+    pub key_auth: bool,
+    // unix timestamp in seconds of the handshake,
+    // the server rejects handshakes outside of its skew window
+    #[serde(default)]
+    pub timestamp: u64,
+    // credentials required to access the public endpoints of the client,
+    // the endpoints are open if empty, unless the server has its own policy
+    #[serde(default)]
+    pub public_auth: Vec<PublicAuth>,
+    // paths and methods exposed by the client,
+    // the server rejects the other requests without forwarding them
+    #[serde(default)]
+    pub allowlist: RequestAllowlist,
 }
 
 impl TunnelClient {
     pub fn new(id: String, signing_key: String, cl_version: String, min_sv_version: String) -> Self {
-        let alias_id = generate_hmac_key(5); // as client nonce
-        let mac = format!("{}_{}", id, alias_id);
-        let signature = sign_value(mac, signing_key);
+        let mut client = TunnelClient {
+            id,
+            alias_id: generate_hmac_key(5), // as client nonce
+            signature: String::new(),
+            cl_version,
+            min_sv_version,
+            conn_est_at: SystemTime::now(),
+            conn_dc_at: None,
+            token_id: None,
+            key_auth: false,
+            timestamp: unix_timestamp(),
+            public_auth: Vec::new(),
+            allowlist: RequestAllowlist::default(),
+        };
+        client.signature = sign_value(client.handshake_mac(), signing_key);
+        client
+    }
+
+    // the client is authenticated later by the challenge-response,
+    // so there is nothing to be signed here
+    pub fn new_with_key_auth(id: String, cl_version: String, min_sv_version: String) -> Self {
         TunnelClient {
             id,
-            alias_id,
-            signature,
+            alias_id: generate_hmac_key(5),
+            signature: String::new(),
             cl_version,
             min_sv_version,
             conn_est_at: SystemTime::now(),
             conn_dc_at: None,
+            token_id: None,
+            key_auth: true,
+            timestamp: unix_timestamp(),
+            public_auth: Vec::new(),
+            allowlist: RequestAllowlist::default(),
         }
     }
 
+    pub fn with_token_id(mut self, token_id: Option<String>) -> Self {
+        self.token_id = token_id;
+        self
+    }
+
+    pub fn with_public_auth(mut self, public_auth: Vec<PublicAuth>) -> Self {
+        self.public_auth = public_auth;
+        self
+    }
+
+    pub fn with_allowlist(mut self, allowlist: RequestAllowlist) -> Self {
+        self.allowlist = allowlist;
+        self
+    }
+
+    // the signed value binds the client nonce to the handshake time
+    pub fn handshake_mac(&self) -> String {
+        format!("{}_{}_{}", self.id, self.alias_id, self.timestamp)
+    }
+
+    // distance between the handshake time and now, in either direction
+    pub fn timestamp_skew(&self) -> Duration {
+        Duration::from_secs(unix_timestamp().abs_diff(self.timestamp))
+    }
+
     pub fn validate_version(&self, server_version: String, min_cl_server: String) -> bool {
         validate_version(server_version, self.min_sv_version.clone())
             && validate_version(self.cl_version.clone(), min_cl_server)
     }
 }
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
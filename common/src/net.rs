@@ -1,10 +1,11 @@
+use std::fmt;
 use std::sync::Arc;
 
 use futures::io;
 use http::{Request, Response, StatusCode, Version};
 use cookie::{Cookie, CookieJar};
 use serde::{Deserialize, Serialize};
-use tokio::{io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf}, net::TcpStream, sync::Mutex};
+use tokio::{io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf}, net::TcpStream, sync::Mutex};
 use tokio_native_tls::TlsStream;
 
 use crate::convert::response_to_bytes;
@@ -15,12 +16,19 @@ use tokio::time::{timeout, Duration};
 pub const HEALTH_CHECK_PACKET_ACK: &str = "hc_1565b85_ack";
 pub const PACKET_SEPARATOR: &str = "$672d20a$";
 
+pub type IoRead = Box<dyn AsyncRead + Unpin + Send>;
+pub type IoWrite = Box<dyn AsyncWrite + Unpin + Send>;
+
 // make tls as an option
 pub struct TcpStreamTLS {
     pub tcp_read: Option<ReadHalf<TcpStream>>,
     pub tcp_write: Option<WriteHalf<TcpStream>>,
     pub tcp_tls_read: Option<ReadHalf<TlsStream<TcpStream>>>,
-    pub tcp_tls_write: Option<WriteHalf<TlsStream<TcpStream>>>
+    pub tcp_tls_write: Option<WriteHalf<TlsStream<TcpStream>>>,
+    // any other stream that is neither plain TCP nor TLS over TCP
+    // i.e: unix domain socket
+    pub io_read: Option<IoRead>,
+    pub io_write: Option<IoWrite>
 }
 
 impl TcpStreamTLS {
@@ -29,7 +37,46 @@ impl TcpStreamTLS {
             tcp_read: Some(tcp_read),
             tcp_write: Some(tcp_write),
             tcp_tls_read: None,
-            tcp_tls_write: None
+            tcp_tls_write: None,
+            io_read: None,
+            io_write: None
+        }
+    }
+
+    pub fn from_io<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static
+    {
+        let (io_read, io_write) = tokio::io::split(stream);
+        TcpStreamTLS {
+            tcp_read: None,
+            tcp_write: None,
+            tcp_tls_read: None,
+            tcp_tls_write: None,
+            io_read: Some(Box::new(io_read)),
+            io_write: Some(Box::new(io_write))
+        }
+    }
+
+    pub fn from_io_read(io_read: IoRead) -> Self {
+        TcpStreamTLS {
+            tcp_read: None,
+            tcp_write: None,
+            tcp_tls_read: None,
+            tcp_tls_write: None,
+            io_read: Some(io_read),
+            io_write: None
+        }
+    }
+
+    pub fn from_io_write(io_write: IoWrite) -> Self {
+        TcpStreamTLS {
+            tcp_read: None,
+            tcp_write: None,
+            tcp_tls_read: None,
+            tcp_tls_write: None,
+            io_read: None,
+            io_write: Some(io_write)
         }
     }
 
@@ -38,7 +85,9 @@ impl TcpStreamTLS {
             tcp_read: Some(tcp),
             tcp_write: None,
             tcp_tls_read: None,
-            tcp_tls_write: None
+            tcp_tls_write: None,
+            io_read: None,
+            io_write: None
         }
     }
 
@@ -47,7 +96,9 @@ impl TcpStreamTLS {
             tcp_read: None,
             tcp_write: Some(tcp),
             tcp_tls_read: None,
-            tcp_tls_write: None
+            tcp_tls_write: None,
+            io_read: None,
+            io_write: None
         }
     }
 
@@ -56,7 +107,9 @@ impl TcpStreamTLS {
             tcp_read: None,
             tcp_write: None,
             tcp_tls_read: Some(tcp_tls_read),
-            tcp_tls_write: Some(tcp_tls_write)
+            tcp_tls_write: Some(tcp_tls_write),
+            io_read: None,
+            io_write: None
         }
     }
 
@@ -65,7 +118,9 @@ impl TcpStreamTLS {
             tcp_read: None,
             tcp_write: None,
             tcp_tls_read: Some(tcp),
-            tcp_tls_write: None
+            tcp_tls_write: None,
+            io_read: None,
+            io_write: None
         }
     }
 
@@ -74,7 +129,9 @@ impl TcpStreamTLS {
             tcp_read: None,
             tcp_write: None,
             tcp_tls_read: None,
-            tcp_tls_write: Some(tcp)
+            tcp_tls_write: Some(tcp),
+            io_read: None,
+            io_write: None
         }
     }
 
@@ -82,9 +139,15 @@ impl TcpStreamTLS {
         self.tcp_tls_read.is_some() || self.tcp_tls_write.is_some()
     }
 
+    pub fn use_io(&self) -> bool {
+        self.io_read.is_some() || self.io_write.is_some()
+    }
+
     pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if self.use_tls() {
             self.tcp_tls_read.as_mut().unwrap().read(buf).await
+        } else if self.use_io() {
+            self.io_read.as_mut().unwrap().read(buf).await
         } else {
             self.tcp_read.as_mut().unwrap().read(buf).await
         }
@@ -93,6 +156,8 @@ impl TcpStreamTLS {
     pub async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
         if self.use_tls() {
             self.tcp_tls_write.as_mut().unwrap().write_all(buf).await
+        } else if self.use_io() {
+            self.io_write.as_mut().unwrap().write_all(buf).await
         } else {
             self.tcp_write.as_mut().unwrap().write_all(buf).await
         }
@@ -124,10 +189,22 @@ impl TcpStreamTLS {
     pub async fn flush(&mut self) -> Result<(), std::io::Error> {
         if self.tcp_tls_write.is_some() {
             self.tcp_tls_write.as_mut().unwrap().flush().await
+        } else if self.io_write.is_some() {
+            self.io_write.as_mut().unwrap().flush().await
         } else {
             self.tcp_write.as_mut().unwrap().flush().await
         }
     }
+
+    pub async fn shutdown(&mut self) -> Result<(), std::io::Error> {
+        if self.tcp_tls_write.is_some() {
+            self.tcp_tls_write.as_mut().unwrap().shutdown().await
+        } else if self.io_write.is_some() {
+            self.io_write.as_mut().unwrap().shutdown().await
+        } else {
+            self.tcp_write.as_mut().unwrap().shutdown().await
+        }
+    }
 }
 
 // IMPORTANT
@@ -460,14 +537,94 @@ pub async fn read_bytes_from_socket_for_http(stream: &mut TcpStreamTLS, res: &mu
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpReadErrorKind {
+    // the header section exceeds the byte limit
+    HeaderTooLarge,
+    // the header section exceeds the count limit
+    TooManyHeaders,
+    // the body exceeds the size limit
+    PayloadTooLarge,
+    // the header or body read timeout exceeded, i.e: slow clients
+    Timeout,
+    // any other failure, i.e: connection reset
+    Io,
+}
+
+impl HttpReadErrorKind {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            HttpReadErrorKind::HeaderTooLarge => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            HttpReadErrorKind::TooManyHeaders => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            HttpReadErrorKind::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            HttpReadErrorKind::Timeout => StatusCode::REQUEST_TIMEOUT,
+            HttpReadErrorKind::Io => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpReadError {
+    pub kind: HttpReadErrorKind,
+    pub message: String,
+}
+
+impl HttpReadError {
+    pub fn new(kind: HttpReadErrorKind, message: String) -> Self {
+        HttpReadError { kind, message }
+    }
+}
+
+impl fmt::Display for HttpReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for HttpReadError {
+    fn from(message: String) -> Self {
+        HttpReadError::new(HttpReadErrorKind::Io, message)
+    }
+}
+
+impl From<HttpReadError> for String {
+    fn from(error: HttpReadError) -> Self {
+        error.message
+    }
+}
+
+// limits of reading an http message, 0 means unlimited and zero duration disables the timeout
+#[derive(Debug, Clone, Default)]
+pub struct HttpReadLimits {
+    pub max_header_bytes: usize,
+    pub max_header_count: usize,
+    pub max_body_size: usize,
+    // time to read the whole header section
+    pub header_timeout: Duration,
+    // time to read the whole body
+    pub body_timeout: Duration,
+}
+
+impl HttpReadLimits {
+    pub fn new(max_header_bytes: usize, max_header_count: usize, max_body_size: usize, header_timeout: Duration, body_timeout: Duration) -> Self {
+        HttpReadLimits { max_header_bytes, max_header_count, max_body_size, header_timeout, body_timeout }
+    }
+}
+
 pub struct HttpReader<'a> {
     tcp_read: &'a mut TcpStreamTLS,
-    break_limit: i32
+    break_limit: i32,
+    limits: HttpReadLimits,
 }
 
 impl<'a> HttpReader<'a> {
     pub fn from_tcp_stream(stream: &'a mut TcpStreamTLS) -> Self {
-        HttpReader { tcp_read: stream, break_limit: 100 }
+        HttpReader { tcp_read: stream, break_limit: 100, limits: HttpReadLimits::default() }
+    }
+
+    pub fn with_limits(mut self, limits: HttpReadLimits) -> Self {
+        self.limits = limits;
+        self
     }
 
     /// Params:
@@ -475,46 +632,27 @@ impl<'a> HttpReader<'a> {
     /// - `immediate_close`: 
     ///   A boolean flag indicating whether to close the stream immediately after reading the headers.
     ///   Useful for reading a client request, as opposed to server response.
-    pub async fn read(&mut self, res: &mut Vec<u8>, immediate_close: bool) -> Result<(), String> {
-        let mut break_cnt = 0;
-        let mut buffer = [0; 1024];
-        let mut prev_len = res.len();
-        // reading headers
-        loop {
-            let n = self.tcp_read.read(&mut buffer).await.map_err(|e| format!("Error reading socket: {}", e))?;
-
-            res.extend_from_slice(&buffer[..n]);
-            // end of headers
-            if res.windows(4).any(|w| w == b"\r\n\r\n") {
-                break;
-            }
-
-            // try at most the break_limit for any empty transfer
-            // TODO: this is not really required
-            let curr_len = res.len();
-            if prev_len == curr_len {
-                if break_cnt == self.break_limit {
-                    _info!("Socket reading break limit exceeded.");
-                    break;
-                }
-                break_cnt += 1;
-            }
-            
-            prev_len = curr_len;
-        }
+    pub async fn read(&mut self, res: &mut Vec<u8>, immediate_close: bool) -> Result<(), HttpReadError> {
+        // reading headers, unless they have been read by the caller
+        let header_timeout = self.limits.header_timeout;
+        with_read_timeout(header_timeout, "Timed out reading request headers", self.read_headers(res)).await?;
 
         // check headers
-        let headers_text = String::from_utf8_lossy(&res);
+        let headers_text = String::from_utf8_lossy(&res).to_string();
         let headers_end = match headers_text.find("\r\n\r\n") {
             Some(value) => value + 4, // skip \r\n\r\n
             None => {
                 return Ok(());
             }
         };
+        self.check_headers(&headers_text[..headers_end])?;
         
-        let content_length: Option<usize> = headers_text
+        // header names are case-insensitive
+        let content_length: Option<usize> = headers_text[..headers_end]
             .lines()
-            .find_map(|line| line.strip_prefix("Content-Length:").map(|len| len.trim().parse().ok()))
+            .find_map(|line| line.split_once(':')
+                .filter(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+                .map(|(_, len)| len.trim().parse().ok()))
             .flatten();
         let is_chunked = headers_text
             .lines()
@@ -532,6 +670,91 @@ impl<'a> HttpReader<'a> {
         //     .lines()
         //     .find_map(|line| line.strip_prefix("Connection:").map(|len| len.trim().to_lowercase().to_string()));
 
+        // reject a declared body over the limit before reading it
+        if let Some(len) = content_length {
+            self.check_body_size(len)?;
+        }
+
+        let body_timeout = self.limits.body_timeout;
+        with_read_timeout(body_timeout, "Timed out reading request body", self.read_body(res, headers_end, content_length, is_chunked, immediate_close)).await?;
+
+        // the body might have come along with the headers
+        self.check_body_size(res.len() - headers_end)
+    }
+
+    async fn read_headers(&mut self, res: &mut Vec<u8>) -> Result<(), HttpReadError> {
+        let mut break_cnt = 0;
+        let mut buffer = [0; 1024];
+        let mut prev_len = res.len();
+        if res.windows(4).any(|w| w == b"\r\n\r\n") {
+            return Ok(());
+        }
+
+        loop {
+            let n = self.tcp_read.read(&mut buffer).await.map_err(|e| format!("Error reading socket: {}", e))?;
+
+            res.extend_from_slice(&buffer[..n]);
+            // end of headers
+            if res.windows(4).any(|w| w == b"\r\n\r\n") {
+                break;
+            }
+            // the end has not been found within the limit
+            if self.limits.max_header_bytes > 0 && res.len() > self.limits.max_header_bytes {
+                return Err(HttpReadError::new(
+                    HttpReadErrorKind::HeaderTooLarge,
+                    format!("Request headers exceed the limit of {} bytes", self.limits.max_header_bytes)
+                ));
+            }
+
+            // try at most the break_limit for any empty transfer
+            // TODO: this is not really required
+            let curr_len = res.len();
+            if prev_len == curr_len {
+                if break_cnt == self.break_limit {
+                    _info!("Socket reading break limit exceeded.");
+                    break;
+                }
+                break_cnt += 1;
+            }
+        
+            prev_len = curr_len;
+        }
+
+        Ok(())
+    }
+
+    // `headers` includes the request/status line and the ending separator
+    fn check_headers(&self, headers: &str) -> Result<(), HttpReadError> {
+        if self.limits.max_header_bytes > 0 && headers.len() > self.limits.max_header_bytes {
+            return Err(HttpReadError::new(
+                HttpReadErrorKind::HeaderTooLarge,
+                format!("Request headers exceed the limit of {} bytes", self.limits.max_header_bytes)
+            ));
+        }
+
+        let header_count = headers.lines().skip(1).filter(|line| !line.is_empty()).count();
+        if self.limits.max_header_count > 0 && header_count > self.limits.max_header_count {
+            return Err(HttpReadError::new(
+                HttpReadErrorKind::TooManyHeaders,
+                format!("Request has {} headers, exceeds the limit of {}", header_count, self.limits.max_header_count)
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_body_size(&self, size: usize) -> Result<(), HttpReadError> {
+        if self.limits.max_body_size > 0 && size > self.limits.max_body_size {
+            return Err(HttpReadError::new(
+                HttpReadErrorKind::PayloadTooLarge,
+                format!("Body size {} bytes exceeds the limit of {} bytes", size, self.limits.max_body_size)
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn read_body(&mut self, res: &mut Vec<u8>, headers_end: usize, content_length: Option<usize>, is_chunked: bool, immediate_close: bool) -> Result<(), HttpReadError> {
         if is_chunked {
             self.read_by_chunk_size(res, headers_end).await?;
         } else if let Some(len) = content_length {
@@ -553,16 +776,16 @@ impl<'a> HttpReader<'a> {
             // }
 
             // if read_until_closed {
-            //     self.read_until_close(res).await?;
+            //     self.read_until_close(res, headers_end).await?;
             // }
 
-            self.read_until_close(res).await?;
+            self.read_until_close(res, headers_end).await?;
         }
 
         Ok(())
     }
 
-    async fn read_by_chunk_size(&mut self, res: &mut Vec<u8>, headers_end: usize) -> Result<(), String> {
+    async fn read_by_chunk_size(&mut self, res: &mut Vec<u8>, headers_end: usize) -> Result<(), HttpReadError> {
         let mut break_cnt = 0;
         let mut buffer = [0; 1024];
         let mut prev_len = res.len();
@@ -626,20 +849,29 @@ impl<'a> HttpReader<'a> {
                 break;
             }
             
-            // read the remaining data in chunk
-            while (body_start + chunk_size) > res.len() {
+            // read the remaining data in chunk,
+            // the size comes from the peer, so it must not overflow the offsets
+            let (body_size, chunk_end) = match (decoded_body.len().checked_add(chunk_size), body_start.checked_add(chunk_size)) {
+                (Some(body_size), Some(chunk_end)) => (body_size, chunk_end),
+                _ => return Err(HttpReadError::new(
+                    HttpReadErrorKind::PayloadTooLarge,
+                    format!("Chunk size {} bytes is too large", chunk_size)
+                )),
+            };
+            self.check_body_size(body_size)?;
+            while chunk_end > res.len() {
                 let n = self.tcp_read.read(&mut buffer).await
                     .map_err(|e| format!("Error reading socket: {}", e))?;
                 if n == 0 {
-                    return Err("Error reading socket: Connection closed before completing chunked transfer".to_string());
+                    return Err("Error reading socket: Connection closed before completing chunked transfer".to_string().into());
                 }
 
                 res.extend_from_slice(&buffer[..n]);
             }
             
             // extract chunk data
-            decoded_body.extend_from_slice(&res[body_start..body_start + chunk_size]);
-            body_start += chunk_size;
+            decoded_body.extend_from_slice(&res[body_start..chunk_end]);
+            body_start = chunk_end;
             
             // perform another reading, if ending separator has not been read
             if (body_start + 2) > res.len() {
@@ -658,7 +890,7 @@ impl<'a> HttpReader<'a> {
         Ok(())
     }
 
-    async fn read_by_content_len(&mut self, res: &mut Vec<u8>, headers_end: usize, content_len: usize) -> Result<(), String> {
+    async fn read_by_content_len(&mut self, res: &mut Vec<u8>, headers_end: usize, content_len: usize) -> Result<(), HttpReadError> {
         let mut buffer = [0; 1024];
         // handle data with Content-Length
         let target_len = headers_end + content_len;
@@ -688,7 +920,7 @@ impl<'a> HttpReader<'a> {
         Ok(())
     }
 
-    async fn read_until_close(&mut self, res: &mut Vec<u8>) -> Result<(), String> {
+    async fn read_until_close(&mut self, res: &mut Vec<u8>, headers_end: usize) -> Result<(), HttpReadError> {
         let mut buffer = [0; 1024];
         // continue reading until end of connection
         loop {
@@ -698,36 +930,77 @@ impl<'a> HttpReader<'a> {
             }
             
             res.extend_from_slice(&buffer[..n]);
+            self.check_body_size(res.len() - headers_end)?;
         }
 
         Ok(())
     }
 }
 
+// zero duration means no timeout
+async fn with_read_timeout<T, F>(duration: Duration, message: &str, future: F) -> Result<T, HttpReadError>
+where
+    F: std::future::Future<Output = Result<T, HttpReadError>>
+{
+    if duration.is_zero() {
+        return future.await;
+    }
+
+    match timeout(duration, future).await {
+        Ok(result) => result,
+        Err(_) => Err(HttpReadError::new(HttpReadErrorKind::Timeout, String::from(message))),
+    }
+}
+
 // standard http response for project-wide
 #[derive(Serialize, Deserialize, Clone)]
 pub struct HttpResponse {
     success: bool,
-    message: String
+    message: String,
+    // machine-readable error code, i.e: upstream_timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    // additional error detail, only for debugging purpose
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<String>
 }
 
 impl HttpResponse {
     pub fn new(success: bool, message: String) -> Self {
-        HttpResponse { success, message }
+        HttpResponse { success, message, code: None, detail: None }
+    }
+
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    pub fn with_detail(mut self, detail: Option<String>) -> Self {
+        self.detail = detail;
+        self
     }
 }
 
 // returns json response for http request
 pub fn http_json_response_as_bytes(response: HttpResponse, status: StatusCode) -> Result<Vec<u8>, String> {
+    http_json_response_with_headers_as_bytes(response, status, Vec::new())
+}
+
+// same as `http_json_response_as_bytes` with additional headers, i.e: WWW-Authenticate
+pub fn http_json_response_with_headers_as_bytes(response: HttpResponse, status: StatusCode, headers: Vec<(String, String)>) -> Result<Vec<u8>, String> {
     let json = serde_json::to_string(&response)
         .map_err(|e| format!("Error parsing json response: {}", e))?;
     let json_bytes = json.as_bytes();
     let content_length = json_bytes.len();
-    let res = Response::builder()
+    let mut builder = Response::builder()
         .version(Version::HTTP_11)
         .status(status)
         .header("Content-Type", "application/json")
-        .header("Content-Length", content_length.to_string())
+        .header("Content-Length", content_length.to_string());
+    for (name, value) in headers {
+        builder = builder.header(name, value);
+    }
+    let res = builder
         .body(Vec::from(json_bytes))
         .map_err(|e| format!("Error building json response: {}", e))?;
 
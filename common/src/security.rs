@@ -1,7 +1,19 @@
 
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use aes_gcm::{aead::Aead, Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
 use rand::Rng;
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
+
+// prefix of the values encrypted by `encrypt_value`, followed by hex of salt | nonce | ciphertext
+pub const ENCRYPTED_VALUE_PREFIX: &str = "enc:v1:";
+const ENCRYPTION_SALT_LEN: usize = 16;
+const ENCRYPTION_NONCE_LEN: usize = 12;
 
 // create a signature of any value using provided secret
 // this is used by client service to create signature for server handshake
@@ -32,6 +44,126 @@ pub fn generate_hmac_key(len: u16) -> String {
 macro_rules! validate_signature {
     ($actual_signature:expr, $pre_signed:expr, $secret:expr) => {{
         let expected = $crate::security::sign_value($pre_signed, $secret);
-        expected == $actual_signature
+        $crate::security::constant_time_eq(expected.as_bytes(), $actual_signature.as_bytes())
     }};
 }
+
+// compare both values without short-circuiting on the first mismatch,
+// so the time taken does not leak how much of a signature is correct
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// generate ed25519 keypair for the client authentication
+// returns the private and public key in hex
+pub fn generate_ed25519_keypair() -> (String, String) {
+    let signing_key = SigningKey::generate(&mut rand::thread_rng());
+    let private_key = hex::encode(signing_key.to_bytes());
+    let public_key = hex::encode(signing_key.verifying_key().to_bytes());
+
+    (private_key, public_key)
+}
+
+// derive the public key in hex from the private key in hex
+pub fn ed25519_public_key_of(private_key: String) -> Result<String, String> {
+    let signing_key = parse_ed25519_private_key(private_key)?;
+
+    Ok(hex::encode(signing_key.verifying_key().to_bytes()))
+}
+
+// create an ed25519 signature of any value using the private key in hex
+pub fn sign_value_ed25519(message: String, private_key: String) -> Result<String, String> {
+    let signing_key = parse_ed25519_private_key(private_key)?;
+    let signature = signing_key.sign(message.as_bytes());
+
+    Ok(hex::encode(signature.to_bytes()))
+}
+
+// validate an ed25519 signature in hex using the public key in hex
+pub fn validate_signature_ed25519(signature: String, message: String, public_key: String) -> bool {
+    let verifying_key = match parse_ed25519_public_key(public_key) {
+        Ok(value) => value,
+        Err(_) => return false,
+    };
+    let signature: [u8; 64] = match hex::decode(signature).ok().and_then(|b| b.try_into().ok()) {
+        Some(value) => value,
+        None => return false,
+    };
+
+    verifying_key.verify(message.as_bytes(), &Signature::from_bytes(&signature)).is_ok()
+}
+
+pub fn parse_ed25519_public_key(public_key: String) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(public_key.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| String::from("Invalid ed25519 public key: must be 32 bytes in hex"))?;
+
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid ed25519 public key: {}", e))
+}
+
+fn parse_ed25519_private_key(private_key: String) -> Result<SigningKey, String> {
+    let bytes: [u8; 32] = hex::decode(private_key.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| String::from("Invalid ed25519 private key: must be 32 bytes in hex"))?;
+
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+// the key derivation is intentionally slow, so the derived keys are cached per process,
+// and the values encrypted by the same process share the salt
+static ENCRYPTION_SALT: Lazy<[u8; ENCRYPTION_SALT_LEN]> = Lazy::new(|| rand::thread_rng().gen());
+static DERIVED_KEYS: Lazy<Mutex<HashMap<String, [u8; 32]>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn is_encrypted_value(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_VALUE_PREFIX)
+}
+
+// encrypt the value with AES-256-GCM, the key is derived from the passphrase (or key file content) with argon2
+pub fn encrypt_value(value: &str, passphrase: &str) -> Result<String, String> {
+    let salt = *ENCRYPTION_SALT;
+    let nonce: [u8; ENCRYPTION_NONCE_LEN] = rand::thread_rng().gen();
+    let key = derive_encryption_key(passphrase, &salt)?;
+
+    let cipher = <Aes256Gcm as aes_gcm::KeyInit>::new(Key::<Aes256Gcm>::from_slice(&key));
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), value.as_bytes())
+        .map_err(|e| format!("Error encrypting value: {}", e))?;
+
+    Ok(format!("{}{}", ENCRYPTED_VALUE_PREFIX, hex::encode([&salt[..], &nonce[..], &ciphertext[..]].concat())))
+}
+
+pub fn decrypt_value(value: &str, passphrase: &str) -> Result<String, String> {
+    let data = value.strip_prefix(ENCRYPTED_VALUE_PREFIX)
+        .and_then(|v| hex::decode(v).ok())
+        .filter(|v| v.len() > ENCRYPTION_SALT_LEN + ENCRYPTION_NONCE_LEN)
+        .ok_or_else(|| String::from("Invalid encrypted value"))?;
+    let (salt, rest) = data.split_at(ENCRYPTION_SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(ENCRYPTION_NONCE_LEN);
+    let key = derive_encryption_key(passphrase, salt)?;
+
+    let cipher = <Aes256Gcm as aes_gcm::KeyInit>::new(Key::<Aes256Gcm>::from_slice(&key));
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| String::from("Error decrypting value: wrong passphrase or key file"))?;
+
+    String::from_utf8(plaintext).map_err(|e| format!("Error decrypting value: {}", e))
+}
+
+fn derive_encryption_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    // never keep the passphrase itself in the cache key
+    let cache_key = format!("{}_{}", hex::encode(Sha256::digest(passphrase.as_bytes())), hex::encode(salt));
+    if let Some(key) = DERIVED_KEYS.lock().unwrap().get(&cache_key) {
+        return Ok(*key);
+    }
+
+    let mut key = [0u8; 32];
+    Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Error deriving encryption key: {}", e))?;
+    DERIVED_KEYS.lock().unwrap().insert(cache_key, key);
+
+    Ok(key)
+}
@@ -14,3 +14,114 @@ pub fn generate_rand_id(len: usize) -> String {
         .collect();
     chars
 }
+
+// `*` and `?` do not cross `/`, `**` matches any number of segments
+pub fn glob_match_path(pattern: &str, path: &str) -> Result<bool, String> {
+    glob_match_with(pattern, path, true)
+}
+
+// `*` matches any characters, `/` included
+pub fn glob_match(pattern: &str, value: &str) -> Result<bool, String> {
+    glob_match_with(pattern, value, false)
+}
+
+fn glob_match_with(pattern: &str, value: &str, literal_separator: bool) -> Result<bool, String> {
+    Ok(GlobPattern::build(pattern, literal_separator)?.is_match(value))
+}
+
+// a glob compiled once, for matching many values
+#[derive(Clone, Debug)]
+pub struct GlobPattern {
+    matcher: globset::GlobMatcher,
+}
+
+impl GlobPattern {
+    // same as `glob_match_path`
+    pub fn path(pattern: &str) -> Result<Self, String> {
+        Self::build(pattern, true)
+    }
+
+    // same as `glob_match`
+    pub fn any(pattern: &str) -> Result<Self, String> {
+        Self::build(pattern, false)
+    }
+
+    fn build(pattern: &str, literal_separator: bool) -> Result<Self, String> {
+        let glob = globset::GlobBuilder::new(pattern)
+            .literal_separator(literal_separator)
+            .build()
+            .map_err(|e| format!("Invalid glob pattern {}: {}", pattern, e))?;
+
+        Ok(GlobPattern { matcher: glob.compile_matcher() })
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.matcher.is_match(value)
+    }
+}
+
+// the path as the underlying service might resolve it, percent-decoded and without dot segments,
+// i.e: `/%2egit/config` and `/x/../.git/config` are both `/.git/config`.
+// backslashes and the encoded separators are treated as `/`, empty segments are dropped
+pub fn normalize_path(path: &str) -> String {
+    let decoded = percent_decode(path).replace('\\', "/");
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {},
+            ".." => { segments.pop(); },
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut normalized = format!("/{}", segments.join("/"));
+    if !segments.is_empty() && decoded.ends_with('/') {
+        normalized.push('/');
+    }
+    normalized
+}
+
+// invalid escapes are kept as they are
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = |b: u8| (b as char).to_digit(16);
+            if let (Some(high), Some(low)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+// the pattern must match the whole value
+pub fn regex_match(pattern: &str, value: &str) -> Result<bool, String> {
+    Ok(RegexPattern::new(pattern)?.is_match(value))
+}
+
+// a regex compiled once, same as `regex_match`
+#[derive(Clone, Debug)]
+pub struct RegexPattern {
+    regex: regex::Regex,
+}
+
+impl RegexPattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let regex = regex::Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|e| format!("Invalid regex pattern {}: {}", pattern, e))?;
+
+        Ok(RegexPattern { regex })
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+}
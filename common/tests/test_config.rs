@@ -5,7 +5,17 @@ mod tests {
     use std::fs::{self, File};
     use std::io::Write;
     use std::path::Path;
-    use common::config::{get_config_path, get_configs_from_dot_env, get_configs_from_proc_env, init_env_from_config, set_configs};
+    use common::config::{
+        get_config_path, get_configs_from_dot_env, get_configs_from_proc_env, init_env_from_config, keys, set_config_encryption, set_configs,
+        CONFIG_ENV_KEY_FILE, CONFIG_ENV_PASSPHRASE,
+    };
+    use serial_test::serial;
+
+    // every test shares the config file next to the test binary and the process env,
+    // so these run one at a time and leave nothing behind
+    const TEST_ENV_KEYS: [&str; 5] = [
+        "TEST_KEY_1", "TEST_KEY_2", "TEST_KEY_3", CONFIG_ENV_PASSPHRASE, CONFIG_ENV_KEY_FILE,
+    ];
 
 
     fn get_test_env_path() -> String {
@@ -27,8 +37,11 @@ mod tests {
         writeln!(file, "TEST_KEY_2=test_value_2").expect("Failed to write to mock .env file");
     }
 
-    // remove the mock .env file
+    // remove the mock .env file and the env vars set by the tests
     fn teardown() {
+        for key in TEST_ENV_KEYS.iter().chain([keys::CONFIG_KEY_SERVER_SECRET, keys::CONFIG_KEY_GLOBAL_CONFIG_ENCRYPTION].iter()) {
+            env::remove_var(key);
+        }
         let env_path = get_test_env_path();
         let env_path_exists = Path::new(&env_path).exists();
         if env_path_exists {
@@ -42,6 +55,7 @@ mod tests {
     }
 
     #[test]
+    #[serial]
     fn test_get_configs_from_dot_env() {
         setup();
         let configs = get_configs_from_dot_env();
@@ -52,12 +66,13 @@ mod tests {
         teardown();
     }
     #[test]
+    #[serial]
     fn test_set_configs() {
         setup();
         let mut new_configs = HashMap::new();
         new_configs.insert("TEST_KEY_2".to_string(), "new_value_2".to_string());
         new_configs.insert("TEST_KEY_3".to_string(), "test_value_3".to_string());
-        set_configs(new_configs);
+        set_configs(new_configs).unwrap();
 
         let configs_from_dot_env = get_configs_from_dot_env();
         let configs_from_proc_env = get_configs_from_proc_env();
@@ -75,7 +90,8 @@ mod tests {
         teardown();
     }
 
-     #[test]
+    #[test]
+    #[serial]
     fn test_init_env_from_config() {
         setup();
         init_env_from_config();
@@ -84,4 +100,54 @@ mod tests {
         assert_eq!(env::var("TEST_KEY_2").unwrap(), "test_value_2");
         teardown();
     }
+
+    #[test]
+    #[serial]
+    fn test_config_encryption() {
+        setup();
+        env::set_var(CONFIG_ENV_PASSPHRASE, "test passphrase");
+        set_configs(HashMap::from([(keys::CONFIG_KEY_SERVER_SECRET.to_string(), "secret_value".to_string())])).unwrap();
+
+        // migrate the plaintext config
+        assert_eq!(set_config_encryption(true).unwrap(), 1);
+        let raw = fs::read_to_string(get_test_env_path()).unwrap();
+        assert!(!raw.contains("secret_value"));
+        assert!(raw.contains(&format!("{}=enc:v1:", keys::CONFIG_KEY_SERVER_SECRET)));
+        assert!(raw.contains("TEST_KEY_1=test_value_1"));
+
+        // decrypted transparently
+        assert_eq!(get_configs_from_dot_env().get(keys::CONFIG_KEY_SERVER_SECRET).unwrap(), "secret_value");
+        env::remove_var(keys::CONFIG_KEY_SERVER_SECRET);
+        init_env_from_config();
+        assert_eq!(env::var(keys::CONFIG_KEY_SERVER_SECRET).unwrap(), "secret_value");
+
+        // the values can not be read with another passphrase
+        env::set_var(CONFIG_ENV_PASSPHRASE, "other passphrase");
+        assert!(set_config_encryption(false).is_err());
+
+        // back to plaintext
+        env::set_var(CONFIG_ENV_PASSPHRASE, "test passphrase");
+        set_config_encryption(false).unwrap();
+        let raw = fs::read_to_string(get_test_env_path()).unwrap();
+        assert!(raw.contains(&format!("{}=secret_value", keys::CONFIG_KEY_SERVER_SECRET)));
+
+        teardown();
+    }
+
+    #[test]
+    #[serial]
+    fn test_set_configs_without_passphrase() {
+        setup();
+        env::set_var(CONFIG_ENV_PASSPHRASE, "test passphrase");
+        set_config_encryption(true).unwrap();
+        let raw = fs::read_to_string(get_test_env_path()).unwrap();
+
+        // nothing is written, the existing file is kept as is
+        env::remove_var(CONFIG_ENV_PASSPHRASE);
+        let res = set_configs(HashMap::from([(keys::CONFIG_KEY_SERVER_SECRET.to_string(), "secret_value".to_string())]));
+        assert!(res.is_err());
+        assert_eq!(fs::read_to_string(get_test_env_path()).unwrap(), raw);
+
+        teardown();
+    }
 }
@@ -56,6 +56,21 @@ mod tests {
         assert_eq!(&buf, b"hello");
     }
 
+    #[tokio::test]
+    async fn test_tcp_stream_tls_from_io_read_write() {
+        let (server_io, client_io) = tokio::io::duplex(64);
+        let mut server_stream = net::TcpStreamTLS::from_io(server_io);
+        let mut client_stream = net::TcpStreamTLS::from_io(client_io);
+        assert!(server_stream.use_io());
+        assert!(!server_stream.use_tls());
+
+        client_stream.write_all("GET / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello".as_bytes()).await.unwrap();
+
+        let mut res = Vec::new();
+        net::HttpReader::from_tcp_stream(&mut server_stream).read(&mut res, true).await.unwrap();
+        assert!(String::from_utf8(res).unwrap().ends_with("hello"));
+    }
+
     #[tokio::test]
     async fn test_http_json_response_as_bytes() {
         let response = net::HttpResponse::new(true, "test message".to_string());
@@ -157,4 +172,63 @@ mod tests {
         let (_, mut write) = tokio::io::split(stream);
         write.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nHello, world!").await.unwrap();
     }
-}
\ No newline at end of file
+
+    async fn read_with_limits(request: Vec<u8>, limits: net::HttpReadLimits) -> Result<Vec<u8>, net::HttpReadError> {
+        let (server_io, client_io) = tokio::io::duplex(1024);
+        let mut server_stream = net::TcpStreamTLS::from_io(server_io);
+        let mut client_stream = net::TcpStreamTLS::from_io(client_io);
+        // keep the client side open, i.e: a slow client
+        tokio::spawn(async move {
+            client_stream.write_all(&request).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        });
+
+        let mut res = Vec::new();
+        net::HttpReader::from_tcp_stream(&mut server_stream).with_limits(limits).read(&mut res, true).await?;
+        Ok(res)
+    }
+
+    #[tokio::test]
+    async fn test_http_reader_limits() {
+        use std::time::Duration;
+        use net::{HttpReadErrorKind, HttpReadLimits};
+
+        let limits = HttpReadLimits::new(128, 2, 8, Duration::from_millis(200), Duration::from_millis(200));
+
+        // within the limits
+        let res = read_with_limits(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello".to_vec(), limits.clone()).await.unwrap();
+        assert!(String::from_utf8(res).unwrap().ends_with("hello"));
+
+        let err = read_with_limits(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 9\r\n\r\nhellohell".to_vec(), limits.clone()).await.unwrap_err();
+        assert_eq!(err.kind, HttpReadErrorKind::PayloadTooLarge);
+        assert_eq!(err.kind.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let err = read_with_limits(b"GET / HTTP/1.1\r\nHost: a\r\nA: 1\r\nB: 2\r\n\r\n".to_vec(), limits.clone()).await.unwrap_err();
+        assert_eq!(err.kind, HttpReadErrorKind::TooManyHeaders);
+        assert_eq!(err.kind.status(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+
+        let long_header = format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", "a".repeat(256)).into_bytes();
+        let err = read_with_limits(long_header, limits.clone()).await.unwrap_err();
+        assert_eq!(err.kind, HttpReadErrorKind::HeaderTooLarge);
+
+        // the header section never ends
+        let err = read_with_limits(b"GET / HTTP/1.1\r\nHost: a\r\n".to_vec(), limits.clone()).await.unwrap_err();
+        assert_eq!(err.kind, HttpReadErrorKind::Timeout);
+        assert_eq!(err.kind.status(), StatusCode::REQUEST_TIMEOUT);
+
+        // the body is shorter than declared
+        let err = read_with_limits(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhe".to_vec(), limits.clone()).await.unwrap_err();
+        assert_eq!(err.kind, HttpReadErrorKind::Timeout);
+
+        // no limits by default
+        let res = read_with_limits(b"POST / HTTP/1.1\r\nContent-Length: 9\r\n\r\nhellohell".to_vec(), HttpReadLimits::default()).await.unwrap();
+        assert!(String::from_utf8(res).unwrap().ends_with("hellohell"));
+
+        // a chunk size overflowing the body size is rejected, with or without the limits
+        let oversized = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\nffffffffffffffff\r\n".to_vec();
+        let err = read_with_limits(oversized.clone(), limits).await.unwrap_err();
+        assert_eq!(err.kind, HttpReadErrorKind::PayloadTooLarge);
+        let err = read_with_limits(oversized, HttpReadLimits::default()).await.unwrap_err();
+        assert_eq!(err.kind, HttpReadErrorKind::PayloadTooLarge);
+    }
+}
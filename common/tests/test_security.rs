@@ -1,6 +1,11 @@
 #[cfg(test)]
 mod tests {
-    use common::security::{sign_value, generate_hmac_key};
+    use common::security::{
+        sign_value, generate_hmac_key, generate_ed25519_keypair, ed25519_public_key_of,
+        sign_value_ed25519, validate_signature_ed25519, constant_time_eq,
+        encrypt_value, decrypt_value, is_encrypted_value,
+    };
+    use common::validate_signature;
 
     #[test]
     fn test_sign_value() {
@@ -22,14 +27,57 @@ mod tests {
         let message = "test message".to_string();
         let secret = "test secret".to_string();
         let signature = sign_value(message.clone(), secret.clone());
-        
-        macro_rules! validate_signature {
-            ($actual_signature:expr, $pre_signed:expr, $secret:expr) => {{
-                let expected = common::security::sign_value($pre_signed.to_string(), $secret.to_string());
-                expected == $actual_signature
-            }};
-        }
-
-        assert!(validate_signature!(signature, message, secret));
+
+        assert!(validate_signature!(signature.clone(), message.clone(), secret.clone()));
+        assert!(!validate_signature!(signature.clone(), "other message".to_string(), secret.clone()));
+        assert!(!validate_signature!(signature[..10].to_string(), message, secret));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abcdef", b"abcdef"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"abcdef", b"abcdeg"));
+        assert!(!constant_time_eq(b"abcdef", b"abcde"));
+    }
+
+    #[test]
+    fn test_ed25519_sign_and_validate() {
+        let (private_key, public_key) = generate_ed25519_keypair();
+        assert_eq!(private_key.len(), 64);
+        assert_eq!(public_key.len(), 64);
+        assert_eq!(ed25519_public_key_of(private_key.clone()).unwrap(), public_key);
+
+        let message = "tunnel_id_client_id_alias_id".to_string();
+        let signature = sign_value_ed25519(message.clone(), private_key).unwrap();
+        assert!(validate_signature_ed25519(signature.clone(), message.clone(), public_key.clone()));
+        assert!(!validate_signature_ed25519(signature.clone(), "other message".to_string(), public_key));
+
+        // signed by another key
+        let (_, other_public_key) = generate_ed25519_keypair();
+        assert!(!validate_signature_ed25519(signature, message.clone(), other_public_key.clone()));
+        // malformed signature
+        assert!(!validate_signature_ed25519("zz".to_string(), message, other_public_key));
+    }
+
+    #[test]
+    fn test_ed25519_invalid_keys() {
+        assert!(sign_value_ed25519("message".to_string(), "abcd".to_string()).is_err());
+        assert!(ed25519_public_key_of("not hex".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt_value() {
+        let encrypted = encrypt_value("my secret", "passphrase").unwrap();
+        assert!(is_encrypted_value(&encrypted));
+        assert!(!encrypted.contains("my secret"));
+        assert_eq!(decrypt_value(&encrypted, "passphrase").unwrap(), "my secret");
+
+        // a fresh nonce for every value
+        assert_ne!(encrypt_value("my secret", "passphrase").unwrap(), encrypted);
+
+        assert!(decrypt_value(&encrypted, "wrong passphrase").is_err());
+        assert!(decrypt_value("enc:v1:zz", "passphrase").is_err());
+        assert!(!is_encrypted_value("my secret"));
     }
 }
@@ -1,11 +1,15 @@
 #[cfg(test)]
 mod tests {
-    use std::time::UNIX_EPOCH;
+    use std::time::{Duration, UNIX_EPOCH};
     use common::data::dto::{
-        cache_config::CacheConfig,
+        api_token::ApiToken,
+        cache_config::{CacheConfig, CacheKey, CacheMode},
         cache::Cache,
+        filter_rule::{FilterAction, FilterMatch, FilterRule},
+        public_auth::{PublicAuth, PublicAuthScheme},
         public_request::PublicRequest,
         public_response::PublicResponse,
+        request_allowlist::RequestAllowlist,
         tunnel_ack::TunnelAck,
         tunnel_client::TunnelClient,
     };
@@ -160,6 +164,9 @@ mod tests {
         assert_eq!(deserialized.min_sv_version, tunnel_client.min_sv_version);
         assert!(!deserialized.alias_id.is_empty());
         assert!(deserialized.conn_dc_at.is_none());
+        assert_eq!(deserialized.timestamp, tunnel_client.timestamp);
+        assert!(deserialized.timestamp_skew().as_secs() <= 1);
+        assert_eq!(deserialized.handshake_mac(), format!("client_abc123_{}_{}", deserialized.alias_id, deserialized.timestamp));
     }
 
     #[test]
@@ -199,6 +206,9 @@ mod tests {
         assert_eq!(deserialized.id, "client_test");
         assert_eq!(deserialized.alias_id, "alias123");
         assert_eq!(deserialized.signature, "test_sig");
+        // handshakes without timestamp are always out of the skew window
+        assert_eq!(deserialized.timestamp, 0);
+        assert!(deserialized.timestamp_skew().as_secs() > 300);
     }
 
     #[test]
@@ -217,6 +227,26 @@ mod tests {
         assert_eq!(deserialized.method, "POST");
         assert_eq!(deserialized.path, "/api/users");
         assert_eq!(deserialized.exp_duration, 7200);
+        // configs written before the mode and the key were introduced keep the previous behavior
+        assert_eq!(deserialized.mode, CacheMode::Fixed);
+        assert_eq!(deserialized.key, CacheKey::default());
+        assert!(deserialized.key.body && !deserialized.key.authorized);
+
+        let deserialized: CacheConfig = serde_json::from_str(&raw_json.replace("7200", r#"7200, "mode": "http""#))
+            .expect("Failed to deserialize CacheConfig from raw JSON");
+        assert_eq!(deserialized.mode, CacheMode::Http);
+    }
+
+    #[test]
+    fn test_cache_key_new() {
+        let key = CacheKey::new(vec!["X-Tenant, Accept-Language".into(), "x-tenant".into()], vec!["session".into()], vec!["utm_source".into()]).unwrap();
+        assert_eq!(key.headers, vec!["accept-language".to_string(), "x-tenant".to_string()]);
+        assert_eq!(key.cookies, vec!["session".to_string()]);
+        assert_eq!(key.ignore_query, vec!["utm_source".to_string()]);
+        assert_eq!(key.to_string(), "headers: accept-language, x-tenant; cookies: session; ignore query: utm_source");
+        assert_eq!(CacheKey::default().to_string(), "-");
+
+        assert!(CacheKey::new(vec!["X Tenant".into()], vec![], vec![]).is_err());
     }
 
     #[test]
@@ -235,6 +265,8 @@ mod tests {
         assert_eq!(deserialized.data, b"test data".to_vec());
         let expected_time = UNIX_EPOCH + std::time::Duration::new(1234567890, 123456789);
         assert_eq!(deserialized.expired_at, expected_time);
+        assert!(deserialized.vary.is_empty());
+        assert_eq!(deserialized.etag, None);
     }
 
     #[test]
@@ -370,6 +402,7 @@ mod tests {
         assert_eq!(deserialized.cl_version, ""); // Should default to empty string
         assert_eq!(deserialized.min_sv_version, ""); // Should default to empty string
         assert!(deserialized.conn_dc_at.is_none());
+        assert!(deserialized.token_id.is_none());
     }
 
     #[test]
@@ -427,4 +460,116 @@ mod tests {
         assert_eq!(deserialized.cl_version, "");
         assert_eq!(deserialized.min_sv_version, "");
     }
+
+    #[test]
+    fn test_api_token_serialization() {
+        let token = ApiToken::new(vec!["client123".to_string()], Some(Duration::from_secs(3600)));
+        assert!(token.id.starts_with("tk_"));
+        assert!(!token.is_expired());
+        assert!(token.allows("client123"));
+        assert!(!token.allows("client456"));
+
+        let serialized = serde_json::to_string(&token).expect("Failed to serialize ApiToken");
+        let deserialized: ApiToken = serde_json::from_str(&serialized).expect("Failed to deserialize ApiToken");
+        assert_eq!(deserialized.id, token.id);
+        assert_eq!(deserialized.key, token.key);
+        assert_eq!(deserialized.client_ids, token.client_ids);
+        assert_eq!(deserialized.expires_at, token.expires_at);
+    }
+
+    #[test]
+    fn test_api_token_expiry_and_wildcard() {
+        let token = ApiToken::new(vec!["*".to_string()], Some(Duration::ZERO));
+        assert!(token.is_expired());
+        assert!(token.allows("any_client"));
+
+        let token = ApiToken::new(vec!["client123".to_string()], None);
+        assert!(token.expires_at.is_none());
+        assert!(!token.is_expired());
+    }
+
+    #[test]
+    fn test_public_auth_matches() {
+        // admin:secret
+        let basic = PublicAuth::basic("admin", "secret").unwrap();
+        assert_eq!(basic, PublicAuth::basic_from_str("admin:secret").unwrap());
+        assert!(!basic.credential_hash.contains("secret"));
+        assert!(basic.matches("Basic YWRtaW46c2VjcmV0"));
+        assert!(basic.matches("basic YWRtaW46c2VjcmV0"));
+        assert!(!basic.matches("Basic YWRtaW46d3Jvbmc="));
+        assert!(!basic.matches("Bearer admin:secret"));
+        assert!(!basic.matches("Basic not-base64"));
+
+        let bearer = PublicAuth::bearer("token123").unwrap();
+        assert!(bearer.matches("Bearer token123"));
+        assert!(!bearer.matches("Bearer token1234"));
+        assert!(!bearer.matches("token123"));
+
+        assert!(PublicAuth::basic_from_str("admin").is_err());
+        assert!(PublicAuth::basic("", "secret").is_err());
+        assert!(PublicAuth::bearer(" ").is_err());
+    }
+
+    #[test]
+    fn test_public_auth_serialization() {
+        let client = TunnelClient::new("client123".to_string(), "key".to_string(), "0.2.0".to_string(), "0.2.0".to_string())
+            .with_public_auth(vec![PublicAuth::bearer("token123").unwrap()]);
+        let serialized = serde_json::to_string(&client).expect("Failed to serialize TunnelClient");
+        let deserialized: TunnelClient = serde_json::from_str(&serialized).expect("Failed to deserialize TunnelClient");
+        assert_eq!(deserialized.public_auth.len(), 1);
+        assert_eq!(deserialized.public_auth[0].scheme, PublicAuthScheme::Bearer);
+        assert!(deserialized.public_auth[0].matches("Bearer token123"));
+    }
+
+    #[test]
+    fn test_filter_rule_raw_json_deserialization() {
+        let json = r#"{"client_id":"*","name":"block-git","priority":10,"path":"/.git/**","action":{"type":"deny","status":404}}"#;
+        let rule: FilterRule = serde_json::from_str(json).expect("Failed to deserialize FilterRule");
+        assert!(rule.is_global());
+        assert_eq!(rule.path, Some("/.git/**".to_string()));
+        assert_eq!(rule.method, None);
+        assert_eq!(rule.action, FilterAction::Deny { status: 404 });
+
+        let rule = FilterRule::new("client1".to_string(), "tag".to_string(), FilterAction::SetHeader { name: "X-Edge".to_string(), value: "1".to_string() })
+            .with_header(Some("X-Debug=on".parse::<FilterMatch>().unwrap()));
+        let serialized = serde_json::to_string(&rule).expect("Failed to serialize FilterRule");
+        assert!(serialized.contains(r#""action":{"type":"set_header","name":"X-Edge","value":"1"}"#));
+        let deserialized: FilterRule = serde_json::from_str(&serialized).expect("Failed to deserialize FilterRule");
+        assert_eq!(deserialized, rule);
+        assert!("=on".parse::<FilterMatch>().is_err());
+    }
+
+    #[test]
+    fn test_request_allowlist_raw_json_deserialization() {
+        // the allowlist is missing from older clients
+        let raw_json = r#"{"id":"client123","alias_id":"alias","signature":"sig","conn_est_at":{"secs_since_epoch":0,"nanos_since_epoch":0}}"#;
+        let client: TunnelClient = serde_json::from_str(raw_json).unwrap();
+        assert!(client.allowlist.is_empty());
+
+        let raw_json = r#"{"paths":["/api/public/*"],"methods":["GET","HEAD"]}"#;
+        let allowlist: RequestAllowlist = serde_json::from_str(raw_json).unwrap();
+        assert!(allowlist.check("GET", "/api/public/users").is_ok());
+        assert!(allowlist.check("head", "/api/public/users").is_ok());
+        assert!(allowlist.check("POST", "/api/public/users").is_err());
+        assert!(allowlist.check("GET", "/api/public/users/1").is_err());
+        assert!(allowlist.check("GET", "/api/public/%2e%2e/private").is_err());
+        assert!(allowlist.check("GET", "/api/public/..%2Fprivate").is_err());
+    }
+
+    #[test]
+    fn test_request_allowlist_new() {
+        let allowlist = RequestAllowlist::new(vec!["/static/**".to_string()], vec!["get, head".to_string()]).unwrap();
+        assert_eq!(allowlist.methods, vec!["GET".to_string(), "HEAD".to_string()]);
+        assert!(allowlist.check("GET", "/static/css/main.css").is_ok());
+
+        // only the methods are restricted
+        let allowlist = RequestAllowlist::new(Vec::new(), vec!["GET".to_string()]).unwrap();
+        assert!(allowlist.check("GET", "/anything/../else").is_ok());
+        assert!(allowlist.check("DELETE", "/anything").is_err());
+
+        assert!(RequestAllowlist::new(vec!["api/*".to_string()], Vec::new()).is_err());
+        assert!(RequestAllowlist::new(vec!["/api/[".to_string()], Vec::new()).is_err());
+        assert!(RequestAllowlist::new(Vec::new(), vec!["GET /".to_string()]).is_err());
+        assert!(RequestAllowlist::default().is_empty());
+    }
 }
@@ -7,13 +7,24 @@ use std::{
 
 use common::{
     config::*, 
-    data::dto::cache_config::CacheConfig, 
+    data::dto::{admin_command::{AdminCommand, AdminResponse}, cache_config::CacheConfig, filter_rule::FilterRule, ip_rule::{IpRule, IpRuleAction}, public_auth::PublicAuth}, 
     security::generate_hmac_key
 };
 
 use crate::{
-    data::repository::cache_repo::{CacheRepo, CacheRepoProcMemImpl}, 
-    service::cache_service::CacheService
+    data::{
+        repository::{
+            audit_repo::AuditRepoFileImpl,
+            cache_repo::{CacheRepo, CacheRepoProcMemImpl, CacheRepoRedisImpl, DEFAULT_CACHE_MAX_ENTRIES, DEFAULT_CACHE_MAX_SIZE},
+            token_repo::{TokenRepo, TokenRepoFileImpl, TokenRepoRedisImpl},
+        },
+        store::redis::RedisDataStore,
+    },
+    get_admin_socket_path,
+    get_audit_log_file_path,
+    get_tokens_file_path,
+    handler::admin_handler::{execute_admin_command, send_admin_command},
+    service::{audit_service::AuditService, cache_service::{show_cache_stats as print_cache_stats, CacheService}, client_key_service::ClientKeyService, connection_limit_service::ConnectionLimitService, ip_filter_service::IpFilterService, public_auth_service::PublicAuthService, request_filter_service::RequestFilterService, secret_service::SecretService, token_service::TokenService}
 };
 
 use openssl::{
@@ -21,11 +32,11 @@ use openssl::{
     bn::{BigNum, MsbOption},
     hash::MessageDigest,
     nid::Nid,
-    pkey::PKey,
+    pkey::{PKey, Private},
     pkcs12::Pkcs12,
     rsa::Rsa,
     x509::{
-        X509, X509NameBuilder, X509Req, X509ReqBuilder,
+        X509, X509NameBuilder, X509Ref, X509Req, X509ReqBuilder,
         extension::{BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAlternativeName},
     },
 };
@@ -47,6 +58,10 @@ pub struct ServerRequestConfig {
     pub cache_client_id: bool,
     pub return_tunnel_id: bool,
     pub tls: bool,
+    // require client certificates signed by the CA on the client listener
+    pub mtls: bool,
+    // unix socket of the admin channel, none disables the channel
+    pub admin_socket_path: Option<PathBuf>,
 }
 
 impl ServerRequestConfig {
@@ -66,10 +81,22 @@ impl ServerRequestConfig {
             client_request_limit,
             cache_client_id,
             return_tunnel_id,
-            tls
+            tls,
+            mtls: false,
+            admin_socket_path: Some(get_admin_socket_path()),
         }
     }
 
+    pub fn with_mtls(mut self, mtls: bool) -> Self {
+        self.mtls = mtls;
+        self
+    }
+
+    pub fn with_admin_socket_path(mut self, admin_socket_path: Option<PathBuf>) -> Self {
+        self.admin_socket_path = admin_socket_path;
+        self
+    }
+
     pub fn public_svc_address(&self) -> String {
         format!("{}:{}", self.host, self.public_port)
     }
@@ -118,9 +145,12 @@ pub fn generate_server_secret(force: bool) -> () {
     }
 
     let key = generate_hmac_key(32);
-    set_configs(HashMap::from([
+    if let Err(e) = set_configs(HashMap::from([
         (String::from(keys::CONFIG_KEY_SERVER_SECRET), key.clone())
-    ]));
+    ])) {
+        println!("{}", e);
+        return;
+    }
 
     println!("Server Secret generated!");
     println!("Value: {}", key);
@@ -359,6 +389,112 @@ pub fn get_server_identity_from_pem() -> Result<Identity, String> {
 }
 
 
+pub fn get_ssl_dir() -> PathBuf {
+    PathBuf::from(get_config_path()).join("ssl")
+}
+
+fn read_ca_from_pem(ssl_dir: &Path) -> Result<(X509, PKey<Private>), String> {
+    let ca_crt = fs::read(ssl_dir.join("ca.crt")).map_err(|e| format!("read ca.crt: {}", e))?;
+    let ca_key = fs::read(ssl_dir.join("ca.key")).map_err(|e| format!("read ca.key: {}", e))?;
+
+    let ca_cert = X509::from_pem(&ca_crt).map_err(|e| format!("parse ca cert pem: {}", e))?;
+    let ca_pkey = PKey::private_key_from_pem(&ca_key).map_err(|e| format!("parse ca key pem: {}", e))?;
+
+    Ok((ca_cert, ca_pkey))
+}
+
+// sign a client certificate with the CA, the client id is set as the subject CN
+pub fn issue_client_certificate(ca_cert: &X509Ref, ca_pkey: &PKey<Private>, client_id: &str, days: u32) -> Result<(X509, PKey<Private>), String> {
+    let rsa = Rsa::generate(2048).map_err(|e| format!("generate client key: {}", e))?;
+    let pkey = PKey::from_rsa(rsa).map_err(|e| format!("create client PKey: {}", e))?;
+
+    let mut name = X509NameBuilder::new().map_err(|e| format!("init client subject: {}", e))?;
+    name.append_entry_by_nid(Nid::ORGANIZATIONNAME, "Organization").ok();
+    name.append_entry_by_nid(Nid::COMMONNAME, client_id).map_err(|e| format!("set client subject: {}", e))?;
+    let name = name.build();
+
+    let mut builder = X509::builder().map_err(|e| format!("init client cert builder: {}", e))?;
+    builder.set_version(2).ok();
+
+    let mut serial = BigNum::new().map_err(|e| format!("init serial: {}", e))?;
+    serial.rand(128, MsbOption::MAYBE_ZERO, false).ok();
+    let serial = serial.to_asn1_integer().map_err(|e| format!("serial: {}", e))?;
+    builder.set_serial_number(&serial).ok();
+    builder.set_subject_name(&name).ok();
+    builder.set_issuer_name(ca_cert.subject_name()).ok();
+    builder.set_pubkey(&pkey).ok();
+    builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).ok();
+    builder.set_not_after(&Asn1Time::days_from_now(days).unwrap()).ok();
+
+    // append extensions
+    if let Ok(ext) = BasicConstraints::new().critical().build() {
+        builder.append_extension(ext).ok();
+    }
+    if let Ok(ext) = KeyUsage::new().digital_signature().key_encipherment().build() {
+        builder.append_extension(ext).ok();
+    }
+    if let Ok(ext) = ExtendedKeyUsage::new().client_auth().build() {
+        builder.append_extension(ext).ok();
+    }
+
+    builder.sign(ca_pkey, MessageDigest::sha256()).map_err(|e| format!("sign client cert: {}", e))?;
+
+    Ok((builder.build(), pkey))
+}
+
+// the client id is the CN of the certificate subject
+pub fn client_id_from_certificate(cert: &X509Ref) -> Option<String> {
+    cert.subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .next()
+        .and_then(|entry| String::from_utf8(entry.data().as_slice().to_vec()).ok())
+}
+
+pub fn issue_client_cert(client_id: String, days: u32, force: bool) -> () {
+    // the client id is used as the file name
+    if client_id.is_empty() || !client_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        println!("Client ID must only contain alphanumeric characters, `-` or `_`.");
+        return;
+    }
+
+    let ssl_dir = get_ssl_dir();
+    let (ca_cert, ca_pkey) = match read_ca_from_pem(&ssl_dir) {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Failed to load the CA ({}), generate it first with `trabas server ssl-config generate-keys`.", e);
+            return;
+        }
+    };
+
+    let clients_dir = ssl_dir.join("clients");
+    if let Err(e) = create_dir_all(&clients_dir) {
+        println!("Failed to create clients directory: {}", e);
+        return;
+    }
+
+    let crt_path = clients_dir.join(format!("{}.crt", client_id));
+    let key_path = clients_dir.join(format!("{}.key", client_id));
+    if !force && (crt_path.exists() || key_path.exists()) {
+        println!("Client certificate already exists. Use --force to reissue.");
+        println!("Paths:\n- {}\n- {}", crt_path.display(), key_path.display());
+        return;
+    }
+
+    let (cert, pkey) = match issue_client_certificate(&ca_cert, &ca_pkey, &client_id, days) {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Failed to issue client certificate: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = write_bytes(&key_path, &pkey.private_key_to_pem_pkcs8().unwrap(), true) { println!("{}", e); return; }
+    if let Err(e) = write_bytes(&crt_path, &cert.to_pem().unwrap(), true) { println!("{}", e); return; }
+
+    println!("Client certificate issued for client ID: {}\nPaths:\n- {}\n- {}", client_id, crt_path.display(), key_path.display());
+    println!("Copy both files into the client ssl directory as `client.crt` and `client.key`.");
+}
+
 pub fn set_server_configs(
     key: Option<String>,
     redis_enable: Option<String>,
@@ -367,16 +503,56 @@ pub fn set_server_configs(
     redis_pass: Option<String>,
     public_endpoint: Option<String>,
     public_request_timeout: Option<String>,
+    public_max_header_bytes: Option<String>,
+    public_max_header_count: Option<String>,
+    public_max_body_size: Option<String>,
+    public_header_timeout: Option<String>,
+    public_body_timeout: Option<String>,
+    handshake_max_skew: Option<String>,
+    rate_limit_client_rps: Option<String>,
+    rate_limit_client_burst: Option<String>,
+    rate_limit_ip_rps: Option<String>,
+    rate_limit_ip_burst: Option<String>,
+    rate_limit_max_wait: Option<String>,
+    public_max_connections: Option<String>,
+    public_max_client_connections: Option<String>,
+    max_tunnels_per_client: Option<String>,
+    audit_log_enable: Option<String>,
+    audit_log_path: Option<String>,
+    audit_log_max_size: Option<String>,
+    audit_log_max_files: Option<String>,
+    cache_max_entries: Option<String>,
+    cache_max_size: Option<String>,
+    cache_sweep_interval: Option<String>,
     force: bool,
 ) -> () {
     let config = get_configs_from_proc_env();
     let mut config_to_set = HashMap::new();
 
     #[derive(PartialEq, Copy, Clone)]
-    enum ValueType { Int }
+    enum ValueType { Int, Float }
     let key_types: HashMap<&str, ValueType> = [
         (keys::CONFIG_KEY_SERVER_REDIS_PORT, ValueType::Int),
         (keys::CONFIG_KEY_SERVER_PUBLIC_REQUEST_TIMEOUT, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_BYTES, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_COUNT, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_PUBLIC_MAX_BODY_SIZE, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_PUBLIC_HEADER_TIMEOUT, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_PUBLIC_BODY_TIMEOUT, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_HANDSHAKE_MAX_SKEW, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_RPS, ValueType::Float),
+        (keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_BURST, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_RATE_LIMIT_IP_RPS, ValueType::Float),
+        (keys::CONFIG_KEY_SERVER_RATE_LIMIT_IP_BURST, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_RATE_LIMIT_MAX_WAIT, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_PUBLIC_MAX_CONNECTIONS, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_PUBLIC_MAX_CLIENT_CONNECTIONS, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_MAX_TUNNELS_PER_CLIENT, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_AUDIT_LOG_MAX_SIZE, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_AUDIT_LOG_MAX_FILES, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_CACHE_MAX_ENTRIES, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_CACHE_MAX_SIZE, ValueType::Int),
+        (keys::CONFIG_KEY_SERVER_CACHE_SWEEP_INTERVAL, ValueType::Int),
         // TODO: add more types as needed
     ].iter().map(|(k, v)| (*k, *v)).collect();
 
@@ -388,6 +564,27 @@ pub fn set_server_configs(
         (redis_pass, keys::CONFIG_KEY_SERVER_REDIS_PASS, "Redis Pass"),
         (public_endpoint, keys::CONFIG_KEY_SERVER_PUBLIC_ENDPOINT, "Public Endpoint"),
         (public_request_timeout, keys::CONFIG_KEY_SERVER_PUBLIC_REQUEST_TIMEOUT, "Public Request Timeout"),
+        (public_max_header_bytes, keys::CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_BYTES, "Public Max Header Bytes"),
+        (public_max_header_count, keys::CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_COUNT, "Public Max Header Count"),
+        (public_max_body_size, keys::CONFIG_KEY_SERVER_PUBLIC_MAX_BODY_SIZE, "Public Max Body Size"),
+        (public_header_timeout, keys::CONFIG_KEY_SERVER_PUBLIC_HEADER_TIMEOUT, "Public Header Timeout"),
+        (public_body_timeout, keys::CONFIG_KEY_SERVER_PUBLIC_BODY_TIMEOUT, "Public Body Timeout"),
+        (handshake_max_skew, keys::CONFIG_KEY_SERVER_HANDSHAKE_MAX_SKEW, "Handshake Max Skew"),
+        (rate_limit_client_rps, keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_RPS, "Client Rate Limit"),
+        (rate_limit_client_burst, keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_BURST, "Client Rate Limit Burst"),
+        (rate_limit_ip_rps, keys::CONFIG_KEY_SERVER_RATE_LIMIT_IP_RPS, "IP Rate Limit"),
+        (rate_limit_ip_burst, keys::CONFIG_KEY_SERVER_RATE_LIMIT_IP_BURST, "IP Rate Limit Burst"),
+        (rate_limit_max_wait, keys::CONFIG_KEY_SERVER_RATE_LIMIT_MAX_WAIT, "Rate Limit Max Wait"),
+        (public_max_connections, keys::CONFIG_KEY_SERVER_PUBLIC_MAX_CONNECTIONS, "Public Max Connections"),
+        (public_max_client_connections, keys::CONFIG_KEY_SERVER_PUBLIC_MAX_CLIENT_CONNECTIONS, "Public Max Client Connections"),
+        (max_tunnels_per_client, keys::CONFIG_KEY_SERVER_MAX_TUNNELS_PER_CLIENT, "Max Tunnels Per Client"),
+        (audit_log_enable, keys::CONFIG_KEY_SERVER_AUDIT_LOG_ENABLE, "Audit Log enable flag"),
+        (audit_log_path, keys::CONFIG_KEY_SERVER_AUDIT_LOG_PATH, "Audit Log Path"),
+        (audit_log_max_size, keys::CONFIG_KEY_SERVER_AUDIT_LOG_MAX_SIZE, "Audit Log Max Size"),
+        (audit_log_max_files, keys::CONFIG_KEY_SERVER_AUDIT_LOG_MAX_FILES, "Audit Log Max Files"),
+        (cache_max_entries, keys::CONFIG_KEY_SERVER_CACHE_MAX_ENTRIES, "Cache Max Entries"),
+        (cache_max_size, keys::CONFIG_KEY_SERVER_CACHE_MAX_SIZE, "Cache Max Size"),
+        (cache_sweep_interval, keys::CONFIG_KEY_SERVER_CACHE_SWEEP_INTERVAL, "Cache Sweep Interval"),
     ];
 
     for (opt, key_str, msg) in config_options.iter() {
@@ -400,6 +597,12 @@ pub fn set_server_configs(
                         return;
                     }
                 }
+                Some(ValueType::Float) => {
+                    if !val.parse::<f64>().is_ok_and(|v| v.is_finite() && v >= 0.0) {
+                        println!("{msg} must be a non-negative number.");
+                        return;
+                    }
+                }
                 // TODO: might add boolean
                 _ => {}
             }
@@ -411,12 +614,83 @@ pub fn set_server_configs(
         }
     }
 
-    set_configs(config_to_set);
+    if let Err(e) = set_configs(config_to_set) {
+        println!("{}", e);
+        return;
+    }
 
     println!("Server Configurations have been set!");
     println!("You may find the value later again in the config file");
 }
 
+// Connection Limits
+// the caps are fixed once the server is running, 0 means unlimited
+pub fn get_connection_limit_service() -> ConnectionLimitService {
+    let get_value = |key: &str| std::env::var(key)
+        .ok()
+        .and_then(|val| val.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    ConnectionLimitService::new(
+        get_value(keys::CONFIG_KEY_SERVER_PUBLIC_MAX_CONNECTIONS),
+        get_value(keys::CONFIG_KEY_SERVER_PUBLIC_MAX_CLIENT_CONNECTIONS)
+    )
+}
+
+// Audit Log
+pub const DEFAULT_AUDIT_LOG_MAX_SIZE: u64 = 10 * 1024 * 1024; // 10 MiB
+pub const DEFAULT_AUDIT_LOG_MAX_FILES: usize = 5;
+
+// the audit log is disabled unless it's explicitly enabled
+pub fn get_audit_service() -> AuditService {
+    let enabled = std::env::var(keys::CONFIG_KEY_SERVER_AUDIT_LOG_ENABLE).unwrap_or_default() == "true";
+    if !enabled {
+        return AuditService::new(None);
+    }
+
+    let path = std::env::var(keys::CONFIG_KEY_SERVER_AUDIT_LOG_PATH)
+        .ok()
+        .filter(|val| !val.is_empty())
+        .map(std::path::PathBuf::from)
+        .unwrap_or(get_audit_log_file_path());
+    let max_size = std::env::var(keys::CONFIG_KEY_SERVER_AUDIT_LOG_MAX_SIZE)
+        .ok()
+        .and_then(|val| val.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_AUDIT_LOG_MAX_SIZE);
+    let max_files = std::env::var(keys::CONFIG_KEY_SERVER_AUDIT_LOG_MAX_FILES)
+        .ok()
+        .and_then(|val| val.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_AUDIT_LOG_MAX_FILES);
+
+    AuditService::new(Some(Arc::new(AuditRepoFileImpl::new(path, max_size, max_files))))
+}
+
+// Cache Store
+pub const DEFAULT_CACHE_SWEEP_INTERVAL: u64 = 60;
+
+// the in process cache is bounded, 0 means unlimited
+pub fn get_proc_mem_cache_repo() -> CacheRepoProcMemImpl {
+    let get_value = |key: &str, default: usize| std::env::var(key)
+        .ok()
+        .and_then(|val| val.parse::<usize>().ok())
+        .unwrap_or(default);
+
+    CacheRepoProcMemImpl::new().with_limits(
+        get_value(keys::CONFIG_KEY_SERVER_CACHE_MAX_ENTRIES, DEFAULT_CACHE_MAX_ENTRIES),
+        get_value(keys::CONFIG_KEY_SERVER_CACHE_MAX_SIZE, DEFAULT_CACHE_MAX_SIZE)
+    )
+}
+
+// interval of removing the expired entries of the in process cache, 0 disables it
+pub fn get_cache_sweep_interval() -> std::time::Duration {
+    let seconds = std::env::var(keys::CONFIG_KEY_SERVER_CACHE_SWEEP_INTERVAL)
+        .ok()
+        .and_then(|val| val.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_CACHE_SWEEP_INTERVAL);
+
+    std::time::Duration::from_secs(seconds)
+}
+
 // Cache Configs
 pub fn get_cache_service(
         cache_repo: Arc<dyn CacheRepo + Send + Sync>, 
@@ -440,11 +714,12 @@ fn get_cache_service_for_settings() -> CacheService {
     get_cache_service(cache_repo, config_handler)
 }
 
-pub async fn set_cache_config(client_id: String, method: String, path: String, exp_duration: u32) {
+pub async fn set_cache_config(config: CacheConfig) {
     let cache_service = get_cache_service_for_settings();
-    cache_service.set_cache_config(CacheConfig::new(client_id.clone(), method.clone(), path.clone(), exp_duration)).await.unwrap();
+    cache_service.set_cache_config(config.clone()).await.unwrap();
 
-    println!("Cache config has been set (Client ID: {}, Method: {}, Path: {}, Duration: {} seconds)", client_id, method, path, exp_duration);
+    println!("Cache config has been set (Client ID: {}, Method: {}, Path: {}, Duration: {} seconds, Mode: {}, Key: {})",
+        config.client_id, config.method, config.path, config.exp_duration, config.mode, config.key);
 }
 
 pub async fn remove_cache_config(client_id: String, method: String, path: String) {
@@ -460,3 +735,302 @@ pub async fn show_cache_config() {
 
     cache_service.show_cache_config().await.unwrap();
 }
+
+// Cached Responses
+// the in process cache is only reachable through the admin channel of the running server,
+// while redis could be used directly when no server is running on this machine
+async fn send_cache_command(command: AdminCommand) -> Result<AdminResponse, String> {
+    let configs = validate_configs();
+    let admin_error = match send_admin_command(&get_admin_socket_path(), &command).await {
+        Ok(response) => return Ok(response),
+        Err(e) => e,
+    };
+
+    let use_redis = configs.get(keys::CONFIG_KEY_SERVER_REDIS_ENABLE).map(|v| v == "true").unwrap_or(false);
+    if !use_redis {
+        return Err(format!("No running server could be reached ({})", admin_error));
+    }
+
+    let connection = match RedisDataStore::new() {
+        Ok(store) => store.client.get_multiplexed_async_connection().await
+            .map_err(|e| format!("Failed to connect to Redis: {}", e))?,
+        Err(e) => return Err(format!("Failed to connect to Redis: {}", e)),
+    };
+    let cache_service = get_cache_service(
+        Arc::new(CacheRepoRedisImpl::new(connection)),
+        Arc::new(ConfigHandlerImpl{})
+    );
+
+    Ok(execute_admin_command(command, &cache_service).await)
+}
+
+pub async fn purge_cache(client_id: String, path: Option<String>, method: Option<String>) {
+    let command = AdminCommand::PurgeCache {
+        client_id: client_id.clone(),
+        path,
+        method,
+    };
+
+    match send_cache_command(command).await {
+        Ok(AdminResponse::Purged { count }) => println!("{} cached responses have been purged (Client ID: {})", count, client_id),
+        Ok(AdminResponse::Error { message }) | Err(message) => println!("Failed to purge cache: {}", message),
+        Ok(response) => println!("Failed to purge cache: unexpected response {:?}", response),
+    }
+}
+
+pub async fn show_cache_stats() {
+    match send_cache_command(AdminCommand::CacheStats).await {
+        Ok(AdminResponse::CacheStats { stats }) => print_cache_stats(&stats).unwrap(),
+        Ok(AdminResponse::Error { message }) | Err(message) => println!("Failed to get cache stats: {}", message),
+        Ok(response) => println!("Failed to get cache stats: unexpected response {:?}", response),
+    }
+}
+
+// IP Rules
+fn get_ip_filter_service_for_settings() -> IpFilterService {
+    validate_configs();
+    let config_handler = Arc::new(ConfigHandlerImpl{});
+
+    IpFilterService::new(config_handler, String::from(keys::CONFIG_KEY_SERVER_IP_RULES))
+}
+
+pub async fn set_ip_rule(client_id: String, action: IpRuleAction, cidr: String) {
+    let ip_filter_service = get_ip_filter_service_for_settings();
+
+    match ip_filter_service.set_ip_rule(IpRule::new(client_id, action, cidr)).await {
+        Ok(rule) => println!("IP rule has been set (Client ID: {}, Action: {}, CIDR: {})", rule.client_id, rule.action, rule.cidr),
+        Err(e) => println!("Failed to set ip rule: {}", e),
+    }
+}
+
+pub async fn remove_ip_rule(client_id: String, cidr: String) {
+    let ip_filter_service = get_ip_filter_service_for_settings();
+
+    match ip_filter_service.remove_ip_rule(client_id.clone(), cidr.clone()).await {
+        Ok(_) => println!("IP rule has been removed (Client ID: {}, CIDR: {})", client_id, cidr),
+        Err(e) => println!("Failed to remove ip rule: {}", e),
+    }
+}
+
+pub async fn show_ip_rules() {
+    let ip_filter_service = get_ip_filter_service_for_settings();
+
+    ip_filter_service.show_ip_rules().await.unwrap();
+}
+
+// Filter Rules
+fn get_request_filter_service_for_settings() -> RequestFilterService {
+    validate_configs();
+    let config_handler = Arc::new(ConfigHandlerImpl{});
+
+    RequestFilterService::new(config_handler, String::from(keys::CONFIG_KEY_SERVER_FILTER_RULES))
+}
+
+pub async fn set_filter_rule(rule: FilterRule) {
+    let request_filter_service = get_request_filter_service_for_settings();
+
+    match request_filter_service.set_filter_rule(rule).await {
+        Ok(rule) => println!("Filter rule has been set (Client ID: {}, Name: {}, Priority: {}, Action: {})", rule.client_id, rule.name, rule.priority, rule.action),
+        Err(e) => println!("Failed to set filter rule: {}", e),
+    }
+}
+
+pub async fn remove_filter_rule(client_id: String, name: String) {
+    let request_filter_service = get_request_filter_service_for_settings();
+
+    match request_filter_service.remove_filter_rule(client_id.clone(), name.clone()).await {
+        Ok(_) => println!("Filter rule has been removed (Client ID: {}, Name: {})", client_id, name),
+        Err(e) => println!("Failed to remove filter rule: {}", e),
+    }
+}
+
+pub async fn show_filter_rules() {
+    let request_filter_service = get_request_filter_service_for_settings();
+
+    request_filter_service.show_filter_rules().await.unwrap();
+}
+
+// API Tokens
+// tokens must be stored where the running server reads them,
+// which is redis when enabled, otherwise the tokens file in the config directory
+async fn get_token_service_for_settings() -> TokenService {
+    let configs = validate_configs();
+    let use_redis = configs.get(keys::CONFIG_KEY_SERVER_REDIS_ENABLE).map(|v| v == "true").unwrap_or(false);
+
+    let token_repo: Arc<dyn TokenRepo + Send + Sync> = if use_redis {
+        let connection = match RedisDataStore::new() {
+            Ok(store) => store.client.get_multiplexed_async_connection().await
+                .map_err(|e| format!("{}", e)),
+            Err(e) => Err(format!("{}", e)),
+        };
+        match connection {
+            Ok(value) => Arc::new(TokenRepoRedisImpl::new(value)),
+            Err(e) => panic!("Failed to connect to Redis: {}", e),
+        }
+    } else {
+        Arc::new(TokenRepoFileImpl::new(get_tokens_file_path()))
+    };
+
+    TokenService::new(token_repo)
+}
+
+pub async fn create_token(client_ids: Vec<String>, ttl: Option<u64>) {
+    let token_service = get_token_service_for_settings().await;
+    let token = match token_service.create_token(client_ids, ttl.map(std::time::Duration::from_secs)).await {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Failed to create token: {}", e);
+            return;
+        }
+    };
+
+    println!("API Token has been created!");
+    println!("Token ID: {}", token.id);
+    println!("Key: {}", token.key);
+    println!("Client IDs: {}", token.client_ids.join(", "));
+    println!("Set both values in the client config (--token-id and --server-signing-key),");
+    println!("the key will not be shown again");
+}
+
+pub async fn show_tokens() {
+    let token_service = get_token_service_for_settings().await;
+
+    token_service.show_tokens().await.unwrap();
+}
+
+pub async fn revoke_token(id: String) {
+    let token_service = get_token_service_for_settings().await;
+
+    match token_service.revoke_token(id.clone()).await {
+        Ok(_) => println!("API Token {} has been revoked", id),
+        Err(e) => println!("Failed to revoke token: {}", e),
+    }
+}
+
+// Client Public Keys
+fn get_client_key_service_for_settings() -> ClientKeyService {
+    validate_configs();
+    let config_handler = Arc::new(ConfigHandlerImpl{});
+
+    ClientKeyService::new(config_handler, String::from(keys::CONFIG_KEY_SERVER_CLIENT_KEYS))
+}
+
+pub async fn set_client_key(client_id: String, public_key: String) {
+    let client_key_service = get_client_key_service_for_settings();
+
+    match client_key_service.set_public_key(client_id.clone(), public_key).await {
+        Ok(_) => println!("Public key has been registered (Client ID: {})", client_id),
+        Err(e) => println!("Failed to register public key: {}", e),
+    }
+}
+
+pub async fn remove_client_key(client_id: String) {
+    let client_key_service = get_client_key_service_for_settings();
+
+    match client_key_service.remove_public_key(client_id.clone()).await {
+        Ok(_) => println!("Public key has been removed (Client ID: {})", client_id),
+        Err(e) => println!("Failed to remove public key: {}", e),
+    }
+}
+
+pub async fn show_client_keys() {
+    let client_key_service = get_client_key_service_for_settings();
+
+    client_key_service.show_public_keys().await.unwrap();
+}
+
+// Public Auth Policies
+fn get_public_auth_service_for_settings() -> PublicAuthService {
+    validate_configs();
+    let config_handler = Arc::new(ConfigHandlerImpl{});
+
+    PublicAuthService::new(config_handler, String::from(keys::CONFIG_KEY_SERVER_PUBLIC_AUTH))
+}
+
+pub async fn set_public_auth(client_id: String, auth: PublicAuth) {
+    let public_auth_service = get_public_auth_service_for_settings();
+    let scheme = auth.scheme;
+
+    match public_auth_service.set_public_auth(client_id.clone(), auth).await {
+        Ok(_) => println!("Public {:?} Auth has been set (Client ID: {})", scheme, client_id),
+        Err(e) => println!("Failed to set public auth: {}", e),
+    }
+}
+
+pub async fn remove_public_auth(client_id: String) {
+    let public_auth_service = get_public_auth_service_for_settings();
+
+    match public_auth_service.remove_public_auth(client_id.clone()).await {
+        Ok(_) => println!("Public Auth policy has been removed (Client ID: {})", client_id),
+        Err(e) => println!("Failed to remove public auth policy: {}", e),
+    }
+}
+
+pub async fn show_public_auth() {
+    let public_auth_service = get_public_auth_service_for_settings();
+
+    public_auth_service.show_public_auth().await.unwrap();
+}
+
+// Server Secret Rotation
+fn get_secret_service_for_settings() -> SecretService {
+    validate_configs();
+    let config_handler = Arc::new(ConfigHandlerImpl{});
+
+    SecretService::new(config_handler)
+}
+
+pub async fn rotate_server_secret(key: Option<String>, overlap: u64) {
+    let secret_service = get_secret_service_for_settings();
+
+    let secret = match secret_service.rotate_secret(key, std::time::Duration::from_secs(overlap)).await {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Failed to rotate server secret: {}", e);
+            return;
+        }
+    };
+
+    println!("Server Secret has been rotated!");
+    println!("Value: {}", secret);
+    if overlap > 0 {
+        println!("The previous secret is still accepted for {} seconds, move the clients to the new value (--server-signing-key) meanwhile", overlap);
+    } else {
+        println!("The previous secret is no longer accepted");
+    }
+}
+
+pub async fn expire_previous_server_secrets() {
+    let secret_service = get_secret_service_for_settings();
+
+    if let Err(e) = secret_service.expire_previous_secrets().await {
+        println!("Failed to expire previous server secrets: {}", e);
+        return;
+    }
+    println!("Previous server secrets are no longer accepted");
+}
+
+pub async fn show_server_secrets() {
+    let secret_service = get_secret_service_for_settings();
+
+    secret_service.show_secrets().await.unwrap();
+}
+
+pub async fn show_identity_key(force: bool) {
+    let secret_service = get_secret_service_for_settings();
+
+    let public_key = match secret_service.init_identity_key(force).await {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Failed to get server identity key: {}", e);
+            return;
+        }
+    };
+
+    if force {
+        println!("Server Identity Key has been regenerated, the clients must pin the new public key");
+    }
+    println!("Public Key: {}", public_key);
+    println!("Pin it on the key authenticated clients:");
+    println!("  trabas client set-config --server-public-key {}", public_key);
+}
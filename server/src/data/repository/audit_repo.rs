@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tokio::{fs::{File, OpenOptions}, io::AsyncWriteExt, sync::Mutex};
+
+use common::convert::to_json_string;
+use common::data::dto::audit_event::AuditEvent;
+
+#[async_trait]
+pub trait AuditRepo {
+    async fn append(&self, event: AuditEvent) -> Result<(), String>;
+}
+
+// append-only JSON lines file, rotated once it exceeds the max size:
+// `audit.log` -> `audit.log.1` -> ... -> `audit.log.{max_files}`, the oldest one is removed
+pub struct AuditRepoFileImpl {
+    path: PathBuf,
+    // 0 means no rotation
+    max_size: u64,
+    // number of rotated files to keep
+    max_files: usize,
+    // opened file and its current size
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl AuditRepoFileImpl {
+    pub fn new(path: PathBuf, max_size: u64, max_files: usize) -> Self {
+        AuditRepoFileImpl { path, max_size, max_files, file: Mutex::new(None) }
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    async fn open(&self) -> Result<(File, u64), String> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await
+                .map_err(|e| format!("Error creating audit log directory: {}", e))?;
+        }
+
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        // the log reveals who connects from where, keep it private
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = options.open(&self.path).await
+            .map_err(|e| format!("Error opening audit log: {}", e))?;
+        let size = file.metadata().await
+            .map_err(|e| format!("Error reading audit log: {}", e))?
+            .len();
+
+        Ok((file, size))
+    }
+
+    async fn rotate(&self) -> Result<(), String> {
+        if self.max_files == 0 {
+            return tokio::fs::remove_file(&self.path).await
+                .map_err(|e| format!("Error rotating audit log: {}", e));
+        }
+
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if tokio::fs::try_exists(&from).await.unwrap_or(false) {
+                tokio::fs::rename(&from, self.rotated_path(index + 1)).await
+                    .map_err(|e| format!("Error rotating audit log: {}", e))?;
+            }
+        }
+        tokio::fs::rename(&self.path, self.rotated_path(1)).await
+            .map_err(|e| format!("Error rotating audit log: {}", e))
+    }
+}
+
+#[async_trait]
+impl AuditRepo for AuditRepoFileImpl {
+    async fn append(&self, event: AuditEvent) -> Result<(), String> {
+        let mut line = to_json_string(&event);
+        line.push('\n');
+
+        let mut file = self.file.lock().await;
+        if file.is_none() {
+            *file = Some(self.open().await?);
+        }
+
+        // a single line is never split across files
+        let size = file.as_ref().map(|(_, size)| *size).unwrap_or_default();
+        if self.max_size > 0 && size > 0 && size + line.len() as u64 > self.max_size {
+            *file = None;
+            self.rotate().await?;
+            *file = Some(self.open().await?);
+        }
+
+        let (writer, size) = file.as_mut().unwrap();
+        if let Err(e) = writer.write_all(line.as_bytes()).await {
+            // reopen on the next event, i.e: the file was removed
+            *file = None;
+            return Err(format!("Error writing audit log: {}", e));
+        }
+        writer.flush().await.map_err(|e| format!("Error writing audit log: {}", e))?;
+        *size += line.len() as u64;
+
+        Ok(())
+    }
+}
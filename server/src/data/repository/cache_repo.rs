@@ -1,19 +1,34 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::{BTreeMap, HashMap}, sync::Arc, time::{Duration, SystemTime}};
 
 use async_trait::async_trait;
 use common::{convert::{from_json_slice, to_json_vec}, data::dto::cache::Cache};
 use redis::{aio::MultiplexedConnection, AsyncCommands};
 use tokio::sync::Mutex;
 
-const REDIS_KEY_CACHE: &str = "request_cache"; // hash
+const REDIS_KEY_CACHE_PREFIX: &str = "request_cache_";
+// the hash of the previous versions, all entries were kept in it without expiry
+const REDIS_KEY_LEGACY_CACHE: &str = "request_cache";
+// set once the legacy hash has been removed, outside of the cache prefix
+const REDIS_KEY_LEGACY_CACHE_REMOVED: &str = "legacy_request_cache_removed";
+// fields of an entry, the cache without its data and the data itself
+const REDIS_FIELD_CACHE_META: &str = "meta";
+const REDIS_FIELD_CACHE_DATA: &str = "data";
+// number of entries read per round trip when listing
+const REDIS_LIST_BATCH_SIZE: usize = 100;
 
 #[async_trait]
 pub trait CacheRepo {
     async fn get(&self, key: String) -> Result<Cache, String>;
     async fn set(&self, key: String, data: Cache) -> Result<(), String>;
+    // all entries which are not expired yet, without their data, along with the size of the data
+    async fn list(&self) -> Result<Vec<(String, Cache, usize)>, String>;
+    async fn delete(&self, key: String) -> Result<(), String>;
+    async fn count(&self) -> Result<usize, String>;
 }
 
-// Cache with redis implementation
+// Cache with redis implementation,
+// each entry is a hash of its own key and expires along with the cache.
+// the data is kept apart from the rest, so listing never loads the cached responses
 pub struct CacheRepoRedisImpl {
     connection: MultiplexedConnection
 }
@@ -22,58 +37,277 @@ impl CacheRepoRedisImpl {
     pub fn new(connection: MultiplexedConnection) -> Self {
         CacheRepoRedisImpl { connection }
     }
+
+    async fn scan_keys(&self) -> Result<Vec<String>, String> {
+        let mut connection = self.connection.clone();
+        let mut iter: redis::AsyncIter<String> = connection.scan_match(format!("{}*", REDIS_KEY_CACHE_PREFIX)).await
+            .map_err(|e| format!("Error listing cache: {}.", e))?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+
+        Ok(keys)
+    }
+
+    // the entries of the legacy hash never expire, drop them once.
+    // UNLINK frees the hash in the background, so a large one does not block redis.
+    // returns the number of removed legacy entries
+    pub async fn remove_legacy_cache(&self) -> Result<usize, String> {
+        let mut connection = self.connection.clone();
+        let removed: bool = connection.exists(REDIS_KEY_LEGACY_CACHE_REMOVED).await
+            .map_err(|e| format!("Error checking legacy cache: {}.", e))?;
+        if removed {
+            return Ok(0);
+        }
+
+        let count: usize = connection.hlen(REDIS_KEY_LEGACY_CACHE).await
+            .map_err(|e| format!("Error checking legacy cache: {}.", e))?;
+        connection.unlink::<_, ()>(REDIS_KEY_LEGACY_CACHE).await
+            .map_err(|e| format!("Error removing legacy cache: {}.", e))?;
+        connection.set::<_, _, ()>(REDIS_KEY_LEGACY_CACHE_REMOVED, 1).await
+            .map_err(|e| format!("Error marking legacy cache as removed: {}.", e))?;
+
+        Ok(count)
+    }
 }
 
 #[async_trait]
 impl CacheRepo for CacheRepoRedisImpl {
 
     async fn get(&self, key: String) -> Result<Cache, String> {
-        let data: Vec<u8> = self.connection.clone().hget(REDIS_KEY_CACHE, key.clone()).await
+        let (meta, data): (Option<Vec<u8>>, Option<Vec<u8>>) = self.connection.clone()
+            .hget(format!("{}{}", REDIS_KEY_CACHE_PREFIX, key), &[REDIS_FIELD_CACHE_META, REDIS_FIELD_CACHE_DATA]).await
             .map_err(|e| format!("Error getting cache {}: {}.", key, e))?;
-        
-        if data.len() == 0 {
-            return Err(String::from("No cache was found."));
-        }
 
-        let res: Cache = from_json_slice(&data).unwrap();
+        let meta = match meta {
+            Some(value) if !value.is_empty() => value,
+            _ => return Err(String::from("No cache was found.")),
+        };
 
-        Ok(res)
+        let mut cache: Cache = from_json_slice(&meta).ok_or(format!("Invalid cache {}.", key))?;
+        cache.data = data.unwrap_or_default();
+        Ok(cache)
     }
 
     async fn set(&self, key: String, cache: Cache) -> Result<(), String> {
-        let data = to_json_vec(&cache);
-        self.connection.clone().hset::<_, _, _, ()>(REDIS_KEY_CACHE, key.clone(), data).await
+        // round up, the cache is still checked against its expiry when read
+        let ttl = match cache.expired_at.duration_since(SystemTime::now()) {
+            Ok(value) => value.as_millis() as u64 + 1,
+            Err(_) => return Ok(()),
+        };
+        let redis_key = format!("{}{}", REDIS_KEY_CACHE_PREFIX, key);
+        let meta = to_json_vec(&Cache { data: Vec::new(), ..cache.clone() });
+        // replace the whole entry at once, no field of the previous one is left behind
+        redis::pipe().atomic()
+            .del(&redis_key).ignore()
+            .hset_multiple(&redis_key, &[(REDIS_FIELD_CACHE_META, meta), (REDIS_FIELD_CACHE_DATA, cache.data)]).ignore()
+            .pexpire(&redis_key, ttl as i64).ignore()
+            .query_async::<_, ()>(&mut self.connection.clone()).await
             .map_err(|e| format!("Error setting cache {}: {}.", key, e))?;
 
         Ok(())
     }
+
+    async fn list(&self) -> Result<Vec<(String, Cache, usize)>, String> {
+        let mut connection = self.connection.clone();
+        let mut entries = Vec::new();
+        for redis_keys in self.scan_keys().await?.chunks(REDIS_LIST_BATCH_SIZE) {
+            let mut pipe = redis::pipe();
+            for redis_key in redis_keys {
+                pipe.hget(redis_key, REDIS_FIELD_CACHE_META)
+                    .cmd("HSTRLEN").arg(redis_key).arg(REDIS_FIELD_CACHE_DATA);
+            }
+            let values: Vec<(Option<Vec<u8>>, usize)> = pipe.query_async(&mut connection).await
+                .map_err(|e| format!("Error listing cache: {}.", e))?;
+
+            for (redis_key, (meta, size)) in redis_keys.iter().zip(values) {
+                // might be expired since the scan
+                let cache: Cache = match meta.and_then(|value| from_json_slice(&value)) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                entries.push((redis_key[REDIS_KEY_CACHE_PREFIX.len()..].to_string(), cache, size));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    async fn delete(&self, key: String) -> Result<(), String> {
+        self.connection.clone().del::<_, ()>(format!("{}{}", REDIS_KEY_CACHE_PREFIX, key)).await
+            .map_err(|e| format!("Error deleting cache {}: {}.", key, e))
+    }
+
+    async fn count(&self) -> Result<usize, String> {
+        Ok(self.scan_keys().await?.len())
+    }
+}
+
+pub const DEFAULT_CACHE_MAX_ENTRIES: usize = 10_000;
+pub const DEFAULT_CACHE_MAX_SIZE: usize = 64 * 1024 * 1024; // 64 MiB
+
+struct CacheEntry {
+    cache: Cache,
+    size: usize,
+    // position in the recency order
+    tick: u64,
+}
+
+#[derive(Default)]
+struct CacheStore {
+    entries: HashMap<String, CacheEntry>,
+    // the least recently used first
+    recency: BTreeMap<u64, String>,
+    size: usize,
+    tick: u64,
+}
+
+impl CacheStore {
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.tick);
+            self.recency.insert(tick, key.to_string());
+            entry.tick = tick;
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.tick);
+        self.size -= entry.size;
+        Some(entry)
+    }
 }
 
-// cache with in process memory implementation
+// cache with in process memory implementation,
+// bounded by the number of entries and their size, the least recently used ones are evicted first
 pub struct CacheRepoProcMemImpl {
-    cache: Arc<Mutex<HashMap<String, Cache>>>,
+    store: Arc<Mutex<CacheStore>>,
+    // 0 means unlimited
+    max_entries: usize,
+    // in bytes, 0 means unlimited
+    max_size: usize,
 }
 
 impl CacheRepoProcMemImpl {
     pub fn new() -> Self {
         CacheRepoProcMemImpl {
-            cache: Arc::new(Mutex::new(HashMap::new())),
+            store: Arc::new(Mutex::new(CacheStore::default())),
+            max_entries: DEFAULT_CACHE_MAX_ENTRIES,
+            max_size: DEFAULT_CACHE_MAX_SIZE,
+        }
+    }
+
+    pub fn with_limits(mut self, max_entries: usize, max_size: usize) -> Self {
+        self.max_entries = max_entries;
+        self.max_size = max_size;
+        self
+    }
+
+    // remove the expired entries, returns the number of removed ones
+    pub async fn sweep(&self) -> usize {
+        let mut store = self.store.lock().await;
+        let now = SystemTime::now();
+        let expired: Vec<String> = store.entries.iter()
+            .filter(|(_, entry)| entry.cache.expired_at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired.iter() {
+            store.remove(key);
         }
+
+        expired.len()
     }
+
+    // sweep the expired entries periodically until the repo is dropped
+    pub fn spawn_sweeper(self: &Arc<Self>, interval: Duration) {
+        let repo = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // the first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match repo.upgrade() {
+                    Some(repo) => { repo.sweep().await; },
+                    None => break,
+                }
+            }
+        });
+    }
+}
+
+fn get_entry_size(key: &str, cache: &Cache) -> usize {
+    key.len()
+        + cache.data.len()
+        + cache.vary.iter().map(|name| name.len()).sum::<usize>()
+        + cache.etag.as_ref().map(|value| value.len()).unwrap_or_default()
+        + cache.last_modified.as_ref().map(|value| value.len()).unwrap_or_default()
+        + cache.client_id.len() + cache.method.len() + cache.path.len()
 }
 
 #[async_trait]
 impl CacheRepo for CacheRepoProcMemImpl {
     async fn get(&self, key: String) -> Result<Cache, String> {
-        if let Some(value) = self.cache.lock().await.get(&key) {
-            return Ok((*value).clone());
+        let mut store = self.store.lock().await;
+        let expired = match store.entries.get(&key) {
+            Some(entry) => entry.cache.expired_at <= SystemTime::now(),
+            None => return Err(String::from("No cache was found.")),
+        };
+        if expired {
+            store.remove(&key);
+            return Err(String::from("No cache was found."));
         }
 
-        Err(String::from("No cache was found."))
+        store.touch(&key);
+        Ok(store.entries[&key].cache.clone())
     }
 
     async fn set(&self, key: String, data: Cache) -> Result<(), String> {
-        self.cache.lock().await.insert(key, data);
+        let size = get_entry_size(&key, &data);
+        if self.max_size > 0 && size > self.max_size {
+            return Err(format!("Cache {} of {} bytes exceeds the max cache size.", key, size));
+        }
+
+        let mut store = self.store.lock().await;
+        store.remove(&key);
+        // evict the least recently used entries to make room
+        while (self.max_entries > 0 && store.entries.len() >= self.max_entries)
+            || (self.max_size > 0 && store.size + size > self.max_size) {
+            let oldest = match store.recency.values().next() {
+                Some(value) => value.clone(),
+                None => break,
+            };
+            store.remove(&oldest);
+        }
+
+        store.tick += 1;
+        let tick = store.tick;
+        store.recency.insert(tick, key.clone());
+        store.entries.insert(key, CacheEntry { cache: data, size, tick });
+        store.size += size;
         Ok(())
-    }    
+    }
+
+    async fn list(&self) -> Result<Vec<(String, Cache, usize)>, String> {
+        let store = self.store.lock().await;
+        let now = SystemTime::now();
+        // listing does not count as a use
+        Ok(store.entries.iter()
+            .filter(|(_, entry)| entry.cache.expired_at > now)
+            .map(|(key, entry)| (key.clone(), Cache { data: Vec::new(), ..entry.cache.clone() }, entry.cache.data.len()))
+            .collect())
+    }
+
+    async fn delete(&self, key: String) -> Result<(), String> {
+        self.store.lock().await.remove(&key);
+        Ok(())
+    }
+
+    async fn count(&self) -> Result<usize, String> {
+        Ok(self.store.lock().await.entries.len())
+    }
 }
@@ -1,12 +1,24 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::{BTreeSet, HashMap}, sync::Arc, time::{Duration, Instant}};
 
 use async_trait::async_trait;
-use redis::{aio::MultiplexedConnection, AsyncCommands};
+use redis::{aio::MultiplexedConnection, AsyncCommands, Script};
 use tokio::sync::Mutex;
 use common::{convert::{from_json_slice, to_json_vec}, data::dto::tunnel_client::TunnelClient};
 
 const REDIS_KEY_CLIENT_PREFIX: &str = "tunnel_clients_";
 const REDIS_KEY_CLIENT_ALIAS_MAP: &str = "tunnel_clients_alias_map";
+const REDIS_KEY_NONCE_PREFIX: &str = "tunnel_nonces_";
+// the tunnel count is checked and the tunnel created in a single step across all server instances
+const REDIS_CREATE_TUNNEL_SCRIPT: &str = r#"
+local max_tunnels = tonumber(ARGV[3])
+if max_tunnels > 0 and redis.call('HEXISTS', KEYS[1], ARGV[1]) == 0 and redis.call('HLEN', KEYS[1]) >= max_tunnels then
+    return 0
+end
+redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+return 1
+"#;
+// nonces kept in memory within their window, the handshakes are rejected beyond it
+const DEFAULT_MAX_NONCES: usize = 100_000;
 
 #[async_trait]
 pub trait ClientRepo {
@@ -14,20 +26,26 @@ pub trait ClientRepo {
     async fn get(&self, client_id: String, tunnel_id: String) -> Result<TunnelClient, String>;
     async fn get_all(&self, id: String) -> Result<Vec<TunnelClient>, String>;
     async fn get_id_by_alias(&self, alias_id: String) -> Result<String, String>;
-    async fn create(&self, client: TunnelClient, tunnel_id: String) -> Result<(), String>;
+    // the tunnel is not created if the client has `max_tunnels` tunnels already (0 means unlimited),
+    // checked and created at once. returns false if the cap has been reached
+    async fn create(&self, client: TunnelClient, tunnel_id: String, max_tunnels: i64) -> Result<bool, String>;
     async fn create_alias(&self, alias_id: String, client_id: String) -> Result<(), String>;
     async fn remove_alias(&self, alias_id: String) -> Result<(), String>;
     async fn remove(&self, client_id: String, tunnel_id: String) -> Result<(), String>;
+    // remember the handshake nonce for the given time,
+    // returns false if the nonce has already been seen
+    async fn add_nonce(&self, nonce: String, ttl: Duration) -> Result<bool, String>;
 }
 
 // Redis implementation
 pub struct ClientRepoRedisImpl {
-    connection: MultiplexedConnection
+    connection: MultiplexedConnection,
+    create_script: Script,
 }
 
 impl ClientRepoRedisImpl {
     pub fn new(connection: MultiplexedConnection) -> Self {
-        ClientRepoRedisImpl { connection }
+        ClientRepoRedisImpl { connection, create_script: Script::new(REDIS_CREATE_TUNNEL_SCRIPT) }
     }
 }
 
@@ -75,12 +93,17 @@ impl ClientRepo for ClientRepoRedisImpl {
         Ok(data)
     }
 
-    async fn create(&self, client: TunnelClient, tunnel_id: String) -> Result<(), String> {
+    async fn create(&self, client: TunnelClient, tunnel_id: String, max_tunnels: i64) -> Result<bool, String> {
         let key = format!("{}{}", REDIS_KEY_CLIENT_PREFIX, client.id);
         let data = to_json_vec(&client);
-        self.connection.clone().hset::<_, _, _, i32>(key, tunnel_id, data).await
+        let created: i32 = self.create_script
+            .key(key)
+            .arg(tunnel_id)
+            .arg(data)
+            .arg(max_tunnels)
+            .invoke_async(&mut self.connection.clone()).await
             .map_err(|e| format!("Error setting client {}: {}", client.id, e))?;
-        Ok(())
+        Ok(created == 1)
     }
 
     async fn create_alias(&self, alias_id: String, client_id: String) -> Result<(), String> {
@@ -101,12 +124,49 @@ impl ClientRepo for ClientRepoRedisImpl {
             .map_err(|e| format!("Error removing tunnel {} for client {}: {}", tunnel_id, client_id, e))?;
         Ok(())
     }
+
+    async fn add_nonce(&self, nonce: String, ttl: Duration) -> Result<bool, String> {
+        // SET NX is atomic, so the nonce is accepted once across all server instances
+        let key = format!("{}{}", REDIS_KEY_NONCE_PREFIX, nonce);
+        let res: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl.as_secs().max(1))
+            .query_async(&mut self.connection.clone()).await
+            .map_err(|e| format!("Error setting handshake nonce {}: {}", nonce, e))?;
+        Ok(res.is_some())
+    }
 }
 
 // In process memory implementation
 pub struct ClientRepoProcMemImpl {
     data: Arc<Mutex<HashMap<String, HashMap<String, TunnelClient>>>>,
-    alias_map: Arc<Mutex<HashMap<String, String>>>
+    alias_map: Arc<Mutex<HashMap<String, String>>>,
+    nonces: Arc<Mutex<NonceStore>>,
+    max_nonces: usize,
+}
+
+// seen nonces with their expiry, ordered by the expiry
+// so the expired ones are dropped from the front only
+#[derive(Default)]
+struct NonceStore {
+    expiry: HashMap<String, Instant>,
+    by_expiry: BTreeSet<(Instant, String)>,
+}
+
+impl NonceStore {
+    fn remove_expired(&mut self, now: Instant) {
+        while let Some((expired_at, _)) = self.by_expiry.first() {
+            if *expired_at > now {
+                break;
+            }
+            if let Some((_, nonce)) = self.by_expiry.pop_first() {
+                self.expiry.remove(&nonce);
+            }
+        }
+    }
 }
 
 impl ClientRepoProcMemImpl {
@@ -114,8 +174,15 @@ impl ClientRepoProcMemImpl {
         ClientRepoProcMemImpl { 
             data: Arc::new(Mutex::new(HashMap::new())),
             alias_map: Arc::new(Mutex::new(HashMap::new())),
+            nonces: Arc::new(Mutex::new(NonceStore::default())),
+            max_nonces: DEFAULT_MAX_NONCES,
         }
     }
+
+    pub fn with_max_nonces(mut self, max_nonces: usize) -> Self {
+        self.max_nonces = max_nonces;
+        self
+    }
 }
 
 #[async_trait]
@@ -155,11 +222,14 @@ impl ClientRepo for ClientRepoProcMemImpl {
         Err(String::from("Error getting client ID by alias: no valid map exists"))
     }
 
-    async fn create(&self, client: TunnelClient, tunnel_id: String) -> Result<(), String> {
+    async fn create(&self, client: TunnelClient, tunnel_id: String, max_tunnels: i64) -> Result<bool, String> {
         let mut data = self.data.lock().await;
         let entry = data.entry(client.id.clone()).or_insert_with(HashMap::new);
+        if max_tunnels > 0 && !entry.contains_key(&tunnel_id) && entry.len() as i64 >= max_tunnels {
+            return Ok(false);
+        }
         entry.insert(tunnel_id, client);
-        Ok(())
+        Ok(true)
     }
 
     async fn create_alias(&self, alias_id: String, client_id: String) -> Result<(), String> {
@@ -179,4 +249,23 @@ impl ClientRepo for ClientRepoProcMemImpl {
         }
         Ok(())
     }
+
+    async fn add_nonce(&self, nonce: String, ttl: Duration) -> Result<bool, String> {
+        let mut nonces = self.nonces.lock().await;
+        // drop the expired nonces along the way
+        let now = Instant::now();
+        nonces.remove_expired(now);
+        if nonces.expiry.contains_key(&nonce) {
+            return Ok(false);
+        }
+        // evicting a live nonce would allow replaying it, reject the handshake instead
+        if nonces.expiry.len() >= self.max_nonces {
+            return Err(String::from("too many handshakes within the nonce window, try again later"));
+        }
+
+        let expired_at = now + ttl;
+        nonces.expiry.insert(nonce.clone(), expired_at);
+        nonces.by_expiry.insert((expired_at, nonce));
+        Ok(true)
+    }
 }
@@ -1,4 +1,7 @@
+pub mod audit_repo;
 pub mod cache_repo;
 pub mod client_repo;
+pub mod rate_limit_repo;
 pub mod request_repo;
-pub mod response_repo;
\ No newline at end of file
+pub mod response_repo;
+pub mod token_repo;
\ No newline at end of file
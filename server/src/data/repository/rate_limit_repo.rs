@@ -0,0 +1,157 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use redis::{aio::MultiplexedConnection, Script};
+use tokio::{sync::Mutex, time::Instant};
+
+const REDIS_KEY_RATE_LIMIT_PREFIX: &str = "tunnel_rate_limit_";
+// how often the full buckets are dropped from memory
+const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// token bucket in redis, the redis clock is used so all server instances share the same time.
+// the tokens are returned as string since lua numbers are truncated into integers
+const REDIS_TAKE_TOKEN_SCRIPT: &str = r#"
+local rate = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local data = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(data[1])
+local ts = tonumber(data[2])
+if tokens == nil or ts == nil then
+    tokens = burst
+    ts = now
+end
+tokens = math.min(burst, tokens + math.max(0, now - ts) * rate / 1000)
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(burst / rate * 1000) + 1000)
+return {allowed, tostring(tokens)}
+"#;
+
+// state of a bucket right after taking a token
+#[derive(Debug, Clone)]
+pub struct RateLimitState {
+    pub allowed: bool,
+    // whole tokens left in the bucket
+    pub remaining: u32,
+    // time until a token is available, zero if allowed
+    pub retry_after: Duration,
+    // time until the bucket is full again
+    pub reset: Duration,
+}
+
+impl RateLimitState {
+    pub fn new(allowed: bool, tokens: f64, rate: f64, burst: u32) -> Self {
+        let retry_after = if allowed || tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - tokens) / rate)
+        };
+        RateLimitState {
+            allowed,
+            remaining: tokens.max(0.0).floor() as u32,
+            retry_after,
+            reset: Duration::from_secs_f64(((burst as f64) - tokens).max(0.0) / rate),
+        }
+    }
+}
+
+#[async_trait]
+pub trait RateLimitRepo {
+    // take a token from the bucket of the key,
+    // the bucket holds at most `burst` tokens and is refilled by `rate` tokens per second
+    async fn take(&self, key: String, rate: f64, burst: u32) -> Result<RateLimitState, String>;
+}
+
+// Redis implementation
+pub struct RateLimitRepoRedisImpl {
+    connection: MultiplexedConnection,
+    script: Script,
+}
+
+impl RateLimitRepoRedisImpl {
+    pub fn new(connection: MultiplexedConnection) -> Self {
+        RateLimitRepoRedisImpl { connection, script: Script::new(REDIS_TAKE_TOKEN_SCRIPT) }
+    }
+}
+
+#[async_trait]
+impl RateLimitRepo for RateLimitRepoRedisImpl {
+    async fn take(&self, key: String, rate: f64, burst: u32) -> Result<RateLimitState, String> {
+        let (allowed, tokens): (i32, String) = self.script
+            .key(format!("{}{}", REDIS_KEY_RATE_LIMIT_PREFIX, key))
+            .arg(rate)
+            .arg(burst)
+            .invoke_async(&mut self.connection.clone()).await
+            .map_err(|e| format!("Error taking rate limit token for {}: {}", key, e))?;
+        let tokens: f64 = tokens.parse().unwrap_or(0.0);
+
+        Ok(RateLimitState::new(allowed == 1, tokens, rate, burst))
+    }
+}
+
+// In process memory implementation
+pub struct RateLimitRepoProcMemImpl {
+    buckets: Arc<Mutex<Buckets>>,
+    sweep_interval: Duration,
+}
+
+// a full bucket is the same as no bucket, they are dropped once in a while
+struct Buckets {
+    // tokens, the last refill time, the rate and the burst by key
+    data: HashMap<String, (f64, Instant, f64, u32)>,
+    swept_at: Instant,
+}
+
+impl RateLimitRepoProcMemImpl {
+    pub fn new() -> Self {
+        RateLimitRepoProcMemImpl {
+            buckets: Arc::new(Mutex::new(Buckets { data: HashMap::new(), swept_at: Instant::now() })),
+            sweep_interval: DEFAULT_SWEEP_INTERVAL,
+        }
+    }
+
+    pub fn with_sweep_interval(mut self, sweep_interval: Duration) -> Self {
+        self.sweep_interval = sweep_interval;
+        self
+    }
+
+    // number of the buckets kept in memory
+    pub async fn bucket_count(&self) -> usize {
+        self.buckets.lock().await.data.len()
+    }
+}
+
+impl Default for RateLimitRepoProcMemImpl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RateLimitRepo for RateLimitRepoProcMemImpl {
+    async fn take(&self, key: String, rate: f64, burst: u32) -> Result<RateLimitState, String> {
+        let mut buckets = self.buckets.lock().await;
+        let now = Instant::now();
+        if now.duration_since(buckets.swept_at) >= self.sweep_interval {
+            // each bucket is refilled by its own rate
+            buckets.data.retain(|_, (tokens, updated_at, rate, burst)| *tokens + now.duration_since(*updated_at).as_secs_f64() * *rate < *burst as f64);
+            buckets.swept_at = now;
+        }
+
+        let (tokens, updated_at, _, _) = buckets.data.get(&key).cloned().unwrap_or((burst as f64, now, rate, burst));
+        let mut tokens = (tokens + now.duration_since(updated_at).as_secs_f64() * rate).min(burst as f64);
+        let allowed = tokens >= 1.0;
+        if allowed {
+            tokens -= 1.0;
+        }
+        buckets.data.insert(key, (tokens, now, rate, burst));
+
+        Ok(RateLimitState::new(allowed, tokens, rate, burst))
+    }
+}
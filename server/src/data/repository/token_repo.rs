@@ -0,0 +1,196 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex};
+use common::{convert::{from_json_slice, from_json_string, to_json_string, to_json_vec}, data::dto::api_token::ApiToken};
+
+const REDIS_KEY_TOKENS: &str = "tunnel_tokens";
+
+#[async_trait]
+pub trait TokenRepo {
+    async fn get(&self, id: String) -> Result<ApiToken, String>;
+    async fn get_all(&self) -> Result<Vec<ApiToken>, String>;
+    async fn create(&self, token: ApiToken) -> Result<(), String>;
+    async fn remove(&self, id: String) -> Result<(), String>;
+}
+
+// Redis implementation
+pub struct TokenRepoRedisImpl {
+    connection: MultiplexedConnection
+}
+
+impl TokenRepoRedisImpl {
+    pub fn new(connection: MultiplexedConnection) -> Self {
+        TokenRepoRedisImpl { connection }
+    }
+}
+
+#[async_trait]
+impl TokenRepo for TokenRepoRedisImpl {
+    async fn get(&self, id: String) -> Result<ApiToken, String> {
+        let data: Vec<u8> = self.connection.clone().hget(REDIS_KEY_TOKENS, id.clone()).await
+            .map_err(|e| format!("Error getting token {}: {}", id, e))?;
+        if data.is_empty() {
+            return Err(String::from("Error getting token: no valid token exists"));
+        }
+
+        from_json_slice(&data).ok_or_else(|| String::from("Deserialization error: could not parse ApiToken"))
+    }
+
+    async fn get_all(&self) -> Result<Vec<ApiToken>, String> {
+        let map: HashMap<String, Vec<u8>> = self.connection.clone().hgetall(REDIS_KEY_TOKENS).await
+            .map_err(|e| format!("Error getting tokens: {}", e))?;
+
+        Ok(map.values().filter_map(|value| from_json_slice(value)).collect())
+    }
+
+    async fn create(&self, token: ApiToken) -> Result<(), String> {
+        let data = to_json_vec(&token);
+        self.connection.clone().hset::<_, _, _, i32>(REDIS_KEY_TOKENS, token.id.clone(), data).await
+            .map_err(|e| format!("Error setting token {}: {}", token.id, e))?;
+        Ok(())
+    }
+
+    async fn remove(&self, id: String) -> Result<(), String> {
+        let removed = self.connection.clone().hdel::<_, _, i32>(REDIS_KEY_TOKENS, id.clone()).await
+            .map_err(|e| format!("Error removing token {}: {}", id, e))?;
+        if removed == 0 {
+            return Err(format!("Token {} does not exist", id));
+        }
+        Ok(())
+    }
+}
+
+// In process memory implementation
+pub struct TokenRepoProcMemImpl {
+    data: Arc<Mutex<HashMap<String, ApiToken>>>
+}
+
+impl TokenRepoProcMemImpl {
+    pub fn new() -> Self {
+        TokenRepoProcMemImpl { data: Arc::new(Mutex::new(HashMap::new())) }
+    }
+}
+
+impl Default for TokenRepoProcMemImpl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl TokenRepo for TokenRepoProcMemImpl {
+    async fn get(&self, id: String) -> Result<ApiToken, String> {
+        match self.data.lock().await.get(&id) {
+            Some(token) => Ok(token.clone()),
+            None => Err(String::from("Error getting token: no valid token exists")),
+        }
+    }
+
+    async fn get_all(&self) -> Result<Vec<ApiToken>, String> {
+        Ok(self.data.lock().await.values().cloned().collect())
+    }
+
+    async fn create(&self, token: ApiToken) -> Result<(), String> {
+        self.data.lock().await.insert(token.id.clone(), token);
+        Ok(())
+    }
+
+    async fn remove(&self, id: String) -> Result<(), String> {
+        match self.data.lock().await.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(format!("Token {} does not exist", id)),
+        }
+    }
+}
+
+// File implementation, tokens are stored as json in the config directory.
+// the file is read on every access, so the tokens managed from the CLI
+// take effect on the running server without restarting it
+pub struct TokenRepoFileImpl {
+    path: PathBuf,
+    lock: Mutex<()>
+}
+
+impl TokenRepoFileImpl {
+    pub fn new(path: PathBuf) -> Self {
+        TokenRepoFileImpl { path, lock: Mutex::new(()) }
+    }
+
+    async fn read_tokens(&self) -> Result<HashMap<String, ApiToken>, String> {
+        let data = match tokio::fs::read_to_string(&self.path).await {
+            Ok(value) => value,
+            // no token has been created yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(format!("Error reading tokens file: {}", e)),
+        };
+
+        from_json_string(&data).ok_or_else(|| String::from("Deserialization error: could not parse tokens file"))
+    }
+
+    async fn write_tokens(&self, tokens: &HashMap<String, ApiToken>) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await
+                .map_err(|e| format!("Error creating tokens directory: {}", e))?;
+        }
+
+        // written aside and moved over the file, so a crash never leaves it truncated
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        // left over by an interrupted write
+        if let Err(e) = tokio::fs::remove_file(&tmp_path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(format!("Error removing tokens file: {}", e));
+            }
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // the file contains signing keys, keep it private from the start
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&tmp_path).await
+            .map_err(|e| format!("Error writing tokens file: {}", e))?;
+        file.write_all(to_json_string(tokens).as_bytes()).await
+            .map_err(|e| format!("Error writing tokens file: {}", e))?;
+        file.sync_all().await
+            .map_err(|e| format!("Error writing tokens file: {}", e))?;
+
+        tokio::fs::rename(&tmp_path, &self.path).await
+            .map_err(|e| format!("Error writing tokens file: {}", e))
+    }
+}
+
+#[async_trait]
+impl TokenRepo for TokenRepoFileImpl {
+    async fn get(&self, id: String) -> Result<ApiToken, String> {
+        let _lock = self.lock.lock().await;
+        match self.read_tokens().await?.remove(&id) {
+            Some(token) => Ok(token),
+            None => Err(String::from("Error getting token: no valid token exists")),
+        }
+    }
+
+    async fn get_all(&self) -> Result<Vec<ApiToken>, String> {
+        let _lock = self.lock.lock().await;
+        Ok(self.read_tokens().await?.into_values().collect())
+    }
+
+    async fn create(&self, token: ApiToken) -> Result<(), String> {
+        let _lock = self.lock.lock().await;
+        let mut tokens = self.read_tokens().await?;
+        tokens.insert(token.id.clone(), token);
+        self.write_tokens(&tokens).await
+    }
+
+    async fn remove(&self, id: String) -> Result<(), String> {
+        let _lock = self.lock.lock().await;
+        let mut tokens = self.read_tokens().await?;
+        if tokens.remove(&id).is_none() {
+            return Err(format!("Token {} does not exist", id));
+        }
+        self.write_tokens(&tokens).await
+    }
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+use std::time::Duration;
+use common::convert::{from_json_string, to_json_string};
+use common::data::dto::admin_command::{AdminCommand, AdminResponse};
+use common::{_error, _info};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::service::cache_service::CacheService;
+
+// a command is a single short JSON line
+const ADMIN_COMMAND_MAX_SIZE: u64 = 64 * 1024;
+const ADMIN_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// bind the admin channel, only the owner of the server process may use it
+#[cfg(unix)]
+pub async fn bind_admin_socket(path: &Path) -> Result<UnixListener, String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if path.exists() {
+        // do not take over the channel of another running server
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(format!("Admin channel `{}` is used by another server", path.display()));
+        }
+        // left over by a previous run
+        std::fs::remove_file(path).map_err(|e| format!("Failed to remove admin channel `{}`: {}", path.display(), e))?;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory `{}`: {}", parent.display(), e))?;
+    }
+
+    let listener = UnixListener::bind(path).map_err(|e| format!("Failed to bind admin channel `{}`: {}", path.display(), e))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to set permissions of admin channel `{}`: {}", path.display(), e))?;
+    Ok(listener)
+}
+
+#[cfg(unix)]
+pub async fn register_admin_handler(listener: UnixListener, cache_service: CacheService) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                _error!("[Admin Listener] Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+
+        let cache_service = cache_service.clone();
+        tokio::spawn(async move {
+            let (read, write) = stream.into_split();
+            handle_admin_connection(read, write, cache_service).await;
+        });
+    }
+}
+
+// start the admin channel of a running server in the background
+#[cfg(unix)]
+pub async fn spawn_admin_handler(path: &Path, cache_service: CacheService) {
+    match bind_admin_socket(path).await {
+        Ok(listener) => {
+            _info!("[Admin Listener] Listening on: `{}`", path.display());
+            tokio::spawn(register_admin_handler(listener, cache_service));
+        }
+        Err(msg) => {
+            _error!("[Admin Listener] {}", msg);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn spawn_admin_handler(_path: &Path, _cache_service: CacheService) {
+    _info!("[Admin Listener] The admin channel is only available on unix systems.");
+}
+
+async fn handle_admin_connection<R, W>(read: R, mut write: W, cache_service: CacheService)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut reader = BufReader::new(read).take(ADMIN_COMMAND_MAX_SIZE);
+    let mut line = String::new();
+    let response = match tokio::time::timeout(ADMIN_COMMAND_TIMEOUT, reader.read_line(&mut line)).await {
+        Ok(Ok(_)) => match from_json_string::<AdminCommand>(line.trim()) {
+            Some(command) => execute_admin_command(command, &cache_service).await,
+            None => AdminResponse::Error { message: String::from("Invalid admin command") },
+        },
+        Ok(Err(e)) => AdminResponse::Error { message: format!("Failed to read admin command: {}", e) },
+        Err(_) => AdminResponse::Error { message: String::from("Timed out reading admin command") },
+    };
+
+    let mut response = to_json_string(&response);
+    response.push('\n');
+    if let Err(e) = write.write_all(response.as_bytes()).await {
+        _error!("[Admin Listener] Failed to write response: {}", e);
+    }
+    let _ = write.shutdown().await;
+}
+
+pub async fn execute_admin_command(command: AdminCommand, cache_service: &CacheService) -> AdminResponse {
+    match command {
+        AdminCommand::PurgeCache { client_id, path, method } => {
+            match cache_service.purge_cache(client_id.clone(), path, method).await {
+                Ok(count) => {
+                    _info!("[Admin Listener] Purged {} cache entries of client `{}`", count, client_id);
+                    AdminResponse::Purged { count }
+                }
+                Err(message) => AdminResponse::Error { message },
+            }
+        }
+        AdminCommand::CacheStats => match cache_service.get_cache_stats().await {
+            Ok(stats) => AdminResponse::CacheStats { stats },
+            Err(message) => AdminResponse::Error { message },
+        },
+    }
+}
+
+// send a command to the admin channel of a running server
+#[cfg(unix)]
+pub async fn send_admin_command(path: &Path, command: &AdminCommand) -> Result<AdminResponse, String> {
+    let stream = UnixStream::connect(path).await
+        .map_err(|e| format!("Failed to connect to admin channel `{}`: {}", path.display(), e))?;
+    let (read, mut write) = stream.into_split();
+
+    let mut request = to_json_string(command);
+    request.push('\n');
+    write.write_all(request.as_bytes()).await.map_err(|e| format!("Failed to send admin command: {}", e))?;
+
+    let mut line = String::new();
+    let mut reader = BufReader::new(read);
+    tokio::time::timeout(ADMIN_COMMAND_TIMEOUT, reader.read_line(&mut line)).await
+        .map_err(|_| String::from("Timed out waiting for the server"))?
+        .map_err(|e| format!("Failed to read admin response: {}", e))?;
+
+    from_json_string::<AdminResponse>(line.trim()).ok_or(String::from("Invalid admin response"))
+}
+
+#[cfg(not(unix))]
+pub async fn send_admin_command(_path: &Path, _command: &AdminCommand) -> Result<AdminResponse, String> {
+    Err(String::from("The admin channel is only available on unix systems"))
+}
@@ -1,2 +1,3 @@
+pub mod admin_handler;
 pub mod public_handler;
 pub mod tunnel_handler;
\ No newline at end of file
@@ -1,12 +1,17 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 use chrono::Utc;
 use common::convert::{parse_request_bytes, request_to_bytes, modify_headers_of_response_bytes};
 use common::net::{
     http_json_response_as_bytes,
+    http_json_response_with_headers_as_bytes,
     get_cookie_from_request,
+    HttpReadErrorKind,
+    HttpReadLimits,
     HttpReader,
     HttpResponse,
     TcpStreamTLS
@@ -14,31 +19,67 @@ use common::net::{
 use hex;
 use rand::{self, Rng};
 use sha2::{Sha256, Digest};
-use http::{Request, StatusCode, Uri};
+use http::{header, Request, StatusCode, Uri};
 use tokio::net::TcpStream;
+use common::data::dto::public_auth::PublicAuth;
 use common::data::dto::public_request::PublicRequest;
 use common::{_info, _error};
 use common::config::keys as config_keys;
 use crate::config::ext_keys;
 use crate::service::cache_service::CacheService;
 use crate::service::client_service::ClientService;
+use crate::service::connection_limit_service::ConnectionLimitService;
+use crate::service::ip_filter_service::IpFilterService;
+use crate::service::public_auth_service::PublicAuthService;
 use crate::service::public_service::PublicService;
+use crate::service::rate_limit_service::{rate_limit_headers, RateLimit, RateLimitService, RETRY_AFTER_HEADER};
+use crate::service::request_filter_service::RequestFilterService;
 
 pub async fn register_public_handler(
     stream: TcpStream, 
+    peer_addr: IpAddr,
     client_service: ClientService, 
     public_service: PublicService, 
     cache_service: CacheService, 
+    public_auth_service: PublicAuthService,
+    ip_filter_service: IpFilterService,
+    request_filter_service: RequestFilterService,
+    rate_limit_service: RateLimitService,
+    connection_limit_service: ConnectionLimitService,
     cache_client_id: bool,
     return_tunnel_id: bool
 ) {
     tokio::spawn(async move {
+        // the server-wide rules drop the connection before reading anything
+        if let Err(msg) = ip_filter_service.check_global(peer_addr).await {
+            _error!("Public connection rejected: {}", msg);
+            return;
+        }
+
         let (read_stream, write_stream) = tokio::io::split(stream);
+        let mut stream = TcpStreamTLS::from_tcp(read_stream, write_stream);
+
+        // the permit is held until the connection is done
+        let _permit = match connection_limit_service.acquire_global() {
+            Ok(value) => value,
+            Err(msg) => {
+                _error!("Public connection rejected: {}", msg);
+                reject_connection(&mut stream, msg).await;
+                return;
+            }
+        };
+
         public_handler(
-            TcpStreamTLS::from_tcp(read_stream, write_stream), 
+            stream, 
+            peer_addr,
             client_service, 
             public_service, 
             cache_service, 
+            public_auth_service,
+            ip_filter_service,
+            request_filter_service,
+            rate_limit_service,
+            connection_limit_service,
             cache_client_id, 
             return_tunnel_id).await;
     });
@@ -48,9 +89,15 @@ pub async fn register_public_handler(
 // TODO: implement error responses
 async fn public_handler(
     stream: TcpStreamTLS, 
+    peer_addr: IpAddr,
     client_service: ClientService, 
     public_service: PublicService, 
     cache_service: CacheService,
+    public_auth_service: PublicAuthService,
+    ip_filter_service: IpFilterService,
+    request_filter_service: RequestFilterService,
+    rate_limit_service: RateLimitService,
+    connection_limit_service: ConnectionLimitService,
     cache_client_id: bool,
     return_tunenl_id: bool
 ) -> () {
@@ -58,8 +105,25 @@ async fn public_handler(
     
     // read data as bytes
     let mut raw_request = Vec::new();
-    if let Err(e) = HttpReader::from_tcp_stream(&mut *stream.lock().await).read(&mut raw_request, true).await {
+    let read_result = HttpReader::from_tcp_stream(&mut *stream.lock().await)
+        .with_limits(get_public_read_limits())
+        .read(&mut raw_request, true).await;
+    if let Err(e) = read_result {
         _error!("Error reading incoming request: {}", e);
+        // the connection is likely gone on io errors
+        if e.kind == HttpReadErrorKind::Io {
+            return;
+        }
+
+        let response = match http_json_response_as_bytes(
+        HttpResponse::new(false, e.message.clone()), e.kind.status()) {
+            Ok(value) => value,
+            Err(_) => {
+                return;
+            }
+        };
+
+        let _ = stream.lock().await.write_all(&response).await;
         return;
     }
 
@@ -85,7 +149,7 @@ async fn public_handler(
     };
 
     // get client and transfer request at the same time
-    let (request, client_id, path) = match get_client_id(request, cache_client_id) {
+    let (mut request, client_id, path) = match get_client_id(request, cache_client_id) {
         Ok(value) => value,
         Err(msg) => {
             _error!("{}", msg);
@@ -122,32 +186,129 @@ async fn public_handler(
         }
     };
 
+    // the permit of the client is held until the request is done
+    let _client_permit = match connection_limit_service.acquire_client(client_id.clone()).await {
+        Ok(value) => value,
+        Err(msg) => {
+            _error!("Public Request rejected for client `{}`: {}", client_id, msg);
+            reject_connection(&mut *stream.lock().await, msg).await;
+            return;
+        }
+    };
+
+    // check the ip rules of the client
+    if let Err(msg) = ip_filter_service.check_client(client_id.clone(), peer_addr).await {
+        _error!("Public Request rejected for client `{}`: {}", client_id, msg);
+        let response = match http_json_response_as_bytes(
+        HttpResponse::new(false, String::from("Forbidden")), StatusCode::FORBIDDEN) {
+            Ok(value) => value,
+            Err(_) => {
+                return;
+            }
+        };
+
+        stream.lock().await.write_all(&response).await.unwrap();
+        return;
+    }
+
+    // rate limits of the source address and the client,
+    // checked before the filters and the credentials so denied requests are limited too
+    let rate_limits = get_rate_limits();
+    let rate_limit_headers = match check_rate_limits(&rate_limit_service, &rate_limits, client_id.clone(), peer_addr).await {
+        Ok(headers) => headers,
+        Err(headers) => {
+            _error!("Public Request rejected for client `{}`: rate limit exceeded", client_id);
+            let response = match http_json_response_with_headers_as_bytes(
+            HttpResponse::new(false, String::from("Too many requests")), StatusCode::TOO_MANY_REQUESTS, headers) {
+                Ok(value) => value,
+                Err(_) => {
+                    return;
+                }
+            };
+
+            stream.lock().await.write_all(&response).await.unwrap();
+            return;
+        }
+    };
+
+    // the filter rules may deny the request or modify its headers
+    if let Err((status, msg)) = request_filter_service.filter_request(client_id.clone(), &path, &mut request).await {
+        _error!("Public Request rejected for client `{}`: {}", client_id, msg);
+        let response = match http_json_response_as_bytes(
+        HttpResponse::new(false, String::from(status.canonical_reason().unwrap_or("Denied"))), status) {
+            Ok(value) => value,
+            Err(_) => {
+                return;
+            }
+        };
+
+        stream.lock().await.write_all(&response).await.unwrap();
+        return;
+    }
+
+    // check the credentials of the public endpoints before the request reaches the cache or the client
+    let client_auth = client_service.get_public_auth(client_id.clone()).await;
+    let required_auth = public_auth_service.get_required_auth(client_id.clone(), client_auth).await;
+    if let Err(msg) = authorize_request(&mut request, &required_auth) {
+        _error!("Public Request rejected for client `{}`: {}", client_id, msg);
+        // a challenge for each accepted scheme
+        let mut headers = Vec::new();
+        for auth in required_auth.iter() {
+            let challenge = (header::WWW_AUTHENTICATE.to_string(), auth.challenge());
+            if !headers.contains(&challenge) {
+                headers.push(challenge);
+            }
+        }
+        let response = match http_json_response_with_headers_as_bytes(
+        HttpResponse::new(false, msg), StatusCode::UNAUTHORIZED, headers) {
+            Ok(value) => value,
+            Err(_) => {
+                return;
+            }
+        };
+
+        stream.lock().await.write_all(&response).await.unwrap();
+        return;
+    }
+
+    // the client declares what it exposes, reject the rest without a tunnel round trip
+    if let Err(msg) = client_service.check_allowlist(client_id.clone(), request.method().as_str(), request.uri().path()).await {
+        _error!("Public Request rejected for client `{}`: {}", client_id, msg);
+        let response = match http_json_response_as_bytes(
+        HttpResponse::new(false, String::from("Forbidden")), StatusCode::FORBIDDEN) {
+            Ok(value) => value,
+            Err(_) => {
+                return;
+            }
+        };
+
+        stream.lock().await.write_all(&response).await.unwrap();
+        return;
+    }
+
     raw_request = request_to_bytes(&request);
 
     let request_id = generate_request_id(client_id.clone());
 
-    // check whether a cache of the request is available
-    // TODO: Uniqueness up to header values (i.e: cookies) might make the cache ineffective
-    //       even if the actual request share same input to the underlying client.
-    //       That's why the unique factors are only request URI and Body.
-    //       at least in my case (author) is still reliable, the request was sent to the tunnel (trabas) with no significant header values.
-    //       If we want to include the headers to uniqueness of the request,
-    //       might find a better approach to cover all cases. i.e: excluding serveral keys from header
+    // check whether a cache of the request is available,
+    // the request is unique by its URI, method and body, plus the headers and cookies of the cache config key
     let request_uri = request.uri().to_string();
     let request_method = String::from(request.method().as_str());
     let request_body = get_unique_body_as_bytes(request.clone());
+    let request_headers = request.headers().clone();
 
     _info!("Public Request: `{}`, client: `{}`, path: [{}] `{}`", request_id.clone(), client_id.clone(), request_method.clone(), request_uri.clone());
 
     // check cache
     let cache_config = cache_service.get_cache_config(client_id.clone(), request_method.clone(), path.clone()).await;
     match cache_config {
-        Ok(_) => {
-            match cache_service.get_cache(client_id.clone(), request_uri.clone(), request_method.clone(), request_body.clone()).await {
+        Ok(ref config) => {
+            match cache_service.get_cache(client_id.clone(), request_uri.clone(), request_method.clone(), request_body.clone(), &request_headers, config).await {
                 Ok(cached_response) => {
                     _info!("Public Request: {} processed [cache hit].", request_id);
         
                     // return the cached response to public client
+                    let cached_response = add_rate_limit_headers(cached_response, &rate_limit_headers);
                     stream.lock().await.write_all(&cached_response).await.unwrap();
         
                     return
@@ -164,9 +325,9 @@ async fn public_handler(
     };
 
     // enqueue the request
-    if let Err(e) = public_service.enqueue_request(client_id.clone(), public_request).await {
-        let response = match http_json_response_as_bytes(
-        HttpResponse::new(false, e), StatusCode::from_u16(503).unwrap()) {
+    if let Err(e) = public_service.enqueue_request(client_id.clone(), public_request, rate_limits.max_wait).await {
+        let response = match http_json_response_with_headers_as_bytes(
+        HttpResponse::new(false, e), StatusCode::from_u16(503).unwrap(), vec![(RETRY_AFTER_HEADER.to_string(), String::from("1"))]) {
             Ok(value) => value,
             Err(_) => {
                 return;
@@ -222,7 +383,7 @@ async fn public_handler(
     // write cache
     match cache_config {
         Ok(config) => {
-            if let Err(msg) = cache_service.set_cache(client_id, request_uri, request_method, request_body, res.clone(), config).await {
+            if let Err(msg) = cache_service.set_cache(client_id, request_uri, request_method, request_body, &request_headers, res.clone(), config).await {
                 _error!("Error writing cache for request {}: {}", request_id.clone(), msg);
             }
         },
@@ -232,9 +393,142 @@ async fn public_handler(
     _info!("Public Request: {} processed.", request_id);
 
     // finally return the response to public client
+    let res = add_rate_limit_headers(res, &rate_limit_headers);
     stream.lock().await.write_all(&res).await.unwrap();
 }
 
+// responds with 503 and closes the connection,
+// the unread request is drained so the response is not discarded by a reset
+async fn reject_connection(stream: &mut TcpStreamTLS, msg: String) {
+    let response = match http_json_response_with_headers_as_bytes(
+    HttpResponse::new(false, msg), StatusCode::SERVICE_UNAVAILABLE, vec![(RETRY_AFTER_HEADER.to_string(), String::from("1"))]) {
+        Ok(value) => value,
+        Err(_) => {
+            return;
+        }
+    };
+
+    if stream.write_all(&response).await.is_err() || stream.shutdown().await.is_err() {
+        return;
+    }
+
+    let mut buf = [0u8; 1024];
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+    while let Ok(Ok(n)) = tokio::time::timeout_at(deadline, stream.read(&mut buf)).await {
+        if n == 0 {
+            break;
+        }
+    }
+}
+
+// limits of reading the public requests, 0 disables the limit
+fn get_public_read_limits() -> HttpReadLimits {
+    let get_value = |key: &str, default: u64| std::env::var(key)
+        .ok()
+        .and_then(|val| val.parse::<u64>().ok())
+        .unwrap_or(default);
+
+    HttpReadLimits::new(
+        get_value(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_BYTES, 16 * 1024) as usize, // default is 16 KiB
+        get_value(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_COUNT, 100) as usize,
+        get_value(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_BODY_SIZE, 10 * 1024 * 1024) as usize, // default is 10 MiB
+        Duration::from_secs(get_value(config_keys::CONFIG_KEY_SERVER_PUBLIC_HEADER_TIMEOUT, 30)),
+        Duration::from_secs(get_value(config_keys::CONFIG_KEY_SERVER_PUBLIC_BODY_TIMEOUT, 60)),
+    )
+}
+
+struct RateLimits {
+    client: Option<RateLimit>,
+    ip: Option<RateLimit>,
+    // how long a request may wait for capacity before it's rejected
+    max_wait: Duration,
+}
+
+// rate limits of the public requests, a zero rate disables the limit
+fn get_rate_limits() -> RateLimits {
+    let get_rate = |key: &str| std::env::var(key)
+        .ok()
+        .and_then(|val| val.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    let get_burst = |key: &str| std::env::var(key)
+        .ok()
+        .and_then(|val| val.parse::<u32>().ok());
+
+    RateLimits {
+        client: RateLimit::new(
+            get_rate(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_RPS),
+            get_burst(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_BURST)
+        ),
+        ip: RateLimit::new(
+            get_rate(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_IP_RPS),
+            get_burst(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_IP_BURST)
+        ),
+        max_wait: Duration::from_millis(std::env::var(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_MAX_WAIT)
+            .ok()
+            .and_then(|val| val.parse::<u64>().ok())
+            .unwrap_or(0)), // rejected right away by default
+    }
+}
+
+// takes a token of each enabled scope, the headers of the scope closest to its limit are returned.
+// once a scope rejects the request, the headers of that scope are returned as the error
+async fn check_rate_limits(
+    rate_limit_service: &RateLimitService,
+    rate_limits: &RateLimits,
+    client_id: String,
+    peer_addr: IpAddr
+) -> Result<Vec<(String, String)>, Vec<(String, String)>> {
+    let scopes = [
+        (rate_limits.ip, format!("ip_{}", peer_addr.to_canonical())),
+        (rate_limits.client, format!("client_{}", client_id)),
+    ];
+
+    let mut headers = Vec::new();
+    let mut lowest_remaining = u32::MAX;
+    for (limit, key) in scopes {
+        let Some(limit) = limit else { continue };
+        let state = rate_limit_service.acquire(key, limit, rate_limits.max_wait).await;
+        if !state.allowed {
+            return Err(rate_limit_headers(&limit, &state));
+        }
+        if state.remaining < lowest_remaining {
+            lowest_remaining = state.remaining;
+            headers = rate_limit_headers(&limit, &state);
+        }
+    }
+
+    Ok(headers)
+}
+
+fn add_rate_limit_headers(res: Vec<u8>, headers: &Vec<(String, String)>) -> Vec<u8> {
+    if headers.is_empty() {
+        return res;
+    }
+
+    let headers_to_remove = headers.iter().map(|(name, _)| name.clone()).collect();
+    let headers_to_set = headers.iter().cloned().collect();
+    modify_headers_of_response_bytes(&res, headers_to_remove, headers_to_set, HashMap::new(), true)
+}
+
+// the endpoints are open when no credential is required,
+// the accepted credential is not forwarded to the client
+fn authorize_request<T>(request: &mut Request<T>, required_auth: &Vec<PublicAuth>) -> Result<(), String> {
+    if required_auth.is_empty() {
+        return Ok(());
+    }
+
+    let authorization = match request.headers().get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        Some(value) => value.to_string(),
+        None => return Err(String::from("Authorization is required")),
+    };
+    if !required_auth.iter().any(|auth| auth.matches(&authorization)) {
+        return Err(String::from("Invalid credentials"));
+    }
+
+    request.headers_mut().remove(header::AUTHORIZATION);
+    Ok(())
+}
+
 fn normalize_response_headers(res: Vec<u8>, to_cache_client_id: Option<String>, to_return_tunnel_id: Option<String>) -> Vec<u8> {
     let headers_to_remove = vec![
         "Transfer-Encoding".to_string(),
@@ -1,10 +1,14 @@
 use common::convert::{from_json_slice, to_json_vec};
+use common::data::dto::audit_event::{AuditEvent, AuditEventType, AuditOutcome};
 use common::data::dto::tunnel_ack::TunnelAck;
+use common::data::dto::tunnel_challenge::{challenge_message, TunnelChallenge};
 use common::net::{
     append_path_to_url, prepare_packet, read_bytes_from_mutexed_socket_for_internal, read_bytes_from_socket_for_internal, separate_packets, TcpStreamTLS, HEALTH_CHECK_PACKET_ACK
 };
 use common::{validate_signature, _error, _info};
 use tokio::time::{sleep, Instant};
+use chrono::{DateTime, Local};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use std::u64;
@@ -15,11 +19,15 @@ use common::data::dto::public_response::PublicResponse;
 use common::data::dto::tunnel_client::TunnelClient;
 
 use crate::config::ext_keys;
+use crate::service::audit_service::AuditService;
+use crate::service::client_key_service::ClientKeyService;
 use crate::service::client_service::ClientService;
 use crate::service::public_service::PublicService;
+use crate::service::secret_service::SecretService;
+use crate::service::token_service::TokenService;
 use crate::version::{get_server_version, get_min_client_version};
 
-pub async fn register_tunnel_handler(mut read_stream: TcpStreamTLS, mut write_stream: TcpStreamTLS, client_service: ClientService, public_service: PublicService) -> () {
+pub async fn register_tunnel_handler(mut read_stream: TcpStreamTLS, mut write_stream: TcpStreamTLS, peer_addr: SocketAddr, client_service: ClientService, public_service: PublicService, token_service: TokenService, client_key_service: ClientKeyService, secret_service: SecretService, audit_service: AuditService, cert_client_id: Option<String>) -> () {
     let tunnel_id = string::generate_rand_id(32);
     
     _info!("Pending tunnel [{}] connection.", tunnel_id.clone());
@@ -45,45 +53,124 @@ pub async fn register_tunnel_handler(mut read_stream: TcpStreamTLS, mut write_st
     let client: TunnelClient = match from_json_slice(&raw_response) {
         Some(value) => value,
         None => {
-            let tunnel_ack = TunnelAck::fails(tunnel_id, "Invalid request".to_string());
-            let packet = prepare_packet(to_json_vec(&tunnel_ack));
-            write_stream.write_all(&packet).await.unwrap();
-            _error!("{}", tunnel_ack.message);
+            let event = AuditEvent::new(AuditEventType::Registration, AuditOutcome::Failure, tunnel_id)
+                .with_peer_addr(Some(peer_addr.to_string()));
+            deny_registration(&mut write_stream, &audit_service, event, "Invalid request".to_string()).await;
             return;    
         }
     };
 
+    // every outcome of the handshake is audited
+    let handshake_event = |event: AuditEventType, outcome: AuditOutcome| {
+        AuditEvent::new(event, outcome, tunnel_id.clone())
+            .with_client_id(Some(client.id.clone()))
+            .with_peer_addr(Some(peer_addr.to_string()))
+            .with_version(Some(client.cl_version.clone()))
+            .with_token_id(client.token_id.clone())
+            .with_secret(client.signature.clone())
+    };
+    let registration_failure = || handshake_event(AuditEventType::Registration, AuditOutcome::Failure);
+
     // validate versions
     let version = get_server_version();
     let min_client_version = get_min_client_version();
     if !client.validate_version(version.clone(), min_client_version.clone()) {
-        let tunnel_ack = TunnelAck::fails(
-            tunnel_id,
+        deny_registration(
+            &mut write_stream,
+            &audit_service,
+            registration_failure(),
             format!(
                 "Version mismatch: Server version code = {} (required ≥ {}) | Client version code = {} (required ≥ {}).",
                 version.clone(),
                 client.min_sv_version,
                 client.cl_version,
                 min_client_version
-            ),
-        );
-        let packet = prepare_packet(to_json_vec(&tunnel_ack));
-        write_stream.write_all(&packet).await.unwrap();
-        _error!("{}", tunnel_ack.message);
+            )
+        ).await;
         return;
     }
 
     let client_id = client.id.clone();
-    let client_mac = format!("{}_{}", client.id, client.alias_id);
-    // validate connection before registering client
-    if !validate_signature(client.signature.clone(), client_mac.clone()) {
-        let tunnel_ack = TunnelAck::fails(
-            tunnel_id,
-            format!("Client Registration Denied. client_id: {}, signature: {}", client_id, client.signature),
-        );
-        let packet = prepare_packet(to_json_vec(&tunnel_ack));
-        write_stream.write_all(&packet).await.unwrap();
-        _error!("{}", tunnel_ack.message);
+    let client_mac = client.handshake_mac();
+    // with mutual TLS, the client id must be the one the certificate was issued for
+    if let Some(cert_client_id) = cert_client_id {
+        if cert_client_id != client_id {
+            deny_registration(
+                &mut write_stream,
+                &audit_service,
+                registration_failure(),
+                format!("Client Registration Denied. client_id: {}, the certificate was issued for client_id: {}", client_id, cert_client_id)
+            ).await;
+            return;
+        }
+    }
+
+    // a captured handshake must not be usable later on,
+    // it has to be recent and its nonce must not have been seen within the window (checked once validated)
+    let max_skew = get_handshake_max_skew();
+    if client.timestamp_skew() > max_skew {
+        deny_registration(
+            &mut write_stream,
+            &audit_service,
+            registration_failure(),
+            format!("Client Registration Denied. client_id: {}, handshake timestamp is outside of the allowed window ({}s), check the clock of both machines", client_id, max_skew.as_secs())
+        ).await;
+        return;
+    }
+
+    // key authenticated clients prove the ownership of the registered key
+    // by signing the server nonce, the ack is signed with the server identity key instead
+    let signing_key = if client.key_auth {
+        None
+    } else {
+        // clients with an API token sign the handshake with the token key,
+        // otherwise with one of the active server secrets.
+        // validate connection before registering client
+        let matched_key = match client.token_id.clone() {
+            Some(token_id) => match token_service.get_signing_key(token_id, client_id.clone()).await {
+                Ok(value) => Some((value, None))
+                    .filter(|(key, _)| validate_signature(client.signature.clone(), client_mac.clone(), key.clone())),
+                Err(e) => {
+                    deny_registration(&mut write_stream, &audit_service, registration_failure(), format!("Client Registration Denied. client_id: {}, {}", client_id, e)).await;
+                    return;
+                }
+            },
+            None => secret_service.find_handshake_secret(client.signature.clone(), client_mac.clone()).await,
+        };
+        let signing_key = match matched_key {
+            Some((key, None)) => key,
+            Some((key, Some(expires_at))) => {
+                _info!(
+                    "Client {} signed the handshake with a previous server secret, it will be rejected after {}.",
+                    client_id,
+                    DateTime::<Local>::from(expires_at).format("%Y-%m-%d %H:%M:%S")
+                );
+                key
+            },
+            None => {
+                deny_registration(
+                    &mut write_stream,
+                    &audit_service,
+                    handshake_event(AuditEventType::SignatureMismatch, AuditOutcome::Failure),
+                    format!("Client Registration Denied. client_id: {}, signature mismatch", client_id)
+                ).await;
+                return;
+            }
+        };
+        Some(signing_key)
+    };
+
+    // every client must sign the server nonce as well,
+    // so the handshake is bound to this connection
+    if let Err(e) = validate_challenge(&mut read_stream, &mut write_stream, &client_key_service, &client, tunnel_id.clone(), signing_key.clone()).await {
+        deny_registration(&mut write_stream, &audit_service, registration_failure(), format!("Client Registration Denied. client_id: {}, {}", client_id, e)).await;
+        return;
+    }
+
+    // the nonce is only remembered once the handshake is proven to be genuine,
+    // so forged handshakes can neither burn the nonce of a client nor fill the store
+    if let Err(e) = client_service.register_nonce(client_id.clone(), client.alias_id.clone(), max_skew * 2).await {
+        deny_registration(&mut write_stream, &audit_service, registration_failure(), format!("Client Registration Denied. client_id: {}, {}", client_id, e)).await;
         return;
     }
 
@@ -96,18 +183,51 @@ pub async fn register_tunnel_handler(mut read_stream: TcpStreamTLS, mut write_st
         format!("{}{} or {}?{}={}", endpoint_prefix, &client.id, &endpoint_prefix, ext_keys::CLIENT_ID_COOKIE_KEY, &client.id),
         format!("{}{} or {}?{}={}", endpoint_prefix, &client.alias_id, &endpoint_prefix, ext_keys::CLIENT_ID_COOKIE_KEY, &client.alias_id),
     ];
-    let tunnel_ack = TunnelAck::success(tunnel_id.clone(), client_mac, get_server_secret(), public_endpoints);
+    // the ack is signed with the secret the client signed the handshake with,
+    // the client only holds that one, i.e: a previous secret until it is moved to the rotated one
+    // the key authenticated clients verify the ack with the pinned server identity key
+    let tunnel_ack = match signing_key {
+        Some(signing_key) => TunnelAck::success(tunnel_id.clone(), client_mac, signing_key, public_endpoints),
+        None => {
+            let signed = match secret_service.get_identity_key().await {
+                Some(identity_key) => TunnelAck::success_with_identity_key(tunnel_id.clone(), client_mac, identity_key, public_endpoints),
+                None => Err(String::from("server identity key has not been set")),
+            };
+            match signed {
+                Ok(value) => value,
+                Err(e) => {
+                    deny_registration(&mut write_stream, &audit_service, registration_failure(), format!("Client Registration Denied. client_id: {}, {}", client_id, e)).await;
+                    return;
+                }
+            }
+        }
+    };
+
+    // kept for auditing the disconnect
+    let cl_version = client.cl_version.clone();
+    let token_id = client.token_id.clone();
+    // the tunnels of a client are capped, the handshake fails once the cap is reached.
+    // the cap is checked while registering, so concurrent handshakes cannot exceed it
+    let max_tunnels = get_max_tunnels_per_client();
+    if let Err(e) = client_service.register_client(client.clone(), tunnel_id.clone(), max_tunnels).await {
+        deny_registration(&mut write_stream, &audit_service, registration_failure(), format!("Client Registration Denied. client_id: {}, {}", client_id, e)).await;
+        return;
+    }
+
     let packet = prepare_packet(to_json_vec(&tunnel_ack));
-    write_stream.write_all(&packet).await.unwrap();
+    if let Err(e) = write_stream.write_all(&packet).await {
+        _error!("Failed to send ack to client_id: {}, tunnel_id: {}: {}", client_id, tunnel_id, e);
+        let _ = client_service.disconnect_client(client_id, tunnel_id).await;
+        return;
+    }
 
-    let msg = format!("Client Registration Successful. client_id: {}, signature: {}, tunnel_id: {}", client_id, client.signature, tunnel_id.clone());
+    let msg = format!("Client Registration Successful. client_id: {}, tunnel_id: {}", client_id, tunnel_id.clone());
     _info!("{}", msg);
+    audit_service.record(handshake_event(AuditEventType::Registration, AuditOutcome::Success)).await;
 
     // sleep for 1.5 seconds to prevent race condition with healthcheck packet
     sleep(Duration::from_millis(1500)).await;
 
-    client_service.register_client(client, tunnel_id.clone()).await.unwrap();
-
     // isolate stream and service inside Arc
     let read_stream_arc = Arc::new(Mutex::new(read_stream));
     let write_stream_arc = Arc::new(Mutex::new(write_stream));
@@ -138,7 +258,7 @@ pub async fn register_tunnel_handler(mut read_stream: TcpStreamTLS, mut write_st
     let client_id3 = client_id.clone();
 
     // tunnel ids for each handler
-    let tunnel_id1 = tunnel_id;
+    let tunnel_id1 = tunnel_id.clone();
     let tunnel_id2 = tunnel_id1.clone();
     let tunnel_id3 = tunnel_id2.clone();
 
@@ -149,7 +269,7 @@ pub async fn register_tunnel_handler(mut read_stream: TcpStreamTLS, mut write_st
     // the ordering of locks is opposite in the sender and receiver handlers
     // Sender: public_service -> stream
     // Receiver: stream -> public_service
-    tokio::spawn(async move {
+    let sender = tokio::spawn(async move {
         tunnel_sender_handler(
             handler_stopped1, 
             tunnel_cnt1,
@@ -159,7 +279,7 @@ pub async fn register_tunnel_handler(mut read_stream: TcpStreamTLS, mut write_st
             client_id1, 
             tunnel_id1).await;
     });
-    tokio::spawn(async move {
+    let receiver = tokio::spawn(async move {
         tunnel_receiver_handler(
             handler_stopped2, 
             read_stream_arc, 
@@ -176,15 +296,92 @@ pub async fn register_tunnel_handler(mut read_stream: TcpStreamTLS, mut write_st
             client_id3, 
             tunnel_id3).await;
     });
+
+    // the tunnel is gone once both of its directions are closed
+    tokio::spawn(async move {
+        let _ = sender.await;
+        let _ = receiver.await;
+        let event = AuditEvent::new(AuditEventType::Disconnect, AuditOutcome::Success, tunnel_id)
+            .with_client_id(Some(client_id))
+            .with_peer_addr(Some(peer_addr.to_string()))
+            .with_version(Some(cl_version))
+            .with_token_id(token_id);
+        audit_service.record(event).await;
+    });
 }
 
-fn validate_signature(signature: String, mac: String) -> bool {
-    let secret = get_server_secret();
+async fn deny_registration(write_stream: &mut TcpStreamTLS, audit_service: &AuditService, event: AuditEvent, message: String) {
+    let tunnel_ack = TunnelAck::fails(event.tunnel_id.clone(), message.clone());
+    let packet = prepare_packet(to_json_vec(&tunnel_ack));
+    if let Err(e) = write_stream.write_all(&packet).await {
+        _error!("Error sending tunnel ack: {}", e);
+    }
+    _error!("{}", tunnel_ack.message);
+    audit_service.record(event.with_reason(message)).await;
+}
+
+// send the tunnel id as nonce and validate the signed response,
+// against the signing key if provided, otherwise against the public key registered for the client
+async fn validate_challenge(
+    read_stream: &mut TcpStreamTLS,
+    write_stream: &mut TcpStreamTLS,
+    client_key_service: &ClientKeyService,
+    client: &TunnelClient,
+    tunnel_id: String,
+    signing_key: Option<String>,
+) -> Result<(), String> {
+    // fail early, no need to challenge unknown clients
+    if signing_key.is_none() {
+        client_key_service.get_public_key(client.id.clone()).await?;
+    }
+
+    let challenge = TunnelChallenge::new(tunnel_id.clone());
+    write_stream.write_all(&prepare_packet(to_json_vec(&challenge))).await
+        .map_err(|e| format!("error sending challenge: {}", e))?;
+
+    let mut raw_response = Vec::new();
+    const SOCKET_TIMEOUT_MILLIS: u64 = 5000; // 5 seconds timeout
+    read_bytes_from_socket_for_internal(read_stream, &mut raw_response, SOCKET_TIMEOUT_MILLIS).await
+        .map_err(|e| format!("error reading challenge response: {}", e))?;
+
+    let (packets, _) = separate_packets(raw_response);
+    let response: TunnelChallenge = packets.get(0)
+        .and_then(|packet| from_json_slice(packet))
+        .ok_or_else(|| String::from("invalid challenge response"))?;
+    if response.nonce != tunnel_id {
+        return Err(String::from("challenge nonce mismatch"));
+    }
+
+    let message = challenge_message(tunnel_id, client);
+    match signing_key {
+        Some(signing_key) => {
+            if !validate_signature(response.signature, message, signing_key) {
+                return Err(String::from("invalid challenge signature"));
+            }
+            Ok(())
+        },
+        None => client_key_service.validate_signature(client.id.clone(), response.signature, message).await,
+    }
+}
+
+fn validate_signature(signature: String, mac: String, secret: String) -> bool {
     validate_signature!(signature, mac, secret)
 }
 
-fn get_server_secret() -> String {
-    std::env::var(config::keys::CONFIG_KEY_SERVER_SECRET).unwrap_or_default()
+fn get_handshake_max_skew() -> Duration {
+    let secs = std::env::var(config::keys::CONFIG_KEY_SERVER_HANDSHAKE_MAX_SKEW)
+        .ok()
+        .and_then(|val| val.parse::<u64>().ok())
+        .unwrap_or(300); // default window is 5 minutes
+    Duration::from_secs(secs)
+}
+
+// 0 means unlimited
+fn get_max_tunnels_per_client() -> i64 {
+    std::env::var(config::keys::CONFIG_KEY_SERVER_MAX_TUNNELS_PER_CLIENT)
+        .ok()
+        .and_then(|val| val.parse::<i64>().ok())
+        .unwrap_or(0)
 }
 
 // Tunnel Connection
@@ -5,21 +5,31 @@ pub mod types;
 pub mod config;
 pub mod version;
 
-use common::_info;
+use common::{_error, _info};
 
-use common::config::{ConfigHandler, ConfigHandlerImpl, keys::CONFIG_KEY_SERVER_REDIS_ENABLE};
-use config::{ServerRequestConfig, get_server_identity_from_pem, validate_configs, get_cache_service};
-use data::repository::cache_repo::{CacheRepo, CacheRepoRedisImpl, CacheRepoProcMemImpl};
+use common::config::{get_config_path, ConfigHandler, ConfigHandlerImpl, keys::{CONFIG_KEY_SERVER_CLIENT_KEYS, CONFIG_KEY_SERVER_FILTER_RULES, CONFIG_KEY_SERVER_IP_RULES, CONFIG_KEY_SERVER_PUBLIC_AUTH, CONFIG_KEY_SERVER_REDIS_ENABLE}};
+use config::{ServerRequestConfig, client_id_from_certificate, get_server_identity_from_pem, get_ssl_dir, validate_configs, get_audit_service, get_cache_service, get_cache_sweep_interval, get_connection_limit_service, get_proc_mem_cache_repo};
+use data::repository::cache_repo::{CacheRepo, CacheRepoRedisImpl};
 use data::repository::client_repo::{ClientRepo, ClientRepoRedisImpl, ClientRepoProcMemImpl};
+use data::repository::rate_limit_repo::{RateLimitRepo, RateLimitRepoRedisImpl, RateLimitRepoProcMemImpl};
 use data::repository::request_repo::{RequestRepo, RequestRepoRedisImpl, RequestRepoProcMemImpl};
 use data::repository::response_repo::{ResponseRepo, ResponsRepoRedisImpl, ResponsRepoProcMemImpl};
+use data::repository::token_repo::{TokenRepo, TokenRepoRedisImpl, TokenRepoFileImpl};
 use data::store::redis::RedisDataStore;
+use handler::admin_handler::spawn_admin_handler;
 use handler::public_handler::register_public_handler;
 use handler::tunnel_handler::register_tunnel_handler;
+use service::client_key_service::ClientKeyService;
 use service::client_service::ClientService;
+use service::ip_filter_service::IpFilterService;
+use service::public_auth_service::PublicAuthService;
 use service::public_service::PublicService;
+use service::rate_limit_service::RateLimitService;
+use service::request_filter_service::RequestFilterService;
+use service::secret_service::SecretService;
+use service::token_service::TokenService;
 
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use redis::aio::MultiplexedConnection;
 
 // TLS
@@ -27,6 +37,10 @@ use tokio_native_tls::TlsAcceptor as TokioTlsAcceptor;
 use native_tls::TlsAcceptor;
 use common::net::TcpStreamTLS;
 
+// mutual TLS
+use openssl::ssl::{Ssl, SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+use tokio_openssl::SslStream;
+
 // entry point of the server service
 pub async fn entry_point(config: ServerRequestConfig) {
     // validate required configs
@@ -67,9 +81,17 @@ pub async fn entry_point(config: ServerRequestConfig) {
 
         // init repo to be injected
         let cache_repo = std::sync::Arc::new(CacheRepoRedisImpl::new(redis_connection.clone()));
+        match cache_repo.remove_legacy_cache().await {
+            Ok(count) if count > 0 => _info!("Removed {} cache entries of the previous version.", count),
+            Ok(_) => {},
+            Err(msg) => _error!("{}", msg),
+        }
         let client_repo = std::sync::Arc::new(ClientRepoRedisImpl::new(redis_connection.clone()));
         let request_repo = std::sync::Arc::new(RequestRepoRedisImpl::new(redis_connection.clone()));
         let response_repo = std::sync::Arc::new(ResponsRepoRedisImpl::new(redis_connection.clone()));
+        let token_repo = std::sync::Arc::new(TokenRepoRedisImpl::new(redis_connection.clone()));
+        // the rate limits are shared across the server instances
+        let rate_limit_repo = std::sync::Arc::new(RateLimitRepoRedisImpl::new(redis_connection.clone()));
         // run the services
         run(
             config,
@@ -77,15 +99,24 @@ pub async fn entry_point(config: ServerRequestConfig) {
             client_repo,
             request_repo,
             response_repo,
+            token_repo,
+            rate_limit_repo,
             config_handler
         ).await;
     } else {
         // store data in trabas process
         // init repo to be injected
-        let cache_repo = std::sync::Arc::new(CacheRepoProcMemImpl::new());
+        let cache_repo = std::sync::Arc::new(get_proc_mem_cache_repo());
+        let sweep_interval = get_cache_sweep_interval();
+        if !sweep_interval.is_zero() {
+            cache_repo.spawn_sweeper(sweep_interval);
+        }
         let client_repo = std::sync::Arc::new(ClientRepoProcMemImpl::new());
         let request_repo = std::sync::Arc::new(RequestRepoProcMemImpl::new());
         let response_repo = std::sync::Arc::new(ResponsRepoProcMemImpl::new());
+        // tokens must survive restarts, so keep them in the config directory
+        let token_repo = std::sync::Arc::new(TokenRepoFileImpl::new(get_tokens_file_path()));
+        let rate_limit_repo = std::sync::Arc::new(RateLimitRepoProcMemImpl::new());
         // run the services
         run(
             config,
@@ -93,6 +124,8 @@ pub async fn entry_point(config: ServerRequestConfig) {
             client_repo,
             request_repo,
             response_repo,
+            token_repo,
+            rate_limit_repo,
             config_handler
         ).await;
     }
@@ -104,6 +137,8 @@ pub async fn run(
     client_repo: std::sync::Arc<dyn ClientRepo + Send + Sync>,
     request_repo: std::sync::Arc<dyn RequestRepo + Send + Sync>,
     response_repo: std::sync::Arc<dyn ResponseRepo + Send + Sync>,
+    token_repo: std::sync::Arc<dyn TokenRepo + Send + Sync>,
+    rate_limit_repo: std::sync::Arc<dyn RateLimitRepo + Send + Sync>,
     config_handler: std::sync::Arc<dyn ConfigHandler + Send + Sync>,
 ) {
     // init instances
@@ -113,9 +148,31 @@ pub async fn run(
     _info!("[Public Listener] Listening on: `{}`", public_listener.local_addr().unwrap());
     _info!("[Client Listener] Listening on: `{}`", client_listener.local_addr().unwrap());
 
+    let client_key_service = ClientKeyService::new(config_handler.clone(), String::from(CONFIG_KEY_SERVER_CLIENT_KEYS));
+    let secret_service = SecretService::new(config_handler.clone());
+    // generated once, the key authenticated clients pin its public key
+    match secret_service.init_identity_key(false).await {
+        Ok(public_key) => _info!("Server identity public key: {}", public_key),
+        Err(e) => panic!("Failed to initialize server identity key: {}", e),
+    }
+    let public_auth_service = PublicAuthService::new(config_handler.clone(), String::from(CONFIG_KEY_SERVER_PUBLIC_AUTH));
+    let ip_filter_service = IpFilterService::new(config_handler.clone(), String::from(CONFIG_KEY_SERVER_IP_RULES));
+    let request_filter_service = RequestFilterService::new(config_handler.clone(), String::from(CONFIG_KEY_SERVER_FILTER_RULES));
     let cache_service = get_cache_service(cache_repo, config_handler);
+    // purging and inspecting the cache of this process from the cli
+    match config.admin_socket_path {
+        Some(ref path) => spawn_admin_handler(path, cache_service.clone()).await,
+        None => _info!("[Admin Listener] The admin channel is disabled."),
+    }
     let client_service = ClientService::new(client_repo);
     let public_service = PublicService::new(request_repo, response_repo, config.client_request_limit);
+    let token_service = TokenService::new(token_repo);
+    let rate_limit_service = RateLimitService::new(rate_limit_repo);
+    let connection_limit_service = get_connection_limit_service();
+    let audit_service = get_audit_service();
+    if audit_service.is_enabled() {
+        _info!("Audit log of the tunnel registrations is enabled.");
+    }
     let tls_acceptor: Option<TokioTlsAcceptor> = if config.tls {
         match build_tls_acceptor() {
             Ok(a) => Some(a),
@@ -124,34 +181,72 @@ pub async fn run(
             }
         }
     } else { None };
+    let mtls_acceptor: Option<SslAcceptor> = if config.mtls {
+        match build_mtls_acceptor() {
+            Ok(a) => Some(a),
+            Err(e) => {
+                panic!("Failed to initialize mutual TLS acceptor: {}", e);
+            }
+        }
+    } else { None };
 
     loop {
         tokio::select! {
-            Ok((socket, _)) = public_listener.accept() => {
+            Ok((socket, peer_addr)) = public_listener.accept() => {
                 register_public_handler(
                     socket, 
+                    peer_addr.ip(),
                     client_service.clone(), 
                     public_service.clone(), 
                     cache_service.clone(), 
+                    public_auth_service.clone(),
+                    ip_filter_service.clone(),
+                    request_filter_service.clone(),
+                    rate_limit_service.clone(),
+                    connection_limit_service.clone(),
                     config.cache_client_id,
                     config.return_tunnel_id
                 ).await;
             }
-            Ok((socket, _)) = client_listener.accept() => {
-                if let Some(ref acceptor) = tls_acceptor {
+            Ok((socket, peer_addr)) = client_listener.accept() => {
+                if let Some(ref acceptor) = mtls_acceptor {
+                    _info!("[Client Listener] Accepting connections with mutual TLS...");
+
+                    let acceptor = acceptor.clone();
+                    let cs = client_service.clone();
+                    let ps = public_service.clone();
+                    let ts = token_service.clone();
+                    let ks = client_key_service.clone();
+                    let ss = secret_service.clone();
+                    let aus = audit_service.clone();
+                    tokio::spawn(async move {
+                        match accept_mtls(acceptor, socket).await {
+                            Ok((read, write, cert_client_id)) => {
+                                register_tunnel_handler(read, write, peer_addr, cs, ps, ts, ks, ss, aus, Some(cert_client_id)).await;
+                            }
+                            Err(e) => {
+                                _info!("Mutual TLS handshake failed: {}", e);
+                            }
+                        }
+                    });
+                } else if let Some(ref acceptor) = tls_acceptor {
                     _info!("[Client Listener] Accepting connections with TLS...");
 
                     let s = socket;
                     let acceptor = acceptor.clone();
                     let cs = client_service.clone();
                     let ps = public_service.clone();
+                    let ts = token_service.clone();
+                    let ks = client_key_service.clone();
+                    let ss = secret_service.clone();
+                    let aus = audit_service.clone();
                     tokio::spawn(async move {
                         match acceptor.accept(s).await {
                             Ok(tls_stream) => {
                                 let (r, w) = tokio::io::split(tls_stream);
                                 let read = TcpStreamTLS::from_tcp_tls_read(r);
                                 let write = TcpStreamTLS::from_tcp_tls_write(w);
-                                register_tunnel_handler(read, write, cs, ps).await;
+                                register_tunnel_handler(read, write, peer_addr, cs, ps, ts, ks, ss, aus, None).await;
                             }
                             Err(e) => {
                                 _info!("TLS handshake failed: {}", e);
@@ -163,16 +258,56 @@ pub async fn run(
                     let (r, w) = tokio::io::split(socket);
                     let read = TcpStreamTLS::from_tcp_read(r);
                     let write = TcpStreamTLS::from_tcp_write(w);
-                    register_tunnel_handler(read, write, client_service.clone(), public_service.clone()).await;
+                    register_tunnel_handler(read, write, peer_addr, client_service.clone(), public_service.clone(), token_service.clone(), client_key_service.clone(), secret_service.clone(), audit_service.clone(), None).await;
                 }
             }
         }
     }
 }
 
+pub fn get_tokens_file_path() -> std::path::PathBuf {
+    std::path::PathBuf::from(get_config_path()).join("tokens.json")
+}
+
+pub fn get_audit_log_file_path() -> std::path::PathBuf {
+    std::path::PathBuf::from(get_config_path()).join("audit.log")
+}
+
+pub fn get_admin_socket_path() -> std::path::PathBuf {
+    std::path::PathBuf::from(get_config_path()).join("admin.sock")
+}
+
 fn build_tls_acceptor() -> Result<TokioTlsAcceptor, String> {
     let identity = get_server_identity_from_pem()?;
     let acceptor = TlsAcceptor::builder(identity).build().map_err(|e| format!("build TlsAcceptor: {}", e))?;
     
     Ok(TokioTlsAcceptor::from(acceptor))
 }
+
+// same as TLS, but the clients must present a certificate signed by the CA
+fn build_mtls_acceptor() -> Result<SslAcceptor, String> {
+    let ssl_dir = get_ssl_dir();
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).map_err(|e| format!("build SslAcceptor: {}", e))?;
+    builder.set_private_key_file(ssl_dir.join("server.key"), SslFiletype::PEM).map_err(|e| format!("read server.key: {}", e))?;
+    builder.set_certificate_chain_file(ssl_dir.join("server.crt")).map_err(|e| format!("read server.crt: {}", e))?;
+    builder.check_private_key().map_err(|e| format!("check server.key: {}", e))?;
+    builder.set_ca_file(ssl_dir.join("ca.crt")).map_err(|e| format!("read ca.crt: {}", e))?;
+    builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+
+    Ok(builder.build())
+}
+
+// returns the split stream and the client id of the certificate
+async fn accept_mtls(acceptor: SslAcceptor, socket: TcpStream) -> Result<(TcpStreamTLS, TcpStreamTLS, String), String> {
+    let ssl = Ssl::new(acceptor.context()).map_err(|e| format!("{}", e))?;
+    let mut stream = SslStream::new(ssl, socket).map_err(|e| format!("{}", e))?;
+    std::pin::Pin::new(&mut stream).accept().await.map_err(|e| format!("{}", e))?;
+
+    let cert_client_id = stream.ssl()
+        .peer_certificate()
+        .and_then(|cert| client_id_from_certificate(&cert))
+        .ok_or_else(|| String::from("the client certificate has no client ID"))?;
+
+    let (r, w) = tokio::io::split(stream);
+    Ok((TcpStreamTLS::from_io_read(Box::new(r)), TcpStreamTLS::from_io_write(Box::new(w)), cert_client_id))
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use common::_error;
+use common::data::dto::audit_event::AuditEvent;
+use crate::data::repository::audit_repo::AuditRepo;
+
+// security events of the tunnels, written only when the audit log is enabled
+#[derive(Clone)]
+pub struct AuditService {
+    audit_repo: Option<Arc<dyn AuditRepo + Send + Sync>>,
+}
+
+impl AuditService {
+    pub fn new(audit_repo: Option<Arc<dyn AuditRepo + Send + Sync>>) -> Self {
+        AuditService { audit_repo }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.audit_repo.is_some()
+    }
+
+    // failing to audit should not take the tunnel down, it's only reported to the log
+    pub async fn record(&self, event: AuditEvent) {
+        let audit_repo = match &self.audit_repo {
+            Some(value) => value,
+            None => return,
+        };
+
+        if let Err(msg) = audit_repo.append(event.redacted()).await {
+            _error!("Audit event is not recorded: {}", msg);
+        }
+    }
+}
@@ -1,13 +1,19 @@
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
 use cli_table::{format::Justify, Cell, Style, Table};
 use sha2::{Digest, Sha256};
 
-use common::data::dto::{cache::Cache, cache_config::CacheConfig};
+use chrono::DateTime;
+use http::{header, HeaderMap, HeaderName, Response, StatusCode};
+use common::data::dto::{
+    cache::Cache,
+    cache_config::{CacheConfig, CacheKey, CacheMode, CompiledCacheConfig, CACHE_CONFIG_ANY},
+    cache_stats::{CacheStats, ClientCacheStats},
+};
 use common::config::ConfigHandler;
-use common::convert::{from_json_string, to_json_string};
+use common::convert::{from_json_string, parse_response_bytes, response_to_bytes, to_json_string};
 use common::_info;
 use crate::data::repository::cache_repo::CacheRepo;
 
@@ -17,6 +23,8 @@ pub struct CacheService {
     cache_repo: Arc<dyn CacheRepo + Send + Sync>,
     config_handler: Arc<dyn ConfigHandler + Send + Sync>,
     config_key: String,
+    // the configs compiled from the last seen config value
+    compiled_configs: Arc<Mutex<(String, Arc<Vec<CompiledCacheConfig>>)>>,
 }
 
 impl CacheService {
@@ -25,33 +33,92 @@ impl CacheService {
         config_handler: Arc<dyn ConfigHandler + Send + Sync>,
         config_key: String,
     ) -> Self {
-        Self { cache_repo, config_handler, config_key }
+        Self { cache_repo, config_handler, config_key, compiled_configs: Arc::new(Mutex::new((String::new(), Arc::new(Vec::new())))) }
     }
 
-    fn get_cache_key(&self, client_id: String, uri: String, method: String, body: Vec<u8>) -> String {
+    fn get_cache_key(&self, client_id: String, uri: String, method: String, body: Vec<u8>, headers: &HeaderMap, key: &CacheKey) -> String {
         let mut hasher = Sha256::new();
         hasher.update(client_id.as_bytes());
-        hasher.update(uri.as_bytes());
+        hasher.update(normalize_uri(&uri, key).as_bytes());
         hasher.update(method.as_bytes());
-        hasher.update(body);
+        if key.body {
+            hasher.update(body);
+        }
+        // the additional parts are only appended when configured,
+        // the default key stays the same as before
+        for name in key.headers.iter() {
+            hasher.update(b"\nheader:");
+            update_header_values(&mut hasher, name, headers);
+        }
+        if !key.cookies.is_empty() {
+            let cookies = get_cookies(headers);
+            for name in key.cookies.iter() {
+                hasher.update(format!("\ncookie:{}:", name).as_bytes());
+                if let Some(value) = cookies.get(name) {
+                    hasher.update(value.as_bytes());
+                }
+            }
+        }
+        if key.authorized {
+            hasher.update(b"\nheader:");
+            update_header_values(&mut hasher, header::AUTHORIZATION.as_str(), headers);
+        }
+
+        let hash_result = hasher.finalize();
+        format!("{:x}", hash_result)
+    }
+
+    // the response is kept per the values of the request headers it varies on
+    fn get_variant_key(&self, key: &str, vary: &Vec<String>, headers: &HeaderMap) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(key.as_bytes());
+        for name in vary {
+            update_header_values(&mut hasher, name, headers);
+            hasher.update(b"\n");
+        }
 
         let hash_result = hasher.finalize();
         format!("{:x}", hash_result)
     }
 
-    // Get request cache, if it's already expired, it will return error
+    async fn get_valid_cache(&self, key: String) -> Result<Cache, String> {
+        let cache = self.cache_repo.get(key.clone()).await?;
+        match cache.expired_at.elapsed() {
+            Ok(duration) => Err(format!("Cache {} has been expired for {} seconds", key, duration.as_secs())),
+            Err(_) => Ok(cache),
+        }
+    }
+
+    // Get request cache, if it's already expired, it will return error.
+    // a conditional request matching the cached validators gets 304 Not Modified
     pub async fn get_cache(
         &self,
         client_id: String,
         uri: String,
         method: String,
         body: Vec<u8>,
+        headers: &HeaderMap,
+        cache_config: &CacheConfig,
     ) -> Result<Vec<u8>, String> {
-        let key = self.get_cache_key(client_id, uri, method, body);
-        let cache = self.cache_repo.get(key.clone()).await?;
-        match cache.expired_at.elapsed() {
-            Ok(duration) => return Err(format!("Cache {} has been expired for {} seconds", key, duration.as_secs())),
-            Err(_) => _info!("A valid cache for {} was found", key),
+        if !is_cacheable_request(headers, &cache_config.key) {
+            return Err(String::from("Request with authorization is not cached"));
+        }
+        if cache_config.mode == CacheMode::Http {
+            let directives = parse_cache_control(headers);
+            if directives.contains_key("no-store") || directives.contains_key("no-cache") {
+                return Err(String::from("Request asks not to be served from the cache"));
+            }
+        }
+
+        let key = self.get_cache_key(client_id, uri, method.clone(), body, headers, &cache_config.key);
+        let mut cache = self.get_valid_cache(key.clone()).await?;
+        if !cache.vary.is_empty() {
+            cache = self.get_valid_cache(self.get_variant_key(&key, &cache.vary, headers)).await?;
+        }
+        _info!("A valid cache for {} was found", key);
+
+        if is_not_modified(&method, headers, &cache) {
+            return Ok(not_modified_response(&cache.data));
         }
 
         Ok(cache.data)
@@ -64,14 +131,112 @@ impl CacheService {
         uri: String,
         method: String,
         body: Vec<u8>,
+        headers: &HeaderMap,
         data: Vec<u8>,
         cache_config: CacheConfig,
     ) -> Result<(), String> {
-        let key = self.get_cache_key(client_id.clone(), uri, method, body);
-        let expired_at = SystemTime::now() + Duration::new(cache_config.exp_duration as u64, 0);
-        let cache = Cache::new(expired_at, data);
-        // write cache
-        self.cache_repo.set(key, cache).await
+        if !is_cacheable_request(headers, &cache_config.key) {
+            _info!("Response of [{}] {} is not cached, the request carries authorization", method, uri);
+            return Ok(());
+        }
+        // the response is cached as it is in fixed mode, only the validators are read from it
+        let (ttl, vary, etag, last_modified) = match cache_config.mode {
+            CacheMode::Fixed => (
+                Duration::from_secs(cache_config.exp_duration as u64),
+                Vec::new(),
+                find_response_header(&data, header::ETAG),
+                find_response_header(&data, header::LAST_MODIFIED),
+            ),
+            CacheMode::Http => {
+                // the request itself might forbid storing the response
+                if parse_cache_control(headers).contains_key("no-store") {
+                    return Ok(());
+                }
+                let response = parse_response_bytes(&data)
+                    .ok_or_else(|| String::from("Response cannot be parsed"))?;
+                match get_http_cache_policy(&response, Duration::from_secs(cache_config.exp_duration as u64)) {
+                    Some((ttl, vary)) => (
+                        ttl,
+                        vary,
+                        get_header_value(response.headers(), header::ETAG),
+                        get_header_value(response.headers(), header::LAST_MODIFIED),
+                    ),
+                    None => {
+                        _info!("Response of [{}] {} is not cacheable", method, uri);
+                        return Ok(());
+                    }
+                }
+            },
+        };
+        if ttl.is_zero() {
+            return Ok(());
+        }
+
+        let path = uri.split(['?', '#']).next().unwrap_or_default().to_string();
+        let key = self.get_cache_key(client_id.clone(), uri, method.clone(), body, headers, &cache_config.key);
+        let expired_at = SystemTime::now() + ttl;
+        let cache = Cache::new(expired_at, data)
+            .with_validators(etag, last_modified)
+            .with_request(client_id.clone(), method.clone(), path.clone());
+        if vary.is_empty() {
+            // write cache
+            return self.cache_repo.set(key, cache).await;
+        }
+
+        // the headers to vary on are kept under the request key, pointing to the actual response
+        let variant_key = self.get_variant_key(&key, &vary, headers);
+        self.cache_repo.set(key, Cache::new(expired_at, Vec::new()).with_vary(vary).with_request(client_id, method, path)).await?;
+        self.cache_repo.set(variant_key, cache).await
+    }
+
+    // remove the cached responses of the client (`*` for all clients),
+    // the path could be a glob or a regex prefixed by `~` like the cache configs.
+    // returns the number of removed entries
+    pub async fn purge_cache(&self, client_id: String, path: Option<String>, method: Option<String>) -> Result<usize, String> {
+        // any path unless specified, `**` matches the empty path of the entries cached by older versions too
+        let filter = CacheConfig::new(
+            client_id,
+            method.unwrap_or(String::from(CACHE_CONFIG_ANY)),
+            path.unwrap_or(String::from("**")),
+            0
+        );
+        filter.validate()?;
+        let filter = CompiledCacheConfig::compile(filter)?;
+
+        let mut count = 0;
+        for (key, cache, _) in self.cache_repo.list().await? {
+            if filter.matches(&cache.client_id, &cache.method, &cache.path) {
+                self.cache_repo.delete(key).await?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    pub async fn get_cache_stats(&self) -> Result<CacheStats, String> {
+        let mut clients: BTreeMap<String, ClientCacheStats> = BTreeMap::new();
+        let mut size = 0;
+        let entries = self.cache_repo.list().await?;
+        let fresh = entries.len();
+        for (key, cache, data_size) in entries {
+            let entry_size = key.len() + data_size;
+            let client = clients.entry(cache.client_id.clone()).or_insert(ClientCacheStats {
+                client_id: cache.client_id,
+                ..Default::default()
+            });
+            client.entries += 1;
+            client.size += entry_size;
+            size += entry_size;
+        }
+
+        let count = self.cache_repo.count().await?;
+        Ok(CacheStats {
+            entries: count.max(fresh),
+            expired: count.saturating_sub(fresh),
+            size,
+            clients: clients.into_values().collect(),
+        })
     }
 
     fn get_cache_config_key(&self, client_id: String, method: String, path: String) -> String {
@@ -99,29 +264,41 @@ impl CacheService {
         Vec::new()
     }
 
-    pub async fn get_cache_config(&self, client_id: String, method: String, path: String) -> Result<CacheConfig, String> {
-        let key = self.get_cache_config_key(
-            client_id.clone(),
-            method.clone(),
-            path.clone(),
-        );
-        
-        let cache_configs = self.get_cache_configs().await;
-        for config in cache_configs {
-            let comp_key = self.get_cache_config_key(
-                config.client_id.clone(),
-                config.method.clone(),
-                config.path.clone(),
-            );
-            if key == comp_key {
-                return Ok(config);
-            }
+    // the config is read on every request so the changes from the CLI apply right away,
+    // the path patterns are only compiled again once the config value changes
+    async fn get_compiled_configs(&self) -> Arc<Vec<CompiledCacheConfig>> {
+        let value = self.config_handler.get_configs().await.remove(&self.config_key).unwrap_or_default();
+        let mut compiled_configs = self.compiled_configs.lock().await;
+        if compiled_configs.0 != value {
+            let configs: Vec<CacheConfig> = from_json_string(&value).unwrap_or_default();
+            let configs = configs.into_iter()
+                .filter_map(|config| {
+                    let path = config.path.clone();
+                    CompiledCacheConfig::compile(config)
+                        .map_err(|e| _info!("Cache config of path `{}` is skipped: {}", path, e))
+                        .ok()
+                })
+                .collect();
+            *compiled_configs = (value, Arc::new(configs));
         }
 
-        Err(String::from(format!("No cache config found for client_id: {}, method: {}, path: {}.", client_id, method, path)))
+        compiled_configs.1.clone()
+    }
+
+    // the most specific matching rule, see `CacheConfig::cmp_precedence`
+    pub async fn get_cache_config(&self, client_id: String, method: String, path: String) -> Result<CacheConfig, String> {
+        let compiled_configs = self.get_compiled_configs().await;
+        let config = compiled_configs.iter()
+            .filter(|compiled| compiled.matches(&client_id, &method, &path))
+            .map(|compiled| &compiled.config)
+            .min_by(|a, b| a.cmp_precedence(b))
+            .cloned();
+
+        config.ok_or(format!("No cache config found for client_id: {}, method: {}, path: {}.", client_id, method, path))
     }
 
     pub async fn set_cache_config(&self, config: CacheConfig) -> Result<(), String> {
+        config.validate()?;
         let key = self.get_cache_config_key(
             config.client_id.clone(),
             config.method.clone(),
@@ -136,8 +313,10 @@ impl CacheService {
                 check_config.path.clone()
             );
             if key == comp_key {
-                // if exists just update the duration of expiration
+                // if exists just update the duration of expiration, the mode and the key
                 check_config.exp_duration = config.exp_duration;
+                check_config.mode = config.mode;
+                check_config.key = config.key.clone();
                 exists = true;
                 break;
             }
@@ -151,7 +330,7 @@ impl CacheService {
         let config_value = to_json_string(&cache_configs);
         self.config_handler.set_configs(HashMap::from([
             (self.config_key.clone(), config_value)
-        ])).await;
+        ])).await?;
 
         Ok(())
     }
@@ -175,54 +354,39 @@ impl CacheService {
         let config_value = to_json_string(&cache_configs);
         self.config_handler.set_configs(HashMap::from([
             (self.config_key.clone(), config_value)
-        ])).await;
+        ])).await?;
 
         Ok(())
     }
 
     pub async fn show_cache_config(&self) -> Result<(), String> {
         let mut configs = self.get_cache_configs().await;
-        // sort by client id and path
-        configs.sort_by(|a, b| {
-            if a.client_id < b.client_id {
-                return Ordering::Less;
-            }
-            
-            if a.client_id > b.client_id {
-                return Ordering::Greater;
-            }
-
-            if a.path < b.path {
-                return Ordering::Less;
-            }
-
-            if a.path > b.path {
-                return Ordering::Greater;
-            }
-
-            if a.method < b.method {
-                return Ordering::Less;
-            }
-
-            Ordering::Greater
-        });
+        // the first matching rule from the top is applied
+        configs.sort_by(|a, b| a.cmp_precedence(b));
 
         let table = configs
             .iter()
-            .map(|config| {
+            .enumerate()
+            .map(|(index, config)| {
                 vec![
+                    (index + 1).cell().justify(Justify::Center),
                     config.clone().client_id.cell().justify(Justify::Left),
                     config.clone().method.cell().justify(Justify::Left),
                     config.clone().path.cell().justify(Justify::Left),
                     config.clone().exp_duration.cell().justify(Justify::Center),
+                    config.mode.cell().justify(Justify::Left),
+                    config.key.to_string().cell().justify(Justify::Left),
                 ]
             })
             .table()
             .title(vec![
+                "Precedence".cell().bold(true),
                 "Client ID".cell().bold(true),
                 "Method".cell().bold(true),
                 "Path".cell().bold(true),
                 "Expiry Duration (Seconds)".cell().bold(true),
+                "Mode".cell().bold(true),
+                "Key".cell().bold(true),
             ])
             .bold(true);
 
@@ -234,3 +398,243 @@ impl CacheService {
         Ok(())
     }
 }
+
+// print the stats of the cached responses, per client
+pub fn show_cache_stats(stats: &CacheStats) -> Result<(), String> {
+    let table = stats.clients
+        .iter()
+        .map(|client| {
+            vec![
+                // cached by older versions, without the request metadata
+                (if client.client_id.is_empty() { "-" } else { client.client_id.as_str() }).cell().justify(Justify::Left),
+                client.entries.cell().justify(Justify::Center),
+                client.size.cell().justify(Justify::Center),
+            ]
+        })
+        .table()
+        .title(vec![
+            "Client ID".cell().bold(true),
+            "Entries".cell().bold(true),
+            "Size (Bytes)".cell().bold(true),
+        ])
+        .bold(true);
+
+    let table_display = table.display().map_err(|e| format!("{}", e))?;
+
+    println!("Cached Responses:");
+    println!("{}", table_display);
+    println!("Total: {} entries ({} expired), {} bytes", stats.entries, stats.expired, stats.size);
+
+    Ok(())
+}
+
+// statuses cacheable by default, RFC 9110 section 15.1
+const HEURISTICALLY_CACHEABLE_STATUSES: [u16; 11] = [200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+// validators and metadata kept in the 304 response
+const NOT_MODIFIED_HEADERS: [HeaderName; 6] = [
+    header::CACHE_CONTROL,
+    header::CONTENT_LOCATION,
+    header::ETAG,
+    header::EXPIRES,
+    header::LAST_MODIFIED,
+    header::VARY,
+];
+
+fn update_header_values(hasher: &mut Sha256, name: &str, headers: &HeaderMap) {
+    let values: Vec<&str> = headers.get_all(name).iter()
+        .map(|value| value.to_str().unwrap_or_default().trim())
+        .collect();
+    hasher.update(name.as_bytes());
+    hasher.update(b":");
+    hasher.update(values.join(",").as_bytes());
+}
+
+fn get_cookies(headers: &HeaderMap) -> HashMap<String, String> {
+    let mut cookies = HashMap::new();
+    for value in headers.get_all(header::COOKIE).iter() {
+        for cookie in value.to_str().unwrap_or_default().split(';') {
+            if let Some((name, value)) = cookie.split_once('=') {
+                cookies.insert(name.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+
+    cookies
+}
+
+// credentials usually mean a response for a particular user
+fn is_cacheable_request(headers: &HeaderMap, key: &CacheKey) -> bool {
+    key.authorized || !headers.contains_key(header::AUTHORIZATION)
+}
+
+// drop the ignored query params and sort the rest if configured
+pub fn normalize_uri(uri: &str, key: &CacheKey) -> String {
+    if key.ignore_query.is_empty() && !key.sort_query {
+        return uri.to_string();
+    }
+
+    let (path, query) = match uri.split_once('?') {
+        Some(value) => value,
+        None => return uri.to_string(),
+    };
+    if key.ignore_query.iter().any(|name| name == "*") {
+        return path.to_string();
+    }
+    let mut params: Vec<&str> = query.split('&')
+        .filter(|param| !param.is_empty())
+        .filter(|param| {
+            let name = param.split_once('=').map(|(name, _)| name).unwrap_or(param);
+            !key.ignore_query.iter().any(|ignored| ignored == name)
+        })
+        .collect();
+    if key.sort_query {
+        params.sort();
+    }
+    if params.is_empty() {
+        return path.to_string();
+    }
+
+    format!("{}?{}", path, params.join("&"))
+}
+
+// a header of the raw response, without the header count limit of parsing the whole response
+fn find_response_header(data: &[u8], name: HeaderName) -> Option<String> {
+    let head_len = data.windows(4).position(|window| window == b"\r\n\r\n").unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..head_len])
+        .split("\r\n")
+        .skip(1) // status line
+        .filter_map(|line| line.split_once(':'))
+        .find(|(header_name, _)| header_name.trim().eq_ignore_ascii_case(name.as_str()))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn get_header_value(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers.get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// lowercased directives and their unquoted values
+pub fn parse_cache_control(headers: &HeaderMap) -> HashMap<String, String> {
+    let mut directives = HashMap::new();
+    for value in headers.get_all(header::CACHE_CONTROL).iter() {
+        for directive in value.to_str().unwrap_or_default().split(',') {
+            let (name, value) = directive.split_once('=').unwrap_or((directive, ""));
+            let name = name.trim().to_lowercase();
+            if !name.is_empty() {
+                directives.insert(name, value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+
+    directives
+}
+
+// time to live and the request headers to vary on, none if the response must not be stored.
+// the default ttl is used when the response has no explicit freshness
+pub fn get_http_cache_policy(response: &Response<Vec<u8>>, default_ttl: Duration) -> Option<(Duration, Vec<String>)> {
+    if !HEURISTICALLY_CACHEABLE_STATUSES.contains(&response.status().as_u16()) {
+        return None;
+    }
+
+    let headers = response.headers();
+    let directives = parse_cache_control(headers);
+    // no-cache requires revalidation on every use, which is left to the client
+    if ["no-store", "private", "no-cache"].iter().any(|d| directives.contains_key(*d)) {
+        return None;
+    }
+
+    let mut vary: Vec<String> = Vec::new();
+    for value in headers.get_all(header::VARY).iter() {
+        for name in value.to_str().unwrap_or_default().split(',') {
+            let name = name.trim().to_lowercase();
+            if name == "*" {
+                return None;
+            }
+            if !name.is_empty() && !vary.contains(&name) {
+                vary.push(name);
+            }
+        }
+    }
+    vary.sort();
+
+    // shared caches prefer s-maxage over max-age, then Expires relative to Date
+    let max_age = directives.get("s-maxage")
+        .or_else(|| directives.get("max-age"))
+        .map(|value| value.parse::<u64>().map(Duration::from_secs).unwrap_or(Duration::ZERO));
+    let ttl = match max_age {
+        Some(value) => value,
+        None => match get_header_value(headers, header::EXPIRES) {
+            Some(expires) => {
+                let date = get_header_value(headers, header::DATE)
+                    .and_then(|value| DateTime::parse_from_rfc2822(&value).ok())
+                    .map(|value| value.timestamp())
+                    .unwrap_or(chrono::Utc::now().timestamp());
+                // an invalid date means already expired
+                DateTime::parse_from_rfc2822(&expires).ok()
+                    .map(|value| Duration::from_secs(value.timestamp().saturating_sub(date).max(0) as u64))
+                    .unwrap_or(Duration::ZERO)
+            },
+            None => default_ttl,
+        },
+    };
+
+    // the response might have been in another cache already
+    let age = get_header_value(headers, header::AGE)
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::ZERO);
+    let ttl = ttl.saturating_sub(age);
+    if ttl.is_zero() {
+        return None;
+    }
+
+    Some((ttl, vary))
+}
+
+// If-None-Match takes precedence over If-Modified-Since, RFC 9110 section 13.2.2
+fn is_not_modified(method: &str, headers: &HeaderMap, cache: &Cache) -> bool {
+    // only a GET or HEAD may be answered with a 304
+    if method != "GET" && method != "HEAD" {
+        return false;
+    }
+
+    if let Some(if_none_match) = get_header_value(headers, header::IF_NONE_MATCH) {
+        let etag = match &cache.etag {
+            Some(value) => value.trim_start_matches("W/"),
+            None => return false,
+        };
+        // weak comparison
+        return if_none_match.split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+
+    let if_modified_since = get_header_value(headers, header::IF_MODIFIED_SINCE)
+        .and_then(|value| DateTime::parse_from_rfc2822(&value).ok());
+    let last_modified = cache.last_modified.as_ref()
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    match (if_modified_since, last_modified) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+fn not_modified_response(data: &[u8]) -> Vec<u8> {
+    let mut builder = Response::builder().status(StatusCode::NOT_MODIFIED);
+    if let Some(cached) = parse_response_bytes(data) {
+        for name in NOT_MODIFIED_HEADERS.iter() {
+            for value in cached.headers().get_all(name).iter() {
+                builder = builder.header(name, value);
+            }
+        }
+    }
+
+    match builder.body(Vec::new()) {
+        Ok(response) => response_to_bytes(&response),
+        Err(_) => data.to_vec(),
+    }
+}
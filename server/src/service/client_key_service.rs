@@ -0,0 +1,103 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use cli_table::{format::Justify, Cell, Style, Table};
+
+use common::config::ConfigHandler;
+use common::convert::{from_json_string, to_json_string};
+use common::security::{parse_ed25519_public_key, validate_signature_ed25519};
+
+// public keys of the clients authenticated with ed25519,
+// only the public keys are stored, so no secret is shared with the clients
+#[derive(Clone)]
+pub struct ClientKeyService {
+    config_handler: Arc<dyn ConfigHandler + Send + Sync>,
+    config_key: String,
+}
+
+impl ClientKeyService {
+    pub fn new(config_handler: Arc<dyn ConfigHandler + Send + Sync>, config_key: String) -> Self {
+        Self { config_handler, config_key }
+    }
+
+    async fn get_client_keys(&self) -> BTreeMap<String, String> {
+        // fetch data from config .env
+        let configs = self.config_handler.get_configs().await;
+        if let Some(value) = configs.get(&self.config_key) {
+            if let Some(keys) = from_json_string(value) {
+                return keys;
+            }
+        }
+
+        BTreeMap::new()
+    }
+
+    async fn write_client_keys(&self, keys: BTreeMap<String, String>) -> Result<(), String> {
+        let config_value = to_json_string(&keys);
+        self.config_handler.set_configs(HashMap::from([
+            (self.config_key.clone(), config_value)
+        ])).await
+    }
+
+    pub async fn get_public_key(&self, client_id: String) -> Result<String, String> {
+        match self.get_client_keys().await.remove(&client_id) {
+            Some(value) => Ok(value),
+            None => Err(format!("No public key registered for client_id: {}", client_id)),
+        }
+    }
+
+    pub async fn set_public_key(&self, client_id: String, public_key: String) -> Result<(), String> {
+        // make sure it's a valid key before storing it
+        let public_key = public_key.trim().to_lowercase();
+        parse_ed25519_public_key(public_key.clone())?;
+
+        let mut keys = self.get_client_keys().await;
+        keys.insert(client_id, public_key);
+        self.write_client_keys(keys).await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_public_key(&self, client_id: String) -> Result<(), String> {
+        let mut keys = self.get_client_keys().await;
+        if keys.remove(&client_id).is_none() {
+            return Err(format!("No public key registered for client_id: {}", client_id));
+        }
+        self.write_client_keys(keys).await?;
+
+        Ok(())
+    }
+
+    pub async fn validate_signature(&self, client_id: String, signature: String, message: String) -> Result<(), String> {
+        let public_key = self.get_public_key(client_id.clone()).await?;
+        if !validate_signature_ed25519(signature, message, public_key) {
+            return Err(format!("Invalid challenge signature for client_id: {}", client_id));
+        }
+
+        Ok(())
+    }
+
+    pub async fn show_public_keys(&self) -> Result<(), String> {
+        let keys = self.get_client_keys().await;
+        let table = keys
+            .iter()
+            .map(|(client_id, public_key)| {
+                vec![
+                    client_id.clone().cell().justify(Justify::Left),
+                    public_key.clone().cell().justify(Justify::Left),
+                ]
+            })
+            .table()
+            .title(vec![
+                "Client ID".cell().bold(true),
+                "Public Key (ed25519)".cell().bold(true),
+            ])
+            .bold(true);
+
+        let table_display = table.display().map_err(|e| format!("{}", e))?;
+
+        println!("Client Public Keys:");
+        println!("{}", table_display);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
-use std::{sync::Arc};
+use std::{sync::Arc, time::Duration};
 
-use common::{data::dto::tunnel_client::TunnelClient};
+use common::data::dto::{public_auth::PublicAuth, tunnel_client::TunnelClient};
 use crate::data::repository::client_repo::ClientRepo;
 
 #[derive(Clone)]
@@ -16,16 +16,29 @@ impl ClientService {
     // register new client ID
     // if the new tunneling client attempts to connect
     // the client ID will be cached 
-    pub async fn register_client(&self, client: TunnelClient, tunnel_id: String) -> Result<(), String> {
+    // fails if the client has `max_tunnels` tunnels already, 0 means unlimited
+    pub async fn register_client(&self, client: TunnelClient, tunnel_id: String, max_tunnels: i64) -> Result<(), String> {
         let client_id = client.id.clone();
         let alias_id = client.alias_id.clone();
         // save to client information to redis store
-        self.client_repo.create(client.clone(), tunnel_id).await?;
+        if !self.client_repo.create(client.clone(), tunnel_id, max_tunnels).await? {
+            return Err(format!("max tunnels per client ({}) has been reached", max_tunnels));
+        }
         // set alias
         self.client_repo.create_alias(alias_id, client_id.clone()).await?;
         Ok(())
     }
 
+    // reject the handshake if its nonce has been used within the ttl,
+    // i.e: a captured handshake is being replayed
+    pub async fn register_nonce(&self, client_id: String, nonce: String, ttl: Duration) -> Result<(), String> {
+        let key = format!("{}_{}", client_id, nonce);
+        if !self.client_repo.add_nonce(key, ttl).await? {
+            return Err(format!("handshake nonce {} has already been used", nonce));
+        }
+        Ok(())
+    }
+
     pub async fn disconnect_client(&self, id: String, tunnel_id: String) -> Result<(), String> {
         let client = self.client_repo.get(id, tunnel_id.clone()).await?;
         // remove the alias
@@ -62,6 +75,39 @@ impl ClientService {
         Err(String::from("Client invalid or inactive"))
     }
 
+    // credentials advertised by the tunnels of the client,
+    // a credential of any tunnel is accepted as the tunnels serve the same endpoints
+    pub async fn get_public_auth(&self, client_id: String) -> Vec<PublicAuth> {
+        let mut public_auth: Vec<PublicAuth> = Vec::new();
+        for client in self.client_repo.get_all(client_id).await.unwrap_or_default() {
+            for auth in client.public_auth {
+                if !public_auth.contains(&auth) {
+                    public_auth.push(auth);
+                }
+            }
+        }
+
+        public_auth
+    }
+
+    // the request is allowed when any tunnel of the client allows it,
+    // a tunnel without allowlist exposes everything
+    pub async fn check_allowlist(&self, client_id: String, method: &str, path: &str) -> Result<(), String> {
+        let clients = self.client_repo.get_all(client_id).await.unwrap_or_default();
+        let mut last_err = None;
+        for client in clients {
+            match client.allowlist.check(method, path) {
+                Ok(_) => return Ok(()),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        match last_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
     pub async fn get_tunnel_count(&self, client_id: String) -> i64 {
         match self.client_repo.get_connection_count(client_id).await {
             Ok(count) => count,
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+// caps of the concurrent public connections, server-wide and per client.
+// a connection holds its permit until it's dropped, 0 means unlimited
+#[derive(Clone)]
+pub struct ConnectionLimitService {
+    global: Option<Arc<Semaphore>>,
+    max_client_connections: usize,
+    clients: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl ConnectionLimitService {
+    pub fn new(max_connections: usize, max_client_connections: usize) -> Self {
+        ConnectionLimitService {
+            global: if max_connections > 0 { Some(Arc::new(Semaphore::new(max_connections))) } else { None },
+            max_client_connections,
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn acquire_global(&self) -> Result<Option<OwnedSemaphorePermit>, String> {
+        match &self.global {
+            Some(semaphore) => semaphore.clone().try_acquire_owned()
+                .map(Some)
+                .map_err(|_| String::from("Max public connections has been reached")),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn acquire_client(&self, client_id: String) -> Result<Option<OwnedSemaphorePermit>, String> {
+        if self.max_client_connections == 0 {
+            return Ok(None);
+        }
+
+        // acquire while holding the map, so an entry cannot be dropped and recreated
+        // between looking it up and taking its permit
+        let mut clients = self.clients.lock().await;
+        // drop the semaphores of the clients without connections,
+        // every outstanding permit holds a reference to its semaphore
+        clients.retain(|id, semaphore| *id == client_id || Arc::strong_count(semaphore) > 1);
+        clients.entry(client_id.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_client_connections)))
+            .clone()
+            .try_acquire_owned()
+            .map(Some)
+            .map_err(|_| format!("Max public connections of client `{}` has been reached", client_id))
+    }
+}
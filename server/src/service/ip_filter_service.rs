@@ -0,0 +1,152 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use cli_table::{format::Justify, Cell, Style, Table};
+use ipnet::IpNet;
+
+use common::config::ConfigHandler;
+use common::convert::{from_json_string, to_json_string};
+use common::data::dto::ip_rule::{IpRule, IpRuleAction, IP_RULE_ALL_CLIENTS};
+
+// CIDR based allow/deny rules of the public requests.
+// a deny rule always wins, once an allow rule exists in a scope
+// only the addresses matching one of the allow rules are accepted
+#[derive(Clone)]
+pub struct IpFilterService {
+    config_handler: Arc<dyn ConfigHandler + Send + Sync>,
+    config_key: String,
+}
+
+impl IpFilterService {
+    pub fn new(config_handler: Arc<dyn ConfigHandler + Send + Sync>, config_key: String) -> Self {
+        Self { config_handler, config_key }
+    }
+
+    async fn get_ip_rules(&self) -> Vec<IpRule> {
+        // fetch data from config .env
+        let configs = self.config_handler.get_configs().await;
+        if let Some(value) = configs.get(&self.config_key) {
+            if let Some(rules) = from_json_string(value) {
+                return rules;
+            }
+        }
+
+        Vec::new()
+    }
+
+    async fn write_ip_rules(&self, rules: Vec<IpRule>) -> Result<(), String> {
+        let config_value = to_json_string(&rules);
+        self.config_handler.set_configs(HashMap::from([
+            (self.config_key.clone(), config_value)
+        ])).await
+    }
+
+    // server-wide rules, checked right after the connection is accepted
+    pub async fn check_global(&self, ip: IpAddr) -> Result<(), String> {
+        let rules: Vec<IpRule> = self.get_ip_rules().await
+            .into_iter()
+            .filter(|rule| rule.is_global())
+            .collect();
+        evaluate_rules(&rules, ip)
+    }
+
+    // rules of the client, checked once the client of the request is known
+    pub async fn check_client(&self, client_id: String, ip: IpAddr) -> Result<(), String> {
+        let rules: Vec<IpRule> = self.get_ip_rules().await
+            .into_iter()
+            .filter(|rule| rule.client_id == client_id)
+            .collect();
+        evaluate_rules(&rules, ip)
+    }
+
+    // it replaces the action of an existing rule of the same client and CIDR
+    pub async fn set_ip_rule(&self, rule: IpRule) -> Result<IpRule, String> {
+        let cidr = parse_cidr(&rule.cidr)?;
+        let rule = IpRule::new(rule.client_id, rule.action, cidr.to_string());
+
+        let mut rules = self.get_ip_rules().await;
+        rules.retain(|value| !(value.client_id == rule.client_id && value.cidr == rule.cidr));
+        rules.push(rule.clone());
+        self.write_ip_rules(rules).await?;
+
+        Ok(rule)
+    }
+
+    pub async fn remove_ip_rule(&self, client_id: String, cidr: String) -> Result<(), String> {
+        let cidr = parse_cidr(&cidr)?.to_string();
+
+        let mut rules = self.get_ip_rules().await;
+        let count = rules.len();
+        rules.retain(|value| !(value.client_id == client_id && value.cidr == cidr));
+        if rules.len() == count {
+            return Err(format!("No ip rule found for client_id: {}, cidr: {}", client_id, cidr));
+        }
+        self.write_ip_rules(rules).await?;
+
+        Ok(())
+    }
+
+    pub async fn show_ip_rules(&self) -> Result<(), String> {
+        let mut rules = self.get_ip_rules().await;
+        // server-wide rules first, then by client id
+        rules.sort_by(|a, b| match (a.is_global(), b.is_global()) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => a.client_id.cmp(&b.client_id).then(a.cidr.cmp(&b.cidr)),
+        });
+
+        let table = rules
+            .iter()
+            .map(|rule| {
+                vec![
+                    rule.client_id.clone().cell().justify(Justify::Left),
+                    rule.action.to_string().cell().justify(Justify::Center),
+                    rule.cidr.clone().cell().justify(Justify::Left),
+                ]
+            })
+            .table()
+            .title(vec![
+                "Client ID".cell().bold(true),
+                "Action".cell().bold(true),
+                "CIDR".cell().bold(true),
+            ])
+            .bold(true);
+
+        let table_display = table.display().map_err(|e| format!("{}", e))?;
+
+        println!("IP Rules (`{}` applies to all clients):", IP_RULE_ALL_CLIENTS);
+        println!("{}", table_display);
+
+        Ok(())
+    }
+}
+
+// a plain address is taken as a single host block
+fn parse_cidr(value: &str) -> Result<IpNet, String> {
+    let value = value.trim();
+    if let Ok(net) = value.parse::<IpNet>() {
+        return Ok(net.trunc());
+    }
+
+    value.parse::<IpAddr>()
+        .map(IpNet::from)
+        .map_err(|_| format!("Invalid CIDR: {}", value))
+}
+
+fn evaluate_rules(rules: &Vec<IpRule>, ip: IpAddr) -> Result<(), String> {
+    // ipv4 clients of a dual stack listener come as ipv4-mapped ipv6 addresses
+    let ip = ip.to_canonical();
+    let matches = |rule: &&IpRule| parse_cidr(&rule.cidr).map(|net| net.contains(&ip)).unwrap_or(false);
+
+    if let Some(rule) = rules.iter().filter(|r| r.action == IpRuleAction::Deny).find(matches) {
+        return Err(format!("address {} is denied by {}", ip, rule.cidr));
+    }
+
+    let mut allow_rules = rules.iter().filter(|r| r.action == IpRuleAction::Allow).peekable();
+    if allow_rules.peek().is_some() && !allow_rules.any(|r| matches(&r)) {
+        return Err(format!("address {} is not allowed", ip));
+    }
+
+    Ok(())
+}
@@ -1,3 +1,12 @@
 pub mod cache_service;
 pub mod public_service;
-pub mod client_service;
\ No newline at end of file
+pub mod client_service;
+pub mod client_key_service;
+pub mod token_service;
+pub mod secret_service;
+pub mod public_auth_service;
+pub mod ip_filter_service;
+pub mod rate_limit_service;
+pub mod connection_limit_service;
+pub mod request_filter_service;
+pub mod audit_service;
@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use cli_table::{format::Justify, Cell, Style, Table};
+
+use common::config::ConfigHandler;
+use common::convert::{from_json_string, to_json_string};
+use common::data::dto::public_auth::PublicAuth;
+
+// client id of the policy applied to all clients without their own policy
+pub const PUBLIC_AUTH_ALL_CLIENTS: &str = "*";
+
+// server policy of the credentials required to access the public endpoints,
+// it takes precedence over the credentials advertised by the clients
+#[derive(Clone)]
+pub struct PublicAuthService {
+    config_handler: Arc<dyn ConfigHandler + Send + Sync>,
+    config_key: String,
+}
+
+impl PublicAuthService {
+    pub fn new(config_handler: Arc<dyn ConfigHandler + Send + Sync>, config_key: String) -> Self {
+        Self { config_handler, config_key }
+    }
+
+    async fn get_policies(&self) -> BTreeMap<String, Vec<PublicAuth>> {
+        // fetch data from config .env
+        let configs = self.config_handler.get_configs().await;
+        if let Some(value) = configs.get(&self.config_key) {
+            if let Some(policies) = from_json_string(value) {
+                return policies;
+            }
+        }
+
+        BTreeMap::new()
+    }
+
+    async fn write_policies(&self, policies: BTreeMap<String, Vec<PublicAuth>>) -> Result<(), String> {
+        let config_value = to_json_string(&policies);
+        self.config_handler.set_configs(HashMap::from([
+            (self.config_key.clone(), config_value)
+        ])).await
+    }
+
+    // the credentials required for the client, the server policy of the client comes first,
+    // then the policy for all clients, then the credentials advertised by the client
+    pub async fn get_required_auth(&self, client_id: String, client_auth: Vec<PublicAuth>) -> Vec<PublicAuth> {
+        let mut policies = self.get_policies().await;
+        if let Some(policy) = policies.remove(&client_id) {
+            return policy;
+        }
+        if let Some(policy) = policies.remove(PUBLIC_AUTH_ALL_CLIENTS) {
+            return policy;
+        }
+
+        client_auth
+    }
+
+    // it replaces the existing credential of the same scheme
+    pub async fn set_public_auth(&self, client_id: String, auth: PublicAuth) -> Result<(), String> {
+        let mut policies = self.get_policies().await;
+        let policy = policies.entry(client_id).or_default();
+        policy.retain(|value| value.scheme != auth.scheme);
+        policy.push(auth);
+        self.write_policies(policies).await
+    }
+
+    pub async fn remove_public_auth(&self, client_id: String) -> Result<(), String> {
+        let mut policies = self.get_policies().await;
+        if policies.remove(&client_id).is_none() {
+            return Err(format!("No public auth policy for client_id: {}", client_id));
+        }
+        self.write_policies(policies).await?;
+
+        Ok(())
+    }
+
+    pub async fn show_public_auth(&self) -> Result<(), String> {
+        let policies = self.get_policies().await;
+        let table = policies
+            .iter()
+            .flat_map(|(client_id, policy)| {
+                policy.iter().map(move |auth| {
+                    vec![
+                        client_id.clone().cell().justify(Justify::Left),
+                        format!("{:?}", auth.scheme).cell().justify(Justify::Center),
+                        // only a prefix of the hash is shown, enough to tell them apart
+                        format!("{}...", &auth.credential_hash[..auth.credential_hash.len().min(12)]).cell().justify(Justify::Left),
+                    ]
+                })
+            })
+            .table()
+            .title(vec![
+                "Client ID".cell().bold(true),
+                "Scheme".cell().bold(true),
+                "Credential (sha256)".cell().bold(true),
+            ])
+            .bold(true);
+
+        let table_display = table.display().map_err(|e| format!("{}", e))?;
+
+        println!("Public Auth Policies:");
+        println!("{}", table_display);
+
+        Ok(())
+    }
+}
@@ -24,13 +24,22 @@ impl PublicService {
 
     // enqueue a public client request to temporary database (redis)
     // the request will further be forwarded to target client service (provider)
-    pub async fn enqueue_request(&self, client_id: String, request: PublicRequest) -> Result<(), String> {
+    // when the queue is full, it waits up to `max_wait` for the queue to be drained
+    pub async fn enqueue_request(&self, client_id: String, request: PublicRequest, max_wait: Duration) -> Result<(), String> {
         // if the request limit is set, the queue len must be checked
         if self.request_limit > 0 {
-            let queue_len = self.request_repo.queue_len(client_id.clone()).await?;
-            // TODO: instead of return right away, make it wait for a particular seconds (?)
-            if queue_len > self.request_limit {
-                return Err(String::from("Max request limit has been reached"))
+            let start_time = Instant::now();
+            loop {
+                let queue_len = self.request_repo.queue_len(client_id.clone()).await?;
+                if queue_len <= self.request_limit {
+                    break;
+                }
+                if start_time.elapsed() >= max_wait {
+                    return Err(String::from("Max request limit has been reached"))
+                }
+
+                // add break interval for 10 ms
+                sleep(Duration::from_millis(10)).await;
             }
         }
 
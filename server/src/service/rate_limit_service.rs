@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use tokio::time::{sleep, Duration, Instant};
+
+use common::_error;
+use crate::data::repository::rate_limit_repo::{RateLimitRepo, RateLimitState};
+
+pub const RATE_LIMIT_LIMIT_HEADER: &str = "RateLimit-Limit";
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "RateLimit-Remaining";
+pub const RATE_LIMIT_RESET_HEADER: &str = "RateLimit-Reset";
+pub const RETRY_AFTER_HEADER: &str = "Retry-After";
+
+// token bucket limit, refilled by `rate` tokens per second up to `burst` tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub rate: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    // a zero rate disables the limit, the burst defaults to the rate rounded up
+    pub fn new(rate: f64, burst: Option<u32>) -> Option<Self> {
+        if rate.is_nan() || rate <= 0.0 || rate.is_infinite() {
+            return None;
+        }
+
+        let burst = burst.filter(|b| *b > 0).unwrap_or(rate.ceil() as u32).max(1);
+        Some(RateLimit { rate, burst })
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService {
+    rate_limit_repo: Arc<dyn RateLimitRepo + Send + Sync>,
+}
+
+impl RateLimitService {
+    pub fn new(rate_limit_repo: Arc<dyn RateLimitRepo + Send + Sync>) -> Self {
+        RateLimitService { rate_limit_repo }
+    }
+
+    // take a token of the key, waiting up to `max_wait` for one to be available.
+    // the store being unavailable should not take the tunnel down, so the request is let through
+    pub async fn acquire(&self, key: String, limit: RateLimit, max_wait: Duration) -> RateLimitState {
+        let deadline = Instant::now() + max_wait;
+        loop {
+            let state = match self.rate_limit_repo.take(key.clone(), limit.rate, limit.burst).await {
+                Ok(value) => value,
+                Err(msg) => {
+                    _error!("Rate limit of `{}` is skipped: {}", key, msg);
+                    return RateLimitState::new(true, limit.burst as f64, limit.rate, limit.burst);
+                }
+            };
+            if state.allowed || Instant::now() + state.retry_after > deadline {
+                return state;
+            }
+
+            sleep(state.retry_after).await;
+        }
+    }
+}
+
+// `RateLimit-*` headers of the state, `Retry-After` is only added once the request is rejected
+pub fn rate_limit_headers(limit: &RateLimit, state: &RateLimitState) -> Vec<(String, String)> {
+    let mut headers = vec![
+        (RATE_LIMIT_LIMIT_HEADER.to_string(), limit.burst.to_string()),
+        (RATE_LIMIT_REMAINING_HEADER.to_string(), state.remaining.to_string()),
+        (RATE_LIMIT_RESET_HEADER.to_string(), ceil_secs(state.reset).to_string()),
+    ];
+    if !state.allowed {
+        headers.push((RETRY_AFTER_HEADER.to_string(), ceil_secs(state.retry_after).max(1).to_string()));
+    }
+
+    headers
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
@@ -0,0 +1,292 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use cli_table::{format::Justify, Cell, Style, Table};
+use http::{header::{HeaderName, HeaderValue}, Method, Request, StatusCode};
+
+use common::_info;
+use common::config::ConfigHandler;
+use common::convert::{from_json_string, to_json_string};
+use common::data::dto::filter_rule::{FilterAction, FilterRule, FILTER_RULE_ALL_CLIENTS};
+use common::string::{glob_match, glob_match_path, normalize_path, GlobPattern};
+
+// declarative rules of the public requests, evaluated right after the client of the request is known.
+// the server-wide and the client rules are evaluated together by priority,
+// the first matching allow or deny stops the evaluation, a request matching no such rule is allowed
+#[derive(Clone)]
+pub struct RequestFilterService {
+    config_handler: Arc<dyn ConfigHandler + Send + Sync>,
+    config_key: String,
+    // the rules compiled from the last seen config value
+    compiled_rules: Arc<Mutex<(String, Arc<Vec<CompiledFilterRule>>)>>,
+}
+
+// a rule with its globs compiled once it's loaded
+struct CompiledFilterRule {
+    rule: FilterRule,
+    path: Option<GlobPattern>,
+    header_value: Option<GlobPattern>,
+    query_value: Option<GlobPattern>,
+}
+
+impl CompiledFilterRule {
+    fn compile(rule: FilterRule) -> Result<Self, String> {
+        let path = rule.path.as_deref().map(GlobPattern::path).transpose()?;
+        let header_value = rule.header.as_ref().and_then(|h| h.value.as_deref()).map(GlobPattern::any).transpose()?;
+        let query_value = rule.query.as_ref().and_then(|q| q.value.as_deref()).map(GlobPattern::any).transpose()?;
+
+        Ok(CompiledFilterRule { rule, path, header_value, query_value })
+    }
+}
+
+impl RequestFilterService {
+    pub fn new(config_handler: Arc<dyn ConfigHandler + Send + Sync>, config_key: String) -> Self {
+        Self { config_handler, config_key, compiled_rules: Arc::new(Mutex::new((String::new(), Arc::new(Vec::new())))) }
+    }
+
+    // the config is read on every request so the changes from the CLI apply right away,
+    // the rules are only compiled again once the config value changes
+    async fn get_compiled_rules(&self) -> Arc<Vec<CompiledFilterRule>> {
+        let value = self.config_handler.get_configs().await.remove(&self.config_key).unwrap_or_default();
+        let mut compiled_rules = self.compiled_rules.lock().await;
+        if compiled_rules.0 != value {
+            let rules: Vec<FilterRule> = from_json_string(&value).unwrap_or_default();
+            let rules = rules.into_iter()
+                .filter_map(|rule| {
+                    let name = rule.name.clone();
+                    CompiledFilterRule::compile(rule)
+                        .map_err(|e| _info!("Filter rule `{}` is skipped: {}", name, e))
+                        .ok()
+                })
+                .collect();
+            *compiled_rules = (value, Arc::new(rules));
+        }
+
+        compiled_rules.1.clone()
+    }
+
+    async fn get_filter_rules(&self) -> Vec<FilterRule> {
+        // fetch data from config .env
+        let configs = self.config_handler.get_configs().await;
+        if let Some(value) = configs.get(&self.config_key) {
+            if let Some(rules) = from_json_string(value) {
+                return rules;
+            }
+        }
+
+        Vec::new()
+    }
+
+    async fn write_filter_rules(&self, rules: Vec<FilterRule>) -> Result<(), String> {
+        let config_value = to_json_string(&rules);
+        self.config_handler.set_configs(HashMap::from([
+            (self.config_key.clone(), config_value)
+        ])).await
+    }
+
+    // applies the header actions to the request,
+    // returns the status and the reason once the request is denied
+    pub async fn filter_request<T>(&self, client_id: String, path: &str, request: &mut Request<T>) -> Result<(), (StatusCode, String)> {
+        let compiled_rules = self.get_compiled_rules().await;
+        let mut rules: Vec<&CompiledFilterRule> = compiled_rules
+            .iter()
+            .filter(|compiled| compiled.rule.is_global() || compiled.rule.client_id == client_id)
+            .collect();
+        // on the same priority, the server-wide rules come first
+        rules.sort_by(|a, b| a.rule.priority.cmp(&b.rule.priority).then(b.rule.is_global().cmp(&a.rule.is_global())));
+        // encoded and dot segments must not get around the path rules
+        let path = normalize_path(path);
+
+        // the later rules see the headers modified by the former ones
+        for compiled in rules {
+            if !matches_request(compiled, &path, request) {
+                continue;
+            }
+
+            let rule = &compiled.rule;
+            match &rule.action {
+                FilterAction::Allow => return Ok(()),
+                FilterAction::Deny { status } => {
+                    let status = StatusCode::from_u16(*status).unwrap_or(StatusCode::FORBIDDEN);
+                    return Err((status, format!("denied by filter rule `{}` of `{}`", rule.name, rule.client_id)));
+                },
+                FilterAction::SetHeader { name, value } => {
+                    match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                        (Ok(name), Ok(value)) => { request.headers_mut().insert(name, value); },
+                        _ => _info!("Filter rule `{}` is skipped: invalid header", rule.name),
+                    }
+                },
+                FilterAction::RemoveHeader { name } => {
+                    request.headers_mut().remove(name.as_str());
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    // it replaces an existing rule of the same client and name
+    pub async fn set_filter_rule(&self, rule: FilterRule) -> Result<FilterRule, String> {
+        let rule = validate_rule(rule)?;
+
+        let mut rules = self.get_filter_rules().await;
+        rules.retain(|value| !(value.client_id == rule.client_id && value.name == rule.name));
+        rules.push(rule.clone());
+        self.write_filter_rules(rules).await?;
+
+        Ok(rule)
+    }
+
+    pub async fn remove_filter_rule(&self, client_id: String, name: String) -> Result<(), String> {
+        let mut rules = self.get_filter_rules().await;
+        let count = rules.len();
+        rules.retain(|value| !(value.client_id == client_id && value.name == name));
+        if rules.len() == count {
+            return Err(format!("No filter rule found for client_id: {}, name: {}", client_id, name));
+        }
+        self.write_filter_rules(rules).await?;
+
+        Ok(())
+    }
+
+    pub async fn show_filter_rules(&self) -> Result<(), String> {
+        let mut rules = self.get_filter_rules().await;
+        // server-wide rules first, then by client id in the order of evaluation
+        rules.sort_by(|a, b| match (a.is_global(), b.is_global()) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => a.client_id.cmp(&b.client_id).then(a.priority.cmp(&b.priority)),
+        });
+
+        let or_any = |value: Option<String>| value.unwrap_or(String::from("*"));
+        let table = rules
+            .iter()
+            .map(|rule| {
+                vec![
+                    rule.client_id.clone().cell().justify(Justify::Left),
+                    rule.priority.cell().justify(Justify::Center),
+                    rule.name.clone().cell().justify(Justify::Left),
+                    or_any(rule.method.clone()).cell().justify(Justify::Center),
+                    or_any(rule.path.clone()).cell().justify(Justify::Left),
+                    or_any(rule.header.as_ref().map(|h| h.to_string())).cell().justify(Justify::Left),
+                    or_any(rule.query.as_ref().map(|q| q.to_string())).cell().justify(Justify::Left),
+                    rule.action.to_string().cell().justify(Justify::Left),
+                ]
+            })
+            .table()
+            .title(vec![
+                "Client ID".cell().bold(true),
+                "Priority".cell().bold(true),
+                "Name".cell().bold(true),
+                "Method".cell().bold(true),
+                "Path".cell().bold(true),
+                "Header".cell().bold(true),
+                "Query".cell().bold(true),
+                "Action".cell().bold(true),
+            ])
+            .bold(true);
+
+        let table_display = table.display().map_err(|e| format!("{}", e))?;
+
+        println!("Request Filter Rules (`{}` applies to all clients, lower priority is evaluated first):", FILTER_RULE_ALL_CLIENTS);
+        println!("{}", table_display);
+
+        Ok(())
+    }
+}
+
+fn matches_request<T>(compiled: &CompiledFilterRule, path: &str, request: &Request<T>) -> bool {
+    let rule = &compiled.rule;
+    if let Some(methods) = &rule.method {
+        if !methods.split(',').any(|m| m.trim() == "*" || m.trim().eq_ignore_ascii_case(request.method().as_str())) {
+            return false;
+        }
+    }
+
+    if let Some(pattern) = &compiled.path {
+        if !pattern.is_match(path) {
+            return false;
+        }
+    }
+
+    if let Some(header) = &rule.header {
+        let values: Vec<&str> = request.headers()
+            .get_all(header.name.as_str())
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect();
+        if !matches_values(compiled.header_value.as_ref(), values) {
+            return false;
+        }
+    }
+
+    if let Some(query) = &rule.query {
+        let values: Vec<&str> = request.uri().query().unwrap_or("")
+            .split('&')
+            .filter_map(|param| match param.split_once('=') {
+                Some((name, value)) if name == query.name => Some(value),
+                None if param == query.name => Some(""),
+                _ => None,
+            })
+            .collect();
+        if !matches_values(compiled.query_value.as_ref(), values) {
+            return false;
+        }
+    }
+
+    true
+}
+
+// the header or query parameter must be present, and match the value pattern if set
+fn matches_values(pattern: Option<&GlobPattern>, values: Vec<&str>) -> bool {
+    match pattern {
+        Some(pattern) => values.iter().any(|value| pattern.is_match(value)),
+        None => !values.is_empty(),
+    }
+}
+
+fn validate_rule(mut rule: FilterRule) -> Result<FilterRule, String> {
+    rule.name = rule.name.trim().to_string();
+    if rule.name.is_empty() {
+        return Err(String::from("Rule name cannot be empty"));
+    }
+
+    if let Some(methods) = &rule.method {
+        let methods: Vec<String> = methods.split(',').map(|m| m.trim().to_uppercase()).collect();
+        for method in methods.iter().filter(|m| *m != "*") {
+            Method::from_bytes(method.as_bytes()).map_err(|_| format!("Invalid method: {}", method))?;
+        }
+        rule.method = Some(methods.join(","));
+    }
+
+    if let Some(path) = &rule.path {
+        glob_match_path(path, "/")?;
+    }
+    for filter_match in [&rule.header, &rule.query].into_iter().flatten() {
+        if let Some(value) = &filter_match.value {
+            glob_match(value, "")?;
+        }
+    }
+    if let Some(header) = &rule.header {
+        HeaderName::from_bytes(header.name.as_bytes()).map_err(|_| format!("Invalid header name: {}", header.name))?;
+    }
+
+    match &rule.action {
+        FilterAction::Deny { status } => {
+            if !(400..600).contains(status) {
+                return Err(format!("Invalid deny status: {}, it must be a 4xx or 5xx status", status));
+            }
+        },
+        FilterAction::SetHeader { name, value } => {
+            HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name: {}", name))?;
+            HeaderValue::from_str(value).map_err(|_| format!("Invalid header value: {}", value))?;
+        },
+        FilterAction::RemoveHeader { name } => {
+            HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name: {}", name))?;
+        },
+        FilterAction::Allow => {},
+    }
+
+    Ok(rule)
+}
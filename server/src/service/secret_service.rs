@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Local};
+use cli_table::{format::Justify, Cell, Style, Table};
+
+use common::config::{keys, ConfigHandler};
+use common::convert::{from_json_string, to_json_string};
+use common::data::dto::previous_secret::PreviousSecret;
+use common::security::{ed25519_public_key_of, generate_ed25519_keypair, generate_hmac_key};
+use common::validate_signature;
+
+// active server secrets, the primary one plus the previous ones kept by the rotation.
+// the config file is read on every access, so a rotation done from the CLI
+// takes effect on the running server without restarting it
+#[derive(Clone)]
+pub struct SecretService {
+    config_handler: Arc<dyn ConfigHandler + Send + Sync>,
+}
+
+impl SecretService {
+    pub fn new(config_handler: Arc<dyn ConfigHandler + Send + Sync>) -> Self {
+        Self { config_handler }
+    }
+
+    pub async fn get_primary_secret(&self) -> String {
+        // fallback to the process env, i.e: set without the config file
+        match self.config_handler.get_configs().await.remove(keys::CONFIG_KEY_SERVER_SECRET) {
+            Some(value) if !value.is_empty() => value,
+            _ => std::env::var(keys::CONFIG_KEY_SERVER_SECRET).unwrap_or_default(),
+        }
+    }
+
+    async fn get_previous_secrets(&self) -> Vec<PreviousSecret> {
+        let configs = self.config_handler.get_configs().await;
+        if let Some(value) = configs.get(keys::CONFIG_KEY_SERVER_PREVIOUS_SECRETS) {
+            if let Some(secrets) = from_json_string(value) {
+                return secrets;
+            }
+        }
+
+        Vec::new()
+    }
+
+    // secrets accepted for the handshake, the primary comes first,
+    // followed by the non-expired previous secrets with their expiry
+    pub async fn get_active_secrets(&self) -> Vec<(String, Option<SystemTime>)> {
+        let mut secrets = vec![(self.get_primary_secret().await, None)];
+        for previous in self.get_previous_secrets().await {
+            if !previous.is_expired() {
+                secrets.push((previous.secret, Some(previous.expires_at)));
+            }
+        }
+
+        secrets
+    }
+
+    // the active secret the client signed the handshake with, the primary one is checked first.
+    // the ack must be signed with this secret, the client cannot verify it with any other
+    pub async fn find_handshake_secret(&self, signature: String, mac: String) -> Option<(String, Option<SystemTime>)> {
+        self.get_active_secrets().await
+            .into_iter()
+            .find(|(secret, _)| validate_signature!(signature.clone(), mac.clone(), secret.clone()))
+    }
+
+    // replace the primary secret, the current one is still accepted for the overlap duration,
+    // returns the new primary secret
+    pub async fn rotate_secret(&self, secret: Option<String>, overlap: Duration) -> Result<String, String> {
+        let secret = secret.unwrap_or_else(|| generate_hmac_key(32));
+        if secret.trim().is_empty() {
+            return Err(String::from("The new secret must not be empty"));
+        }
+
+        let current = self.get_primary_secret().await;
+        if current == secret {
+            return Err(String::from("The new secret must be different from the current one"));
+        }
+
+        // the expired secrets are not needed anymore
+        let mut previous: Vec<PreviousSecret> = self.get_previous_secrets().await
+            .into_iter()
+            .filter(|s| !s.is_expired() && s.secret != secret)
+            .collect();
+        if !current.is_empty() && !overlap.is_zero() {
+            previous.push(PreviousSecret::new(current, overlap));
+        }
+
+        self.config_handler.set_configs(HashMap::from([
+            (String::from(keys::CONFIG_KEY_SERVER_SECRET), secret.clone()),
+            (String::from(keys::CONFIG_KEY_SERVER_PREVIOUS_SECRETS), to_json_string(&previous)),
+        ])).await?;
+
+        Ok(secret)
+    }
+
+    // stop accepting the previous secrets right away
+    pub async fn expire_previous_secrets(&self) -> Result<(), String> {
+        self.config_handler.set_configs(HashMap::from([
+            (String::from(keys::CONFIG_KEY_SERVER_PREVIOUS_SECRETS), to_json_string(&Vec::<PreviousSecret>::new())),
+        ])).await
+    }
+
+    // ed25519 key the acks of the key authenticated clients are signed with,
+    // its public key is pinned by these clients
+    pub async fn get_identity_key(&self) -> Option<String> {
+        match self.config_handler.get_configs().await.remove(keys::CONFIG_KEY_SERVER_IDENTITY_KEY) {
+            Some(value) if !value.is_empty() => Some(value),
+            _ => std::env::var(keys::CONFIG_KEY_SERVER_IDENTITY_KEY).ok().filter(|value| !value.is_empty()),
+        }
+    }
+
+    // generate the identity key unless it is set already, returns its public key.
+    // replacing it breaks every client pinning the previous public key
+    pub async fn init_identity_key(&self, force: bool) -> Result<String, String> {
+        if !force {
+            if let Some(identity_key) = self.get_identity_key().await {
+                return ed25519_public_key_of(identity_key);
+            }
+        }
+
+        let (private_key, public_key) = generate_ed25519_keypair();
+        self.config_handler.set_configs(HashMap::from([
+            (String::from(keys::CONFIG_KEY_SERVER_IDENTITY_KEY), private_key),
+        ])).await?;
+
+        Ok(public_key)
+    }
+
+    pub async fn show_secrets(&self) -> Result<(), String> {
+        let format_time = |time: SystemTime| DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string();
+        // only a prefix of the secret is shown, enough to tell them apart
+        let mask = |secret: &str| format!("{}...", secret.chars().take(8).collect::<String>());
+
+        let mut rows = vec![vec![
+            mask(&self.get_primary_secret().await).cell().justify(Justify::Left),
+            "primary".cell().justify(Justify::Center),
+            "-".cell().justify(Justify::Center),
+            "-".cell().justify(Justify::Center),
+        ]];
+        for previous in self.get_previous_secrets().await {
+            let status = if previous.is_expired() { "expired" } else { "previous" };
+            rows.push(vec![
+                mask(&previous.secret).cell().justify(Justify::Left),
+                status.cell().justify(Justify::Center),
+                format_time(previous.rotated_at).cell().justify(Justify::Center),
+                format_time(previous.expires_at).cell().justify(Justify::Center),
+            ]);
+        }
+
+        let table = rows
+            .table()
+            .title(vec![
+                "Secret".cell().bold(true),
+                "Status".cell().bold(true),
+                "Rotated At".cell().bold(true),
+                "Expires At".cell().bold(true),
+            ])
+            .bold(true);
+
+        let table_display = table.display().map_err(|e| format!("{}", e))?;
+
+        println!("Server Secrets:");
+        println!("{}", table_display);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use cli_table::{format::Justify, Cell, Style, Table};
+
+use common::data::dto::api_token::ApiToken;
+use crate::data::repository::token_repo::TokenRepo;
+
+#[derive(Clone)]
+pub struct TokenService {
+    token_repo: Arc<dyn TokenRepo + Send + Sync>
+}
+
+impl TokenService {
+    pub fn new(token_repo: Arc<dyn TokenRepo + Send + Sync>) -> Self {
+        TokenService { token_repo }
+    }
+
+    pub async fn create_token(&self, client_ids: Vec<String>, ttl: Option<Duration>) -> Result<ApiToken, String> {
+        if client_ids.is_empty() {
+            return Err(String::from("At least one client ID must be allowed for the token"));
+        }
+
+        let token = ApiToken::new(client_ids, ttl);
+        self.token_repo.create(token.clone()).await?;
+
+        Ok(token)
+    }
+
+    pub async fn revoke_token(&self, id: String) -> Result<(), String> {
+        self.token_repo.remove(id).await
+    }
+
+    // get the signing key of the token for the client handshake,
+    // the token must exist, not expired and allowed for the client id
+    pub async fn get_signing_key(&self, token_id: String, client_id: String) -> Result<String, String> {
+        let token = self.token_repo.get(token_id.clone()).await
+            .map_err(|_| format!("Token {} is invalid or has been revoked", token_id))?;
+        if token.is_expired() {
+            return Err(format!("Token {} has expired", token_id));
+        }
+        if !token.allows(&client_id) {
+            return Err(format!("Token {} is not allowed for client_id: {}", token_id, client_id));
+        }
+
+        Ok(token.key)
+    }
+
+    pub async fn show_tokens(&self) -> Result<(), String> {
+        let mut tokens = self.token_repo.get_all().await?;
+        tokens.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+        let format_time = |time: std::time::SystemTime| DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string();
+        let table = tokens
+            .iter()
+            .map(|token| {
+                let expires_at = match token.expires_at {
+                    Some(time) if token.is_expired() => format!("{} (expired)", format_time(time)),
+                    Some(time) => format_time(time),
+                    None => String::from("-"),
+                };
+                vec![
+                    token.id.clone().cell().justify(Justify::Left),
+                    token.client_ids.join(", ").cell().justify(Justify::Left),
+                    format_time(token.created_at).cell().justify(Justify::Center),
+                    expires_at.cell().justify(Justify::Center),
+                ]
+            })
+            .table()
+            .title(vec![
+                "Token ID".cell().bold(true),
+                "Client IDs".cell().bold(true),
+                "Created At".cell().bold(true),
+                "Expires At".cell().bold(true),
+            ])
+            .bold(true);
+
+        let table_display = table.display().map_err(|e| format!("{}", e))?;
+
+        println!("API Tokens:");
+        println!("{}", table_display);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::{BTreeMap, HashMap};
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use common::config::ConfigHandler;
+
+// configs kept in memory, shared by the service tests
+pub struct MemConfigHandler {
+    configs: Mutex<BTreeMap<String, String>>,
+}
+
+impl MemConfigHandler {
+    pub fn new() -> Self {
+        MemConfigHandler { configs: Mutex::new(BTreeMap::new()) }
+    }
+}
+
+#[async_trait]
+impl ConfigHandler for MemConfigHandler {
+    async fn get_configs(&self) -> BTreeMap<String, String> {
+        self.configs.lock().await.clone()
+    }
+
+    async fn set_configs(&self, values: HashMap<String, String>) -> Result<(), String> {
+        self.configs.lock().await.extend(values);
+        Ok(())
+    }
+}
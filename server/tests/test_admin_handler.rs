@@ -0,0 +1,56 @@
+#[cfg(all(test, unix))]
+mod support;
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Arc;
+    use http::{HeaderMap, Response};
+    use common::convert::response_to_bytes;
+    use common::data::dto::admin_command::{AdminCommand, AdminResponse};
+    use common::data::dto::cache_config::CacheConfig;
+    use crate::support::MemConfigHandler;
+    use server::data::repository::cache_repo::CacheRepoProcMemImpl;
+    use server::handler::admin_handler::{bind_admin_socket, register_admin_handler, send_admin_command};
+    use server::service::cache_service::CacheService;
+
+    #[tokio::test]
+    async fn test_admin_channel() {
+        let dir = std::env::temp_dir().join(format!("trabas_admin_{}", std::process::id()));
+        let path = dir.join("admin.sock");
+        let config_handler = Arc::new(MemConfigHandler::new());
+        let cache_service = CacheService::new(Arc::new(CacheRepoProcMemImpl::new()), config_handler, String::from("SV_CACHE_CONFIGS"));
+        let response = response_to_bytes(&Response::builder().status(200).body(b"pong".to_vec()).unwrap());
+        for uri in ["/ping", "/products/1"] {
+            let config = CacheConfig::new("client1".into(), "GET".into(), "*".into(), 60);
+            cache_service.set_cache("client1".into(), uri.into(), "GET".into(), Vec::new(), &HeaderMap::new(), response.clone(), config).await.unwrap();
+        }
+
+        // a stale socket file is replaced
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "").unwrap();
+        let listener = bind_admin_socket(&path).await.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        tokio::spawn(register_admin_handler(listener, cache_service.clone()));
+        // while a running server keeps its channel
+        assert!(bind_admin_socket(&path).await.is_err());
+
+        match send_admin_command(&path, &AdminCommand::CacheStats).await.unwrap() {
+            AdminResponse::CacheStats { stats } => assert_eq!(stats.entries, 2),
+            response => panic!("unexpected response {:?}", response),
+        }
+
+        let command = AdminCommand::PurgeCache { client_id: "client1".into(), path: Some("/products/**".into()), method: None };
+        assert_eq!(send_admin_command(&path, &command).await.unwrap(), AdminResponse::Purged { count: 1 });
+        let command = AdminCommand::PurgeCache { client_id: "client1".into(), path: Some("~(".into()), method: None };
+        assert!(matches!(send_admin_command(&path, &command).await.unwrap(), AdminResponse::Error { .. }));
+
+        match send_admin_command(&path, &AdminCommand::CacheStats).await.unwrap() {
+            AdminResponse::CacheStats { stats } => assert_eq!(stats.entries, 1),
+            response => panic!("unexpected response {:?}", response),
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+    use common::data::dto::audit_event::{AuditEvent, AuditEventType, AuditOutcome, AUDIT_REDACTED};
+    use server::data::repository::audit_repo::{AuditRepo, AuditRepoFileImpl};
+    use server::service::audit_service::AuditService;
+    use std::sync::Arc;
+
+    fn prepare_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("trabas_audit_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn read_events(path: &PathBuf) -> Vec<AuditEvent> {
+        fs::read_to_string(path).unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_audit_service_redacts_secrets() {
+        let dir = prepare_dir("redact");
+        let path = dir.join("audit.log");
+        let audit_service = AuditService::new(Some(Arc::new(AuditRepoFileImpl::new(path.clone(), 0, 0))));
+        assert!(audit_service.is_enabled());
+
+        let event = AuditEvent::new(AuditEventType::SignatureMismatch, AuditOutcome::Failure, "tunnel1".to_string())
+            .with_client_id(Some("client1".to_string()))
+            .with_peer_addr(Some("127.0.0.1:5000".to_string()))
+            .with_version(Some("1".to_string()))
+            .with_secret("s3cr3t_signature".to_string())
+            .with_reason("bad signature: s3cr3t_signature".to_string());
+        audit_service.record(event).await;
+
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("s3cr3t_signature"));
+        assert!(raw.contains(r#""event":"signature_mismatch""#));
+        assert!(raw.contains(r#""outcome":"failure""#));
+
+        let events = read_events(&path);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client_id, Some("client1".to_string()));
+        assert_eq!(events[0].reason, Some(format!("bad signature: {}", AUDIT_REDACTED)));
+
+        // nothing is written while disabled
+        AuditService::new(None).record(AuditEvent::new(AuditEventType::Disconnect, AuditOutcome::Success, "tunnel2".to_string())).await;
+        assert_eq!(read_events(&path).len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_audit_repo_rotation() {
+        let dir = prepare_dir("rotation");
+        let path = dir.join("audit.log");
+        let event = AuditEvent::new(AuditEventType::Registration, AuditOutcome::Success, "tunnel".to_string())
+            .with_client_id(Some("client".to_string()));
+        let line_len = serde_json::to_string(&event).unwrap().len() as u64 + 1;
+        // two events per file, two rotated files
+        let audit_repo = AuditRepoFileImpl::new(path.clone(), line_len * 2, 2);
+
+        for _ in 0..7 {
+            audit_repo.append(event.clone()).await.unwrap();
+        }
+
+        let rotated_path = |index: usize| PathBuf::from(format!("{}.{}", path.display(), index));
+        assert_eq!(read_events(&path).len(), 1);
+        assert_eq!(read_events(&rotated_path(1)).len(), 2);
+        assert_eq!(read_events(&rotated_path(2)).len(), 2);
+        // the oldest one is removed
+        assert!(!rotated_path(3).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+    use common::data::dto::cache::Cache;
+    use server::data::repository::cache_repo::{CacheRepo, CacheRepoProcMemImpl};
+
+    fn new_cache(ttl: Duration, size: usize) -> Cache {
+        Cache::new(SystemTime::now() + ttl, vec![0; size])
+    }
+
+    #[tokio::test]
+    async fn test_cache_repo_evicts_least_recently_used_entries() {
+        let cache_repo = CacheRepoProcMemImpl::new().with_limits(2, 0);
+        let ttl = Duration::from_secs(60);
+        cache_repo.set("a".into(), new_cache(ttl, 1)).await.unwrap();
+        cache_repo.set("b".into(), new_cache(ttl, 1)).await.unwrap();
+        // reading `a` makes `b` the least recently used one
+        assert!(cache_repo.get("a".into()).await.is_ok());
+        cache_repo.set("c".into(), new_cache(ttl, 1)).await.unwrap();
+
+        assert!(cache_repo.get("a".into()).await.is_ok());
+        assert!(cache_repo.get("b".into()).await.is_err());
+        assert!(cache_repo.get("c".into()).await.is_ok());
+
+        // replacing an entry does not evict the others
+        cache_repo.set("c".into(), new_cache(ttl, 2)).await.unwrap();
+        assert!(cache_repo.get("a".into()).await.is_ok());
+        assert_eq!(cache_repo.get("c".into()).await.unwrap().data.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_repo_bounded_by_size() {
+        // the size of an entry includes its key
+        let cache_repo = CacheRepoProcMemImpl::new().with_limits(0, 300);
+        let ttl = Duration::from_secs(60);
+        cache_repo.set("a".into(), new_cache(ttl, 99)).await.unwrap();
+        cache_repo.set("b".into(), new_cache(ttl, 99)).await.unwrap();
+        cache_repo.set("c".into(), new_cache(ttl, 99)).await.unwrap();
+        cache_repo.set("d".into(), new_cache(ttl, 149)).await.unwrap();
+
+        assert!(cache_repo.get("a".into()).await.is_err());
+        assert!(cache_repo.get("b".into()).await.is_err());
+        assert!(cache_repo.get("c".into()).await.is_ok());
+        assert!(cache_repo.get("d".into()).await.is_ok());
+
+        // too large to be cached at all, the existing entries are kept
+        assert!(cache_repo.set("e".into(), new_cache(ttl, 300)).await.is_err());
+        assert!(cache_repo.get("c".into()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cache_repo_sweeps_expired_entries() {
+        let cache_repo = Arc::new(CacheRepoProcMemImpl::new());
+        cache_repo.set("short".into(), new_cache(Duration::from_millis(100), 1)).await.unwrap();
+        cache_repo.set("long".into(), new_cache(Duration::from_secs(60), 1)).await.unwrap();
+        assert_eq!(cache_repo.sweep().await, 0);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(cache_repo.get("short".into()).await.is_err());
+        assert!(cache_repo.get("long".into()).await.is_ok());
+
+        cache_repo.set("short".into(), new_cache(Duration::from_millis(100), 1)).await.unwrap();
+        cache_repo.spawn_sweeper(Duration::from_millis(50));
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        // removed in the background
+        assert_eq!(cache_repo.sweep().await, 0);
+        assert!(cache_repo.get("long".into()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cache_repo_lists_entries_without_data() {
+        let cache_repo = CacheRepoProcMemImpl::new();
+        let cache = new_cache(Duration::from_secs(60), 42)
+            .with_request("client-a".into(), "GET".into(), "/a".into());
+        cache_repo.set("a".into(), cache).await.unwrap();
+        cache_repo.set("expired".into(), new_cache(Duration::ZERO, 1)).await.unwrap();
+
+        let entries = cache_repo.list().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        let (key, cache, size) = &entries[0];
+        assert_eq!(key, "a");
+        assert_eq!(cache.client_id, "client-a");
+        assert_eq!(cache.path, "/a");
+        assert!(cache.data.is_empty());
+        assert_eq!(*size, 42);
+
+        // the entry itself keeps its data
+        assert_eq!(cache_repo.get("a".into()).await.unwrap().data.len(), 42);
+    }
+}
@@ -0,0 +1,305 @@
+#[cfg(test)]
+mod support;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use http::{HeaderMap, HeaderValue, Response};
+    use common::convert::{parse_response_bytes, response_to_bytes};
+    use common::data::dto::cache_config::{CacheConfig, CacheKey, CacheMode};
+    use crate::support::MemConfigHandler;
+    use server::data::repository::cache_repo::CacheRepoProcMemImpl;
+    use server::service::cache_service::{get_http_cache_policy, normalize_uri, CacheService};
+
+    fn new_service() -> CacheService {
+        let config_handler = Arc::new(MemConfigHandler::new());
+        CacheService::new(Arc::new(CacheRepoProcMemImpl::new()), config_handler, String::from("SV_CACHE_CONFIGS"))
+    }
+
+    fn new_response(status: u16, headers: Vec<(&str, &str)>, body: &str) -> Response<Vec<u8>> {
+        let mut builder = Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(name, value);
+        }
+        builder.body(body.as_bytes().to_vec()).unwrap()
+    }
+
+    fn new_headers(headers: Vec<(&'static str, &'static str)>) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    fn http_config() -> CacheConfig {
+        CacheConfig::new("client1".into(), "GET".into(), "/ping".into(), 60).with_mode(CacheMode::Http)
+    }
+
+    #[test]
+    fn test_http_cache_policy() {
+        let default_ttl = Duration::from_secs(60);
+        let policy = |response: Response<Vec<u8>>| get_http_cache_policy(&response, default_ttl);
+
+        assert_eq!(policy(new_response(200, vec![], "")), Some((default_ttl, vec![])));
+        assert_eq!(policy(new_response(200, vec![("Cache-Control", "public, max-age=10")], "")), Some((Duration::from_secs(10), vec![])));
+        // s-maxage is preferred and the age is deducted
+        assert_eq!(policy(new_response(200, vec![("Cache-Control", "max-age=10, s-maxage=30"), ("Age", "5")], "")), Some((Duration::from_secs(25), vec![])));
+        assert_eq!(policy(new_response(200, vec![
+            ("Date", "Tue, 15 Nov 1994 08:12:31 GMT"),
+            ("Expires", "Tue, 15 Nov 1994 08:13:01 GMT"),
+        ], "")), Some((Duration::from_secs(30), vec![])));
+        assert_eq!(policy(new_response(200, vec![("Vary", "Accept-Encoding, accept")], "")), Some((default_ttl, vec!["accept".to_string(), "accept-encoding".to_string()])));
+
+        // not cacheable
+        assert_eq!(policy(new_response(200, vec![("Cache-Control", "no-store")], "")), None);
+        assert_eq!(policy(new_response(200, vec![("Cache-Control", "private, max-age=10")], "")), None);
+        assert_eq!(policy(new_response(200, vec![("Cache-Control", "max-age=0")], "")), None);
+        assert_eq!(policy(new_response(200, vec![("Expires", "0")], "")), None);
+        assert_eq!(policy(new_response(200, vec![("Vary", "*")], "")), None);
+        assert_eq!(policy(new_response(500, vec![], "")), None);
+        assert_eq!(policy(new_response(302, vec![], "")), None);
+    }
+
+    #[tokio::test]
+    async fn test_http_cache_mode() {
+        let service = new_service();
+        let get = |headers: HeaderMap| {
+            let service = service.clone();
+            async move { service.get_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &headers, &http_config()).await }
+        };
+
+        // non cacheable response is not stored
+        let response = response_to_bytes(&new_response(200, vec![("Cache-Control", "no-store")], "pong"));
+        service.set_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &HeaderMap::new(), response, http_config()).await.unwrap();
+        assert!(get(HeaderMap::new()).await.is_err());
+
+        // the response is stored per the header values it varies on
+        let response = response_to_bytes(&new_response(200, vec![
+            ("Cache-Control", "max-age=60"),
+            ("Vary", "Accept-Language"),
+            ("ETag", "\"v1\""),
+        ], "pong"));
+        service.set_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &new_headers(vec![("Accept-Language", "en")]), response.clone(), http_config()).await.unwrap();
+        assert_eq!(get(new_headers(vec![("Accept-Language", "en")])).await.unwrap(), response);
+        assert!(get(new_headers(vec![("Accept-Language", "id")])).await.is_err());
+        // the request asks for a fresh response
+        assert!(get(new_headers(vec![("Accept-Language", "en"), ("Cache-Control", "no-cache")])).await.is_err());
+
+        // conditional requests
+        let not_modified = parse_response_bytes(&get(new_headers(vec![("Accept-Language", "en"), ("If-None-Match", "W/\"v1\"")])).await.unwrap()).unwrap();
+        assert_eq!(not_modified.status().as_u16(), 304);
+        assert_eq!(not_modified.headers().get("etag").unwrap(), "\"v1\"");
+        assert!(not_modified.body().is_empty());
+        assert_eq!(get(new_headers(vec![("Accept-Language", "en"), ("If-None-Match", "\"v2\"")])).await.unwrap(), response);
+    }
+
+    #[tokio::test]
+    async fn test_fixed_cache_mode_with_validators() {
+        let service = new_service();
+        let config = CacheConfig::new("client1".into(), "GET".into(), "/ping".into(), 60);
+        // fixed mode ignores the response directives
+        let response = response_to_bytes(&new_response(200, vec![
+            ("Cache-Control", "no-store"),
+            ("Last-Modified", "Tue, 15 Nov 1994 08:12:31 GMT"),
+        ], "pong"));
+        service.set_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &HeaderMap::new(), response.clone(), config.clone()).await.unwrap();
+
+        let cached = service.get_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &HeaderMap::new(), &config).await.unwrap();
+        assert_eq!(cached, response);
+
+        let headers = new_headers(vec![("If-Modified-Since", "Tue, 15 Nov 1994 08:12:31 GMT")]);
+        let cached = service.get_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &headers, &config).await.unwrap();
+        assert_eq!(parse_response_bytes(&cached).unwrap().status().as_u16(), 304);
+
+        let headers = new_headers(vec![("If-Modified-Since", "Mon, 14 Nov 1994 08:12:31 GMT")]);
+        let cached = service.get_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &headers, &config).await.unwrap();
+        assert_eq!(cached, response);
+
+        // the response is stored regardless of its header count
+        let mut many_headers: Vec<(String, String)> = (0..100).map(|i| (format!("X-Header-{}", i), String::from("value"))).collect();
+        many_headers.push((String::from("ETag"), String::from("\"v1\"")));
+        let mut builder = Response::builder().status(200);
+        for (name, value) in many_headers.iter() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let response = response_to_bytes(&builder.body(b"pong".to_vec()).unwrap());
+        service.set_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &HeaderMap::new(), response.clone(), config.clone()).await.unwrap();
+        let cached = service.get_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &HeaderMap::new(), &config).await.unwrap();
+        assert_eq!(cached, response);
+        let headers = new_headers(vec![("If-None-Match", "\"v1\"")]);
+        let cached = service.get_cache("client1".into(), "/ping".into(), "GET".into(), Vec::new(), &headers, &config).await.unwrap();
+        assert!(cached.starts_with(b"HTTP/1.1 304"), "{}", String::from_utf8_lossy(&cached));
+
+        // only a GET or HEAD is answered with a 304, the other methods get the cached response
+        let config = CacheConfig::new("client1".into(), "POST".into(), "/ping".into(), 60);
+        service.set_cache("client1".into(), "/ping".into(), "POST".into(), Vec::new(), &HeaderMap::new(), response.clone(), config.clone()).await.unwrap();
+        let headers = new_headers(vec![("If-None-Match", "\"v1\"")]);
+        let cached = service.get_cache("client1".into(), "/ping".into(), "POST".into(), Vec::new(), &headers, &config).await.unwrap();
+        assert_eq!(cached, response);
+        let headers = new_headers(vec![("If-None-Match", "*")]);
+        let cached = service.get_cache("client1".into(), "/ping".into(), "POST".into(), Vec::new(), &headers, &config).await.unwrap();
+        assert_eq!(cached, response);
+    }
+
+    #[test]
+    fn test_normalize_uri() {
+        let key = CacheKey::default();
+        assert_eq!(normalize_uri("/ping?b=2&a=1", &key), "/ping?b=2&a=1");
+
+        let key = CacheKey::new(vec![], vec![], vec!["utm_source,utm_medium".into()]).unwrap().with_sort_query(true);
+        assert_eq!(normalize_uri("/ping?utm_source=x&b=2&utm_medium&a=1", &key), "/ping?a=1&b=2");
+        assert_eq!(normalize_uri("/ping?utm_source=x", &key), "/ping");
+        assert_eq!(normalize_uri("/ping", &key), "/ping");
+
+        let key = CacheKey::new(vec![], vec![], vec!["*".into()]).unwrap();
+        assert_eq!(normalize_uri("/ping?b=2&a=1", &key), "/ping");
+    }
+
+    #[tokio::test]
+    async fn test_cache_key_composition() {
+        let service = new_service();
+        let key = CacheKey::new(vec!["Accept-Language".into()], vec!["session".into()], vec!["t".into()]).unwrap()
+            .with_body(false);
+        let config = CacheConfig::new("client1".into(), "POST".into(), "/ping".into(), 60).with_key(key);
+        let response = response_to_bytes(&new_response(200, vec![], "pong"));
+        let set = |uri: &'static str, body: &'static str, headers: HeaderMap| {
+            let (service, config, response) = (service.clone(), config.clone(), response.clone());
+            async move { service.set_cache("client1".into(), uri.into(), "POST".into(), body.as_bytes().to_vec(), &headers, response, config).await }
+        };
+        let get = |uri: &'static str, body: &'static str, headers: HeaderMap| {
+            let (service, config) = (service.clone(), config.clone());
+            async move { service.get_cache("client1".into(), uri.into(), "POST".into(), body.as_bytes().to_vec(), &headers, &config).await }
+        };
+
+        set("/ping?t=1", "a", new_headers(vec![("Accept-Language", "en"), ("Cookie", "session=s1; theme=dark")])).await.unwrap();
+        // ignored query param, body and cookie
+        assert!(get("/ping?t=2", "b", new_headers(vec![("Accept-Language", "en"), ("Cookie", "theme=light; session=s1")])).await.is_ok());
+        // header and cookie of the key
+        assert!(get("/ping", "a", new_headers(vec![("Accept-Language", "id"), ("Cookie", "session=s1")])).await.is_err());
+        assert!(get("/ping", "a", new_headers(vec![("Accept-Language", "en"), ("Cookie", "session=s2")])).await.is_err());
+
+        // requests with authorization are not cached by default
+        let authorized_headers = || new_headers(vec![("Accept-Language", "en"), ("Cookie", "session=s1"), ("Authorization", "Bearer user1")]);
+        assert!(get("/ping", "a", authorized_headers()).await.is_err());
+        set("/auth", "", authorized_headers()).await.unwrap();
+        assert!(get("/auth", "", new_headers(vec![("Accept-Language", "en"), ("Cookie", "session=s1")])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_key_with_authorization() {
+        let service = new_service();
+        let config = CacheConfig::new("client1".into(), "GET".into(), "/me".into(), 60)
+            .with_key(CacheKey::default().with_authorized(true));
+        let response = response_to_bytes(&new_response(200, vec![], "user1"));
+        let headers = new_headers(vec![("Authorization", "Bearer user1")]);
+        service.set_cache("client1".into(), "/me".into(), "GET".into(), Vec::new(), &headers, response.clone(), config.clone()).await.unwrap();
+
+        assert_eq!(service.get_cache("client1".into(), "/me".into(), "GET".into(), Vec::new(), &headers, &config).await.unwrap(), response);
+        // the credentials are part of the key
+        let headers = new_headers(vec![("Authorization", "Bearer user2")]);
+        assert!(service.get_cache("client1".into(), "/me".into(), "GET".into(), Vec::new(), &headers, &config).await.is_err());
+        assert!(service.get_cache("client1".into(), "/me".into(), "GET".into(), Vec::new(), &HeaderMap::new(), &config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_config_precedence() {
+        let service = new_service();
+        let rules = [
+            ("*", "*", "/**", 10),
+            ("*", "GET", "/products/*", 20),
+            ("client1", "*", "~/products/[0-9]+", 30),
+            ("client1", "GET", "/products/*", 40),
+            ("client1", "GET", "/products/featured", 50),
+            ("client1", "*", "/products/*/reviews", 60),
+        ];
+        for (client_id, method, path, exp_duration) in rules {
+            service.set_cache_config(CacheConfig::new(client_id.into(), method.into(), path.into(), exp_duration)).await.unwrap();
+        }
+        let get = |client_id: &'static str, method: &'static str, path: &'static str| {
+            let service = service.clone();
+            async move { service.get_cache_config(client_id.into(), method.into(), path.into()).await.map(|config| config.exp_duration) }
+        };
+
+        assert_eq!(get("client1", "GET", "/products/featured").await, Ok(50));
+        // glob over regex
+        assert_eq!(get("client1", "GET", "/products/123").await, Ok(40));
+        assert_eq!(get("client1", "POST", "/products/123").await, Ok(30));
+        // the longer literal part first
+        assert_eq!(get("client1", "GET", "/products/123/reviews").await, Ok(60));
+        // `*` does not cross `/` and the regex matches the whole path
+        assert_eq!(get("client1", "POST", "/products/123/stock").await, Ok(10));
+        assert_eq!(get("client2", "get", "/products/123").await, Ok(20));
+        assert_eq!(get("client2", "POST", "/products/123").await, Ok(10));
+
+        // invalid patterns are rejected
+        assert!(service.set_cache_config(CacheConfig::new("client1".into(), "GET".into(), "~/products/(".into(), 10)).await.is_err());
+        assert!(service.set_cache_config(CacheConfig::new("client1".into(), "GET".into(), "/products/[".into(), 10)).await.is_err());
+
+        // the compiled patterns follow the config changes
+        service.remove_cache_config("client1".into(), "GET".into(), "/products/*".into()).await.unwrap();
+        assert_eq!(get("client1", "GET", "/products/123").await, Ok(30));
+        assert_eq!(get("client1", "GET", "/products/featured").await, Ok(50));
+    }
+
+    #[tokio::test]
+    async fn test_purge_cache() {
+        let service = new_service();
+        let response = response_to_bytes(&new_response(200, vec![], "pong"));
+        let entries = [
+            ("client1", "GET", "/products/1?page=1"),
+            ("client1", "GET", "/products/2"),
+            ("client1", "POST", "/products/2"),
+            ("client1", "GET", "/orders/1"),
+            ("client2", "GET", "/products/1"),
+        ];
+        for (client_id, method, uri) in entries {
+            let config = CacheConfig::new(client_id.into(), method.into(), "*".into(), 60);
+            service.set_cache(client_id.into(), uri.into(), method.into(), Vec::new(), &HeaderMap::new(), response.clone(), config).await.unwrap();
+        }
+        let cached = |client_id: &'static str, method: &'static str, uri: &'static str| {
+            let service = service.clone();
+            let config = CacheConfig::new(client_id.into(), method.into(), "*".into(), 60);
+            async move { service.get_cache(client_id.into(), uri.into(), method.into(), Vec::new(), &HeaderMap::new(), &config).await.is_ok() }
+        };
+
+        // the query string is not part of the path
+        assert_eq!(service.purge_cache("client1".into(), Some("/products/*".into()), Some("GET".into())).await.unwrap(), 2);
+        assert!(!cached("client1", "GET", "/products/1?page=1").await);
+        assert!(!cached("client1", "GET", "/products/2").await);
+        assert!(cached("client1", "POST", "/products/2").await);
+        assert!(cached("client2", "GET", "/products/1").await);
+
+        assert_eq!(service.purge_cache("client1".into(), Some("~/orders/[0-9]+".into()), None).await.unwrap(), 1);
+        assert_eq!(service.purge_cache("client1".into(), None, None).await.unwrap(), 1);
+        assert!(cached("client2", "GET", "/products/1").await);
+        assert_eq!(service.purge_cache("*".into(), None, None).await.unwrap(), 1);
+        assert_eq!(service.get_cache_stats().await.unwrap().entries, 0);
+
+        assert!(service.purge_cache("client1".into(), Some("~(".into()), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_stats() {
+        let service = new_service();
+        let config = CacheConfig::new("*".into(), "GET".into(), "*".into(), 60);
+        for (client_id, body) in [("client1", "a"), ("client1", "bb"), ("client2", "ccc")] {
+            let response = response_to_bytes(&new_response(200, vec![], body));
+            service.set_cache(client_id.into(), format!("/{}", body), "GET".into(), Vec::new(), &HeaderMap::new(), response, config.clone()).await.unwrap();
+        }
+        // expired but not removed yet
+        let short = CacheConfig::new("client2".into(), "GET".into(), "*".into(), 60).with_mode(CacheMode::Http);
+        let response = response_to_bytes(&new_response(200, vec![("Cache-Control", "max-age=1")], "d"));
+        service.set_cache("client2".into(), "/d".into(), "GET".into(), Vec::new(), &HeaderMap::new(), response, short).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        let stats = service.get_cache_stats().await.unwrap();
+        assert_eq!(stats.entries, 4);
+        assert_eq!(stats.expired, 1);
+        let clients: Vec<(String, usize)> = stats.clients.iter().map(|client| (client.client_id.clone(), client.entries)).collect();
+        assert_eq!(clients, vec![("client1".to_string(), 2), ("client2".to_string(), 1)]);
+        assert_eq!(stats.size, stats.clients.iter().map(|client| client.size).sum::<usize>());
+        assert!(stats.clients[0].size > stats.clients[1].size);
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use openssl::{
+        asn1::Asn1Time,
+        hash::MessageDigest,
+        nid::Nid,
+        pkey::{PKey, Private},
+        rsa::Rsa,
+        x509::{X509, X509NameBuilder, extension::BasicConstraints},
+    };
+    use server::config::{client_id_from_certificate, issue_client_certificate};
+
+    fn generate_ca() -> (X509, PKey<Private>) {
+        let pkey = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "Test CA").unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&pkey).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.append_extension(BasicConstraints::new().critical().ca().build().unwrap()).unwrap();
+        builder.sign(&pkey, MessageDigest::sha256()).unwrap();
+
+        (builder.build(), pkey)
+    }
+
+    #[test]
+    fn test_issue_client_certificate() {
+        let (ca_cert, ca_pkey) = generate_ca();
+        let (cert, pkey) = issue_client_certificate(&ca_cert, &ca_pkey, "client1", 30).unwrap();
+
+        // signed by the CA and bound to the generated key
+        assert!(cert.verify(&ca_pkey).unwrap());
+        assert!(cert.public_key().unwrap().public_eq(&pkey));
+        assert_eq!(
+            cert.issuer_name().entries_by_nid(Nid::COMMONNAME).next().unwrap().data().as_slice(),
+            b"Test CA"
+        );
+        assert_eq!(client_id_from_certificate(&cert), Some(String::from("client1")));
+    }
+
+    #[test]
+    fn test_client_id_from_certificate_without_cn() {
+        let pkey = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::ORGANIZATIONNAME, "Organization").unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&pkey).unwrap();
+        builder.sign(&pkey, MessageDigest::sha256()).unwrap();
+
+        assert_eq!(client_id_from_certificate(&builder.build()), None);
+    }
+}
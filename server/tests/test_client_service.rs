@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use server::data::repository::client_repo::ClientRepoProcMemImpl;
+    use server::service::client_service::ClientService;
+    use common::data::dto::tunnel_client::TunnelClient;
+
+    #[tokio::test]
+    async fn test_register_nonce_rejects_replay() {
+        let client_service = ClientService::new(Arc::new(ClientRepoProcMemImpl::new()));
+        let ttl = Duration::from_secs(600);
+
+        client_service.register_nonce("client_1".into(), "nonce_1".into(), ttl).await.unwrap();
+        assert!(client_service.register_nonce("client_1".into(), "nonce_1".into(), ttl).await.is_err());
+
+        // the nonce is scoped to the client
+        client_service.register_nonce("client_2".into(), "nonce_1".into(), ttl).await.unwrap();
+        client_service.register_nonce("client_1".into(), "nonce_2".into(), ttl).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_register_nonce_expired() {
+        let client_service = ClientService::new(Arc::new(ClientRepoProcMemImpl::new()));
+        let ttl = Duration::from_millis(50);
+
+        client_service.register_nonce("client_1".into(), "nonce_1".into(), ttl).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client_service.register_nonce("client_1".into(), "nonce_1".into(), ttl).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_register_nonce_max_nonces() {
+        let client_service = ClientService::new(Arc::new(ClientRepoProcMemImpl::new().with_max_nonces(2)));
+
+        client_service.register_nonce("client_1".into(), "nonce_1".into(), Duration::from_millis(50)).await.unwrap();
+        client_service.register_nonce("client_1".into(), "nonce_2".into(), Duration::from_secs(600)).await.unwrap();
+        // the live nonces are kept, the new one is rejected
+        assert!(client_service.register_nonce("client_1".into(), "nonce_3".into(), Duration::from_secs(600)).await.is_err());
+        assert!(client_service.register_nonce("client_1".into(), "nonce_2".into(), Duration::from_secs(600)).await.is_err());
+
+        // room is made once a nonce expires
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client_service.register_nonce("client_1".into(), "nonce_3".into(), Duration::from_secs(600)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_register_client_max_tunnels() {
+        let client_service = ClientService::new(Arc::new(ClientRepoProcMemImpl::new()));
+        let new_client = || TunnelClient::new("client_1".into(), "secret".into(), "1.0.0".into(), "1.0.0".into());
+
+        // the concurrent handshakes cannot exceed the cap
+        let mut handles = Vec::new();
+        for i in 0..10 {
+            let client_service = client_service.clone();
+            let client = new_client();
+            handles.push(tokio::spawn(async move {
+                client_service.register_client(client, format!("tunnel_{}", i), 3).await
+            }));
+        }
+        let mut registered = 0;
+        for handle in handles {
+            if handle.await.unwrap().is_ok() {
+                registered += 1;
+            }
+        }
+        assert_eq!(registered, 3);
+        assert_eq!(client_service.get_tunnel_count("client_1".into()).await, 3);
+
+        // a tunnel is allowed again once one is disconnected
+        let client_service = ClientService::new(Arc::new(ClientRepoProcMemImpl::new()));
+        client_service.register_client(new_client(), "tunnel_a".into(), 2).await.unwrap();
+        client_service.register_client(new_client(), "tunnel_b".into(), 2).await.unwrap();
+        assert!(client_service.register_client(new_client(), "tunnel_c".into(), 2).await.is_err());
+        client_service.disconnect_client("client_1".into(), "tunnel_a".into()).await.unwrap();
+        client_service.register_client(new_client(), "tunnel_c".into(), 2).await.unwrap();
+
+        // 0 means unlimited
+        for i in 0..5 {
+            client_service.register_client(new_client(), format!("unlimited_{}", i), 0).await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use server::service::connection_limit_service::ConnectionLimitService;
+
+    #[test]
+    fn test_acquire_global() {
+        let connection_limit_service = ConnectionLimitService::new(2, 0);
+
+        let permit1 = connection_limit_service.acquire_global().unwrap();
+        let permit2 = connection_limit_service.acquire_global().unwrap();
+        assert!(permit1.is_some() && permit2.is_some());
+        assert!(connection_limit_service.acquire_global().is_err());
+
+        // the permit is released once the connection is done
+        drop(permit1);
+        assert!(connection_limit_service.acquire_global().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_client() {
+        let connection_limit_service = ConnectionLimitService::new(0, 1);
+
+        let permit = connection_limit_service.acquire_client("client_1".into()).await.unwrap();
+        assert!(permit.is_some());
+        assert!(connection_limit_service.acquire_client("client_1".into()).await.is_err());
+        // the caps are separated by client
+        assert!(connection_limit_service.acquire_client("client_2".into()).await.is_ok());
+
+        drop(permit);
+        assert!(connection_limit_service.acquire_client("client_1".into()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_unlimited() {
+        let connection_limit_service = ConnectionLimitService::new(0, 0);
+
+        assert!(connection_limit_service.acquire_global().unwrap().is_none());
+        assert!(connection_limit_service.acquire_client("client_1".into()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_client_keeps_outstanding_permits() {
+        let connection_limit_service = ConnectionLimitService::new(0, 1);
+
+        let permit = connection_limit_service.acquire_client("client_1".into()).await.unwrap();
+        // acquiring for other clients drops the idle entries only
+        for i in 0..10 {
+            let other = connection_limit_service.acquire_client(format!("client_{}", i + 2)).await.unwrap();
+            drop(other);
+        }
+        assert!(connection_limit_service.acquire_client("client_1".into()).await.is_err());
+
+        drop(permit);
+        assert!(connection_limit_service.acquire_client("client_1".into()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_client_concurrent() {
+        let connection_limit_service = ConnectionLimitService::new(0, 2);
+
+        // the concurrent connections of a client cannot exceed the cap,
+        // while the connections of other clients come and go
+        let mut handles = Vec::new();
+        for i in 0..50 {
+            let connection_limit_service = connection_limit_service.clone();
+            handles.push(tokio::spawn(async move {
+                let client_id = if i % 2 == 0 { String::from("client_1") } else { format!("client_{}", i) };
+                connection_limit_service.acquire_client(client_id.clone()).await.ok().map(|permit| (client_id, permit))
+            }));
+        }
+        let mut permits = Vec::new();
+        for handle in handles {
+            if let Some(permit) = handle.await.unwrap() {
+                permits.push(permit);
+            }
+        }
+        assert_eq!(permits.iter().filter(|(client_id, _)| client_id == "client_1").count(), 2);
+    }
+}
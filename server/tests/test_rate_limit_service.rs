@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use server::data::repository::rate_limit_repo::RateLimitRepoProcMemImpl;
+    use server::service::rate_limit_service::{rate_limit_headers, RateLimit, RateLimitService};
+
+    #[test]
+    fn test_rate_limit_new() {
+        assert_eq!(RateLimit::new(0.0, Some(10)), None);
+        assert_eq!(RateLimit::new(-1.0, None), None);
+        assert_eq!(RateLimit::new(2.5, None), Some(RateLimit { rate: 2.5, burst: 3 }));
+        assert_eq!(RateLimit::new(0.5, Some(0)), Some(RateLimit { rate: 0.5, burst: 1 }));
+        assert_eq!(RateLimit::new(1.0, Some(5)), Some(RateLimit { rate: 1.0, burst: 5 }));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_burst_then_reject() {
+        let rate_limit_service = RateLimitService::new(Arc::new(RateLimitRepoProcMemImpl::new()));
+        let limit = RateLimit::new(1.0, Some(2)).unwrap();
+
+        let state = rate_limit_service.acquire("client_1".into(), limit, Duration::ZERO).await;
+        assert!(state.allowed);
+        assert_eq!(state.remaining, 1);
+        let state = rate_limit_service.acquire("client_1".into(), limit, Duration::ZERO).await;
+        assert!(state.allowed);
+        assert_eq!(state.remaining, 0);
+
+        let state = rate_limit_service.acquire("client_1".into(), limit, Duration::ZERO).await;
+        assert!(!state.allowed);
+        assert!(state.retry_after > Duration::ZERO && state.retry_after <= Duration::from_secs(1));
+        let headers = rate_limit_headers(&limit, &state);
+        assert!(headers.contains(&("RateLimit-Limit".to_string(), "2".to_string())));
+        assert!(headers.contains(&("RateLimit-Remaining".to_string(), "0".to_string())));
+        assert!(headers.contains(&("Retry-After".to_string(), "1".to_string())));
+
+        // the buckets are separated by key
+        let state = rate_limit_service.acquire("client_2".into(), limit, Duration::ZERO).await;
+        assert!(state.allowed);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_capacity() {
+        let rate_limit_service = RateLimitService::new(Arc::new(RateLimitRepoProcMemImpl::new()));
+        let limit = RateLimit::new(10.0, Some(1)).unwrap();
+
+        assert!(rate_limit_service.acquire("client_1".into(), limit, Duration::ZERO).await.allowed);
+        // a token is refilled every 100 ms
+        assert!(!rate_limit_service.acquire("client_1".into(), limit, Duration::from_millis(20)).await.allowed);
+        assert!(rate_limit_service.acquire("client_1".into(), limit, Duration::from_millis(500)).await.allowed);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_full_buckets() {
+        let rate_limit_repo = Arc::new(RateLimitRepoProcMemImpl::new().with_sweep_interval(Duration::from_millis(100)));
+        let rate_limit_service = RateLimitService::new(rate_limit_repo.clone());
+        let fast = RateLimit::new(100.0, Some(1)).unwrap();
+        let slow = RateLimit::new(0.1, Some(1)).unwrap();
+
+        rate_limit_service.acquire("fast".into(), fast, Duration::ZERO).await;
+        rate_limit_service.acquire("slow".into(), slow, Duration::ZERO).await;
+        assert_eq!(rate_limit_repo.bucket_count().await, 2);
+
+        // the buckets are kept until the next sweep
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        // only the refilled bucket is dropped, by its own rate
+        rate_limit_service.acquire("other".into(), fast, Duration::ZERO).await;
+        assert_eq!(rate_limit_repo.bucket_count().await, 2);
+        assert!(!rate_limit_service.acquire("slow".into(), slow, Duration::ZERO).await.allowed);
+    }
+}
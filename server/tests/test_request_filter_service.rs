@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod support;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use http::{Request, StatusCode};
+    use common::data::dto::filter_rule::{FilterAction, FilterMatch, FilterRule};
+    use crate::support::MemConfigHandler;
+    use server::service::request_filter_service::RequestFilterService;
+
+    fn new_service() -> RequestFilterService {
+        let config_handler = Arc::new(MemConfigHandler::new());
+        RequestFilterService::new(config_handler, String::from("SV_FILTER_RULES"))
+    }
+
+    fn new_request(method: &str, uri: &str) -> Request<Vec<u8>> {
+        Request::builder().method(method).uri(uri).header("X-Debug", "on").body(Vec::new()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_filter_request_by_priority() {
+        let service = new_service();
+        service.set_filter_rule(FilterRule::new("*".into(), "block-git".into(), FilterAction::Deny { status: 404 })
+            .with_path(Some("/.git/**".into()))).await.unwrap();
+        service.set_filter_rule(FilterRule::new("client1".into(), "get-only".into(), FilterAction::Allow)
+            .with_priority(10)
+            .with_method(Some("get, head".into()))).await.unwrap();
+        service.set_filter_rule(FilterRule::new("client1".into(), "deny-others".into(), FilterAction::Deny { status: 405 })
+            .with_priority(20)).await.unwrap();
+
+        let mut request = new_request("POST", "/ping");
+        let (status, _) = service.filter_request("client1".into(), "/ping", &mut request).await.unwrap_err();
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+        let mut request = new_request("GET", "/ping");
+        assert!(service.filter_request("client1".into(), "/ping", &mut request).await.is_ok());
+
+        // the allow rule of the client is evaluated before the server-wide rule
+        let mut request = new_request("GET", "/.git/config");
+        assert!(service.filter_request("client1".into(), "/.git/config", &mut request).await.is_ok());
+        let (status, _) = service.filter_request("client2".into(), "/.git/config", &mut request).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        // `*` does not cross the path segments
+        service.set_filter_rule(FilterRule::new("*".into(), "block-git".into(), FilterAction::Deny { status: 404 })
+            .with_path(Some("/.git/*".into()))).await.unwrap();
+        let mut request = new_request("GET", "/.git/refs/heads");
+        assert!(service.filter_request("client2".into(), "/.git/refs/heads", &mut request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_filter_request_headers() {
+        let service = new_service();
+        service.set_filter_rule(FilterRule::new("*".into(), "strip-debug".into(), FilterAction::RemoveHeader { name: "X-Debug".into() })
+            .with_query(Some("debug".parse::<FilterMatch>().unwrap()))).await.unwrap();
+        service.set_filter_rule(FilterRule::new("*".into(), "tag".into(), FilterAction::SetHeader { name: "X-Edge".into(), value: "trabas".into() })
+            .with_header(Some("X-Debug=o*".parse::<FilterMatch>().unwrap()))).await.unwrap();
+
+        // the header is removed before the second rule is evaluated
+        let mut request = new_request("GET", "/ping?debug");
+        service.filter_request("client1".into(), "/ping", &mut request).await.unwrap();
+        assert!(request.headers().get("X-Debug").is_none());
+        assert!(request.headers().get("X-Edge").is_none());
+
+        let mut request = new_request("GET", "/ping?verbose=1");
+        service.filter_request("client1".into(), "/ping", &mut request).await.unwrap();
+        assert_eq!(request.headers().get("X-Debug").unwrap(), "on");
+        assert_eq!(request.headers().get("X-Edge").unwrap(), "trabas");
+    }
+
+    #[tokio::test]
+    async fn test_set_filter_rule_validation() {
+        let service = new_service();
+
+        assert!(service.set_filter_rule(FilterRule::new("*".into(), " ".into(), FilterAction::Allow)).await.is_err());
+        assert!(service.set_filter_rule(FilterRule::new("*".into(), "a".into(), FilterAction::Deny { status: 200 })).await.is_err());
+        assert!(service.set_filter_rule(FilterRule::new("*".into(), "a".into(), FilterAction::Allow)
+            .with_path(Some("/admin/[".into()))).await.is_err());
+        assert!(service.set_filter_rule(FilterRule::new("*".into(), "a".into(), FilterAction::SetHeader { name: "Bad Name".into(), value: "v".into() })).await.is_err());
+
+        // the methods are normalized
+        let rule = service.set_filter_rule(FilterRule::new("*".into(), "a".into(), FilterAction::Allow)
+            .with_method(Some("get, post".into()))).await.unwrap();
+        assert_eq!(rule.method, Some("GET,POST".into()));
+
+        service.remove_filter_rule("*".into(), "a".into()).await.unwrap();
+        assert!(service.remove_filter_rule("*".into(), "a".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_filter_request_normalized_path() {
+        let service = new_service();
+        service.set_filter_rule(FilterRule::new("*".into(), "block-git".into(), FilterAction::Deny { status: 404 })
+            .with_path(Some("/.git/**".into()))).await.unwrap();
+
+        // encoded and dot segments resolve to the denied path
+        for path in ["/%2egit/config", "/%2Egit/config", "/x/../.git/config", "/x/%2e%2e/.git/config", "/x/..%2f.git/config", "//.git/./config", "/x\\..\\.git/config"] {
+            let mut request = new_request("GET", "/ping");
+            let (status, _) = service.filter_request("client1".into(), path, &mut request).await.unwrap_err();
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", path);
+        }
+
+        for path in ["/git/config", "/x/.git/config", "/%2egitignore", "/.git%zz/config"] {
+            let mut request = new_request("GET", "/ping");
+            assert!(service.filter_request("client1".into(), path, &mut request).await.is_ok(), "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_request_reloads_rules() {
+        let service = new_service();
+        service.set_filter_rule(FilterRule::new("*".into(), "block-admin".into(), FilterAction::Deny { status: 403 })
+            .with_path(Some("/admin/*".into()))).await.unwrap();
+
+        let mut request = new_request("GET", "/admin/users");
+        assert!(service.filter_request("client1".into(), "/admin/users", &mut request).await.is_err());
+
+        // the compiled rules follow the config changes
+        service.remove_filter_rule("*".into(), "block-admin".into()).await.unwrap();
+        assert!(service.filter_request("client1".into(), "/admin/users", &mut request).await.is_ok());
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+    use common::data::dto::api_token::ApiToken;
+    use server::data::repository::token_repo::{TokenRepo, TokenRepoFileImpl};
+
+    fn prepare_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("trabas_tokens_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_token_repo_file_writes() {
+        let dir = prepare_dir("write");
+        let path = dir.join("tokens.json");
+        let token_repo = TokenRepoFileImpl::new(path.clone());
+
+        let token = ApiToken::new(vec!["client1".to_string()], None);
+        token_repo.create(token.clone()).await.unwrap();
+        assert_eq!(token_repo.get(token.id.clone()).await.unwrap().key, token.key);
+
+        // the signing keys are private, and nothing is left aside
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // an interrupted write does not get in the way
+        fs::write(dir.join("tokens.json.tmp"), "{").unwrap();
+        token_repo.remove(token.id.clone()).await.unwrap();
+        assert!(token_repo.get(token.id).await.is_err());
+        assert!(token_repo.get_all().await.unwrap().is_empty());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
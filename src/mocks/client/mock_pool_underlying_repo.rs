@@ -0,0 +1,43 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use client::data::repository::underlying_repo::{ForwardError, UnderlyingRepo};
+use common::net::http_string_response_as_bytes;
+use http::StatusCode;
+
+// mock several upstream instances, where some of them accept connections
+// but fail to process the forwarded requests
+pub struct MockPoolUnderlyingRepo {
+    mock_response: String,
+    down_hosts: HashSet<String>,
+    hits: Arc<Mutex<HashMap<String, usize>>>
+}
+
+impl MockPoolUnderlyingRepo {
+    pub fn new(mock_response: String, down_hosts: Vec<String>, hits: Arc<Mutex<HashMap<String, usize>>>) -> Self {
+        MockPoolUnderlyingRepo {
+            mock_response,
+            down_hosts: down_hosts.into_iter().collect(),
+            hits
+        }
+    }
+}
+
+#[async_trait]
+impl UnderlyingRepo for MockPoolUnderlyingRepo {
+    async fn forward(&self, _: Vec<u8>, host: String) -> Result<Vec<u8>, ForwardError> {
+        *self.hits.lock().unwrap().entry(host.clone()).or_insert(0) += 1;
+        if self.down_hosts.contains(&host) {
+            return Err(format!("Error connecting to underlying service: {} is down", host).into());
+        }
+
+        http_string_response_as_bytes(format!("{} from {}", self.mock_response, host), StatusCode::from_u16(200).unwrap())
+            .map_err(ForwardError::from)
+    }
+
+    async fn test_connection(&self, _: String) -> Result<(), String> {
+        // always return ok for mock
+        Ok(())
+    }
+}
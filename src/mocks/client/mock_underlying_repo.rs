@@ -1,7 +1,7 @@
 use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
-use client::data::repository::underlying_repo::UnderlyingRepo;
+use client::data::repository::underlying_repo::{ForwardError, UnderlyingRepo};
 use common::net::http_string_response_as_bytes;
 use http::StatusCode;
 
@@ -30,14 +30,14 @@ impl<F> UnderlyingRepo for MockUnderlyingRepo<F>
 where
     F: FnMut() -> () + Send + Sync + 'static
 {
-    async fn forward(&self, _: Vec<u8>, _: String) -> Result<Vec<u8>, String> {
+    async fn forward(&self, _: Vec<u8>, _: String) -> Result<Vec<u8>, ForwardError> {
         if let Ok(res) = http_string_response_as_bytes(self.mock_response.clone(), StatusCode::from_u16(200).unwrap()) {
             let mut callback = self.callback.lock().unwrap();
             callback();
             return Ok(res);
         }
 
-        Err(String::from("An error occured"))
+        Err(String::from("An error occured").into())
     }
 
     async fn test_connection(&self, _: String) -> Result<(), String> {
@@ -1 +1,2 @@
+pub mod mock_pool_underlying_repo;
 pub mod mock_underlying_repo;
\ No newline at end of file
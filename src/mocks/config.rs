@@ -24,10 +24,12 @@ impl ConfigHandler for MockConfigHandlerImpl {
         configs.clone()
     }
 
-    async fn set_configs(&self, values: HashMap<String, String>) {
+    async fn set_configs(&self, values: HashMap<String, String>) -> Result<(), String> {
         let mut configs = self.mock_configs.lock().await;
         for (key, value) in values {
             configs.insert(key, value);
         }
+
+        Ok(())
     }
 }
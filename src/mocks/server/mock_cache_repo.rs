@@ -32,4 +32,20 @@ impl CacheRepo for MockCacheRepo {
         
         Ok(())
     }
+
+    async fn list(&self) -> Result<Vec<(String, Cache, usize)>, String> {
+        Ok(self.mock_cache.lock().await.iter()
+            .map(|(key, value)| (key.clone(), Cache { data: Vec::new(), ..value.clone() }, value.data.len()))
+            .collect())
+    }
+
+    async fn delete(&self, key: String) -> Result<(), String> {
+        self.mock_cache.lock().await.remove(&key);
+
+        Ok(())
+    }
+
+    async fn count(&self) -> Result<usize, String> {
+        Ok(self.mock_cache.lock().await.len())
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::{HashMap, HashSet}, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use common::data::dto::tunnel_client::TunnelClient;
@@ -8,6 +8,7 @@ use tokio::sync::Mutex;
 pub struct MockClientRepo {
     mock_data: Arc<Mutex<HashMap<String, HashMap<String, TunnelClient>>>>,
     mock_alias_map: Arc<Mutex<HashMap<String, String>>>,
+    mock_nonces: Arc<Mutex<HashSet<String>>>,
 }
 
 impl MockClientRepo {
@@ -15,6 +16,7 @@ impl MockClientRepo {
         MockClientRepo {
             mock_data: Arc::new(Mutex::new(HashMap::new())),
             mock_alias_map: Arc::new(Mutex::new(HashMap::new())),
+            mock_nonces: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 }
@@ -62,11 +64,14 @@ impl ClientRepo for MockClientRepo {
         Err(String::from("Data not found"))
     }
 
-    async fn create(&self, client: TunnelClient, tunnel_id: String) -> Result<(), String> {
+    async fn create(&self, client: TunnelClient, tunnel_id: String, max_tunnels: i64) -> Result<bool, String> {
         let mut data = self.mock_data.lock().await;
         let entry = data.entry(client.id.clone()).or_insert_with(HashMap::new);
+        if max_tunnels > 0 && !entry.contains_key(&tunnel_id) && entry.len() as i64 >= max_tunnels {
+            return Ok(false);
+        }
         entry.insert(tunnel_id, client);
-        Ok(())
+        Ok(true)
     }
 
     async fn create_alias(&self, alias_id: String, client_id: String) -> Result<(), String> {
@@ -78,4 +83,8 @@ impl ClientRepo for MockClientRepo {
         self.mock_alias_map.lock().await.remove(&alias_id);
         Ok(())
     }
+
+    async fn add_nonce(&self, nonce: String, _: Duration) -> Result<bool, String> {
+        Ok(self.mock_nonces.lock().await.insert(nonce))
+    }
 }
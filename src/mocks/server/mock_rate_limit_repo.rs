@@ -0,0 +1,34 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use server::data::repository::rate_limit_repo::{RateLimitRepo, RateLimitState};
+use tokio::{sync::Mutex, time::Instant};
+
+pub struct MockRateLimitRepo {
+    mock_buckets: Arc<Mutex<HashMap<String, (f64, Instant)>>>,
+}
+
+impl MockRateLimitRepo {
+    pub fn new() -> Self {
+        MockRateLimitRepo {
+            mock_buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimitRepo for MockRateLimitRepo {
+    async fn take(&self, key: String, rate: f64, burst: u32) -> Result<RateLimitState, String> {
+        let mut buckets = self.mock_buckets.lock().await;
+        let now = Instant::now();
+        let (tokens, updated_at) = buckets.get(&key).cloned().unwrap_or((burst as f64, now));
+        let mut tokens = (tokens + now.duration_since(updated_at).as_secs_f64() * rate).min(burst as f64);
+        let allowed = tokens >= 1.0;
+        if allowed {
+            tokens -= 1.0;
+        }
+        buckets.insert(key, (tokens, now));
+
+        Ok(RateLimitState::new(allowed, tokens, rate, burst))
+    }
+}
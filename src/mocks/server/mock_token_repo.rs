@@ -0,0 +1,46 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use common::data::dto::api_token::ApiToken;
+use server::data::repository::token_repo::TokenRepo;
+use tokio::sync::Mutex;
+
+pub struct MockTokenRepo {
+    mock_tokens: Arc<Mutex<HashMap<String, ApiToken>>>,
+}
+
+impl MockTokenRepo {
+    pub fn new() -> Self {
+        MockTokenRepo {
+            mock_tokens: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenRepo for MockTokenRepo {
+    async fn get(&self, id: String) -> Result<ApiToken, String> {
+        if let Some(value) = self.mock_tokens.lock().await.get(&id) {
+            return Ok((*value).clone());
+        }
+
+        Err(String::from("Data not found"))
+    }
+
+    async fn get_all(&self) -> Result<Vec<ApiToken>, String> {
+        Ok(self.mock_tokens.lock().await.values().cloned().collect())
+    }
+
+    async fn create(&self, token: ApiToken) -> Result<(), String> {
+        self.mock_tokens.lock().await.insert(token.id.clone(), token);
+
+        Ok(())
+    }
+
+    async fn remove(&self, id: String) -> Result<(), String> {
+        match self.mock_tokens.lock().await.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(String::from("Data not found")),
+        }
+    }
+}
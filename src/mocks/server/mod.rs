@@ -1,4 +1,6 @@
 pub mod mock_cache_repo;
 pub mod mock_client_repo;
+pub mod mock_rate_limit_repo;
 pub mod mock_request_repo;
-pub mod mock_response_repo;
\ No newline at end of file
+pub mod mock_response_repo;
+pub mod mock_token_repo;
\ No newline at end of file
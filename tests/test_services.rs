@@ -9,7 +9,7 @@ mod tests {
     use env_logger::{Builder, Env, Target};
     use log::{Level, Metadata, Record, SetLoggerError};
     use reqwest::{
-        header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE},
+        header::{HeaderMap, HeaderValue, AUTHORIZATION, COOKIE, SET_COOKIE, WWW_AUTHENTICATE},
         Client, Response,
     };
     use tokio::{
@@ -19,22 +19,35 @@ mod tests {
     };
     use common::{
         _error, _info,
-        config::keys as config_keys,
-        data::dto::cache_config::CacheConfig,
+        config::{keys as config_keys, ConfigHandler},
+        convert::{from_json_string, to_json_string},
+        data::dto::{api_token::ApiToken, audit_event::{AuditEvent, AuditEventType, AuditOutcome}, cache_config::{CacheConfig, CacheMode}, filter_rule::{FilterAction, FilterRule}, ip_rule::{IpRule, IpRuleAction}, public_auth::PublicAuth, request_allowlist::RequestAllowlist},
+        security::generate_ed25519_keypair,
         version::set_root_version,
     };
     use trabas::mocks::{
-        client::mock_underlying_repo::MockUnderlyingRepo,
+        client::{mock_pool_underlying_repo::MockPoolUnderlyingRepo, mock_underlying_repo::MockUnderlyingRepo},
         config::MockConfigHandlerImpl,
         server::{
             mock_cache_repo::MockCacheRepo,
             mock_client_repo::MockClientRepo,
             mock_request_repo::MockRequestRepo,
             mock_response_repo::MockResponseRepo,
+            mock_rate_limit_repo::MockRateLimitRepo,
+            mock_token_repo::MockTokenRepo,
         },
     };
     use trabas::PROJECT_VERSION;
+    use server::data::repository::client_repo::ClientRepo;
+    use server::data::repository::token_repo::TokenRepo;
     use server::service::cache_service::CacheService;
+    use server::service::ip_filter_service::IpFilterService;
+    use server::service::request_filter_service::RequestFilterService;
+    use server::service::public_auth_service::PublicAuthService;
+    use server::service::secret_service::SecretService;
+    use client::handler::main_handler::validate_ack;
+    use common::data::dto::{tunnel_ack::TunnelAck, tunnel_client::TunnelClient};
+    use client::service::upstream_pool::LoadBalanceStrategy;
 
     async fn send_http_request(url: String, cookies: Option<HashMap<String, String>>) -> Result<Response, String> {
         send_http_request_with_timeout(url, cookies, Duration::from_secs(60)).await
@@ -101,6 +114,8 @@ mod tests {
         let client_repo = Arc::new(MockClientRepo::new());
         let request_repo = Arc::new(MockRequestRepo::new());
         let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
         let config_handler = Arc::new(MockConfigHandlerImpl::new());
         let server_exec = tokio::spawn(async move {
             server::run(
@@ -112,11 +127,14 @@ mod tests {
                     false, // no cache client id
                     false,
                     false
-                ),
+                )
+                .with_admin_socket_path(None),
                 cache_repo, 
                 client_repo, 
                 request_repo, 
                 response_repo,
+                token_repo,
+                rate_limit_repo,
                 config_handler).await;
         });
 
@@ -199,6 +217,8 @@ mod tests {
         let client_repo = Arc::new(MockClientRepo::new());
         let request_repo = Arc::new(MockRequestRepo::new());
         let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
         let config_handler = Arc::new(MockConfigHandlerImpl::new());
         let server_exec = tokio::spawn(async move {
             server::run(
@@ -210,11 +230,14 @@ mod tests {
                     false,
                     false,
                     false
-                ), 
+                )
+                .with_admin_socket_path(None), 
                 cache_repo, 
                 client_repo, 
                 request_repo, 
                 response_repo,
+                token_repo,
+                rate_limit_repo,
                 config_handler).await;
         });
 
@@ -291,6 +314,8 @@ mod tests {
         let client_repo = Arc::new(MockClientRepo::new());
         let request_repo = Arc::new(MockRequestRepo::new());
         let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
         let config_handler = Arc::new(MockConfigHandlerImpl::new());
         let cache_service = CacheService::new(
             cache_repo.clone(), config_handler.clone(), String::from(config_keys::CONFIG_KEY_SERVER_CACHE_CONFIGS));
@@ -304,11 +329,14 @@ mod tests {
                     false,
                     false,
                     false
-                ), 
+                )
+                .with_admin_socket_path(None), 
                 cache_repo, 
                 client_repo, 
                 request_repo, 
                 response_repo, 
+                token_repo,
+                rate_limit_repo,
                 config_handler.clone()).await;
         });
 
@@ -406,6 +434,8 @@ mod tests {
         let client_repo = Arc::new(MockClientRepo::new());
         let request_repo = Arc::new(MockRequestRepo::new());
         let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
         let config_handler = Arc::new(MockConfigHandlerImpl::new());
         let server_exec = tokio::spawn(async move {
             // enable the client id cache
@@ -419,11 +449,14 @@ mod tests {
                     cache_client_id,
                     false,
                     false
-                ), 
+                )
+                .with_admin_socket_path(None), 
                 cache_repo, 
                 client_repo, 
                 request_repo, 
                 response_repo,
+                token_repo,
+                rate_limit_repo,
                 config_handler).await;
         });
 
@@ -516,6 +549,8 @@ mod tests {
         let client_repo = Arc::new(MockClientRepo::new());
         let request_repo = Arc::new(MockRequestRepo::new());
         let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
         let config_handler = Arc::new(MockConfigHandlerImpl::new());
         let server_exec = tokio::spawn(async move {
             server::run(
@@ -527,11 +562,14 @@ mod tests {
                     false,
                     true,
                     false
-                ), 
+                )
+                .with_admin_socket_path(None), 
                 cache_repo, 
                 client_repo, 
                 request_repo, 
                 response_repo,
+                token_repo,
+                rate_limit_repo,
                 config_handler).await;
         });
 
@@ -628,6 +666,8 @@ mod tests {
         let client_repo = Arc::new(MockClientRepo::new());
         let request_repo = Arc::new(MockRequestRepo::new());
         let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
         let config_handler = Arc::new(MockConfigHandlerImpl::new());
         let server_exec = tokio::spawn(async move {
             server::run(
@@ -639,11 +679,14 @@ mod tests {
                     false,
                     false,
                     false
-                ),
+                )
+                .with_admin_socket_path(None),
                 cache_repo,
                 client_repo,
                 request_repo,
                 response_repo,
+                token_repo,
+                rate_limit_repo,
                 config_handler
             ).await;
         });
@@ -733,6 +776,84 @@ mod tests {
         client3_exec.abort();
     }
     
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_upstream_pool_failover() {
+        // init mock env
+        init_test_env();
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        // start client service with 3 upstreams, where the second one is failing
+        let mock_response = String::from("pong");
+        let hits = Arc::new(StdMutex::new(HashMap::<String, usize>::new()));
+        let upstreams = vec![
+            String::from("upstream1:3000"),
+            String::from("upstream2:3000"),
+            String::from("upstream3:3000"),
+        ];
+        let underlying_repo = Arc::new(MockPoolUnderlyingRepo::new(mock_response.clone(), vec![upstreams[1].clone()], hits.clone()));
+
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "client_pool");
+        let client_exec = tokio::spawn(async move {
+            client::serve_upstreams(upstreams, LoadBalanceStrategy::RoundRobin, underlying_repo, false).await;
+        });
+
+        // delay for 2 seconds to wait the client to start up
+        sleep(Duration::from_secs(2)).await;
+
+        // all GET requests must succeed, the failing upstream is ejected
+        // and the failed request is retried on another upstream
+        for _ in 0..10 {
+            let url = String::from("http://127.0.0.1:3333/client_pool/ping");
+            let response = match send_http_request(url, None).await {
+                Ok(res) => res.text().await.unwrap(),
+                Err(err) => err
+            };
+
+            assert!(response.starts_with(&mock_response), "Unexpected response: {}", response);
+        }
+
+        let hits = hits.lock().unwrap().clone();
+        _info!("Upstream hits: {:?}", hits);
+        assert_eq!(*hits.get("upstream2:3000").unwrap_or(&0), 1);
+        assert!(*hits.get("upstream1:3000").unwrap_or(&0) > 0);
+        assert!(*hits.get("upstream3:3000").unwrap_or(&0) > 0);
+
+        // abort all services
+        server_exec.abort();
+        client_exec.abort();
+    }
+    
     struct TestLogger {
         logs: StdArc<StdMutex<Vec<String>>>,
     }
@@ -775,6 +896,8 @@ mod tests {
         let client_repo = Arc::new(MockClientRepo::new());
         let request_repo = Arc::new(MockRequestRepo::new());
         let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
         let config_handler = Arc::new(MockConfigHandlerImpl::new());
         let server_exec = tokio::spawn(async move {
             server::run(
@@ -786,11 +909,14 @@ mod tests {
                     false, // no cache client id
                     false,
                     false
-                ),
+                )
+                .with_admin_socket_path(None),
                 cache_repo, 
                 client_repo, 
                 request_repo, 
                 response_repo,
+                token_repo,
+                rate_limit_repo,
                 config_handler).await;
         });
 
@@ -812,14 +938,14 @@ mod tests {
         
         #[async_trait::async_trait]
         impl client::data::repository::underlying_repo::UnderlyingRepo for SlowMockUnderlyingRepo {
-            async fn forward(&self, _: Vec<u8>, _: String) -> Result<Vec<u8>, String> {
+            async fn forward(&self, _: Vec<u8>, _: String) -> Result<Vec<u8>, client::data::repository::underlying_repo::ForwardError> {
                 tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
                 
                 if let Ok(res) = common::net::http_string_response_as_bytes(self.mock_response.clone(), http::StatusCode::from_u16(200).unwrap()) {
                     return Ok(res);
                 }
         
-                Err(String::from("An error occurred"))
+                Err(String::from("An error occurred").into())
             }
 
             async fn test_connection(&self, _: String) -> Result<(), String> {
@@ -876,4 +1002,1089 @@ mod tests {
         server_exec.abort();
         client_exec.abort();
     }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_api_token() {
+        // init mock env
+        init_test_env();
+
+        // issue a token only for token_client
+        let token = ApiToken::new(vec![String::from("token_client")], None);
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        token_repo.create(token.clone()).await.unwrap();
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let server_token_repo = token_repo.clone();
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                server_token_repo,
+                rate_limit_repo,
+                config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        // the client signs the handshake with the token key
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "token_client");
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_TOKEN_ID), token.id.clone());
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY), token.key.clone());
+
+        let mock_response = String::from("pong");
+        let underlying_repo1 = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let underlying_repo2 = underlying_repo1.clone();
+        let client1_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo1, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let response = send_http_request(String::from("http://127.0.0.1:3333/token_client/ping"), None).await;
+        assert!(response.is_ok(), "Expected successful response, got: {:?}", response);
+        assert_eq!(response.unwrap().text().await.unwrap(), mock_response);
+
+        // the token is not allowed for another client id
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "token_client_other");
+        let client2_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo2, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let response = send_http_request(String::from("http://127.0.0.1:3333/token_client_other/ping"), None).await;
+        assert!(response.is_err(), "Expected error response, got: {:?}", response);
+
+        // restore the shared envs for the other tests
+        let server_secret = env::var(String::from(config_keys::CONFIG_KEY_SERVER_SECRET)).unwrap();
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY), server_secret);
+        env::remove_var(String::from(config_keys::CONFIG_KEY_CLIENT_TOKEN_ID));
+
+        // abort services
+        server_exec.abort();
+        client1_exec.abort();
+        client2_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_key_auth() {
+        // init mock env
+        init_test_env();
+
+        // register the public key of key_client only
+        let (private_key, public_key) = generate_ed25519_keypair();
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        // the ack is signed with the server identity key, pinned by the client
+        let (identity_key, identity_public_key) = generate_ed25519_keypair();
+        config_handler.set_configs(HashMap::from([
+            (
+                String::from(config_keys::CONFIG_KEY_SERVER_CLIENT_KEYS),
+                format!("{{\"key_client\":\"{}\"}}", public_key),
+            ),
+            (String::from(config_keys::CONFIG_KEY_SERVER_IDENTITY_KEY), identity_key),
+        ])).await.unwrap();
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        // the client has no shared secret, only its private key
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "key_client");
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_PRIVATE_KEY), private_key);
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY), identity_public_key);
+        env::remove_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY));
+
+        let mock_response = String::from("pong");
+        let underlying_repo1 = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let underlying_repo2 = underlying_repo1.clone();
+        let client1_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo1, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let response = send_http_request(String::from("http://127.0.0.1:3333/key_client/ping"), None).await;
+        assert!(response.is_ok(), "Expected successful response, got: {:?}", response);
+        assert_eq!(response.unwrap().text().await.unwrap(), mock_response);
+
+        // another key, not registered for the client id
+        let (other_private_key, _) = generate_ed25519_keypair();
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "key_client_other");
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_PRIVATE_KEY), other_private_key);
+        let client2_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo2, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let response = send_http_request(String::from("http://127.0.0.1:3333/key_client_other/ping"), None).await;
+        assert!(response.is_err(), "Expected error response, got: {:?}", response);
+
+        // restore the shared envs for the other tests
+        let server_secret = env::var(String::from(config_keys::CONFIG_KEY_SERVER_SECRET)).unwrap();
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY), server_secret);
+        env::remove_var(String::from(config_keys::CONFIG_KEY_CLIENT_PRIVATE_KEY));
+        env::remove_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_PUBLIC_KEY));
+
+        // abort services
+        server_exec.abort();
+        client1_exec.abort();
+        client2_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_rotated_secret() {
+        // init mock env
+        init_test_env();
+
+        // rotate the server secret, the current one is kept as previous secret
+        let previous_secret = env::var(String::from(config_keys::CONFIG_KEY_SERVER_SECRET)).unwrap();
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let secret_service = SecretService::new(config_handler.clone());
+        let primary_secret = secret_service.rotate_secret(None, Duration::from_secs(600)).await.unwrap();
+        assert_ne!(primary_secret, previous_secret);
+        assert_eq!(secret_service.get_active_secrets().await.len(), 2);
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let server_config_handler = config_handler.clone();
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                server_config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        let mock_response = String::from("pong");
+        let underlying_repo = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+
+        // both the primary and the previous secret are accepted during the overlap
+        let mut client_execs = Vec::new();
+        for (client_id, signing_key) in [("rotated_primary", primary_secret.clone()), ("rotated_previous", previous_secret.clone())] {
+            env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), client_id);
+            env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY), signing_key);
+            let underlying_repo = underlying_repo.clone();
+            client_execs.push(tokio::spawn(async move {
+                client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+            }));
+
+            // wait for client to start
+            sleep(Duration::from_secs(2)).await;
+
+            let response = send_http_request(format!("http://127.0.0.1:3333/{}/ping", client_id), None).await;
+            assert!(response.is_ok(), "Expected successful response, got: {:?}", response);
+            assert_eq!(response.unwrap().text().await.unwrap(), mock_response);
+        }
+
+        // the previous secret is rejected once expired
+        secret_service.expire_previous_secrets().await.unwrap();
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "rotated_expired");
+        let underlying_repo = underlying_repo.clone();
+        client_execs.push(tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+        }));
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let response = send_http_request(String::from("http://127.0.0.1:3333/rotated_expired/ping"), None).await;
+        assert!(response.is_err(), "Expected error response, got: {:?}", response);
+
+        // restore the shared envs for the other tests
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY), previous_secret);
+
+        // abort services
+        server_exec.abort();
+        for client_exec in client_execs {
+            client_exec.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotated_secret_ack_signing() {
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let secret_service = SecretService::new(config_handler.clone());
+        let previous_secret = secret_service.rotate_secret(None, Duration::from_secs(600)).await.unwrap();
+        let primary_secret = secret_service.rotate_secret(None, Duration::from_secs(600)).await.unwrap();
+
+        // each client gets the ack signed with the secret it holds,
+        // a client not moved to the rotated secret yet cannot verify the primary one
+        for (signing_key, other_key) in [(primary_secret.clone(), previous_secret.clone()), (previous_secret.clone(), primary_secret.clone())] {
+            let client = TunnelClient::new(String::from("rotated"), signing_key.clone(), String::from("1.0.0"), String::from("1.0.0"));
+            let (secret, _) = secret_service.find_handshake_secret(client.signature.clone(), client.handshake_mac()).await.unwrap();
+            assert_eq!(secret, signing_key);
+
+            let nonce = String::from("tunnel-1");
+            let ack = TunnelAck::success(nonce.clone(), client.handshake_mac(), secret, vec![]);
+            assert!(validate_ack(&ack, &client, Some(&nonce), signing_key));
+            assert!(!validate_ack(&ack, &client, Some(&nonce), other_key));
+        }
+
+        // nothing matches once the previous secrets are expired
+        secret_service.expire_previous_secrets().await.unwrap();
+        let client = TunnelClient::new(String::from("rotated"), previous_secret, String::from("1.0.0"), String::from("1.0.0"));
+        assert!(secret_service.find_handshake_secret(client.signature.clone(), client.handshake_mac()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_public_auth() {
+        // init mock env
+        init_test_env();
+
+        // start server service
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let public_auth_service = PublicAuthService::new(config_handler.clone(), String::from(config_keys::CONFIG_KEY_SERVER_PUBLIC_AUTH));
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let server_config_handler = config_handler.clone();
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                server_config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        // the client requires a bearer token on its public endpoints
+        let client_id = "public_auth_client";
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), client_id);
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_PUBLIC_AUTH), to_json_string(&vec![PublicAuth::bearer("client-token").unwrap()]));
+        let mock_response = String::from("pong");
+        let underlying_repo = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let client_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+        env::remove_var(String::from(config_keys::CONFIG_KEY_CLIENT_PUBLIC_AUTH));
+
+        let url = format!("http://127.0.0.1:3333/{}/ping", client_id);
+        let send = |authorization: Option<&'static str>| {
+            let url = url.clone();
+            async move {
+                let mut request = Client::new().get(url);
+                if let Some(value) = authorization {
+                    request = request.header(AUTHORIZATION, value);
+                }
+                request.send().await.unwrap()
+            }
+        };
+
+        // missing or invalid credentials are challenged
+        let response = send(None).await;
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(response.headers().get(WWW_AUTHENTICATE).unwrap(), "Bearer realm=\"trabas\"");
+        assert_eq!(send(Some("Bearer wrong-token")).await.status().as_u16(), 401);
+
+        let response = send(Some("Bearer client-token")).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), mock_response);
+
+        // the server policy takes precedence over the client credentials
+        public_auth_service.set_public_auth(client_id.to_string(), PublicAuth::basic("admin", "secret").unwrap()).await.unwrap();
+        let response = send(Some("Bearer client-token")).await;
+        assert_eq!(response.status().as_u16(), 401);
+        assert!(response.headers().get(WWW_AUTHENTICATE).unwrap().to_str().unwrap().starts_with("Basic"));
+
+        // admin:secret
+        let response = send(Some("Basic YWRtaW46c2VjcmV0")).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), mock_response);
+
+        // abort services
+        server_exec.abort();
+        client_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_ip_rules() {
+        // init mock env
+        init_test_env();
+
+        // start server service
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let ip_filter_service = IpFilterService::new(config_handler.clone(), String::from(config_keys::CONFIG_KEY_SERVER_IP_RULES));
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let server_config_handler = config_handler.clone();
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                server_config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        let client_id = "ip_rule_client";
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), client_id);
+        let mock_response = String::from("pong");
+        let underlying_repo = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let client_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let url = format!("http://127.0.0.1:3333/{}/ping", client_id);
+        let response = send_http_request(url.clone(), None).await;
+        assert!(response.is_ok(), "Expected successful response, got: {:?}", response);
+
+        // the client only accepts an office network
+        ip_filter_service.set_ip_rule(IpRule::new(client_id.to_string(), IpRuleAction::Allow, "10.0.0.0/8".to_string())).await.unwrap();
+        let response = Client::new().get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 403);
+
+        ip_filter_service.set_ip_rule(IpRule::new(client_id.to_string(), IpRuleAction::Allow, "127.0.0.1".to_string())).await.unwrap();
+        let response = send_http_request(url.clone(), None).await;
+        assert!(response.is_ok(), "Expected successful response, got: {:?}", response);
+        assert_eq!(response.unwrap().text().await.unwrap(), mock_response);
+
+        // the server-wide deny rule drops the connection
+        ip_filter_service.set_ip_rule(IpRule::new("*".to_string(), IpRuleAction::Deny, "127.0.0.0/8".to_string())).await.unwrap();
+        assert!(Client::new().get(url.clone()).send().await.is_err());
+
+        ip_filter_service.remove_ip_rule("*".to_string(), "127.0.0.0/8".to_string()).await.unwrap();
+        let response = send_http_request(url, None).await;
+        assert!(response.is_ok(), "Expected successful response, got: {:?}", response);
+
+        // abort services
+        server_exec.abort();
+        client_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_read_limits() {
+        // init mock env
+        init_test_env();
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_BODY_SIZE), "16");
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_COUNT), "8");
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        let client_id = "read_limit_client";
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), client_id);
+        let mock_response = String::from("pong");
+        let underlying_repo = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let client_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let url = format!("http://127.0.0.1:3333/{}/ping", client_id);
+        let response = Client::new().post(url.clone()).body("small").send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), mock_response);
+
+        let response = Client::new().post(url.clone()).body("a body over the limit").send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 413);
+
+        let mut request = Client::new().get(url);
+        for i in 0..10 {
+            request = request.header(format!("X-Header-{}", i), "value");
+        }
+        assert_eq!(request.send().await.unwrap().status().as_u16(), 431);
+
+        // restore the shared envs for the other tests
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_BODY_SIZE));
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_HEADER_COUNT));
+
+        // abort services
+        server_exec.abort();
+        client_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_rate_limits() {
+        // init mock env
+        init_test_env();
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_RPS), "1");
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_BURST), "2");
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let public_auth_service = PublicAuthService::new(config_handler.clone(), String::from(config_keys::CONFIG_KEY_SERVER_PUBLIC_AUTH));
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        let client_id = "rate_limit_client";
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), client_id);
+        let mock_response = String::from("pong");
+        let underlying_repo = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let client_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        // the burst is served right away
+        let url = format!("http://127.0.0.1:3333/{}/ping", client_id);
+        for remaining in ["1", "0"] {
+            let response = Client::new().get(url.clone()).send().await.unwrap();
+            assert_eq!(response.status().as_u16(), 200);
+            assert_eq!(response.headers().get("RateLimit-Limit").unwrap(), "2");
+            assert_eq!(response.headers().get("RateLimit-Remaining").unwrap(), remaining);
+            assert_eq!(response.text().await.unwrap(), mock_response);
+        }
+
+        // then rejected until the bucket is refilled
+        let response = Client::new().get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 429);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "1");
+        assert_eq!(response.headers().get("RateLimit-Remaining").unwrap(), "0");
+
+        // unless it's allowed to wait for the capacity
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_MAX_WAIT), "2000");
+        let response = Client::new().get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), mock_response);
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_MAX_WAIT));
+
+        // the rejected credentials take a token too, guessing them is limited as well
+        public_auth_service.set_public_auth(client_id.to_string(), PublicAuth::basic("admin", "secret").unwrap()).await.unwrap();
+        sleep(Duration::from_secs(2)).await;
+        let mut statuses = Vec::new();
+        for _ in 0..4 {
+            let response = Client::new().get(url.clone()).header(AUTHORIZATION, "Basic d3Jvbmc6d3Jvbmc=").send().await.unwrap();
+            statuses.push(response.status().as_u16());
+        }
+        assert_eq!(statuses[0], 401);
+        assert_eq!(statuses.last(), Some(&429));
+        assert!(statuses.iter().all(|status| *status == 401 || *status == 429));
+
+        // restore the shared envs for the other tests
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_RPS));
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_CLIENT_BURST));
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_RATE_LIMIT_MAX_WAIT));
+
+        // abort services
+        server_exec.abort();
+        client_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_connection_caps() {
+        // init mock env
+        init_test_env();
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_CONNECTIONS), "2");
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_MAX_TUNNELS_PER_CLIENT), "1");
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let server_client_repo = client_repo.clone();
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    true,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                server_client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        // only one of the tunnels is registered
+        let client_id = "connection_cap_client";
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), client_id);
+        let mock_response = String::from("pong");
+        let underlying_repo1 = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let underlying_repo2 = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let client_tunnel1_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo1, false).await;
+        });
+        sleep(Duration::from_secs(3)).await;
+        let client_tunnel2_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo2, false).await;
+        });
+        sleep(Duration::from_secs(3)).await;
+        assert_eq!(client_repo.get_all(client_id.to_string()).await.unwrap().len(), 1);
+
+        let url = format!("http://127.0.0.1:3333/{}/ping", client_id);
+        let response = Client::new().get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), mock_response);
+
+        // idle connections hold the permits until they are closed
+        let idle1 = tokio::net::TcpStream::connect("127.0.0.1:3333").await.unwrap();
+        let idle2 = tokio::net::TcpStream::connect("127.0.0.1:3333").await.unwrap();
+        sleep(Duration::from_millis(200)).await;
+        let response = Client::new().get(url.clone()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 503);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "1");
+
+        drop(idle1);
+        drop(idle2);
+        sleep(Duration::from_millis(200)).await;
+        let response = Client::new().get(url).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), mock_response);
+
+        // restore the shared envs for the other tests
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_PUBLIC_MAX_CONNECTIONS));
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_MAX_TUNNELS_PER_CLIENT));
+
+        // abort services
+        server_exec.abort();
+        client_tunnel1_exec.abort();
+        client_tunnel2_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_filter_rules() {
+        // init mock env
+        init_test_env();
+
+        // start server service
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let request_filter_service = RequestFilterService::new(config_handler.clone(), String::from(config_keys::CONFIG_KEY_SERVER_FILTER_RULES));
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let server_config_handler = config_handler.clone();
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                server_config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        let client_id = "filter_rule_client";
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), client_id);
+        let mock_response = String::from("pong");
+        let underlying_repo = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let client_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        // block the git directory of all clients, and only allow GET for the client
+        request_filter_service.set_filter_rule(FilterRule::new("*".to_string(), "block-git".to_string(), FilterAction::Deny { status: 404 })
+            .with_path(Some("/.git/**".to_string()))).await.unwrap();
+        request_filter_service.set_filter_rule(FilterRule::new(client_id.to_string(), "get-only".to_string(), FilterAction::Allow)
+            .with_priority(200)
+            .with_method(Some("GET".to_string()))).await.unwrap();
+        request_filter_service.set_filter_rule(FilterRule::new(client_id.to_string(), "deny-others".to_string(), FilterAction::Deny { status: 405 })
+            .with_priority(300)).await.unwrap();
+
+        let url = format!("http://127.0.0.1:3333/{}", client_id);
+        let response = Client::new().get(format!("{}/.git/config", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+
+        let response = Client::new().post(format!("{}/ping", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 405);
+
+        let response = Client::new().get(format!("{}/ping", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), mock_response);
+
+        // abort services
+        server_exec.abort();
+        client_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_client_allowlist() {
+        // init mock env
+        init_test_env();
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let server_config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                server_config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        // the client exposes only the public api and read-only methods
+        let client_id = "allowlist_client";
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), client_id);
+        let mock_response = String::from("pong");
+        let underlying_repo = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+        let allowlist = RequestAllowlist::new(vec!["/api/public/*".to_string()], vec!["GET,HEAD".to_string()]).unwrap();
+        let client_exec = tokio::spawn(async move {
+            client::serve_with_allowlist(
+                vec![String::from("The target underlying address, This has no effect")],
+                LoadBalanceStrategy::RoundRobin,
+                underlying_repo,
+                false,
+                allowlist
+            ).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let url = format!("http://127.0.0.1:3333/{}", client_id);
+        let response = Client::new().get(format!("{}/api/private/ping", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 403);
+
+        // encoded separators must not sneak out of the allowed prefix
+        let response = Client::new().get(format!("{}/api/public/..%2Fprivate", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 403);
+
+        let response = Client::new().post(format!("{}/api/public/ping", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 403);
+
+        let response = Client::new().get(format!("{}/api/public/ping?q=1", url)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), mock_response);
+
+        // abort services
+        server_exec.abort();
+        client_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_audit_log() {
+        // init mock env
+        init_test_env();
+
+        // enable the audit log of the server
+        let audit_dir = env::temp_dir().join(format!("trabas_e2e_audit_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&audit_dir);
+        let audit_path = audit_dir.join("audit.log");
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_AUDIT_LOG_ENABLE), "true");
+        env::set_var(String::from(config_keys::CONFIG_KEY_SERVER_AUDIT_LOG_PATH), audit_path.to_string_lossy().to_string());
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let server_config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0, // no request limit
+                    false, // no cache client id
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None),
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo,
+                token_repo,
+                rate_limit_repo,
+                server_config_handler).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        let mock_response = String::from("pong");
+        let underlying_repo = Arc::new(MockUnderlyingRepo::new(mock_response.clone(), Arc::new(StdMutex::new(|| {}))));
+
+        // a client signing with a wrong key is denied
+        let signing_key = env::var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY)).unwrap();
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "audit_denied");
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY), "77726f6e676b6579313233343536");
+        let denied_underlying_repo = underlying_repo.clone();
+        let denied_client_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), denied_underlying_repo, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+        denied_client_exec.abort();
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_SERVER_SIGNING_KEY), signing_key);
+
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "audit_client");
+        let client_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        let response = send_http_request(String::from("http://127.0.0.1:3333/audit_client/ping"), None).await;
+        assert!(response.is_ok(), "Expected successful response, got: {:?}", response);
+
+        let events: Vec<AuditEvent> = std::fs::read_to_string(&audit_path).unwrap()
+            .lines()
+            .map(|line| from_json_string(line).unwrap())
+            .collect();
+        let find_event = |client_id: &str, event: AuditEventType, outcome: AuditOutcome| events.iter()
+            .find(|e| e.client_id.as_deref() == Some(client_id) && e.event == event && e.outcome == outcome)
+            .cloned();
+
+        let mismatch = find_event("audit_denied", AuditEventType::SignatureMismatch, AuditOutcome::Failure);
+        assert!(mismatch.is_some(), "Expected signature mismatch event, got: {:?}", events);
+        let mismatch = mismatch.unwrap();
+        assert!(mismatch.peer_addr.is_some());
+        assert!(mismatch.reason.is_some());
+
+        let registration = find_event("audit_client", AuditEventType::Registration, AuditOutcome::Success);
+        assert!(registration.is_some(), "Expected registration event, got: {:?}", events);
+        let registration = registration.unwrap();
+        assert!(registration.version.is_some() && registration.peer_addr.is_some());
+
+        // restore the shared envs for the other tests
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_AUDIT_LOG_ENABLE));
+        env::remove_var(String::from(config_keys::CONFIG_KEY_SERVER_AUDIT_LOG_PATH));
+        let _ = std::fs::remove_dir_all(&audit_dir);
+
+        // abort services
+        server_exec.abort();
+        client_exec.abort();
+    }
+
+    #[tokio::test]
+    async fn test_e2e_request_flow_with_http_cache_mode() {
+        // init mock env
+        init_test_env();
+
+        // start server service
+        let cache_repo = Arc::new(MockCacheRepo::new());
+        let client_repo = Arc::new(MockClientRepo::new());
+        let request_repo = Arc::new(MockRequestRepo::new());
+        let response_repo = Arc::new(MockResponseRepo::new());
+        let token_repo = Arc::new(MockTokenRepo::new());
+        let rate_limit_repo = Arc::new(MockRateLimitRepo::new());
+        let config_handler = Arc::new(MockConfigHandlerImpl::new());
+        let cache_service = CacheService::new(
+            cache_repo.clone(), config_handler.clone(), String::from(config_keys::CONFIG_KEY_SERVER_CACHE_CONFIGS));
+        let server_exec = tokio::spawn(async move {
+            server::run(
+                server::config::ServerRequestConfig::new(
+                    "127.0.0.1".to_string(),
+                    3333, 
+                    3334, 
+                    0,
+                    false,
+                    false,
+                    false
+                )
+                .with_admin_socket_path(None), 
+                cache_repo, 
+                client_repo, 
+                request_repo, 
+                response_repo, 
+                token_repo,
+                rate_limit_repo,
+                config_handler.clone()).await;
+        });
+
+        // delay for 2 seconds to wait the server to start up
+        sleep(Duration::from_secs(2)).await;
+
+        // an underlying service telling which responses can be stored
+        struct CachingMockUnderlyingRepo {
+            forward_cnt: Arc<StdMutex<usize>>,
+        }
+
+        #[async_trait::async_trait]
+        impl client::data::repository::underlying_repo::UnderlyingRepo for CachingMockUnderlyingRepo {
+            async fn forward(&self, raw_request: Vec<u8>, _: String) -> Result<Vec<u8>, client::data::repository::underlying_repo::ForwardError> {
+                *self.forward_cnt.lock().unwrap() += 1;
+                let cache_control = if String::from_utf8_lossy(&raw_request).contains("/secret") { "no-store" } else { "max-age=60" };
+                let response = http::Response::builder()
+                    .status(200)
+                    .header("Cache-Control", cache_control)
+                    .header("ETag", "\"v1\"")
+                    .body(b"pong".to_vec())
+                    .unwrap();
+
+                Ok(common::convert::response_to_bytes(&response))
+            }
+
+            async fn test_connection(&self, _: String) -> Result<(), String> {
+                // always return ok for mock
+                Ok(())
+            }
+        }
+
+        let forward_cnt = Arc::new(StdMutex::new(0));
+        let underlying_repo = Arc::new(CachingMockUnderlyingRepo { forward_cnt: forward_cnt.clone() });
+        env::set_var(String::from(config_keys::CONFIG_KEY_CLIENT_ID), "client1");
+        let client_exec = tokio::spawn(async move {
+            client::serve(String::from("The target underlying address, This has no effect"), underlying_repo, false).await;
+        });
+
+        // wait for client to start
+        sleep(Duration::from_secs(2)).await;
+
+        for path in ["/ping", "/secret"] {
+            cache_service.set_cache_config(CacheConfig::new(String::from("client1"), String::from("GET"), String::from(path), 60)
+                .with_mode(CacheMode::Http)).await.unwrap();
+        }
+
+        let client = Client::new();
+        let send = |path: &str, if_none_match: Option<&str>| {
+            let mut request = client.get(format!("http://127.0.0.1:3333/client1{}", path));
+            if let Some(value) = if_none_match {
+                request = request.header("If-None-Match", value);
+            }
+            request.send()
+        };
+
+        // the response is served from the cache once stored
+        for _ in 0..2 {
+            let response = send("/ping", None).await.unwrap();
+            assert_eq!(response.status().as_u16(), 200);
+            assert_eq!(response.text().await.unwrap(), "pong");
+        }
+        assert_eq!(*forward_cnt.lock().unwrap(), 1);
+
+        // a matching validator gets 304 without reaching the client
+        let response = send("/ping", Some("\"v1\"")).await.unwrap();
+        assert_eq!(response.status().as_u16(), 304);
+        assert_eq!(response.headers().get("etag").unwrap(), "\"v1\"");
+        assert_eq!(*forward_cnt.lock().unwrap(), 1);
+
+        // no-store responses are always forwarded
+        for _ in 0..2 {
+            let response = send("/secret", None).await.unwrap();
+            assert_eq!(response.status().as_u16(), 200);
+        }
+        assert_eq!(*forward_cnt.lock().unwrap(), 3);
+
+        // abort all services
+        server_exec.abort();
+        client_exec.abort();
+    }
 }